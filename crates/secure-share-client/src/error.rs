@@ -0,0 +1,83 @@
+use std::fmt;
+
+use secure_share_types::ErrorResponse;
+use validator::ValidationErrors;
+
+#[derive(Debug)]
+pub enum Error {
+    /// The request failed to send or its body could not be decoded
+    Http(reqwest::Error),
+    /// The DTO breaks the server's rules and was not sent
+    Validation(ValidationErrors),
+    /// The server rejected the request with problem details
+    Api(Box<ErrorResponse>),
+    /// The server failed without problem details, e.g. a proxy error page
+    Unexpected { status: u16, body: String },
+    /// An authenticated method was called before logging in
+    NotLoggedIn,
+}
+
+impl Error {
+    /// HTTP status of a response the server rejected.
+    pub fn status(&self) -> Option<u16> {
+        match self {
+            Error::Api(problem) => Some(problem.status),
+            Error::Unexpected { status, .. } => Some(*status),
+            Error::Http(err) => err.status().map(|status| status.as_u16()),
+            Error::Validation(_) | Error::NotLoggedIn => None,
+        }
+    }
+
+    /// Stable error code from the problem details, e.g. `wrong_credentials`.
+    pub fn code(&self) -> Option<&str> {
+        match self {
+            Error::Api(problem) => Some(&problem.code),
+            _ => None,
+        }
+    }
+}
+
+impl fmt::Display for Error {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Error::Http(err) => write!(f, "request failed: {}", err),
+            Error::Validation(errors) => write!(f, "invalid request: {}", errors),
+            Error::Api(problem) => {
+                write!(f, "{} ({})", problem.detail, problem.code)?;
+                for (field, errors) in problem.errors.iter().flatten() {
+                    for error in errors {
+                        write!(f, "\n  {}: {}", field, error.message)?;
+                    }
+                }
+                if let Some(correlation_id) = &problem.correlation_id {
+                    write!(f, "\n  correlation id: {}", correlation_id)?;
+                }
+                Ok(())
+            }
+            Error::Unexpected { status, body } => write!(f, "{}: {}", status, body),
+            Error::NotLoggedIn => write!(f, "not logged in"),
+        }
+    }
+}
+
+impl std::error::Error for Error {
+    fn source(&self) -> Option<&(dyn std::error::Error + 'static)> {
+        match self {
+            Error::Http(err) => Some(err),
+            Error::Validation(errors) => Some(errors),
+            _ => None,
+        }
+    }
+}
+
+impl From<reqwest::Error> for Error {
+    fn from(err: reqwest::Error) -> Self {
+        Error::Http(err)
+    }
+}
+
+impl From<ValidationErrors> for Error {
+    fn from(errors: ValidationErrors) -> Self {
+        Error::Validation(errors)
+    }
+}
@@ -0,0 +1,537 @@
+//! Async client for the secure-share HTTP API.
+//!
+//! ```no_run
+//! # async fn run() -> Result<(), secure_share_client::Error> {
+//! use secure_share_client::{Client, dtos::{LoginUserDto, RequestQueryDto}};
+//!
+//! let mut client = Client::new("http://localhost:8000");
+//! client
+//!     .login(&LoginUserDto {
+//!         email: "alice@example.com".to_string(),
+//!         password: "password123".to_string(),
+//!     })
+//!     .await?;
+//! let received = client.list_received(&RequestQueryDto::default()).await?;
+//! # Ok(())
+//! # }
+//! ```
+
+mod error;
+
+use std::io;
+
+use bytes::Bytes;
+use futures_util::{Stream, StreamExt, TryStreamExt, stream};
+use percent_encoding::percent_decode_str;
+use reqwest::{
+    Body, RequestBuilder, Response,
+    header::{self, HeaderMap},
+    multipart::{Form, Part},
+};
+use serde::de::DeserializeOwned;
+use validator::Validate;
+
+pub use error::Error;
+pub use secure_share_types::{ErrorResponse, FieldError, dtos};
+
+use dtos::{
+    EmailListResponseDto, EnvelopeResponseDto, EnvelopeUploadDto, FileUploadDto, LoginUserDto,
+    RegisterSigningKeyDto, RegisterUserDto, RequestQueryDto, RetrieveFileDto, RotateKeyDto,
+    SIGNATURE_STATUS_HEADER, SearchQueryByEmailDto, ShareDetailsResponseDto, SignatureStatus,
+    SigningKeyResponseDto, UserKeyListResponseDto, UserKeyResponseDto, UserLoginResponseDto,
+    UserReceiveFileListResponseDto, UserResponseDto, UserSendFileListResponseDto,
+};
+
+pub type Result<T, E = Error> = std::result::Result<T, E>;
+
+const UPLOAD_CHUNK_SIZE: usize = 64 * 1024;
+
+/// DTOs are validated with the server's rules before anything is sent, so
+/// invalid input fails with [`Error::Validation`] without a round trip.
+#[derive(Debug, Clone)]
+pub struct Client {
+    http: reqwest::Client,
+    base_url: String,
+    token: Option<String>,
+}
+
+impl Client {
+    /// `base_url` is the server root, e.g. `https://share.example.com`.
+    pub fn new(base_url: impl Into<String>) -> Self {
+        Self::with_http_client(base_url, reqwest::Client::new())
+    }
+
+    /// Uses a preconfigured `reqwest` client, e.g. one with timeouts or a proxy.
+    pub fn with_http_client(base_url: impl Into<String>, http: reqwest::Client) -> Self {
+        Self {
+            http,
+            base_url: base_url.into().trim_end_matches('/').to_string(),
+            token: None,
+        }
+    }
+
+    /// Reuses a token from an earlier [`Client::login`].
+    pub fn with_token(mut self, token: impl Into<String>) -> Self {
+        self.token = Some(token.into());
+        self
+    }
+
+    pub fn set_token(&mut self, token: Option<String>) {
+        self.token = token;
+    }
+
+    pub fn token(&self) -> Option<&str> {
+        self.token.as_deref()
+    }
+
+    pub fn base_url(&self) -> &str {
+        &self.base_url
+    }
+
+    pub async fn register(&self, body: &RegisterUserDto) -> Result<dtos::Response> {
+        body.validate()?;
+        let request = self.http.post(self.url("/auth/register")).json(body);
+        json(request.send().await?).await
+    }
+
+    /// Logs in and keeps the token for the authenticated methods.
+    pub async fn login(&mut self, body: &LoginUserDto) -> Result<UserLoginResponseDto> {
+        body.validate()?;
+        let request = self.http.post(self.url("/auth/login")).json(body);
+        let login: UserLoginResponseDto = json(request.send().await?).await?;
+        self.token = Some(login.token.clone());
+        Ok(login)
+    }
+
+    pub async fn me(&self) -> Result<UserResponseDto> {
+        let request = self.authorized(self.http.get(self.url("/users/me")))?;
+        json(request.send().await?).await
+    }
+
+    /// Finds users whose email address contains `query`, to pick a recipient.
+    pub async fn search_emails(&self, query: &str) -> Result<EmailListResponseDto> {
+        let params = SearchQueryByEmailDto {
+            query: query.to_string(),
+        };
+        params.validate()?;
+        let request = self
+            .http
+            .get(self.url("/users/search-emails"))
+            .query(&params);
+        json(self.authorized(request)?.send().await?).await
+    }
+
+    /// Every key pair of the logged in user, oldest first.
+    pub async fn keys(&self) -> Result<UserKeyListResponseDto> {
+        let request = self.authorized(self.http.get(self.url("/users/keys")))?;
+        json(request.send().await?).await
+    }
+
+    /// Replaces the active key pair; files shared before stay readable.
+    pub async fn rotate_key(&self, body: &RotateKeyDto) -> Result<UserKeyResponseDto> {
+        let request = self.http.post(self.url("/users/keys/rotate")).json(body);
+        let request = self.authorized(request)?;
+        json(request.send().await?).await
+    }
+
+    /// Makes a signing key held only by the caller the one the user's
+    /// uploads are signed with.
+    pub async fn register_signing_key(
+        &self,
+        body: &RegisterSigningKeyDto,
+    ) -> Result<SigningKeyResponseDto> {
+        let request = self.http.put(self.url("/users/signing-key")).json(body);
+        let request = self.authorized(request)?;
+        json(request.send().await?).await
+    }
+
+    /// Uploads a file for the server to encrypt for the recipient.
+    pub async fn upload(&self, file: FileUploadDto) -> Result<dtos::Response> {
+        self.upload_with_progress(file, |_| {}).await
+    }
+
+    /// Like [`Client::upload`], calling `progress` with the size of every
+    /// chunk as it is sent.
+    pub async fn upload_with_progress(
+        &self,
+        file: FileUploadDto,
+        progress: impl Fn(u64) + Send + Sync + 'static,
+    ) -> Result<dtos::Response> {
+        file.validate()?;
+
+        let data = Bytes::from(file.file_data);
+        let length = data.len();
+        let chunks = (0..length)
+            .step_by(UPLOAD_CHUNK_SIZE)
+            .map(move |start| data.slice(start..length.min(start + UPLOAD_CHUNK_SIZE)));
+        let body = Body::wrap_stream(stream::iter(chunks).map(move |chunk| {
+            progress(chunk.len() as u64);
+            Ok::<_, io::Error>(chunk)
+        }));
+        let content_type = match file.content_type.as_str() {
+            "" => "application/octet-stream",
+            content_type => content_type,
+        };
+        let file_part = Part::stream_with_length(body, length as u64)
+            .file_name(file.file_name)
+            .mime_str(content_type)?;
+        let form = Form::new()
+            .part("fileUpload", file_part)
+            .text("recipient_email", file.recipient_email)
+            .text("password", file.password)
+            .text("expiration_date", file.expiration_date);
+
+        let request = self.http.post(self.url("/file/upload")).multipart(form);
+        json(self.authorized(request)?.send().await?).await
+    }
+
+    /// Uploads an envelope sealed by the caller, e.g. with `seal_file` of
+    /// `secure-share-crypto`, so the server never sees the file in clear.
+    pub async fn upload_envelope(&self, envelope: EnvelopeUploadDto) -> Result<dtos::Response> {
+        envelope.validate()?;
+
+        let mut form = Form::new();
+        for (name, bytes) in [
+            ("ciphertext", envelope.ciphertext),
+            ("iv", envelope.iv),
+            ("encrypted_key", envelope.encrypted_key),
+            ("sender_encrypted_key", envelope.sender_encrypted_key),
+            ("encrypted_metadata", envelope.encrypted_metadata),
+            ("signature", envelope.signature),
+        ] {
+            form = form.part(name, Part::bytes(bytes));
+        }
+        let form = form
+            .text("file_size", envelope.file_size.to_string())
+            .text(
+                "recipient_key_version",
+                envelope.recipient_key_version.to_string(),
+            )
+            .text(
+                "sender_key_version",
+                envelope.sender_key_version.to_string(),
+            )
+            .text(
+                "signing_key_version",
+                envelope.signing_key_version.to_string(),
+            )
+            .text("recipient_email", envelope.recipient_email)
+            .text("password", envelope.password)
+            .text("expiration_date", envelope.expiration_date);
+
+        let request = self.http.post(self.url("/file/envelope")).multipart(form);
+        json(self.authorized(request)?.send().await?).await
+    }
+
+    pub async fn list_sent(&self, query: &RequestQueryDto) -> Result<UserSendFileListResponseDto> {
+        query.validate()?;
+        let request = self.http.get(self.url("/list/send")).query(query);
+        json(self.authorized(request)?.send().await?).await
+    }
+
+    pub async fn list_received(
+        &self,
+        query: &RequestQueryDto,
+    ) -> Result<UserReceiveFileListResponseDto> {
+        query.validate()?;
+        let request = self.http.get(self.url("/list/receive")).query(query);
+        json(self.authorized(request)?.send().await?).await
+    }
+
+    /// Details of a share the logged in user sent or received.
+    pub async fn share_details(&self, share_id: &str) -> Result<ShareDetailsResponseDto> {
+        let request = self
+            .http
+            .get(self.url(&format!("/file/shares/{}", share_id)));
+        json(self.authorized(request)?.send().await?).await
+    }
+
+    /// Downloads and decrypts a file shared with the logged in user.
+    pub async fn retrieve(&self, body: &RetrieveFileDto) -> Result<RetrievedFile> {
+        let download = self.retrieve_stream(body).await?;
+        Ok(RetrievedFile {
+            file_name: download.file_name,
+            signature_status: download.signature_status,
+            data: download.response.bytes().await?.to_vec(),
+        })
+    }
+
+    /// Like [`Client::retrieve`], but leaves the body to be streamed, e.g. to
+    /// disk.
+    pub async fn retrieve_stream(&self, body: &RetrieveFileDto) -> Result<Download> {
+        body.validate()?;
+        let request = self.http.post(self.url("/file/register")).json(body);
+        let response = check(self.authorized(request)?.send().await?).await?;
+        Ok(Download {
+            file_name: attachment_file_name(response.headers()),
+            signature_status: signature_status(response.headers()),
+            response,
+        })
+    }
+
+    /// Downloads a file shared with the logged in user as stored, to be
+    /// decrypted by the caller.
+    pub async fn retrieve_envelope(&self, body: &RetrieveFileDto) -> Result<EnvelopeResponseDto> {
+        body.validate()?;
+        let request = self
+            .http
+            .post(self.url("/file/envelope/retrieve"))
+            .json(body);
+        json(self.authorized(request)?.send().await?).await
+    }
+
+    fn url(&self, path: &str) -> String {
+        format!("{}/api{}", self.base_url, path)
+    }
+
+    fn authorized(&self, request: RequestBuilder) -> Result<RequestBuilder> {
+        let token = self.token.as_deref().ok_or(Error::NotLoggedIn)?;
+        Ok(request.bearer_auth(token))
+    }
+}
+
+#[derive(Debug, Clone)]
+pub struct RetrievedFile {
+    /// Name the sender uploaded the file with, without any directories
+    pub file_name: Option<String>,
+    /// `None` when the server does not check signatures
+    pub signature_status: Option<SignatureStatus>,
+    pub data: Vec<u8>,
+}
+
+/// A decrypted file whose body has not been read yet.
+#[derive(Debug)]
+pub struct Download {
+    /// Name the sender uploaded the file with, without any directories
+    pub file_name: Option<String>,
+    /// `None` when the server does not check signatures
+    pub signature_status: Option<SignatureStatus>,
+    response: Response,
+}
+
+impl Download {
+    pub fn content_length(&self) -> Option<u64> {
+        self.response.content_length()
+    }
+
+    pub fn bytes_stream(self) -> impl Stream<Item = Result<Bytes>> {
+        self.response.bytes_stream().map_err(Error::from)
+    }
+}
+
+/// Turns problem details from the server into [`Error::Api`].
+async fn check(response: Response) -> Result<Response> {
+    let status = response.status();
+    if status.is_success() {
+        return Ok(response);
+    }
+
+    let body = response.text().await?;
+    match serde_json::from_str::<ErrorResponse>(&body) {
+        Ok(problem) => Err(Error::Api(Box::new(problem))),
+        Err(_) => Err(Error::Unexpected {
+            status: status.as_u16(),
+            body,
+        }),
+    }
+}
+
+async fn json<T: DeserializeOwned>(response: Response) -> Result<T> {
+    Ok(check(response).await?.json().await?)
+}
+
+/// File name from `Content-Disposition`, preferring the UTF-8 `filename*`,
+/// stripped of any directories so a hostile sender cannot make callers write
+/// outside their chosen directory.
+fn attachment_file_name(headers: &HeaderMap) -> Option<String> {
+    let disposition = headers.get(header::CONTENT_DISPOSITION)?.to_str().ok()?;
+    let params = disposition_params(disposition);
+    let param = |name: &str| {
+        params
+            .iter()
+            .find(|(param, _)| param == name)
+            .map(|(_, value)| value.as_str())
+    };
+    let encoded = param("filename*")
+        .and_then(|value| value.strip_prefix("UTF-8''"))
+        .and_then(|value| percent_decode_str(value).decode_utf8().ok());
+    let name = match encoded {
+        Some(name) => name.into_owned(),
+        None => param("filename")?.to_string(),
+    };
+    // Either separator, whichever platform the sender named the file on
+    let name = name.rsplit(['/', '\\']).next()?;
+    (!matches!(name, "" | "." | "..")).then(|| name.to_string())
+}
+
+/// Parameters after the disposition type, names lowercased and quoted values
+/// unquoted, so a `;` inside quotes stays part of the value.
+fn disposition_params(disposition: &str) -> Vec<(String, String)> {
+    let mut params = vec![String::new()];
+    let (mut quoted, mut escaped) = (false, false);
+    for c in disposition.chars() {
+        match c {
+            _ if escaped => escaped = false,
+            '\\' if quoted => escaped = true,
+            '"' => quoted = !quoted,
+            ';' if !quoted => {
+                params.push(String::new());
+                continue;
+            }
+            _ => {}
+        }
+        params.last_mut().unwrap().push(c);
+    }
+
+    params
+        .iter()
+        .skip(1)
+        .filter_map(|param| {
+            let (name, value) = param.split_once('=')?;
+            let value = value.trim();
+            let value = match value.strip_prefix('"').and_then(|v| v.strip_suffix('"')) {
+                Some(quoted) => {
+                    let mut chars = quoted.chars();
+                    let mut value = String::new();
+                    while let Some(c) = chars.next() {
+                        value.extend(if c == '\\' { chars.next() } else { Some(c) });
+                    }
+                    value
+                }
+                None => value.to_string(),
+            };
+            Some((name.trim().to_ascii_lowercase(), value))
+        })
+        .collect()
+}
+
+/// Whether the server found the sender's signature valid.
+fn signature_status(headers: &HeaderMap) -> Option<SignatureStatus> {
+    headers
+        .get(SIGNATURE_STATUS_HEADER)?
+        .to_str()
+        .ok()?
+        .parse()
+        .ok()
+}
+
+#[cfg(test)]
+mod tests {
+    use reqwest::header::HeaderValue;
+
+    use super::*;
+
+    fn file_name(disposition: &str) -> Option<String> {
+        let mut headers = HeaderMap::new();
+        headers.insert(
+            header::CONTENT_DISPOSITION,
+            HeaderValue::from_str(disposition).unwrap(),
+        );
+        attachment_file_name(&headers)
+    }
+
+    fn response(status: u16, body: &str) -> Response {
+        http::Response::builder()
+            .status(status)
+            .body(body.to_string())
+            .unwrap()
+            .into()
+    }
+
+    #[test]
+    fn file_names_are_stripped_of_directories() {
+        for (disposition, expected) in [
+            ("attachment; filename=\"../../.bashrc\"", Some(".bashrc")),
+            (
+                "attachment; filename=\"..\\\\..\\\\evil.exe\"",
+                Some("evil.exe"),
+            ),
+            ("attachment; filename=\"/etc/passwd\"", Some("passwd")),
+            (
+                "attachment; filename*=UTF-8''%2Fetc%2Fcron.d%2Fjob",
+                Some("job"),
+            ),
+            (
+                "attachment; filename*=UTF-8''..%2F..%2Fnotes.txt",
+                Some("notes.txt"),
+            ),
+            ("attachment; filename=\"..\"", None),
+            ("attachment; filename=\"dir/\"", None),
+            ("attachment", None),
+        ] {
+            assert_eq!(file_name(disposition).as_deref(), expected, "{disposition}");
+        }
+    }
+
+    #[test]
+    fn encoded_file_names_take_precedence() {
+        for disposition in [
+            "attachment; filename=\"r_sum_.pdf\"; filename*=UTF-8''r%C3%A9sum%C3%A9.pdf",
+            "attachment; filename*=UTF-8''r%C3%A9sum%C3%A9.pdf; filename=\"r_sum_.pdf\"",
+        ] {
+            assert_eq!(file_name(disposition).as_deref(), Some("résumé.pdf"));
+        }
+        // Only UTF-8 is decoded; anything else falls back to `filename`
+        let disposition = "attachment; filename=\"plain.txt\"; filename*=ISO-8859-1''caf%E9.txt";
+        assert_eq!(file_name(disposition).as_deref(), Some("plain.txt"));
+    }
+
+    #[test]
+    fn quoted_file_names_keep_their_separators() {
+        for (disposition, expected) in [
+            ("attachment; filename=\"a;b.txt\"", "a;b.txt"),
+            (
+                "inline; filename=\"x; filename=y.txt\"",
+                "x; filename=y.txt",
+            ),
+            (
+                "attachment; filename=\"say \\\"hi\\\".txt\"",
+                "say \"hi\".txt",
+            ),
+            ("attachment; FILENAME=plain.txt", "plain.txt"),
+        ] {
+            assert_eq!(
+                file_name(disposition).as_deref(),
+                Some(expected),
+                "{disposition}"
+            );
+        }
+    }
+
+    #[tokio::test]
+    async fn problem_details_become_api_errors() {
+        let body = r#"{
+            "type": "https://secure-share/problems/wrong_credentials",
+            "title": "Unauthorized",
+            "status": 401,
+            "detail": "Email or password is wrong",
+            "code": "wrong_credentials",
+            "correlation_id": "abc"
+        }"#;
+        let err = check(response(401, body)).await.unwrap_err();
+        let Error::Api(problem) = &err else {
+            panic!("{err:?}");
+        };
+        assert_eq!(problem.correlation_id.as_deref(), Some("abc"));
+        assert_eq!(err.status(), Some(401));
+        assert_eq!(err.code(), Some("wrong_credentials"));
+    }
+
+    #[tokio::test]
+    async fn other_error_bodies_are_kept_as_they_are() {
+        for body in ["<html>502 Bad Gateway</html>", "", r#"{"error": "nope"}"#] {
+            let err = check(response(502, body)).await.unwrap_err();
+            let Error::Unexpected { status, body: kept } = &err else {
+                panic!("{err:?}");
+            };
+            assert_eq!((*status, kept.as_str()), (502, body));
+            assert_eq!(err.code(), None);
+        }
+    }
+
+    #[tokio::test]
+    async fn successful_responses_pass_through() {
+        let response = check(response(204, "")).await.unwrap();
+        assert_eq!(response.status(), 204);
+    }
+}
@@ -0,0 +1,7 @@
+//! The browser build of `secure-share-crypto`, e.g.
+//! `wasm-pack build crates/secure-share-crypto-wasm --target web`. It is a
+//! crate of its own so `secure-share-crypto` stays an rlib, which builds
+//! without `std` where a `cdylib` could not.
+
+#[cfg(target_arch = "wasm32")]
+pub use secure_share_crypto::{Envelope, SealedFile, decrypt_file, decrypt_metadata, encrypt_file};
@@ -0,0 +1,68 @@
+use core::fmt;
+
+#[derive(Debug)]
+pub enum Error {
+    /// Wrapping or unwrapping the AES key failed, e.g. with the wrong private key
+    Rsa(rsa::Error),
+    /// Wrapping or unwrapping the AES key with HPKE failed
+    Hpke(hpke::HpkeError),
+    /// A PEM key could not be parsed
+    Key(rsa::pkcs1::Error),
+    /// A PEM key parsed but is neither RSA nor X25519
+    UnsupportedKey,
+    /// A sender signature is malformed or does not match the file
+    Signature(ed25519_dalek::SignatureError),
+    /// The unwrapped AES key or the IV has the wrong length
+    InvalidLength,
+    /// The ciphertext is truncated or was not decrypted with its own key
+    Padding,
+    /// Decrypted metadata is not the JSON of a [`FileMetadata`](crate::FileMetadata)
+    Metadata(serde_json::Error),
+}
+
+impl fmt::Display for Error {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Error::Rsa(err) => write!(f, "rsa: {}", err),
+            Error::Hpke(err) => write!(f, "hpke: {}", err),
+            Error::Key(err) => write!(f, "invalid key: {}", err),
+            Error::UnsupportedKey => write!(f, "unsupported key type"),
+            Error::Signature(err) => write!(f, "signature: {}", err),
+            Error::InvalidLength => write!(f, "invalid AES key or IV length"),
+            Error::Padding => write!(f, "invalid ciphertext padding"),
+            Error::Metadata(err) => write!(f, "metadata: {}", err),
+        }
+    }
+}
+
+impl core::error::Error for Error {}
+
+impl From<rsa::Error> for Error {
+    fn from(err: rsa::Error) -> Self {
+        Error::Rsa(err)
+    }
+}
+
+impl From<rsa::pkcs1::Error> for Error {
+    fn from(err: rsa::pkcs1::Error) -> Self {
+        Error::Key(err)
+    }
+}
+
+impl From<hpke::HpkeError> for Error {
+    fn from(err: hpke::HpkeError) -> Self {
+        Error::Hpke(err)
+    }
+}
+
+impl From<ed25519_dalek::SignatureError> for Error {
+    fn from(err: ed25519_dalek::SignatureError) -> Self {
+        Error::Signature(err)
+    }
+}
+
+impl From<serde_json::Error> for Error {
+    fn from(err: serde_json::Error) -> Self {
+        Error::Metadata(err)
+    }
+}
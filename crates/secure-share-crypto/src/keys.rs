@@ -0,0 +1,193 @@
+use alloc::{boxed::Box, string::String, vec::Vec};
+use core::fmt;
+
+use hpke::{Deserializable, Kem as _, OpModeR, OpModeS, Serializable};
+use rand_core::CryptoRngCore;
+use rsa::{
+    Pkcs1v15Encrypt, RsaPrivateKey, RsaPublicKey,
+    pkcs1::{
+        DecodeRsaPrivateKey, DecodeRsaPublicKey, EncodeRsaPrivateKey, EncodeRsaPublicKey,
+        pem::{self, LineEnding},
+    },
+};
+
+use crate::{Error, Result};
+
+/// Key encapsulation of the HPKE suite, DHKEM(X25519, HKDF-SHA256)
+pub type HpkeKem = hpke::kem::X25519HkdfSha256;
+pub type HpkeKdf = hpke::kdf::HkdfSha256;
+pub type HpkeAead = hpke::aead::ChaCha20Poly1305;
+
+/// Binds every wrapped file key to this use, so a ciphertext made for
+/// another protocol with the same key pair never opens here.
+pub const HPKE_INFO: &[u8] = b"secure-share file key v1";
+
+const RSA_KEY_BITS: usize = 2048;
+const ENCAPPED_KEY_SIZE: usize = 32;
+
+// DER prefixes of RFC 8410 X25519 keys; the 32 raw key bytes follow.
+const X25519_SPKI_PREFIX: [u8; 12] = [
+    0x30, 0x2a, 0x30, 0x05, 0x06, 0x03, 0x2b, 0x65, 0x6e, 0x03, 0x21, 0x00,
+];
+const X25519_PKCS8_PREFIX: [u8; 16] = [
+    0x30, 0x2e, 0x02, 0x01, 0x00, 0x30, 0x05, 0x06, 0x03, 0x2b, 0x65, 0x6e, 0x04, 0x22, 0x04, 0x20,
+];
+
+/// The public half of a recipient's key pair.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum PublicKey {
+    /// Wraps file keys with PKCS#1 v1.5, kept for accounts created before
+    /// X25519 was supported
+    Rsa(RsaPublicKey),
+    /// Wraps file keys with single-shot HPKE, see [`HPKE_INFO`]
+    X25519(<HpkeKem as hpke::Kem>::PublicKey),
+}
+
+/// The private half of a recipient's key pair.
+#[derive(Clone)]
+pub enum PrivateKey {
+    Rsa(Box<RsaPrivateKey>),
+    X25519(<HpkeKem as hpke::Kem>::PrivateKey),
+}
+
+impl PublicKey {
+    /// Parses a PKCS#1 `RSA PUBLIC KEY` or an RFC 8410 X25519 `PUBLIC KEY`
+    /// PEM, the formats of the base64 decoded `public_key` the API returns.
+    pub fn from_pem(pem: &str) -> Result<Self> {
+        match pem::decode_label(pem.as_bytes()).map_err(rsa::pkcs1::Error::from)? {
+            "RSA PUBLIC KEY" => Ok(PublicKey::Rsa(RsaPublicKey::from_pkcs1_pem(pem)?)),
+            "PUBLIC KEY" => {
+                let der = decode_pem(pem)?;
+                let raw = strip_prefix(&der, &X25519_SPKI_PREFIX)?;
+                Ok(PublicKey::X25519(Deserializable::from_bytes(raw)?))
+            }
+            _ => Err(Error::UnsupportedKey),
+        }
+    }
+
+    pub fn to_pem(&self) -> Result<String> {
+        match self {
+            PublicKey::Rsa(key) => Ok(key.to_pkcs1_pem(LineEnding::LF)?),
+            PublicKey::X25519(key) => {
+                encode_pem("PUBLIC KEY", &X25519_SPKI_PREFIX, &key.to_bytes())
+            }
+        }
+    }
+
+    /// Encrypts a short secret, in practice a file key, for the holder of
+    /// the matching private key.
+    pub fn wrap(&self, rng: &mut impl CryptoRngCore, secret: &[u8]) -> Result<Vec<u8>> {
+        match self {
+            PublicKey::Rsa(key) => Ok(key.encrypt(rng, Pkcs1v15Encrypt, secret)?),
+            PublicKey::X25519(key) => {
+                let (encapped_key, ciphertext) =
+                    hpke::single_shot_seal::<HpkeAead, HpkeKdf, HpkeKem, _>(
+                        &OpModeS::Base,
+                        key,
+                        HPKE_INFO,
+                        secret,
+                        &[],
+                        rng,
+                    )?;
+
+                let mut wrapped = encapped_key.to_bytes().to_vec();
+                wrapped.extend_from_slice(&ciphertext);
+                Ok(wrapped)
+            }
+        }
+    }
+}
+
+impl PrivateKey {
+    /// Generates an RSA-2048 key pair. This takes long enough that async
+    /// callers should run it off their executor.
+    pub fn generate_rsa(rng: &mut impl CryptoRngCore) -> Result<Self> {
+        let key = RsaPrivateKey::new(rng, RSA_KEY_BITS)?;
+        Ok(PrivateKey::Rsa(Box::new(key)))
+    }
+
+    pub fn generate_x25519(rng: &mut impl CryptoRngCore) -> Self {
+        let (private_key, _) = HpkeKem::gen_keypair(rng);
+        PrivateKey::X25519(private_key)
+    }
+
+    /// Parses a PKCS#1 `RSA PRIVATE KEY` or an RFC 8410 X25519 PKCS#8
+    /// `PRIVATE KEY` PEM, as the server writes them.
+    pub fn from_pem(pem: &str) -> Result<Self> {
+        match pem::decode_label(pem.as_bytes()).map_err(rsa::pkcs1::Error::from)? {
+            "RSA PRIVATE KEY" => {
+                let key = RsaPrivateKey::from_pkcs1_pem(pem)?;
+                Ok(PrivateKey::Rsa(Box::new(key)))
+            }
+            "PRIVATE KEY" => {
+                let der = decode_pem(pem)?;
+                let raw = strip_prefix(&der, &X25519_PKCS8_PREFIX)?;
+                Ok(PrivateKey::X25519(Deserializable::from_bytes(raw)?))
+            }
+            _ => Err(Error::UnsupportedKey),
+        }
+    }
+
+    pub fn to_pem(&self) -> Result<String> {
+        match self {
+            PrivateKey::Rsa(key) => Ok(String::from(key.to_pkcs1_pem(LineEnding::LF)?.as_str())),
+            PrivateKey::X25519(key) => {
+                encode_pem("PRIVATE KEY", &X25519_PKCS8_PREFIX, &key.to_bytes())
+            }
+        }
+    }
+
+    pub fn public_key(&self) -> PublicKey {
+        match self {
+            PrivateKey::Rsa(key) => PublicKey::Rsa(key.to_public_key()),
+            PrivateKey::X25519(key) => PublicKey::X25519(HpkeKem::sk_to_pk(key)),
+        }
+    }
+
+    /// Decrypts the result of [`PublicKey::wrap`].
+    pub fn unwrap(&self, wrapped: &[u8]) -> Result<Vec<u8>> {
+        match self {
+            PrivateKey::Rsa(key) => Ok(key.decrypt(Pkcs1v15Encrypt, wrapped)?),
+            PrivateKey::X25519(key) => {
+                if wrapped.len() < ENCAPPED_KEY_SIZE {
+                    return Err(Error::InvalidLength);
+                }
+                let (encapped_key, ciphertext) = wrapped.split_at(ENCAPPED_KEY_SIZE);
+                let encapped_key = Deserializable::from_bytes(encapped_key)?;
+
+                Ok(hpke::single_shot_open::<HpkeAead, HpkeKdf, HpkeKem>(
+                    &OpModeR::Base,
+                    key,
+                    &encapped_key,
+                    HPKE_INFO,
+                    ciphertext,
+                    &[],
+                )?)
+            }
+        }
+    }
+}
+
+impl fmt::Debug for PrivateKey {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            PrivateKey::Rsa(_) => f.write_str("PrivateKey::Rsa(..)"),
+            PrivateKey::X25519(_) => f.write_str("PrivateKey::X25519(..)"),
+        }
+    }
+}
+
+fn decode_pem(pem: &str) -> Result<Vec<u8>> {
+    let (_, der) = pem::decode_vec(pem.as_bytes()).map_err(rsa::pkcs1::Error::from)?;
+    Ok(der)
+}
+
+fn encode_pem(label: &str, prefix: &[u8], raw: &[u8]) -> Result<String> {
+    let mut der = prefix.to_vec();
+    der.extend_from_slice(raw);
+    Ok(pem::encode_string(label, LineEnding::LF, &der).map_err(rsa::pkcs1::Error::from)?)
+}
+
+fn strip_prefix<'a>(der: &'a [u8], prefix: &[u8]) -> Result<&'a [u8]> {
+    der.strip_prefix(prefix).ok_or(Error::UnsupportedKey)
+}
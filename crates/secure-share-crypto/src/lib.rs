@@ -0,0 +1,178 @@
+//! Envelope encryption of shared files, used by the server and, through the
+//! `wasm` feature, by browsers encrypting before upload.
+//!
+//! A file is encrypted with a fresh AES-256-CBC key and PKCS#7 padding, and
+//! that key is encrypted for the recipient's [`PublicKey`]: single-shot HPKE
+//! with X25519, HKDF-SHA256 and ChaCha20-Poly1305, or RSA PKCS#1 v1.5 for
+//! key pairs made before X25519 was supported. The three
+//! parts of an [`Envelope`] are what the server stores for every file. The
+//! same [`FileKey`] seals the file's metadata and may be wrapped for the
+//! sender as well, who signs the result with Ed25519, see [`SignedFile`].
+//! [`seal_file`] does all of that where the file is encrypted, producing the
+//! version 2 envelope, a [`SealedFile`].
+
+#![no_std]
+
+extern crate alloc;
+#[cfg(feature = "std")]
+extern crate std;
+
+mod error;
+mod keys;
+mod sealed;
+mod signing;
+#[cfg(feature = "wasm")]
+mod wasm;
+
+use aes::Aes256;
+use alloc::vec::Vec;
+use cbc::cipher::{BlockDecryptMut, BlockEncryptMut, KeyIvInit, block_padding::Pkcs7};
+use rand_core::CryptoRngCore;
+
+pub use ed25519_dalek::{Signature, SigningKey, VerifyingKey};
+pub use error::Error;
+pub use hpke;
+pub use keys::{HPKE_INFO, HpkeAead, HpkeKdf, HpkeKem, PrivateKey, PublicKey};
+pub use rsa;
+pub use sealed::{ENVELOPE_VERSION, FileMetadata, Recipient, SealedFile, Sender, seal_file};
+pub use signing::{
+    SignedFile, content_digest, signing_key_from_pem, signing_key_to_pem, verifying_key_from_pem,
+    verifying_key_to_pem,
+};
+#[cfg(feature = "wasm")]
+pub use wasm::{decrypt_file, decrypt_metadata, encrypt_file};
+
+pub type Result<T, E = Error> = core::result::Result<T, E>;
+
+const AES_KEY_SIZE: usize = 32;
+const IV_SIZE: usize = 16;
+
+/// An encrypted file together with what its recipient needs to decrypt it.
+#[derive(Debug, Clone, PartialEq, Eq)]
+#[cfg_attr(
+    feature = "wasm",
+    wasm_bindgen::prelude::wasm_bindgen(getter_with_clone)
+)]
+pub struct Envelope {
+    /// AES key encrypted with the recipient's public key
+    pub encrypted_key: Vec<u8>,
+    pub ciphertext: Vec<u8>,
+    pub iv: Vec<u8>,
+}
+
+/// Encrypts `data` so only the holder of the private key of `public_key` can
+/// read it.
+pub fn encrypt(
+    rng: &mut impl CryptoRngCore,
+    data: &[u8],
+    public_key: &PublicKey,
+) -> Result<Envelope> {
+    FileKey::generate(rng).envelope(rng, data, public_key)
+}
+
+/// Decrypts an [`Envelope`] with the recipient's private key.
+pub fn decrypt(
+    encrypted_key: &[u8],
+    ciphertext: &[u8],
+    iv: &[u8],
+    private_key: &PrivateKey,
+) -> Result<Vec<u8>> {
+    FileKey::unwrap_with(encrypted_key, private_key)?.decrypt(ciphertext, iv)
+}
+
+/// The AES key of one file. Besides the contents it can seal the file's
+/// metadata and be wrapped for more than one reader.
+pub struct FileKey([u8; AES_KEY_SIZE]);
+
+impl FileKey {
+    pub fn generate(rng: &mut impl CryptoRngCore) -> Self {
+        let mut key = [0u8; AES_KEY_SIZE];
+        rng.fill_bytes(&mut key);
+        FileKey(key)
+    }
+
+    /// Recovers the key from the `encrypted_key` of an [`Envelope`] or the
+    /// result of [`FileKey::wrap_for`].
+    pub fn unwrap_with(encrypted_key: &[u8], private_key: &PrivateKey) -> Result<Self> {
+        let key = private_key.unwrap(encrypted_key)?;
+        let key = key.try_into().map_err(|_| Error::InvalidLength)?;
+        Ok(FileKey(key))
+    }
+
+    /// Encrypts the key for the holder of the private key of `public_key`.
+    pub fn wrap_for(
+        &self,
+        rng: &mut impl CryptoRngCore,
+        public_key: &PublicKey,
+    ) -> Result<Vec<u8>> {
+        public_key.wrap(rng, &self.0)
+    }
+
+    /// Encrypts `data` under this key, wrapped for `public_key`.
+    pub fn envelope(
+        &self,
+        rng: &mut impl CryptoRngCore,
+        data: &[u8],
+        public_key: &PublicKey,
+    ) -> Result<Envelope> {
+        let mut iv = [0u8; IV_SIZE];
+        rng.fill_bytes(&mut iv);
+
+        Ok(Envelope {
+            encrypted_key: self.wrap_for(rng, public_key)?,
+            ciphertext: self.encrypt(&iv, data),
+            iv: iv.to_vec(),
+        })
+    }
+
+    pub fn decrypt(&self, ciphertext: &[u8], iv: &[u8]) -> Result<Vec<u8>> {
+        let cipher = cbc::Decryptor::<Aes256>::new_from_slices(&self.0, iv)
+            .map_err(|_| Error::InvalidLength)?;
+
+        cipher
+            .decrypt_padded_vec_mut::<Pkcs7>(ciphertext)
+            .map_err(|_| Error::Padding)
+    }
+
+    /// Encrypts `data` with a fresh IV, which is prepended to the result.
+    pub fn seal(&self, rng: &mut impl CryptoRngCore, data: &[u8]) -> Vec<u8> {
+        let mut iv = [0u8; IV_SIZE];
+        rng.fill_bytes(&mut iv);
+
+        let mut sealed = iv.to_vec();
+        sealed.extend_from_slice(&self.encrypt(&iv, data));
+        sealed
+    }
+
+    /// Decrypts the result of [`FileKey::seal`].
+    pub fn open(&self, sealed: &[u8]) -> Result<Vec<u8>> {
+        if sealed.len() < IV_SIZE {
+            return Err(Error::InvalidLength);
+        }
+        let (iv, ciphertext) = sealed.split_at(IV_SIZE);
+        self.decrypt(ciphertext, iv)
+    }
+
+    fn encrypt(&self, iv: &[u8; IV_SIZE], data: &[u8]) -> Vec<u8> {
+        cbc::Encryptor::<Aes256>::new(&self.0.into(), iv.into())
+            .encrypt_padded_vec_mut::<Pkcs7>(data)
+    }
+}
+
+impl core::fmt::Debug for FileKey {
+    fn fmt(&self, f: &mut core::fmt::Formatter<'_>) -> core::fmt::Result {
+        f.write_str("FileKey(..)")
+    }
+}
+
+/// Parses a private key PEM as the server writes them, see
+/// [`PrivateKey::from_pem`].
+pub fn private_key_from_pem(pem: &str) -> Result<PrivateKey> {
+    PrivateKey::from_pem(pem)
+}
+
+/// Parses the base64 decoded `public_key` the API returns, see
+/// [`PublicKey::from_pem`].
+pub fn public_key_from_pem(pem: &str) -> Result<PublicKey> {
+    PublicKey::from_pem(pem)
+}
@@ -0,0 +1,93 @@
+use alloc::{string::String, vec::Vec};
+
+use rand_core::CryptoRngCore;
+use serde::{Deserialize, Serialize};
+
+use crate::{FileKey, PublicKey, Result, SignedFile, SigningKey, content_digest};
+
+/// Version of the stored envelope a [`SealedFile`] holds. Version 1 is an
+/// [`Envelope`](crate::Envelope), whose metadata, sender key and signature
+/// only the server could add.
+pub const ENVELOPE_VERSION: u8 = 2;
+
+/// Descriptive fields of a file, sealed as JSON into `encrypted_metadata`.
+#[derive(Debug, Clone, PartialEq, Eq, Deserialize, Serialize)]
+pub struct FileMetadata {
+    pub file_name: String,
+    pub content_type: String,
+}
+
+impl FileMetadata {
+    pub fn to_json(&self) -> Result<Vec<u8>> {
+        Ok(serde_json::to_vec(self)?)
+    }
+
+    pub fn from_json(json: &[u8]) -> Result<Self> {
+        Ok(serde_json::from_slice(json)?)
+    }
+}
+
+/// The sender of a file, who can read it back and signs it.
+pub struct Sender<'a> {
+    pub id: [u8; 16],
+    pub public_key: &'a PublicKey,
+    pub signing_key: &'a SigningKey,
+}
+
+/// The user a file is shared with.
+pub struct Recipient<'a> {
+    pub id: [u8; 16],
+    pub public_key: &'a PublicKey,
+}
+
+/// Everything the server stores for a file, made by its sender.
+#[derive(Debug, Clone, PartialEq, Eq)]
+#[cfg_attr(
+    feature = "wasm",
+    wasm_bindgen::prelude::wasm_bindgen(getter_with_clone)
+)]
+pub struct SealedFile {
+    /// Always [`ENVELOPE_VERSION`]
+    pub version: u8,
+    /// AES key encrypted with the recipient's public key
+    pub encrypted_key: Vec<u8>,
+    /// AES key encrypted with the sender's public key
+    pub sender_encrypted_key: Vec<u8>,
+    pub ciphertext: Vec<u8>,
+    pub iv: Vec<u8>,
+    /// [`FileMetadata`] as JSON, sealed with the AES key, see [`FileKey::seal`]
+    pub encrypted_metadata: Vec<u8>,
+    /// The sender's Ed25519 signature over the [`SignedFile`]
+    pub signature: Vec<u8>,
+}
+
+/// Encrypts `data` and `metadata` for `recipient` and `sender`, and signs the
+/// result with the sender's key.
+pub fn seal_file(
+    rng: &mut impl CryptoRngCore,
+    data: &[u8],
+    metadata: &FileMetadata,
+    sender: &Sender,
+    recipient: &Recipient,
+) -> Result<SealedFile> {
+    let key = FileKey::generate(rng);
+    let envelope = key.envelope(rng, data, recipient.public_key)?;
+    let signed_file = SignedFile {
+        sender_id: sender.id,
+        recipient_id: recipient.id,
+        content_digest: content_digest(&envelope.iv, &envelope.ciphertext),
+        file_name: &metadata.file_name,
+        content_type: &metadata.content_type,
+        file_size: data.len() as u64,
+    };
+
+    Ok(SealedFile {
+        version: ENVELOPE_VERSION,
+        sender_encrypted_key: key.wrap_for(rng, sender.public_key)?,
+        encrypted_metadata: key.seal(rng, &metadata.to_json()?),
+        signature: signed_file.sign(sender.signing_key).to_bytes().to_vec(),
+        encrypted_key: envelope.encrypted_key,
+        ciphertext: envelope.ciphertext,
+        iv: envelope.iv,
+    })
+}
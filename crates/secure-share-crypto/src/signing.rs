@@ -0,0 +1,92 @@
+use alloc::string::String;
+
+use ed25519_dalek::{
+    Signature, SigningKey, VerifyingKey,
+    pkcs8::{DecodePrivateKey, DecodePublicKey, EncodePrivateKey, EncodePublicKey},
+};
+use rsa::pkcs1::LineEnding;
+use sha2::{Digest, Sha256};
+
+use crate::{Error, Result};
+
+/// Separates file signatures from anything else the same key might sign.
+const SIGNATURE_CONTEXT: &[u8] = b"secure-share file signature v1";
+
+/// What the sender of a file signs: who sent it to whom, the contents as
+/// stored and the metadata the recipient is shown.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct SignedFile<'a> {
+    pub sender_id: [u8; 16],
+    pub recipient_id: [u8; 16],
+    /// See [`content_digest`]
+    pub content_digest: [u8; 32],
+    pub file_name: &'a str,
+    pub content_type: &'a str,
+    /// Size of the original file in bytes
+    pub file_size: u64,
+}
+
+/// SHA-256 of the IV followed by the ciphertext of an [`Envelope`](crate::Envelope).
+pub fn content_digest(iv: &[u8], ciphertext: &[u8]) -> [u8; 32] {
+    let mut hasher = Sha256::new();
+    hasher.update((iv.len() as u64).to_be_bytes());
+    hasher.update(iv);
+    hasher.update(ciphertext);
+    hasher.finalize().into()
+}
+
+impl SignedFile<'_> {
+    /// SHA-256 over every field, which is what the signature covers. Strings
+    /// are length prefixed so no two files share a digest by moving bytes
+    /// from one field to the next.
+    pub fn digest(&self) -> [u8; 32] {
+        let mut hasher = Sha256::new();
+        hasher.update(SIGNATURE_CONTEXT);
+        hasher.update(self.sender_id);
+        hasher.update(self.recipient_id);
+        hasher.update(self.content_digest);
+        for field in [self.file_name, self.content_type] {
+            hasher.update((field.len() as u64).to_be_bytes());
+            hasher.update(field.as_bytes());
+        }
+        hasher.update(self.file_size.to_be_bytes());
+        hasher.finalize().into()
+    }
+
+    pub fn sign(&self, signing_key: &SigningKey) -> Signature {
+        use ed25519_dalek::Signer;
+
+        signing_key.sign(&self.digest())
+    }
+
+    /// Checks a signature made by [`SignedFile::sign`] with the private half
+    /// of `verifying_key`.
+    pub fn verify(&self, verifying_key: &VerifyingKey, signature: &[u8]) -> Result<()> {
+        let signature = Signature::from_slice(signature)?;
+        Ok(verifying_key.verify_strict(&self.digest(), &signature)?)
+    }
+}
+
+/// Parses an RFC 8410 Ed25519 `PRIVATE KEY` PEM.
+pub fn signing_key_from_pem(pem: &str) -> Result<SigningKey> {
+    SigningKey::from_pkcs8_pem(pem).map_err(|err| Error::Key(err.into()))
+}
+
+pub fn signing_key_to_pem(signing_key: &SigningKey) -> Result<String> {
+    let pem = signing_key
+        .to_pkcs8_pem(LineEnding::LF)
+        .map_err(|err| Error::Key(err.into()))?;
+    Ok(String::from(pem.as_str()))
+}
+
+/// Parses an RFC 8410 Ed25519 `PUBLIC KEY` PEM, the format of the base64
+/// decoded `signing_key` the API returns.
+pub fn verifying_key_from_pem(pem: &str) -> Result<VerifyingKey> {
+    VerifyingKey::from_public_key_pem(pem).map_err(|err| Error::Key(err.into()))
+}
+
+pub fn verifying_key_to_pem(verifying_key: &VerifyingKey) -> Result<String> {
+    verifying_key
+        .to_public_key_pem(LineEnding::LF)
+        .map_err(|err| Error::Key(err.into()))
+}
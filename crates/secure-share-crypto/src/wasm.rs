@@ -0,0 +1,82 @@
+use alloc::{
+    string::{String, ToString},
+    vec::Vec,
+};
+
+use rand_core::OsRng;
+use wasm_bindgen::prelude::*;
+
+use crate::{FileMetadata, Recipient, SealedFile, Sender};
+
+/// Encrypts a file for the recipient and the sender, whose ids are the 16
+/// bytes of their UUIDs, and signs it with the sender's Ed25519 signing key.
+/// The result is the version 2 envelope the server stores.
+#[allow(clippy::too_many_arguments)]
+#[wasm_bindgen(js_name = encryptFile)]
+pub fn encrypt_file(
+    data: &[u8],
+    file_name: String,
+    content_type: String,
+    sender_id: &[u8],
+    recipient_id: &[u8],
+    recipient_public_key_pem: &str,
+    sender_public_key_pem: &str,
+    signing_key_pem: &str,
+) -> Result<SealedFile, JsError> {
+    let recipient_public_key =
+        crate::public_key_from_pem(recipient_public_key_pem).map_err(js_error)?;
+    let sender_public_key = crate::public_key_from_pem(sender_public_key_pem).map_err(js_error)?;
+    let signing_key = crate::signing_key_from_pem(signing_key_pem).map_err(js_error)?;
+    let metadata = FileMetadata {
+        file_name,
+        content_type,
+    };
+    let sender = Sender {
+        id: user_id(sender_id)?,
+        public_key: &sender_public_key,
+        signing_key: &signing_key,
+    };
+    let recipient = Recipient {
+        id: user_id(recipient_id)?,
+        public_key: &recipient_public_key,
+    };
+    crate::seal_file(&mut OsRng, data, &metadata, &sender, &recipient).map_err(js_error)
+}
+
+/// Decrypts a downloaded envelope with the recipient's private key PEM.
+#[wasm_bindgen(js_name = decryptFile)]
+pub fn decrypt_file(
+    encrypted_key: &[u8],
+    ciphertext: &[u8],
+    iv: &[u8],
+    private_key_pem: &str,
+) -> Result<Vec<u8>, JsError> {
+    let private_key = crate::private_key_from_pem(private_key_pem).map_err(js_error)?;
+    crate::decrypt(encrypted_key, ciphertext, iv, &private_key).map_err(js_error)
+}
+
+/// Opens the `encrypted_metadata` of a version 2 envelope, returning the
+/// metadata JSON: `{"file_name": ..., "content_type": ...}`.
+#[wasm_bindgen(js_name = decryptMetadata)]
+pub fn decrypt_metadata(
+    encrypted_key: &[u8],
+    encrypted_metadata: &[u8],
+    private_key_pem: &str,
+) -> Result<String, JsError> {
+    let private_key = crate::private_key_from_pem(private_key_pem).map_err(js_error)?;
+    let metadata = crate::FileKey::unwrap_with(encrypted_key, &private_key)
+        .and_then(|key| key.open(encrypted_metadata))
+        .map_err(js_error)?;
+    // Checked so callers can rely on the shape
+    FileMetadata::from_json(&metadata).map_err(js_error)?;
+    String::from_utf8(metadata).map_err(|err| JsError::new(&err.to_string()))
+}
+
+fn user_id(id: &[u8]) -> Result<[u8; 16], JsError> {
+    id.try_into()
+        .map_err(|_| JsError::new("user ids are the 16 bytes of a UUID"))
+}
+
+fn js_error(err: crate::Error) -> JsError {
+    JsError::new(&err.to_string())
+}
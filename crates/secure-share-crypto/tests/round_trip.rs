@@ -0,0 +1,425 @@
+//! Runs natively with `cargo test` and on wasm32 with
+//! `wasm-pack test --node -- --features wasm`, so both builds are held to the
+//! same envelope encrypted by openssl, the same file key wrapped with HPKE by
+//! Python's `cryptography`, the RFC 9180 test vector and a sender signature
+//! made by `cryptography`.
+
+use rand_core::OsRng;
+use secure_share_crypto::{
+    ENVELOPE_VERSION, Envelope, Error, FileKey, FileMetadata, HpkeAead, HpkeKdf, HpkeKem,
+    PrivateKey, PublicKey, Recipient, Sender, SignedFile, SigningKey, content_digest, decrypt,
+    encrypt,
+    hpke::{self, Deserializable, OpModeR},
+    private_key_from_pem, public_key_from_pem, seal_file, signing_key_from_pem, signing_key_to_pem,
+    verifying_key_from_pem, verifying_key_to_pem,
+};
+#[cfg(target_arch = "wasm32")]
+use wasm_bindgen_test::wasm_bindgen_test as test;
+
+const PRIVATE_KEY: &str = include_str!("fixtures/private_key.pem");
+const PUBLIC_KEY: &str = include_str!("fixtures/public_key.pem");
+const X25519_PRIVATE_KEY: &str = include_str!("fixtures/x25519_private_key.pem");
+const X25519_PUBLIC_KEY: &str = include_str!("fixtures/x25519_public_key.pem");
+const SIGNING_KEY: &str = include_str!("fixtures/ed25519_private_key.pem");
+const VERIFYING_KEY: &str = include_str!("fixtures/ed25519_public_key.pem");
+
+fn fixture() -> Envelope {
+    Envelope {
+        encrypted_key: include_bytes!("fixtures/encrypted_key.bin").to_vec(),
+        ciphertext: include_bytes!("fixtures/ciphertext.bin").to_vec(),
+        iv: include_bytes!("fixtures/iv.bin").to_vec(),
+    }
+}
+
+fn key_pairs() -> [(PublicKey, PrivateKey); 2] {
+    [
+        (PUBLIC_KEY, PRIVATE_KEY),
+        (X25519_PUBLIC_KEY, X25519_PRIVATE_KEY),
+    ]
+    .map(|(public_key, private_key)| {
+        (
+            public_key_from_pem(public_key).unwrap(),
+            private_key_from_pem(private_key).unwrap(),
+        )
+    })
+}
+
+fn round_trip(data: &[u8], public_key: &PublicKey, private_key: &PrivateKey) -> Vec<u8> {
+    let envelope = encrypt(&mut OsRng, data, public_key).unwrap();
+    assert_eq!(envelope.iv.len(), 16);
+    assert_eq!(envelope.ciphertext.len(), (data.len() / 16 + 1) * 16);
+
+    decrypt(
+        &envelope.encrypted_key,
+        &envelope.ciphertext,
+        &envelope.iv,
+        private_key,
+    )
+    .unwrap()
+}
+
+#[test]
+fn decrypts_openssl_envelope() {
+    let private_key = private_key_from_pem(PRIVATE_KEY).unwrap();
+    let envelope = fixture();
+
+    let data = decrypt(
+        &envelope.encrypted_key,
+        &envelope.ciphertext,
+        &envelope.iv,
+        &private_key,
+    )
+    .unwrap();
+
+    assert_eq!(data, include_bytes!("fixtures/plaintext.txt"));
+}
+
+#[test]
+fn decrypts_envelope_with_hpke_wrapped_key() {
+    let private_key = private_key_from_pem(X25519_PRIVATE_KEY).unwrap();
+    assert!(matches!(private_key, PrivateKey::X25519(_)));
+    let envelope = fixture();
+
+    // The same file key as the openssl envelope, wrapped for the X25519 key
+    let data = decrypt(
+        include_bytes!("fixtures/hpke_encrypted_key.bin"),
+        &envelope.ciphertext,
+        &envelope.iv,
+        &private_key,
+    )
+    .unwrap();
+
+    assert_eq!(data, include_bytes!("fixtures/plaintext.txt"));
+}
+
+#[test]
+fn opens_rfc_9180_test_vector() {
+    // RFC 9180 A.2.1, DHKEM(X25519, HKDF-SHA256), HKDF-SHA256,
+    // ChaCha20Poly1305, sequence number 0
+    let private_key = <HpkeKem as hpke::Kem>::PrivateKey::from_bytes(&hex(
+        "8057991eef8f1f1af18f4a9491d16a1ce333f695d4db8e38da75975c4478e0fb",
+    ))
+    .unwrap();
+    let encapped_key = <HpkeKem as hpke::Kem>::EncappedKey::from_bytes(&hex(
+        "1afa08d3dec047a643885163f1180476fa7ddb54c6a8029ea33f95796bf2ac4a",
+    ))
+    .unwrap();
+
+    let plaintext = hpke::single_shot_open::<HpkeAead, HpkeKdf, HpkeKem>(
+        &OpModeR::Base,
+        &private_key,
+        &encapped_key,
+        &hex("4f6465206f6e2061204772656369616e2055726e"),
+        &hex(concat!(
+            "1c5250d8034ec2b784ba2cfd69dbdb8af406cfe3ff938e131f0def8c8b60b4db",
+            "21993c62ce81883d2dd1b51a28"
+        )),
+        &hex("436f756e742d30"),
+    )
+    .unwrap();
+    assert_eq!(plaintext, b"Beauty is truth, truth beauty");
+
+    let public_key = PrivateKey::X25519(private_key).public_key();
+    let expected = <HpkeKem as hpke::Kem>::PublicKey::from_bytes(&hex(
+        "4310ee97d88cc1f088a5576c77ab0cf5c3ac797f3d95139c6c84b5429c59662a",
+    ))
+    .unwrap();
+    assert_eq!(public_key, PublicKey::X25519(expected));
+}
+
+#[test]
+fn round_trips_across_block_boundaries() {
+    for (public_key, private_key) in key_pairs() {
+        for len in [0, 1, 15, 16, 17, 4096] {
+            let data: Vec<u8> = (0..len).map(|i| i as u8).collect();
+            assert_eq!(round_trip(&data, &public_key, &private_key), data);
+        }
+    }
+}
+
+#[test]
+fn pem_keys_round_trip() {
+    for (public_key, private_key) in key_pairs() {
+        let pem = private_key.to_pem().unwrap();
+        let reparsed = private_key_from_pem(&pem).unwrap();
+        assert_eq!(reparsed.public_key(), public_key);
+        assert_eq!(
+            public_key_from_pem(&public_key.to_pem().unwrap()).unwrap(),
+            public_key
+        );
+    }
+
+    let generated = PrivateKey::generate_x25519(&mut OsRng);
+    let pem = generated.public_key().to_pem().unwrap();
+    assert!(pem.starts_with("-----BEGIN PUBLIC KEY-----"));
+    assert_eq!(public_key_from_pem(&pem).unwrap(), generated.public_key());
+
+    let certificate = PUBLIC_KEY.replace("RSA PUBLIC KEY", "CERTIFICATE");
+    assert!(matches!(
+        public_key_from_pem(&certificate),
+        Err(Error::UnsupportedKey)
+    ));
+}
+
+#[test]
+fn rejects_tampered_iv_or_ciphertext() {
+    let private_key = private_key_from_pem(PRIVATE_KEY).unwrap();
+    let envelope = fixture();
+
+    let short_iv = decrypt(
+        &envelope.encrypted_key,
+        &envelope.ciphertext,
+        &envelope.iv[..8],
+        &private_key,
+    );
+    assert!(matches!(short_iv, Err(Error::InvalidLength)));
+
+    let truncated = decrypt(
+        &envelope.encrypted_key,
+        &envelope.ciphertext[..envelope.ciphertext.len() - 1],
+        &envelope.iv,
+        &private_key,
+    );
+    assert!(matches!(truncated, Err(Error::Padding)));
+}
+
+#[test]
+fn rejects_garbled_encrypted_key() {
+    let private_key = private_key_from_pem(PRIVATE_KEY).unwrap();
+    let mut envelope = fixture();
+    envelope.encrypted_key[0] ^= 0xff;
+
+    let result = decrypt(
+        &envelope.encrypted_key,
+        &envelope.ciphertext,
+        &envelope.iv,
+        &private_key,
+    );
+    assert!(matches!(result, Err(Error::Rsa(_))));
+
+    let private_key = private_key_from_pem(X25519_PRIVATE_KEY).unwrap();
+    let mut encrypted_key = include_bytes!("fixtures/hpke_encrypted_key.bin").to_vec();
+    let last = encrypted_key.len() - 1;
+    encrypted_key[last] ^= 0xff;
+
+    let result = decrypt(
+        &encrypted_key,
+        &envelope.ciphertext,
+        &envelope.iv,
+        &private_key,
+    );
+    assert!(matches!(result, Err(Error::Hpke(_))));
+    let result = decrypt(
+        &encrypted_key[..16],
+        &envelope.ciphertext,
+        &envelope.iv,
+        &private_key,
+    );
+    assert!(matches!(result, Err(Error::InvalidLength)));
+}
+
+#[test]
+fn sealed_metadata_opens_with_the_file_key() {
+    let [
+        (public_key, private_key),
+        (sender_public_key, sender_private_key),
+    ] = key_pairs();
+    let key = FileKey::generate(&mut OsRng);
+    let envelope = key.envelope(&mut OsRng, b"contents", &public_key).unwrap();
+    let sealed = key.seal(&mut OsRng, b"report.pdf");
+    let wrapped = key.wrap_for(&mut OsRng, &sender_public_key).unwrap();
+    assert_ne!(sealed, key.seal(&mut OsRng, b"report.pdf"));
+
+    // Either wrapping of the key opens both the contents and the metadata
+    for (encrypted_key, private_key) in [
+        (&envelope.encrypted_key, &private_key),
+        (&wrapped, &sender_private_key),
+    ] {
+        let key = FileKey::unwrap_with(encrypted_key, private_key).unwrap();
+        assert_eq!(key.open(&sealed).unwrap(), b"report.pdf");
+        let contents = key.decrypt(&envelope.ciphertext, &envelope.iv).unwrap();
+        assert_eq!(contents, b"contents");
+    }
+
+    assert!(matches!(key.open(&sealed[..8]), Err(Error::InvalidLength)));
+}
+
+/// The file `signature.bin` was made for: the openssl envelope's contents,
+/// sent between two fixed users.
+fn signed_fixture(envelope: &Envelope) -> SignedFile<'static> {
+    let id = |hex_id: &str| hex(hex_id).try_into().unwrap();
+    SignedFile {
+        sender_id: id("6f1c2a4e0b7d4c399a572f0e8d3b1c64"),
+        recipient_id: id("d2b8e5f13a6c4e078b915c4f7a0e2d13"),
+        content_digest: content_digest(&envelope.iv, &envelope.ciphertext),
+        file_name: "plaintext.txt",
+        content_type: "text/plain",
+        file_size: include_bytes!("fixtures/plaintext.txt").len() as u64,
+    }
+}
+
+#[test]
+fn verifies_cryptography_signature() {
+    let signing_key = signing_key_from_pem(SIGNING_KEY).unwrap();
+    let verifying_key = verifying_key_from_pem(VERIFYING_KEY).unwrap();
+    assert_eq!(signing_key.verifying_key(), verifying_key);
+    let signature = include_bytes!("fixtures/signature.bin");
+    let file = signed_fixture(&fixture());
+
+    file.verify(&verifying_key, signature).unwrap();
+    // Ed25519 is deterministic, so signing again gives the same bytes
+    assert_eq!(file.sign(&signing_key).to_bytes(), *signature);
+}
+
+#[test]
+fn rejects_signature_for_another_file() {
+    let verifying_key = verifying_key_from_pem(VERIFYING_KEY).unwrap();
+    let signature = include_bytes!("fixtures/signature.bin");
+    let mut envelope = fixture();
+    envelope.ciphertext[0] ^= 0xff;
+    let file = signed_fixture(&fixture());
+
+    let tampered = [
+        signed_fixture(&envelope),
+        SignedFile {
+            recipient_id: file.sender_id,
+            ..file.clone()
+        },
+        SignedFile {
+            file_name: "plaintext.txt.exe",
+            ..file.clone()
+        },
+        SignedFile {
+            file_size: file.file_size + 1,
+            ..file.clone()
+        },
+    ];
+    for file in tampered {
+        let result = file.verify(&verifying_key, signature);
+        assert!(matches!(result, Err(Error::Signature(_))), "{:?}", file);
+    }
+
+    let other_key = SigningKey::generate(&mut OsRng).verifying_key();
+    let result = file.verify(&other_key, signature);
+    assert!(matches!(result, Err(Error::Signature(_))));
+    let result = file.verify(&verifying_key, &signature[..63]);
+    assert!(matches!(result, Err(Error::Signature(_))));
+}
+
+#[test]
+fn signing_keys_round_trip_through_pem() {
+    let signing_key = SigningKey::generate(&mut OsRng);
+    let pem = signing_key_to_pem(&signing_key).unwrap();
+    assert_eq!(signing_key_from_pem(&pem).unwrap(), signing_key);
+
+    let verifying_key = signing_key.verifying_key();
+    let pem = verifying_key_to_pem(&verifying_key).unwrap();
+    assert_eq!(verifying_key_from_pem(&pem).unwrap(), verifying_key);
+    assert!(verifying_key_from_pem(X25519_PUBLIC_KEY).is_err());
+}
+
+#[test]
+fn sealed_files_carry_everything_the_server_stores() {
+    let [
+        (public_key, private_key),
+        (sender_public_key, sender_private_key),
+    ] = key_pairs();
+    let signing_key = signing_key_from_pem(SIGNING_KEY).unwrap();
+    let data = include_bytes!("fixtures/plaintext.txt");
+    let metadata = FileMetadata {
+        file_name: "plaintext.txt".to_string(),
+        content_type: "text/plain".to_string(),
+    };
+    let sender = Sender {
+        id: [1; 16],
+        public_key: &sender_public_key,
+        signing_key: &signing_key,
+    };
+    let recipient = Recipient {
+        id: [2; 16],
+        public_key: &public_key,
+    };
+    let sealed = seal_file(&mut OsRng, data, &metadata, &sender, &recipient).unwrap();
+    assert_eq!(sealed.version, ENVELOPE_VERSION);
+
+    for (encrypted_key, private_key) in [
+        (&sealed.encrypted_key, &private_key),
+        (&sealed.sender_encrypted_key, &sender_private_key),
+    ] {
+        let key = FileKey::unwrap_with(encrypted_key, private_key).unwrap();
+        let opened = FileMetadata::from_json(&key.open(&sealed.encrypted_metadata).unwrap());
+        assert_eq!(opened.unwrap(), metadata);
+        assert_eq!(key.decrypt(&sealed.ciphertext, &sealed.iv).unwrap(), data);
+    }
+
+    let signed_file = SignedFile {
+        sender_id: sender.id,
+        recipient_id: recipient.id,
+        content_digest: content_digest(&sealed.iv, &sealed.ciphertext),
+        file_name: &metadata.file_name,
+        content_type: &metadata.content_type,
+        file_size: data.len() as u64,
+    };
+    let verifying_key = verifying_key_from_pem(VERIFYING_KEY).unwrap();
+    signed_file
+        .verify(&verifying_key, &sealed.signature)
+        .unwrap();
+}
+
+#[cfg(all(target_arch = "wasm32", feature = "wasm"))]
+#[test]
+fn bindings_round_trip() {
+    use secure_share_crypto::{decrypt_file, decrypt_metadata, encrypt_file};
+
+    let data = include_bytes!("fixtures/plaintext.txt");
+    let sealed = encrypt_file(
+        data,
+        "plaintext.txt".to_string(),
+        "text/plain".to_string(),
+        &[1; 16],
+        &[2; 16],
+        PUBLIC_KEY,
+        X25519_PUBLIC_KEY,
+        SIGNING_KEY,
+    )
+    .unwrap();
+    let decrypted = decrypt_file(
+        &sealed.encrypted_key,
+        &sealed.ciphertext,
+        &sealed.iv,
+        PRIVATE_KEY,
+    )
+    .unwrap();
+    assert_eq!(decrypted, data);
+
+    let metadata = decrypt_metadata(
+        &sealed.sender_encrypted_key,
+        &sealed.encrypted_metadata,
+        X25519_PRIVATE_KEY,
+    )
+    .unwrap();
+    assert_eq!(
+        metadata,
+        r#"{"file_name":"plaintext.txt","content_type":"text/plain"}"#
+    );
+    assert!(
+        encrypt_file(
+            data,
+            String::new(),
+            String::new(),
+            &[1; 15],
+            &[2; 16],
+            PUBLIC_KEY,
+            PUBLIC_KEY,
+            SIGNING_KEY
+        )
+        .is_err()
+    );
+}
+
+fn hex(s: &str) -> Vec<u8> {
+    (0..s.len())
+        .step_by(2)
+        .map(|i| u8::from_str_radix(&s[i..i + 2], 16).unwrap())
+        .collect()
+}
@@ -0,0 +1,647 @@
+use chrono::{DateTime, Utc};
+use serde::{Deserialize, Serialize};
+use utoipa::{IntoParams, ToSchema};
+use validator::{Validate, ValidationError};
+
+/// Highest `page` of a listing. Offsets are computed from it, and paging
+/// this deep is better done with a cursor anyway.
+pub const MAX_PAGE: usize = 10_000;
+
+/// Language codes accepted for the user's locale preference.
+pub const SUPPORTED_LOCALES: &[&str] = &["en", "zh"];
+
+#[derive(Debug, Clone, Default, Serialize, Deserialize, Validate, ToSchema)]
+pub struct RegisterUserDto {
+    #[validate(length(
+        min = 10,
+        code = "name_too_short",
+        message = "Name must be at least 10 characters"
+    ))]
+    #[schema(min_length = 10)]
+    pub name: String,
+    #[validate(
+        length(min = 1, code = "email_required", message = "Email is required"),
+        email(code = "email_invalid", message = "Invalid email")
+    )]
+    #[schema(format = Email)]
+    pub email: String,
+    #[validate(length(
+        min = 8,
+        code = "password_too_short",
+        message = "Password must be at least 8 characters"
+    ))]
+    #[schema(format = Password, min_length = 8)]
+    pub password: String,
+    #[validate(
+        length(
+            min = 1,
+            code = "confirm_password_required",
+            message = "Confirm password is required"
+        ),
+        must_match(
+            other = "password",
+            code = "passwords_do_not_match",
+            message = "Passwords do not match"
+        )
+    )]
+    #[serde(rename = "passwordConfirm")]
+    #[schema(format = Password, min_length = 1)]
+    pub password_confirm: String,
+    /// Type of the key pair generated for the account, the server's default
+    /// when omitted
+    pub key_type: Option<KeyType>,
+}
+
+#[derive(Debug, Clone, Default, Serialize, Deserialize, Validate, ToSchema)]
+pub struct LoginUserDto {
+    #[validate(
+        length(min = 1, code = "email_required", message = "Email is required"),
+        email(code = "email_invalid", message = "Invalid email")
+    )]
+    #[schema(format = Email)]
+    pub email: String,
+    #[validate(length(
+        min = 8,
+        code = "password_too_short",
+        message = "Password must be at least 8 characters"
+    ))]
+    #[schema(format = Password, min_length = 8)]
+    pub password: String,
+}
+
+#[derive(Debug, Clone, Default, Serialize, Deserialize, Validate, IntoParams)]
+#[into_params(parameter_in = Query)]
+pub struct RequestQueryDto {
+    /// Page number, starting at 1; use `cursor` to go further than
+    /// `MAX_PAGE`
+    #[validate(range(min = 1, max = MAX_PAGE))]
+    #[param(minimum = 1, maximum = 10000)]
+    pub page: Option<usize>,
+    /// Page size, 10 when omitted
+    #[validate(range(min = 1, max = 50))]
+    #[param(minimum = 1, maximum = 50)]
+    pub limit: Option<usize>,
+    /// `next_cursor` of the previous page; `page` is ignored when set
+    pub cursor: Option<String>,
+    /// `created_at` when omitted
+    #[param(inline)]
+    pub sort: Option<ListSort>,
+    /// Names A to Z and everything else newest or largest first when omitted
+    #[param(inline)]
+    pub order: Option<SortOrder>,
+    /// Active and expired shares when omitted
+    #[param(inline)]
+    pub status: Option<ShareStatus>,
+    /// Only shares with this recipient (sent) or sender (received)
+    #[validate(email(code = "email_invalid", message = "Invalid email"))]
+    #[param(format = Email)]
+    pub email: Option<String>,
+    /// Case-insensitive substring of the file name
+    #[validate(length(
+        min = 1,
+        max = 255,
+        code = "file_name_length",
+        message = "File name filter must be 1 to 255 characters"
+    ))]
+    pub file_name: Option<String>,
+    /// Shared at or after this time
+    pub created_after: Option<DateTime<Utc>>,
+    /// Shared before this time
+    pub created_before: Option<DateTime<Utc>>,
+}
+
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize, ToSchema)]
+#[serde(rename_all = "snake_case")]
+pub enum ListSort {
+    #[default]
+    CreatedAt,
+    FileName,
+    FileSize,
+    ExpirationDate,
+}
+
+impl ListSort {
+    pub fn default_order(self) -> SortOrder {
+        match self {
+            ListSort::FileName => SortOrder::Asc,
+            _ => SortOrder::Desc,
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize, ToSchema)]
+#[serde(rename_all = "lowercase")]
+pub enum SortOrder {
+    Asc,
+    Desc,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize, ToSchema)]
+#[serde(rename_all = "lowercase")]
+pub enum ShareStatus {
+    Active,
+    Expired,
+}
+
+/// How files are wrapped for the holder of a key pair.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize, ToSchema)]
+#[serde(rename_all = "lowercase")]
+pub enum KeyType {
+    /// RSA-2048 with PKCS#1 v1.5, kept for existing accounts
+    Rsa,
+    /// HPKE with X25519, HKDF-SHA256 and ChaCha20-Poly1305
+    X25519,
+}
+
+impl KeyType {
+    pub fn as_str(self) -> &'static str {
+        match self {
+            KeyType::Rsa => "rsa",
+            KeyType::X25519 => "x25519",
+        }
+    }
+}
+
+impl std::str::FromStr for KeyType {
+    type Err = String;
+
+    fn from_str(value: &str) -> Result<Self, Self::Err> {
+        match value {
+            "rsa" => Ok(KeyType::Rsa),
+            "x25519" => Ok(KeyType::X25519),
+            other => Err(format!("unknown key type {}", other)),
+        }
+    }
+}
+
+/// Response header of a download carrying its [`SignatureStatus`].
+pub const SIGNATURE_STATUS_HEADER: &str = "x-signature-status";
+
+/// Whether a file's sender signature checks out against the contents and
+/// metadata as served.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize, ToSchema)]
+#[serde(rename_all = "lowercase")]
+pub enum SignatureStatus {
+    /// Signed by the sender's signing key recorded for the file
+    Verified,
+    /// Signed, but the signature does not match what is stored
+    Invalid,
+    /// Uploaded before files were signed
+    Unsigned,
+}
+
+impl SignatureStatus {
+    pub fn as_str(self) -> &'static str {
+        match self {
+            SignatureStatus::Verified => "verified",
+            SignatureStatus::Invalid => "invalid",
+            SignatureStatus::Unsigned => "unsigned",
+        }
+    }
+}
+
+impl std::str::FromStr for SignatureStatus {
+    type Err = String;
+
+    fn from_str(value: &str) -> Result<Self, Self::Err> {
+        match value {
+            "verified" => Ok(SignatureStatus::Verified),
+            "invalid" => Ok(SignatureStatus::Invalid),
+            "unsigned" => Ok(SignatureStatus::Unsigned),
+            other => Err(format!("unknown signature status {}", other)),
+        }
+    }
+}
+
+#[derive(Debug, Serialize, Deserialize, ToSchema)]
+pub struct FilterUserDto {
+    pub id: String,
+    pub name: String,
+    pub email: String,
+    pub public_key: Option<String>,
+    /// Base64 encoded RFC 8410 SPKI PEM of the Ed25519 key uploads are signed
+    /// with, `null` until the first upload for older accounts
+    pub signing_key: Option<String>,
+    /// Preferred language for error messages, `null` to follow `Accept-Language`
+    pub locale: Option<String>,
+    pub created_at: DateTime<Utc>,
+    pub updated_at: DateTime<Utc>,
+}
+
+#[derive(Debug, Serialize, Deserialize, ToSchema)]
+pub struct UserData {
+    pub user: FilterUserDto,
+}
+
+#[derive(Debug, Serialize, Deserialize, ToSchema)]
+pub struct UserResponseDto {
+    pub status: String,
+    pub data: UserData,
+}
+
+/// One of the current user's key pairs. Files keep the version they were
+/// wrapped to, so retired pairs still decrypt what was shared before.
+#[derive(Debug, Serialize, Deserialize, ToSchema)]
+pub struct UserKeyDto {
+    /// Starts at 1 and goes up with every rotation
+    pub version: i32,
+    pub key_type: KeyType,
+    /// Base64 encoded PEM: PKCS#1 for `rsa`, RFC 8410 SPKI for `x25519`
+    pub public_key: String,
+    /// `active` for the key pair new files are wrapped to, `retired` otherwise
+    pub status: String,
+    pub created_at: DateTime<Utc>,
+    pub retired_at: Option<DateTime<Utc>>,
+}
+
+#[derive(Debug, Default, Clone, Serialize, Deserialize, Validate, ToSchema)]
+pub struct RotateKeyDto {
+    /// Type of the new key pair, the server's default when omitted
+    pub key_type: Option<KeyType>,
+}
+
+#[derive(Debug, Serialize, Deserialize, ToSchema)]
+pub struct UserKeyResponseDto {
+    pub status: String,
+    pub key: UserKeyDto,
+}
+
+#[derive(Debug, Serialize, Deserialize, ToSchema)]
+pub struct UserKeyListResponseDto {
+    pub status: String,
+    /// Oldest first
+    pub keys: Vec<UserKeyDto>,
+}
+
+/// One of the current user's signing keys. Files record the version they
+/// were signed with, so retired keys still verify what was sent before.
+#[derive(Debug, Serialize, Deserialize, ToSchema)]
+pub struct SigningKeyDto {
+    /// Starts at 1 and goes up with every registered key
+    pub version: i32,
+    /// Base64 encoded RFC 8410 SPKI PEM of the Ed25519 public key
+    pub public_key: String,
+    /// `server` when the server signs the user's uploads, `client` when only
+    /// the user has the private key
+    pub held_by: String,
+    /// `active` for the key new uploads are signed with, `retired` otherwise
+    pub status: String,
+    pub created_at: DateTime<Utc>,
+    pub retired_at: Option<DateTime<Utc>>,
+}
+
+#[derive(Debug, Default, Clone, Serialize, Deserialize, Validate, ToSchema)]
+pub struct RegisterSigningKeyDto {
+    /// Base64 encoded RFC 8410 SPKI PEM of an Ed25519 public key whose
+    /// private half only the user holds
+    #[validate(length(
+        min = 1,
+        code = "signing_key_required",
+        message = "Signing key is required"
+    ))]
+    pub public_key: String,
+}
+
+#[derive(Debug, Serialize, Deserialize, ToSchema)]
+pub struct SigningKeyResponseDto {
+    pub status: String,
+    pub key: SigningKeyDto,
+}
+
+#[derive(Debug, Serialize, Deserialize, ToSchema)]
+pub struct UserSendFileDto {
+    /// Pass to `GET /api/file/shares/{id}` for the details
+    pub share_id: String,
+    pub file_id: String,
+    pub file_name: String,
+    /// Size of the original file in bytes
+    pub file_size: i64,
+    /// Sniffed from the file's signature at upload, falling back to what the
+    /// uploader declared
+    pub content_type: String,
+    pub recipient_email: String,
+    pub recipient_name: String,
+    /// Successful downloads by the recipient
+    pub download_count: i64,
+    pub last_downloaded_at: Option<DateTime<Utc>>,
+    pub password_protected: bool,
+    pub expired: bool,
+    pub expiration_date: DateTime<Utc>,
+    pub created_at: DateTime<Utc>,
+}
+
+#[derive(Debug, Serialize, Deserialize, ToSchema)]
+pub struct UserSendFileListResponseDto {
+    pub status: String,
+    pub files: Vec<UserSendFileDto>,
+    /// Total matching the filters, across all pages
+    pub results: i64,
+    /// Pass as `cursor` to fetch the next page; `null` on the last one
+    pub next_cursor: Option<String>,
+}
+
+#[derive(Debug, Serialize, Deserialize, ToSchema)]
+pub struct UserReceiveFileDto {
+    /// What `shared_id` of the download expects; pass to
+    /// `GET /api/file/shares/{id}` for the details
+    pub share_id: String,
+    /// The stored file; downloads go by `share_id`
+    pub file_id: String,
+    pub file_name: String,
+    /// Size of the original file in bytes
+    pub file_size: i64,
+    /// Sniffed from the file's signature at upload, falling back to what the
+    /// uploader declared
+    pub content_type: String,
+    pub sender_email: String,
+    pub sender_name: String,
+    /// Successful downloads so far
+    pub download_count: i64,
+    pub last_downloaded_at: Option<DateTime<Utc>>,
+    pub password_protected: bool,
+    pub expired: bool,
+    pub expiration_date: DateTime<Utc>,
+    pub created_at: DateTime<Utc>,
+}
+
+#[derive(Debug, Serialize, Deserialize, ToSchema)]
+pub struct UserReceiveFileListResponseDto {
+    pub status: String,
+    pub files: Vec<UserReceiveFileDto>,
+    /// Total matching the filters, across all pages
+    pub results: i64,
+    /// Pass as `cursor` to fetch the next page; `null` on the last one
+    pub next_cursor: Option<String>,
+}
+
+/// A share as seen by its sender or recipient, without the file contents.
+#[derive(Debug, Serialize, Deserialize, ToSchema)]
+pub struct ShareDetailsDto {
+    pub share_id: String,
+    /// The stored file; downloads go by `share_id`
+    pub file_id: String,
+    pub file_name: String,
+    /// Size of the original file in bytes
+    pub file_size: i64,
+    /// Sniffed from the file's signature at upload, falling back to what the
+    /// uploader declared
+    pub content_type: String,
+    pub sender_email: String,
+    pub sender_name: String,
+    pub recipient_email: String,
+    pub recipient_name: String,
+    /// Successful downloads by the recipient
+    pub download_count: i64,
+    pub last_downloaded_at: Option<DateTime<Utc>>,
+    pub password_protected: bool,
+    pub expired: bool,
+    pub expiration_date: DateTime<Utc>,
+    pub created_at: DateTime<Utc>,
+    /// Check of the sender's signature, `null` when the current user cannot
+    /// read the file's metadata
+    pub signature_status: Option<SignatureStatus>,
+    /// Version of the sender's signing key the file was signed with, `null`
+    /// when unsigned
+    pub signing_key_version: Option<i32>,
+}
+
+#[derive(Debug, Serialize, Deserialize, ToSchema)]
+pub struct ShareDetailsResponseDto {
+    pub status: String,
+    pub share: ShareDetailsDto,
+}
+
+#[derive(Debug, Serialize, Deserialize, ToSchema)]
+pub struct UserLoginResponseDto {
+    pub status: String,
+    pub token: String,
+}
+
+#[derive(Debug, Serialize, Deserialize, ToSchema)]
+pub struct Response {
+    pub status: String,
+    pub message: String,
+}
+
+#[derive(Debug, Serialize, Deserialize, ToSchema)]
+pub struct ReadinessChecksDto {
+    /// `ok` or `fail`
+    pub database: String,
+    /// `ok` or `fail`
+    pub key_directory: String,
+    /// `ok`, `fail`, or `disabled` without a key encryption key
+    pub kek: String,
+}
+
+#[derive(Debug, Serialize, Deserialize, ToSchema)]
+pub struct ReadinessResponseDto {
+    pub status: String,
+    pub checks: ReadinessChecksDto,
+}
+
+#[derive(Debug, Clone, Default, Serialize, Deserialize, Validate, ToSchema)]
+pub struct NamedUpdateDto {
+    #[validate(length(min = 1, code = "name_required", message = "Name is required"))]
+    #[schema(min_length = 1)]
+    pub name: String,
+}
+
+#[derive(Debug, Clone, Default, Serialize, Deserialize, Validate, ToSchema)]
+pub struct LocaleUpdateDto {
+    /// One of `en` or `zh`; `null` clears the preference
+    #[validate(custom(function = "validate_locale"))]
+    #[schema(example = "zh")]
+    pub locale: Option<String>,
+}
+
+#[derive(Debug, Default, Clone, Serialize, Deserialize, Validate, ToSchema)]
+pub struct UserPasswordUpdateDto {
+    #[validate(length(
+        min = 8,
+        code = "password_too_short",
+        message = "Password must be at least 8 characters long"
+    ))]
+    #[schema(format = Password, min_length = 8)]
+    pub new_password: String,
+    #[validate(must_match(
+        other = "new_password",
+        code = "passwords_do_not_match",
+        message = "Passwords do not match"
+    ))]
+    #[schema(format = Password)]
+    pub new_password_confirm: String,
+    #[validate(length(
+        min = 8,
+        code = "old_password_too_short",
+        message = "Old password must be at least 8 characters long"
+    ))]
+    #[schema(format = Password, min_length = 8)]
+    pub old_password: String,
+}
+
+#[derive(Debug, Clone, Default, Serialize, Deserialize, Validate, IntoParams)]
+#[into_params(parameter_in = Query)]
+pub struct SearchQueryByEmailDto {
+    /// Part of the recipient's email address
+    #[validate(length(min = 1, code = "query_required", message = "Query is required"))]
+    #[param(min_length = 1)]
+    pub query: String,
+}
+
+#[derive(Debug, Serialize, Deserialize, ToSchema)]
+pub struct FilterEmailDto {
+    pub email: String,
+}
+
+#[derive(Debug, Serialize, Deserialize, ToSchema)]
+pub struct EmailListResponseDto {
+    pub status: String,
+    pub emails: Vec<FilterEmailDto>,
+}
+
+#[derive(Debug, Clone, Default, Serialize, Deserialize, Validate)]
+pub struct FileUploadDto {
+    pub file_name: String,
+    #[validate(length(min = 1, code = "file_required", message = "A file is required"))]
+    pub file_data: Vec<u8>,
+    /// MIME type of the file; `application/octet-stream` when empty
+    #[validate(length(
+        max = 255,
+        code = "content_type_too_long",
+        message = "Content type must be at most 255 characters"
+    ))]
+    pub content_type: String,
+    #[validate(email(code = "email_invalid", message = "Invalid email"))]
+    pub recipient_email: String,
+    /// Password the recipient must provide to download the file
+    #[validate(length(
+        min = 8,
+        code = "password_too_short",
+        message = "Password must be at least 8 characters long"
+    ))]
+    pub password: String,
+    /// RFC 3339 timestamp in the future
+    #[validate(custom(function = "validate_expiration_date"))]
+    pub expiration_date: String,
+}
+
+#[derive(Debug, Clone, Default, Serialize, Deserialize, Validate, ToSchema)]
+pub struct RetrieveFileDto {
+    /// `file_id` of an entry in the received list
+    #[validate(length(
+        min = 1,
+        code = "shared_id_required",
+        message = "Shared ID must not be empty"
+    ))]
+    #[schema(format = Uuid, min_length = 1)]
+    pub shared_id: String,
+    #[validate(length(
+        min = 1,
+        code = "password_required",
+        message = "Password must not be empty"
+    ))]
+    #[schema(format = Password, min_length = 1)]
+    pub password: String,
+}
+
+/// A version 2 envelope its sender sealed, as made by `seal_file` of
+/// `secure-share-crypto`. Read from the multipart form of
+/// `POST /api/file/envelope`, where the byte fields are binary parts.
+#[derive(Debug, Clone, Default, Serialize, Deserialize, Validate)]
+pub struct EnvelopeUploadDto {
+    #[validate(length(min = 1, code = "file_required", message = "A file is required"))]
+    pub ciphertext: Vec<u8>,
+    pub iv: Vec<u8>,
+    /// AES key wrapped for the recipient's active key pair
+    pub encrypted_key: Vec<u8>,
+    /// AES key wrapped for the sender's active key pair
+    pub sender_encrypted_key: Vec<u8>,
+    pub encrypted_metadata: Vec<u8>,
+    pub signature: Vec<u8>,
+    /// Size of the original file in bytes
+    #[validate(range(min = 0))]
+    pub file_size: i64,
+    pub recipient_key_version: i32,
+    pub sender_key_version: i32,
+    pub signing_key_version: i32,
+    #[validate(email(code = "email_invalid", message = "Invalid email"))]
+    pub recipient_email: String,
+    /// Password the recipient must provide to download the file
+    #[validate(length(
+        min = 8,
+        code = "password_too_short",
+        message = "Password must be at least 8 characters long"
+    ))]
+    pub password: String,
+    /// RFC 3339 timestamp in the future
+    #[validate(custom(function = "validate_expiration_date"))]
+    pub expiration_date: String,
+}
+
+/// A stored file as its recipient downloads it to decrypt themselves. Byte
+/// fields are base64 encoded.
+#[derive(Debug, Serialize, Deserialize, ToSchema)]
+pub struct EnvelopeDto {
+    pub share_id: String,
+    pub file_id: String,
+    #[schema(format = Uuid)]
+    pub sender_id: String,
+    /// Size of the original file in bytes
+    pub file_size: i64,
+    pub ciphertext: String,
+    pub iv: String,
+    /// AES key wrapped for the current user's key pair of `recipient_key_version`
+    pub encrypted_key: String,
+    pub recipient_key_version: i32,
+    /// `null` for files stored before names were encrypted; the share details
+    /// carry their name
+    pub encrypted_metadata: Option<String>,
+    pub signature: Option<String>,
+    /// Version of the sender's signing key the file was signed with, `null`
+    /// when unsigned
+    pub signing_key_version: Option<i32>,
+    /// Base64 encoded PEM of that signing key
+    pub sender_signing_key: Option<String>,
+    /// The server's check of the sender's signature
+    pub signature_status: SignatureStatus,
+}
+
+#[derive(Debug, Serialize, Deserialize, ToSchema)]
+pub struct EnvelopeResponseDto {
+    pub status: String,
+    pub envelope: EnvelopeDto,
+}
+
+fn validate_expiration_date(expiration_date: &str) -> Result<(), ValidationError> {
+    if expiration_date.is_empty() {
+        let mut error = ValidationError::new("expiration_date_required");
+        error.message = Some("Expiration date is required.".into());
+        return Err(error);
+    }
+
+    let parsed_date = DateTime::parse_from_rfc3339(expiration_date).map_err(|_| {
+        let mut error = ValidationError::new("invalid_date_format");
+        error.message =
+            Some("Invalid date format. Expected format is YYYY-MM-DDTHH:MM:ssssssZ.".into());
+        error
+    })?;
+
+    let now = Utc::now();
+    if parsed_date <= now {
+        let mut error = ValidationError::new("expiration_date_past");
+        error.message = Some("Expiration date must be in the future.".into());
+        return Err(error);
+    }
+
+    Ok(())
+}
+
+fn validate_locale(locale: &str) -> Result<(), ValidationError> {
+    if !SUPPORTED_LOCALES.contains(&locale) {
+        let mut error = ValidationError::new("unsupported_locale");
+        error.message = Some("Unsupported locale".into());
+        return Err(error);
+    }
+
+    Ok(())
+}
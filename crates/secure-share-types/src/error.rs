@@ -0,0 +1,41 @@
+use std::{collections::BTreeMap, fmt};
+
+use serde::{Deserialize, Serialize};
+use utoipa::ToSchema;
+
+pub const PROBLEM_JSON: &str = "application/problem+json";
+
+/// One failed validation rule of a field.
+#[derive(Debug, Clone, Serialize, Deserialize, ToSchema)]
+pub struct FieldError {
+    /// Stable identifier of the rule, e.g. `password_too_short`
+    pub code: String,
+    pub message: String,
+}
+
+/// RFC 7807 problem details body returned for every failed request.
+#[derive(Debug, Clone, Serialize, Deserialize, ToSchema)]
+pub struct ErrorResponse {
+    /// URI reference identifying the problem type, derived from `code`
+    #[serde(rename = "type")]
+    pub problem_type: String,
+    /// Reason phrase of the HTTP status
+    pub title: String,
+    pub status: u16,
+    /// Human readable explanation of this occurrence
+    pub detail: String,
+    /// Stable machine-readable error code
+    pub code: String,
+    /// Quote this when reporting an internal error; it matches the server logs
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub correlation_id: Option<String>,
+    /// Failed rules per field, only present for `validation_failed`
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub errors: Option<BTreeMap<String, Vec<FieldError>>>,
+}
+
+impl fmt::Display for ErrorResponse {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "{}", serde_json::to_string(&self).unwrap())
+    }
+}
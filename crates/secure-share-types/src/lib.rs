@@ -0,0 +1,7 @@
+//! Request and response bodies of the secure-share HTTP API, shared by the
+//! server and its clients.
+
+pub mod dtos;
+pub mod error;
+
+pub use error::{ErrorResponse, FieldError, PROBLEM_JSON};
@@ -0,0 +1,6 @@
+-- Add migration script here
+CREATE TABLE rate_limit_buckets (
+    key TEXT PRIMARY KEY,                                 -- Route group plus client identity, e.g. "auth:ip:127.0.0.1"
+    tokens DOUBLE PRECISION NOT NULL,                     -- Tokens left in the bucket at updated_at
+    updated_at TIMESTAMP WITH TIME ZONE NOT NULL DEFAULT NOW()
+);
@@ -0,0 +1,3 @@
+-- Add migration script here
+ALTER TABLE users
+    ADD COLUMN locale VARCHAR(16); -- Preferred language for messages, falls back to Accept-Language when NULL
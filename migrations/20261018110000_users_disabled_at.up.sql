@@ -0,0 +1,3 @@
+-- Add migration script here
+ALTER TABLE users
+    ADD COLUMN disabled_at TIMESTAMP WITH TIME ZONE; -- Set by an operator to block login; NULL for active users
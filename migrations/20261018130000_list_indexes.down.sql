@@ -0,0 +1,10 @@
+-- Add migration script here
+DROP INDEX IF EXISTS shared_links_expiration_date_idx;
+DROP INDEX IF EXISTS shared_links_recipient_expiration_date_idx;
+DROP INDEX IF EXISTS shared_links_recipient_created_at_idx;
+DROP INDEX IF EXISTS shared_links_file_id_idx;
+
+DROP INDEX IF EXISTS files_file_name_trgm_idx;
+DROP INDEX IF EXISTS files_user_file_size_idx;
+DROP INDEX IF EXISTS files_user_file_name_idx;
+DROP INDEX IF EXISTS files_user_created_at_idx;
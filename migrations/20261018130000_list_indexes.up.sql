@@ -0,0 +1,13 @@
+-- Add migration script here
+-- Trigram index for the file name substring filter on the sent and received lists
+CREATE EXTENSION IF NOT EXISTS pg_trgm;
+
+CREATE INDEX files_user_created_at_idx ON files (user_id, created_at, id);
+CREATE INDEX files_user_file_name_idx ON files (user_id, file_name, id);
+CREATE INDEX files_user_file_size_idx ON files (user_id, file_size, id);
+CREATE INDEX files_file_name_trgm_idx ON files USING gin (LOWER(file_name) gin_trgm_ops);
+
+CREATE INDEX shared_links_file_id_idx ON shared_links (file_id);
+CREATE INDEX shared_links_recipient_created_at_idx ON shared_links (recipient_user_id, created_at, id);
+CREATE INDEX shared_links_recipient_expiration_date_idx ON shared_links (recipient_user_id, expiration_date, id);
+CREATE INDEX shared_links_expiration_date_idx ON shared_links (expiration_date); -- Expired file cleanup
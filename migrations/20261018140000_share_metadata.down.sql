@@ -0,0 +1,7 @@
+-- Add migration script here
+ALTER TABLE shared_links
+    DROP COLUMN IF EXISTS last_downloaded_at,
+    DROP COLUMN IF EXISTS download_count;
+
+ALTER TABLE files
+    DROP COLUMN IF EXISTS content_type;
@@ -0,0 +1,7 @@
+-- Add migration script here
+ALTER TABLE files
+    ADD COLUMN content_type VARCHAR(255) NOT NULL DEFAULT 'application/octet-stream'; -- As declared by the uploader
+
+ALTER TABLE shared_links
+    ADD COLUMN download_count BIGINT NOT NULL DEFAULT 0,    -- Successful downloads by the recipient
+    ADD COLUMN last_downloaded_at TIMESTAMP WITH TIME ZONE;
@@ -0,0 +1,7 @@
+-- Add migration script here
+CREATE INDEX files_user_file_name_idx ON files (user_id, file_name, id);
+CREATE INDEX files_file_name_trgm_idx ON files USING gin (LOWER(file_name) gin_trgm_ops);
+
+ALTER TABLE files
+    DROP COLUMN IF EXISTS sender_encrypted_aes_key,
+    DROP COLUMN IF EXISTS encrypted_metadata;
@@ -0,0 +1,11 @@
+-- Add migration script here
+-- New uploads keep their name and content type only in encrypted_metadata,
+-- leaving file_name empty and content_type at its default. The size stays in
+-- the clear, as the length of encrypted_file gives it away anyway.
+ALTER TABLE files
+    ADD COLUMN encrypted_metadata BYTEA,        -- Sealed with the file's AES key; NULL for older files
+    ADD COLUMN sender_encrypted_aes_key BYTEA;  -- The AES key wrapped for the sender, who lists the file
+
+-- Names are sorted and searched after decryption
+DROP INDEX IF EXISTS files_file_name_trgm_idx;
+DROP INDEX IF EXISTS files_user_file_name_idx;
@@ -0,0 +1,6 @@
+-- Add migration script here
+ALTER TABLE files
+    DROP COLUMN IF EXISTS sender_key_version,
+    DROP COLUMN IF EXISTS recipient_key_version;
+
+DROP TABLE IF EXISTS user_keys;
@@ -0,0 +1,25 @@
+-- Add migration script here
+-- Every key pair a user has had. Rotating retires the active pair instead of
+-- replacing it, so files wrapped to an older public key stay readable.
+-- users.public_key keeps a copy of the active public key.
+CREATE TABLE user_keys (
+    user_id UUID NOT NULL REFERENCES users(id) ON DELETE CASCADE,
+    version INTEGER NOT NULL,
+    public_key TEXT NOT NULL,
+    status VARCHAR(16) NOT NULL DEFAULT 'active' CHECK (status IN ('active', 'retired')),
+    created_at TIMESTAMP WITH TIME ZONE DEFAULT NOW(),
+    retired_at TIMESTAMP WITH TIME ZONE,
+    PRIMARY KEY (user_id, version)
+);
+
+CREATE UNIQUE INDEX user_keys_active_idx ON user_keys (user_id) WHERE status = 'active';
+
+-- Key pairs from before versioning are version 1
+INSERT INTO user_keys (user_id, version, public_key, status, created_at)
+SELECT id, 1, public_key, 'active', COALESCE(updated_at, created_at, NOW())
+FROM users
+WHERE public_key IS NOT NULL;
+
+ALTER TABLE files
+    ADD COLUMN recipient_key_version INTEGER NOT NULL DEFAULT 1, -- Key pair of the recipient encrypted_aes_key is wrapped to
+    ADD COLUMN sender_key_version INTEGER NOT NULL DEFAULT 1;    -- Likewise for sender_encrypted_aes_key
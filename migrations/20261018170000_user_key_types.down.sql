@@ -0,0 +1,2 @@
+-- Add migration script here
+ALTER TABLE user_keys DROP COLUMN IF EXISTS key_type;
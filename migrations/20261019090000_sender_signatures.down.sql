@@ -0,0 +1,6 @@
+-- Add migration script here
+ALTER TABLE files
+    DROP COLUMN IF EXISTS signature,
+    DROP COLUMN IF EXISTS content_digest;
+
+ALTER TABLE users DROP COLUMN IF EXISTS signing_key;
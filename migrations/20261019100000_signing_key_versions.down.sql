@@ -0,0 +1,4 @@
+-- Add migration script here
+ALTER TABLE files DROP COLUMN IF EXISTS signing_key_version;
+
+DROP TABLE IF EXISTS user_signing_keys;
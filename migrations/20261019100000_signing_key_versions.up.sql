@@ -0,0 +1,26 @@
+-- Add migration script here
+-- Every Ed25519 key a user has signed uploads with; users.signing_key keeps a
+-- copy of the active one. The server holds the private half of `server` keys
+-- and signs the user's uploads with it. `client` keys were registered by the
+-- user, who then signs their own uploads.
+CREATE TABLE user_signing_keys (
+    user_id UUID NOT NULL REFERENCES users(id) ON DELETE CASCADE,
+    version INTEGER NOT NULL,
+    public_key TEXT NOT NULL,
+    held_by VARCHAR(16) NOT NULL CHECK (held_by IN ('server', 'client')),
+    status VARCHAR(16) NOT NULL DEFAULT 'active' CHECK (status IN ('active', 'retired')),
+    created_at TIMESTAMP WITH TIME ZONE DEFAULT NOW(),
+    retired_at TIMESTAMP WITH TIME ZONE,
+    PRIMARY KEY (user_id, version)
+);
+
+CREATE UNIQUE INDEX user_signing_keys_active_idx ON user_signing_keys (user_id) WHERE status = 'active';
+
+-- Signing keys from before versioning were generated by the server
+INSERT INTO user_signing_keys (user_id, version, public_key, held_by, status, created_at)
+SELECT id, 1, signing_key, 'server', 'active', COALESCE(updated_at, created_at, NOW())
+FROM users
+WHERE signing_key IS NOT NULL;
+
+ALTER TABLE files ADD COLUMN signing_key_version INTEGER; -- Signing key of the sender `signature` was made with
+UPDATE files SET signing_key_version = 1 WHERE signature IS NOT NULL;
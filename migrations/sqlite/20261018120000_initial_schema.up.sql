@@ -0,0 +1,36 @@
+-- Same tables as the Postgres migrations. SQLite has no UUID type or
+-- uuid-ossp, so ids are 16 byte BLOBs generated by the application, and
+-- timestamps are RFC 3339 TEXT written by the application so they compare
+-- in order.
+
+CREATE TABLE users (
+    id BLOB PRIMARY KEY NOT NULL,
+    name TEXT NOT NULL,
+    email TEXT UNIQUE NOT NULL,
+    password TEXT NOT NULL,     -- Store hashed passwords
+    public_key TEXT,            -- Store public key for encryption
+    locale TEXT,                -- Preferred language for messages, falls back to Accept-Language when NULL
+    disabled_at TEXT,           -- Set by an operator to block login; NULL for active users
+    created_at TEXT,
+    updated_at TEXT
+);
+
+CREATE TABLE files (
+    id BLOB PRIMARY KEY NOT NULL,
+    user_id BLOB REFERENCES users(id) ON DELETE CASCADE,
+    file_name TEXT NOT NULL,
+    file_size INTEGER NOT NULL,
+    encrypted_aes_key BLOB NOT NULL, -- Store encrypted AES key
+    encrypted_file BLOB NOT NULL,    -- Store the actual encrypted file content
+    iv BLOB NOT NULL,                -- Initialization Vector for AES encryption
+    created_at TEXT
+);
+
+CREATE TABLE shared_links (
+    id BLOB PRIMARY KEY NOT NULL,
+    file_id BLOB REFERENCES files(id) ON DELETE CASCADE,
+    recipient_user_id BLOB REFERENCES users(id) ON DELETE CASCADE,
+    password TEXT NOT NULL,          -- Password protection (required)
+    expiration_date TEXT NOT NULL,   -- Expiration date (required)
+    created_at TEXT
+);
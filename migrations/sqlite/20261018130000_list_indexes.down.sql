@@ -0,0 +1,8 @@
+DROP INDEX IF EXISTS shared_links_expiration_date_idx;
+DROP INDEX IF EXISTS shared_links_recipient_expiration_date_idx;
+DROP INDEX IF EXISTS shared_links_recipient_created_at_idx;
+DROP INDEX IF EXISTS shared_links_file_id_idx;
+
+DROP INDEX IF EXISTS files_user_file_size_idx;
+DROP INDEX IF EXISTS files_user_file_name_idx;
+DROP INDEX IF EXISTS files_user_created_at_idx;
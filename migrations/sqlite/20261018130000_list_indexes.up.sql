@@ -0,0 +1,11 @@
+-- Indexes behind the sorted and filtered sent and received lists. SQLite has
+-- no trigram index, so the file name substring filter scans the user's files.
+
+CREATE INDEX files_user_created_at_idx ON files (user_id, created_at, id);
+CREATE INDEX files_user_file_name_idx ON files (user_id, file_name, id);
+CREATE INDEX files_user_file_size_idx ON files (user_id, file_size, id);
+
+CREATE INDEX shared_links_file_id_idx ON shared_links (file_id);
+CREATE INDEX shared_links_recipient_created_at_idx ON shared_links (recipient_user_id, created_at, id);
+CREATE INDEX shared_links_recipient_expiration_date_idx ON shared_links (recipient_user_id, expiration_date, id);
+CREATE INDEX shared_links_expiration_date_idx ON shared_links (expiration_date); -- Expired file cleanup
@@ -0,0 +1,4 @@
+ALTER TABLE shared_links DROP COLUMN last_downloaded_at;
+ALTER TABLE shared_links DROP COLUMN download_count;
+
+ALTER TABLE files DROP COLUMN content_type;
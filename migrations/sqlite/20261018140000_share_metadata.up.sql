@@ -0,0 +1,4 @@
+ALTER TABLE files ADD COLUMN content_type TEXT NOT NULL DEFAULT 'application/octet-stream'; -- As declared by the uploader
+
+ALTER TABLE shared_links ADD COLUMN download_count INTEGER NOT NULL DEFAULT 0; -- Successful downloads by the recipient
+ALTER TABLE shared_links ADD COLUMN last_downloaded_at TEXT;
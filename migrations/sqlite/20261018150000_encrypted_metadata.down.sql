@@ -0,0 +1,4 @@
+CREATE INDEX files_user_file_name_idx ON files (user_id, file_name, id);
+
+ALTER TABLE files DROP COLUMN sender_encrypted_aes_key;
+ALTER TABLE files DROP COLUMN encrypted_metadata;
@@ -0,0 +1,4 @@
+ALTER TABLE files DROP COLUMN sender_key_version;
+ALTER TABLE files DROP COLUMN recipient_key_version;
+
+DROP TABLE user_keys;
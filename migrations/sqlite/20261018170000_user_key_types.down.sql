@@ -0,0 +1 @@
+ALTER TABLE user_keys DROP COLUMN key_type;
@@ -0,0 +1,2 @@
+-- Key pairs made before X25519 was supported are RSA-2048
+ALTER TABLE user_keys ADD COLUMN key_type TEXT NOT NULL DEFAULT 'rsa' CHECK (key_type IN ('rsa', 'x25519'));
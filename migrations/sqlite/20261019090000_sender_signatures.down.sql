@@ -0,0 +1,4 @@
+ALTER TABLE files DROP COLUMN signature;
+ALTER TABLE files DROP COLUMN content_digest;
+
+ALTER TABLE users DROP COLUMN signing_key;
@@ -0,0 +1,7 @@
+-- Base64 encoded PEM of the Ed25519 public key a user signs uploads with;
+-- the private key is a file in the key directory like their key pairs
+ALTER TABLE users ADD COLUMN signing_key TEXT;
+
+-- Files uploaded before signatures have neither
+ALTER TABLE files ADD COLUMN content_digest BLOB; -- SHA-256 of iv and encrypted_file
+ALTER TABLE files ADD COLUMN signature BLOB;      -- The sender's Ed25519 signature over content_digest and the metadata
@@ -0,0 +1,3 @@
+ALTER TABLE files DROP COLUMN signing_key_version;
+
+DROP TABLE user_signing_keys;
@@ -0,0 +1,636 @@
+use std::{
+    collections::BTreeMap, error::Error, fs, io, path::PathBuf, process::ExitCode, sync::Arc,
+};
+
+use clap::{Parser, Subcommand};
+use metrics_exporter_prometheus::PrometheusBuilder;
+use rand::rngs::OsRng;
+use secure_share::{
+    AppState,
+    config::{Config, DatabaseConfig},
+    db::{
+        AdminExt, DbClient, DbPool, FileRepository, ShareRepository, SqliteClient, UserRepository,
+    },
+    dtos::{KeyType, RegisterUserDto},
+    kek, migrations,
+    models::{FileMetadata, StoredFileKeys, UnsealedFile, User},
+    rate_limit::MemoryRateLimitStore,
+    utils::{decrypt, keys, password},
+};
+use secure_share_crypto::FileKey;
+use tracing_subscriber::EnvFilter;
+use uuid::Uuid;
+use validator::Validate;
+
+type AdminResult = Result<(), Box<dyn Error>>;
+
+/// Operator tasks for a secure-share deployment. Reads the same configuration
+/// as the server.
+#[derive(Debug, Parser)]
+#[command(name = "secure-share-admin", version)]
+struct Cli {
+    #[command(subcommand)]
+    command: Command,
+}
+
+#[derive(Debug, Subcommand)]
+enum Command {
+    /// Manage user accounts
+    #[command(subcommand)]
+    User(UserCommand),
+    /// Inspect and revoke shares
+    #[command(subcommand)]
+    Share(ShareCommand),
+    /// Delete expired shares and their files now instead of waiting for the job
+    Cleanup,
+    /// Report stored bytes per user
+    Storage,
+    /// Check that every stored file decrypts with its recipient's private key
+    Verify,
+    /// Encrypt the names and content types of files uploaded before they were
+    /// encrypted at rest, blanking the clear-text columns; safe to run while
+    /// the server is serving
+    SealNames,
+    /// Manage the key encryption key wrapping stored key material
+    #[command(subcommand)]
+    Kek(KekCommand),
+}
+
+#[derive(Debug, Subcommand)]
+enum UserCommand {
+    /// Register a user and generate their key pair; the password is read from
+    /// stdin when --password is omitted
+    Create {
+        #[arg(long)]
+        name: String,
+        #[arg(long)]
+        email: String,
+        #[arg(long)]
+        password: Option<String>,
+        /// `x25519` or `rsa`, the configured storage.key_type when omitted
+        #[arg(long)]
+        key_type: Option<KeyType>,
+    },
+    /// Block a user from logging in or using existing tokens
+    Disable { email: String },
+    /// Lift a previous disable
+    Enable { email: String },
+    /// Rotate a user's key pair; files already shared with them stay readable
+    /// with the retired pair
+    RegenerateKey {
+        email: String,
+        /// `x25519` or `rsa`, the configured storage.key_type when omitted
+        #[arg(long)]
+        key_type: Option<KeyType>,
+    },
+}
+
+#[derive(Debug, Subcommand)]
+enum ShareCommand {
+    /// List shares, optionally only those sent or received by one user
+    List {
+        #[arg(long)]
+        email: Option<String>,
+    },
+    /// Delete a share and the file behind it
+    Revoke { share_id: Uuid },
+}
+
+#[derive(Debug, Subcommand)]
+enum KekCommand {
+    /// Print a new random key as a line for kek.key_file or kek.env_var
+    Generate {
+        /// Defaults to the current time, e.g. kek-20261018T120000
+        #[arg(long)]
+        id: Option<String>,
+    },
+    /// Count stored keys by the key encryption key wrapping them
+    Status,
+    /// Wrap every stored key with the current key encryption key, leaving
+    /// file contents untouched; safe to run while the server is serving
+    Rewrap,
+}
+
+#[tokio::main]
+async fn main() -> ExitCode {
+    dotenv::dotenv().ok();
+    let cli = Cli::parse();
+
+    tracing_subscriber::fmt()
+        .with_writer(io::stderr)
+        .with_env_filter(EnvFilter::try_from_default_env().unwrap_or_else(|_| "warn".into()))
+        .init();
+
+    let config = match Config::init() {
+        Ok(config) => config,
+        Err(err) => {
+            eprintln!("🔥 {}", err);
+            return ExitCode::FAILURE;
+        }
+    };
+
+    match run(cli.command, config).await {
+        Ok(()) => ExitCode::SUCCESS,
+        Err(err) => {
+            eprintln!("🔥 {}", err);
+            ExitCode::FAILURE
+        }
+    }
+}
+
+async fn run(command: Command, config: Config) -> AdminResult {
+    let database = DatabaseConfig {
+        max_connections: 2,
+        min_connections: 0,
+        ..config.database.clone()
+    };
+    let pool = DbPool::connect(&config.database_url, &database).await?;
+    migrations::prepare(&pool, false).await?;
+
+    let result = match &pool {
+        DbPool::Postgres(inner) => {
+            let db_client = DbClient::new(inner.clone());
+            run_with(command, config, &pool, db_client).await
+        }
+        DbPool::Sqlite(inner) => {
+            let db_client = SqliteClient::new(inner.clone());
+            run_with(command, config, &pool, db_client).await
+        }
+    };
+
+    pool.close().await;
+    result
+}
+
+async fn run_with<D>(command: Command, config: Config, pool: &DbPool, db_client: D) -> AdminResult
+where
+    D: AdminExt + UserRepository + FileRepository + ShareRepository + Clone + 'static,
+{
+    let kek_provider = kek::from_config(&config.kek)?;
+    let app_state = Arc::new(AppState {
+        env: config,
+        users: Arc::new(db_client.clone()),
+        files: Arc::new(db_client.clone()),
+        shares: Arc::new(db_client.clone()),
+        db_pool: Some(pool.clone()),
+        kek: kek_provider,
+        rate_limit_store: Arc::new(MemoryRateLimitStore::default()),
+        // Not installed globally; nothing scrapes the admin tool
+        metrics: PrometheusBuilder::new().build_recorder().handle(),
+    });
+
+    match command {
+        Command::User(command) => user(command, app_state, &db_client).await,
+        Command::Share(command) => share(command, &db_client).await,
+        Command::Cleanup => {
+            let deleted = db_client.delete_expired_files().await?;
+            println!("deleted {} expired files", deleted);
+            Ok(())
+        }
+        Command::Storage => storage(&db_client).await,
+        Command::Verify => verify(&app_state, &db_client).await,
+        Command::SealNames => seal_names(&app_state, &db_client).await,
+        Command::Kek(command) => kek(command, &app_state, &db_client).await,
+    }
+}
+
+async fn find_user(db_client: &impl UserRepository, email: &str) -> Result<User, Box<dyn Error>> {
+    db_client
+        .get_user(None, None, Some(email))
+        .await?
+        .ok_or_else(|| format!("no user with email {}", email).into())
+}
+
+async fn user(
+    command: UserCommand,
+    app_state: Arc<AppState>,
+    db_client: &(impl AdminExt + UserRepository),
+) -> AdminResult {
+    match command {
+        UserCommand::Create {
+            name,
+            email,
+            password,
+            key_type,
+        } => {
+            let password = match password {
+                Some(password) => password,
+                None => {
+                    let mut line = String::new();
+                    io::stdin().read_line(&mut line)?;
+                    line.trim_end_matches(['\r', '\n']).to_string()
+                }
+            };
+            // Same rules as POST /api/auth/register
+            let dto = RegisterUserDto {
+                name,
+                email,
+                password: password.clone(),
+                password_confirm: password,
+                key_type,
+            };
+            dto.validate()?;
+
+            let hash_password = password::hash(&dto.password).map_err(|err| err.to_string())?;
+            let user = db_client
+                .save_user(dto.name, dto.email, hash_password)
+                .await?;
+            let key_type = key_type.unwrap_or(app_state.env.storage.key_type);
+            keys::generete_key(app_state.clone(), user.clone(), key_type).await?;
+            keys::signing_key(&app_state, &user).await?;
+            println!("created user {} ({})", user.email, user.id);
+        }
+        UserCommand::Disable { email } => {
+            let user = find_user(db_client, &email).await?;
+            let user = db_client.set_user_disabled(user.id, true).await?;
+            println!("disabled {}", user.email);
+        }
+        UserCommand::Enable { email } => {
+            let user = find_user(db_client, &email).await?;
+            let user = db_client.set_user_disabled(user.id, false).await?;
+            println!("enabled {}", user.email);
+        }
+        UserCommand::RegenerateKey { email, key_type } => {
+            let user = find_user(db_client, &email).await?;
+            let key_type = key_type.unwrap_or(app_state.env.storage.key_type);
+            let key = keys::generete_key(app_state.clone(), user.clone(), key_type).await?;
+            println!(
+                "generated {} key pair version {} for {}",
+                key.key_type, key.version, user.email
+            );
+        }
+    }
+
+    Ok(())
+}
+
+async fn share(command: ShareCommand, db_client: &impl AdminExt) -> AdminResult {
+    match command {
+        ShareCommand::List { email } => {
+            let shares = db_client.list_shares(email.as_deref()).await?;
+            for share in &shares {
+                println!(
+                    "{}  {}  {} -> {}  expires {}  downloads {}  {}",
+                    share.share_id,
+                    share.file_id,
+                    share.sender_email,
+                    share.recipient_email,
+                    share
+                        .expiration_date
+                        .map(|date| date.to_rfc3339())
+                        .unwrap_or_default(),
+                    share.download_count,
+                    if share.encrypted_metadata.is_some() {
+                        "(encrypted)"
+                    } else {
+                        &share.file_name
+                    }
+                );
+            }
+            println!("{} shares", shares.len());
+        }
+        ShareCommand::Revoke { share_id } => {
+            if db_client.revoke_share(share_id).await? {
+                println!("revoked {}", share_id);
+            } else {
+                return Err(format!("no share with id {}", share_id).into());
+            }
+        }
+    }
+
+    Ok(())
+}
+
+async fn storage(db_client: &impl AdminExt) -> AdminResult {
+    let usage = db_client.storage_usage().await?;
+    println!("{:>8}  {:>14}  {:>14}  email", "files", "plaintext", "stored");
+    for row in &usage {
+        println!(
+            "{:>8}  {:>14}  {:>14}  {}",
+            row.file_count, row.plaintext_bytes, row.stored_bytes, row.email
+        );
+    }
+    let files: i64 = usage.iter().map(|row| row.file_count).sum();
+    let plaintext: i64 = usage.iter().map(|row| row.plaintext_bytes).sum();
+    let stored: i64 = usage.iter().map(|row| row.stored_bytes).sum();
+    println!("{:>8}  {:>14}  {:>14}  total", files, plaintext, stored);
+
+    Ok(())
+}
+
+/// Decrypts every file with its recipient's private key, reporting files whose
+/// key, IV or padding is broken or whose size does not match.
+async fn verify(app_state: &AppState, db_client: &impl AdminExt) -> AdminResult {
+    let files = db_client.list_file_recipients().await?;
+    let mut problems = 0;
+
+    for entry in &files {
+        if let Err(problem) = verify_file(app_state, entry.file_id, entry.recipient_user_id).await
+        {
+            problems += 1;
+            println!("{}  {}", entry.file_id, problem);
+        }
+    }
+
+    println!("checked {} files, {} problems", files.len(), problems);
+    if problems > 0 {
+        return Err(format!("{} files failed verification", problems).into());
+    }
+    Ok(())
+}
+
+async fn verify_file(
+    app_state: &AppState,
+    file_id: Uuid,
+    recipient_user_id: Option<Uuid>,
+) -> Result<(), String> {
+    let recipient_user_id = recipient_user_id.ok_or("file has no share")?;
+    let file = app_state
+        .files
+        .get_file(file_id)
+        .await
+        .map_err(|err| err.to_string())?
+        .ok_or("file disappeared while verifying")?;
+
+    if file.iv.len() != 16 {
+        return Err(format!("IV is {} bytes, expected 16", file.iv.len()));
+    }
+    if file.encrypted_file.is_empty() || file.encrypted_file.len() % 16 != 0 {
+        return Err(format!(
+            "ciphertext is {} bytes, not a whole number of blocks",
+            file.encrypted_file.len()
+        ));
+    }
+
+    let private_key =
+        keys::read_private_key(app_state, recipient_user_id, file.recipient_key_version)
+            .await
+            .map_err(|err| format!("cannot read private key: {}", err))?;
+    let encrypted_aes_key = keys::unwrap_stored(app_state, &file.encrypted_aes_key)
+        .await
+        .map_err(|err| format!("cannot unwrap AES key: {}", err))?;
+
+    if let Some(encrypted_metadata) = &file.encrypted_metadata {
+        decrypt::decrypt_metadata(&encrypted_aes_key, encrypted_metadata, &private_key)
+            .map_err(|err| format!("metadata does not decrypt: {}", err))?;
+    }
+
+    let decrypted = decrypt::decrypt_file(
+        encrypted_aes_key,
+        file.encrypted_file,
+        file.iv,
+        &private_key,
+    )
+    .await
+    .map_err(|err| format!("does not decrypt: {}", err))?;
+    if decrypted.len() as i64 != file.file_size {
+        return Err(format!(
+            "decrypted to {} bytes, expected {}",
+            decrypted.len(),
+            file.file_size
+        ));
+    }
+
+    Ok(())
+}
+
+async fn seal_names(app_state: &AppState, db_client: &impl AdminExt) -> AdminResult {
+    let mut after = None;
+    let mut sealed = 0;
+    let mut problems = 0;
+    loop {
+        let batch = db_client.list_unsealed_files(after, 500).await?;
+        let Some(last) = batch.last() else {
+            break;
+        };
+        after = Some(last.file_id);
+        for file in &batch {
+            match seal_file(app_state, db_client, file).await {
+                Ok(true) => sealed += 1,
+                // Sealed or deleted in the meantime
+                Ok(false) => {}
+                Err(problem) => {
+                    problems += 1;
+                    println!("{}  {}", file.file_id, problem);
+                }
+            }
+        }
+    }
+
+    println!("sealed the names of {} files", sealed);
+    if problems > 0 {
+        return Err(format!("{} files kept their clear names", problems).into());
+    }
+    Ok(())
+}
+
+/// Seals the metadata with the file's AES key, as uploads do, and wraps that
+/// key for the sender as well when they have a key pair, so their sent list
+/// can still show the name.
+async fn seal_file(
+    app_state: &AppState,
+    db_client: &impl AdminExt,
+    file: &UnsealedFile,
+) -> Result<bool, String> {
+    let recipient_user_id = file.recipient_user_id.ok_or("file has no share")?;
+    let private_key =
+        keys::read_private_key(app_state, recipient_user_id, file.recipient_key_version)
+            .await
+            .map_err(|err| format!("cannot read private key: {}", err))?;
+    let encrypted_aes_key = keys::unwrap_stored(app_state, &file.encrypted_aes_key)
+        .await
+        .map_err(|err| format!("cannot unwrap AES key: {}", err))?;
+    let key = FileKey::unwrap_with(&encrypted_aes_key, &private_key)
+        .map_err(|err| format!("cannot decrypt AES key: {}", err))?;
+
+    let metadata = FileMetadata {
+        file_name: file.file_name.clone(),
+        content_type: file.content_type.clone(),
+    };
+    let metadata = metadata.to_json().map_err(|err| err.to_string())?;
+    let encrypted_metadata = key.seal(&mut OsRng, &metadata);
+
+    let sender_key = match (&file.sender_encrypted_aes_key, file.sender_user_id) {
+        (None, Some(sender_user_id)) => app_state
+            .users
+            .get_active_user_key(sender_user_id)
+            .await
+            .map_err(|err| err.to_string())?,
+        _ => None,
+    };
+    let sender_key = match sender_key {
+        Some(sender_key) => {
+            let public_key = keys::decode_public_key(&sender_key.public_key)
+                .map_err(|err| format!("sender public key: {}", err))?;
+            let wrapped = key
+                .wrap_for(&mut OsRng, &public_key)
+                .map_err(|err| format!("cannot wrap AES key for the sender: {}", err))?;
+            let wrapped = keys::wrap_stored(app_state, &wrapped)
+                .await
+                .map_err(|err| err.to_string())?;
+            Some((wrapped, sender_key.version))
+        }
+        None => None,
+    };
+
+    db_client
+        .seal_file_metadata(file.file_id, encrypted_metadata, sender_key)
+        .await
+        .map_err(|err| err.to_string())
+}
+
+async fn kek(command: KekCommand, app_state: &AppState, db_client: &impl AdminExt) -> AdminResult {
+    match command {
+        KekCommand::Generate { id } => {
+            let id = id.unwrap_or_else(|| {
+                format!("kek-{}", chrono::Utc::now().format("%Y%m%dT%H%M%S"))
+            });
+            println!("{}", kek::generate_key_line(&id));
+        }
+        KekCommand::Status => kek_status(app_state, db_client).await?,
+        KekCommand::Rewrap => kek_rewrap(app_state, db_client).await?,
+    }
+
+    Ok(())
+}
+
+/// Every private and signing key file in the key directory.
+fn private_key_files(key_dir: &str) -> io::Result<Vec<PathBuf>> {
+    let entries = match fs::read_dir(key_dir) {
+        Ok(entries) => entries,
+        Err(err) if err.kind() == io::ErrorKind::NotFound => return Ok(Vec::new()),
+        Err(err) => return Err(err),
+    };
+    let mut key_files = Vec::new();
+    for entry in entries {
+        let path = entry?.path();
+        let Some(name) = path.file_name().and_then(|name| name.to_str()) else {
+            continue;
+        };
+        if keys::parse_private_key_file_name(name).is_some()
+            || keys::parse_signing_key_file_name(name).is_some()
+        {
+            key_files.push(path);
+        }
+    }
+    Ok(key_files)
+}
+
+/// Every file's stored keys, a batch at a time.
+async fn for_each_file_keys<F>(db_client: &impl AdminExt, mut visit: F) -> AdminResult
+where
+    F: AsyncFnMut(&StoredFileKeys) -> AdminResult,
+{
+    let mut after = None;
+    loop {
+        let batch = db_client.list_file_keys(after, 500).await?;
+        let Some(last) = batch.last() else {
+            return Ok(());
+        };
+        after = Some(last.file_id);
+        for keys in &batch {
+            visit(keys).await?;
+        }
+    }
+}
+
+fn key_label(key_id: Option<String>) -> String {
+    key_id.unwrap_or_else(|| "(none)".to_string())
+}
+
+async fn kek_status(app_state: &AppState, db_client: &impl AdminExt) -> AdminResult {
+    let key_dir = &app_state.env.storage.private_key_dir;
+    let mut private_keys: BTreeMap<String, usize> = BTreeMap::new();
+    for path in private_key_files(key_dir)? {
+        let key_id = keys::key_file_kek_id(&path)?;
+        *private_keys.entry(key_label(key_id)).or_default() += 1;
+    }
+
+    let mut file_keys: BTreeMap<String, usize> = BTreeMap::new();
+    for_each_file_keys(db_client, async |keys| {
+        let stored = std::iter::once(&keys.encrypted_aes_key)
+            .chain(keys.sender_encrypted_aes_key.as_ref());
+        for stored in stored {
+            *file_keys.entry(key_label(kek::stored_key_id(stored)?)).or_default() += 1;
+        }
+        Ok(())
+    })
+    .await?;
+
+    match &app_state.kek {
+        Some(kek) => println!("current key: {}", kek.current_key_id().await?),
+        None => println!("no key encryption key configured"),
+    }
+    for (label, counts) in [("private keys", &private_keys), ("file keys", &file_keys)] {
+        for (key_id, count) in counts {
+            println!("{:<14}{:>8}  {}", label, count, key_id);
+        }
+    }
+    Ok(())
+}
+
+/// Re-wraps what is not yet under the current key. Old keys must stay
+/// configured until this finishes, since the server keeps unwrapping with them.
+async fn kek_rewrap(app_state: &AppState, db_client: &impl AdminExt) -> AdminResult {
+    let kek = app_state
+        .kek
+        .as_deref()
+        .ok_or("no key encryption key configured")?;
+    let current = kek.current_key_id().await?;
+    let is_current = |stored: &[u8]| -> Result<bool, kek::KekError> {
+        Ok(kek::stored_key_id(stored)?.as_deref() == Some(current.as_str()))
+    };
+    let rewrap = async |stored: &[u8]| -> Result<Vec<u8>, Box<dyn Error>> {
+        let key = keys::unwrap_stored(app_state, stored).await?;
+        Ok(keys::wrap_stored(app_state, &key).await?)
+    };
+
+    let key_dir = &app_state.env.storage.private_key_dir;
+    let mut private_keys = 0;
+    for path in private_key_files(key_dir)? {
+        if keys::key_file_kek_id(&path)?.as_deref() == Some(current.as_str()) {
+            continue;
+        }
+        let pem = keys::read_key_file_pem(app_state, &path).await?;
+        keys::write_key_file(app_state, &path, &pem).await?;
+        private_keys += 1;
+    }
+
+    let mut files = 0;
+    let mut changed = 0;
+    for_each_file_keys(db_client, async |keys| {
+        let sender_is_current = match &keys.sender_encrypted_aes_key {
+            Some(stored) => is_current(stored)?,
+            None => true,
+        };
+        if is_current(&keys.encrypted_aes_key)? && sender_is_current {
+            return Ok(());
+        }
+
+        let encrypted_aes_key = rewrap(&keys.encrypted_aes_key).await?;
+        let sender_encrypted_aes_key = match &keys.sender_encrypted_aes_key {
+            Some(stored) => Some(rewrap(stored).await?),
+            None => None,
+        };
+        // Deleted or re-wrapped by someone else in the meantime
+        if db_client
+            .replace_file_keys(keys, encrypted_aes_key, sender_encrypted_aes_key)
+            .await?
+        {
+            files += 1;
+        } else {
+            changed += 1;
+        }
+        Ok(())
+    })
+    .await?;
+
+    println!(
+        "re-wrapped {} private keys and the keys of {} files with {}",
+        private_keys, files, current
+    );
+    if changed > 0 {
+        println!("{} files changed while re-wrapping; run again to cover them", changed);
+    }
+    Ok(())
+}
@@ -0,0 +1,487 @@
+use std::{
+    env,
+    error::Error,
+    fs,
+    io::{self, Write},
+    path::{Path, PathBuf},
+    process::ExitCode,
+};
+
+use chrono::{Duration, SecondsFormat, Utc};
+use clap::{Args, Parser, Subcommand};
+use futures_util::StreamExt;
+use indicatif::{ProgressBar, ProgressStyle};
+use secure_share_client::{
+    Client, Error as ClientError,
+    dtos::{
+        FileUploadDto, KeyType, ListSort, LoginUserDto, RequestQueryDto, RetrieveFileDto,
+        RotateKeyDto, ShareStatus, SignatureStatus, SortOrder,
+    },
+};
+use serde::{
+    Deserialize, Serialize,
+    de::{self, DeserializeOwned, IntoDeserializer},
+};
+
+type CliResult<T = ()> = Result<T, Box<dyn Error>>;
+
+const DEFAULT_SERVER: &str = "http://localhost:8000";
+
+/// Send and receive encrypted files from the terminal.
+#[derive(Debug, Parser)]
+#[command(name = "secure-share-cli", version)]
+struct Cli {
+    #[command(subcommand)]
+    command: Command,
+}
+
+#[derive(Debug, Subcommand)]
+enum Command {
+    /// Log in and store the token in the config file
+    Login {
+        #[arg(long)]
+        email: String,
+        /// Read from stdin when omitted
+        #[arg(long)]
+        password: Option<String>,
+        /// Base URL of the server
+        #[arg(long)]
+        server: Option<String>,
+    },
+    /// Forget the stored token
+    Logout,
+    /// Encrypt a file for a recipient and upload it
+    Upload {
+        file: PathBuf,
+        /// Recipient email address
+        #[arg(long)]
+        to: String,
+        /// Password the recipient needs to download the file; read from stdin when omitted
+        #[arg(long)]
+        password: Option<String>,
+        /// RFC 3339 expiration time, seven days from now when omitted
+        #[arg(long)]
+        expires: Option<String>,
+    },
+    /// List files you have sent
+    Sent(PageArgs),
+    /// List files shared with you
+    Received(PageArgs),
+    /// Show the details of a share you sent or received
+    Show {
+        /// First column of `sent` or `received`
+        share_id: String,
+    },
+    /// Download and decrypt a file shared with you
+    Download {
+        /// First column of `received`
+        share_id: String,
+        /// Share password; read from stdin when omitted
+        #[arg(long)]
+        password: Option<String>,
+        /// Destination path, the original file name in the current directory when omitted
+        #[arg(long, short)]
+        output: Option<PathBuf>,
+        /// Replace the destination if it already exists
+        #[arg(long)]
+        force: bool,
+    },
+    /// Replace your key pair; files shared with you before stay readable
+    RotateKey {
+        /// `x25519` or `rsa`, the server's default when omitted
+        #[arg(long, value_parser = parse_value::<KeyType>)]
+        key_type: Option<KeyType>,
+    },
+}
+
+#[derive(Debug, Args)]
+struct PageArgs {
+    #[arg(long, default_value_t = 1)]
+    page: usize,
+    #[arg(long, default_value_t = 10)]
+    limit: usize,
+    /// Continue where a previous listing stopped, instead of --page
+    #[arg(long)]
+    cursor: Option<String>,
+    /// created_at, file_name, file_size or expiration_date
+    #[arg(long, value_parser = parse_value::<ListSort>)]
+    sort: Option<ListSort>,
+    /// asc or desc
+    #[arg(long, value_parser = parse_value::<SortOrder>)]
+    order: Option<SortOrder>,
+    /// active or expired
+    #[arg(long, value_parser = parse_value::<ShareStatus>)]
+    status: Option<ShareStatus>,
+    /// Only files exchanged with this user
+    #[arg(long)]
+    email: Option<String>,
+    /// Only files whose name contains this text
+    #[arg(long)]
+    name: Option<String>,
+}
+
+impl PageArgs {
+    fn query(&self) -> RequestQueryDto {
+        RequestQueryDto {
+            page: Some(self.page),
+            limit: Some(self.limit),
+            cursor: self.cursor.clone(),
+            sort: self.sort,
+            order: self.order,
+            status: self.status,
+            email: self.email.clone(),
+            file_name: self.name.clone(),
+            created_after: None,
+            created_before: None,
+        }
+    }
+
+    fn print_footer(&self, results: i64, next_cursor: Option<&str>) {
+        if self.cursor.is_none() {
+            println!("page {} of {} files", self.page, results);
+        } else {
+            println!("{} files", results);
+        }
+        if let Some(cursor) = next_cursor {
+            println!("more with --cursor {}", cursor);
+        }
+    }
+}
+
+/// Parses the names the API uses for sort keys, orders, statuses and key
+/// types.
+fn parse_value<T: DeserializeOwned>(value: &str) -> Result<T, String> {
+    T::deserialize(value.into_deserializer()).map_err(|err: de::value::Error| err.to_string())
+}
+
+/// Stored in `$XDG_CONFIG_HOME/secure-share/cli.toml` unless
+/// `SECURE_SHARE_CLI_CONFIG` names another file.
+#[derive(Debug, Default, Serialize, Deserialize)]
+struct CliConfig {
+    server: Option<String>,
+    token: Option<String>,
+}
+
+impl CliConfig {
+    fn path() -> CliResult<PathBuf> {
+        if let Ok(path) = env::var("SECURE_SHARE_CLI_CONFIG") {
+            return Ok(PathBuf::from(path));
+        }
+        let base = match env::var("XDG_CONFIG_HOME") {
+            Ok(dir) => PathBuf::from(dir),
+            Err(_) => {
+                PathBuf::from(env::var("HOME").map_err(|_| "HOME is not set")?).join(".config")
+            }
+        };
+        Ok(base.join("secure-share").join("cli.toml"))
+    }
+
+    fn load() -> CliResult<Self> {
+        let path = Self::path()?;
+        if !path.exists() {
+            return Ok(Self::default());
+        }
+        let content = fs::read_to_string(&path)?;
+        toml::from_str(&content).map_err(|err| format!("{}: {}", path.display(), err).into())
+    }
+
+    fn save(&self) -> CliResult {
+        let path = Self::path()?;
+        if let Some(dir) = path.parent() {
+            fs::create_dir_all(dir)?;
+        }
+        // The token grants full access to the account, so it is never
+        // readable by others, not even for a moment
+        let temp_path = path.with_extension("toml.tmp");
+        let _ = fs::remove_file(&temp_path);
+        let mut options = fs::OpenOptions::new();
+        options.write(true).create_new(true);
+        #[cfg(unix)]
+        {
+            use std::os::unix::fs::OpenOptionsExt;
+            options.mode(0o600);
+        }
+        options
+            .open(&temp_path)?
+            .write_all(toml::to_string(self)?.as_bytes())?;
+        fs::rename(&temp_path, &path)?;
+        Ok(())
+    }
+
+    fn server(&self) -> &str {
+        self.server.as_deref().unwrap_or(DEFAULT_SERVER)
+    }
+
+    fn client(&self) -> Client {
+        let client = Client::new(self.server());
+        match &self.token {
+            Some(token) => client.with_token(token),
+            None => client,
+        }
+    }
+}
+
+#[tokio::main]
+async fn main() -> ExitCode {
+    let cli = Cli::parse();
+    match run(cli.command).await {
+        Ok(()) => ExitCode::SUCCESS,
+        Err(err) => {
+            eprintln!("error: {}", err);
+            ExitCode::FAILURE
+        }
+    }
+}
+
+async fn run(command: Command) -> CliResult {
+    let mut config = CliConfig::load()?;
+
+    match command {
+        Command::Login {
+            email,
+            password,
+            server,
+        } => {
+            if server.is_some() {
+                config.server = server;
+            }
+            let body = LoginUserDto {
+                email,
+                password: password_or_prompt(password, "Password")?,
+            };
+            let login = config.client().login(&body).await.map_err(explain)?;
+            config.token = Some(login.token);
+            config.save()?;
+            println!("logged in to {}", config.server());
+        }
+        Command::Logout => {
+            config.token = None;
+            config.save()?;
+            println!("logged out");
+        }
+        Command::Upload {
+            file,
+            to,
+            password,
+            expires,
+        } => upload(&config.client(), file, to, password, expires).await?,
+        Command::Sent(page) => {
+            let list = config
+                .client()
+                .list_sent(&page.query())
+                .await
+                .map_err(explain)?;
+            for file in &list.files {
+                println!(
+                    "{}  to {}  expires {}  {}",
+                    file.share_id,
+                    file.recipient_email,
+                    file.expiration_date
+                        .to_rfc3339_opts(SecondsFormat::Secs, true),
+                    file.file_name
+                );
+            }
+            page.print_footer(list.results, list.next_cursor.as_deref());
+        }
+        Command::Received(page) => {
+            let list = config
+                .client()
+                .list_received(&page.query())
+                .await
+                .map_err(explain)?;
+            for file in &list.files {
+                println!(
+                    "{}  from {}  expires {}  {}",
+                    file.share_id,
+                    file.sender_email,
+                    file.expiration_date
+                        .to_rfc3339_opts(SecondsFormat::Secs, true),
+                    file.file_name
+                );
+            }
+            page.print_footer(list.results, list.next_cursor.as_deref());
+        }
+        Command::Show { share_id } => {
+            let share = config
+                .client()
+                .share_details(&share_id)
+                .await
+                .map_err(explain)?
+                .share;
+            println!("file        {}", share.file_name);
+            println!("size        {} bytes", share.file_size);
+            println!("type        {}", share.content_type);
+            println!("from        {} <{}>", share.sender_name, share.sender_email);
+            println!(
+                "to          {} <{}>",
+                share.recipient_name, share.recipient_email
+            );
+            println!(
+                "expires     {}{}",
+                share
+                    .expiration_date
+                    .to_rfc3339_opts(SecondsFormat::Secs, true),
+                if share.expired { " (expired)" } else { "" }
+            );
+            println!("downloads   {}", share.download_count);
+            if let Some(status) = share.signature_status {
+                println!("signature   {}", status.as_str());
+            }
+        }
+        Command::RotateKey { key_type } => {
+            let body = RotateKeyDto { key_type };
+            let key = config
+                .client()
+                .rotate_key(&body)
+                .await
+                .map_err(explain)?
+                .key;
+            println!(
+                "now using {} key pair version {}",
+                key.key_type.as_str(),
+                key.version
+            );
+        }
+        Command::Download {
+            share_id,
+            password,
+            output,
+            force,
+        } => download(&config.client(), share_id, password, output, force).await?,
+    }
+
+    Ok(())
+}
+
+async fn upload(
+    client: &Client,
+    path: PathBuf,
+    recipient_email: String,
+    password: Option<String>,
+    expires: Option<String>,
+) -> CliResult {
+    let file_name = path
+        .file_name()
+        .and_then(|name| name.to_str())
+        .ok_or("the path has no file name")?
+        .to_string();
+    let form_data = FileUploadDto {
+        file_name: file_name.clone(),
+        file_data: fs::read(&path)?,
+        content_type: String::new(),
+        recipient_email,
+        password: password_or_prompt(password, "Share password")?,
+        expiration_date: expires.unwrap_or_else(|| {
+            (Utc::now() + Duration::days(7)).to_rfc3339_opts(SecondsFormat::Secs, true)
+        }),
+    };
+
+    let progress = progress_bar(form_data.file_data.len() as u64);
+    let result = client
+        .upload_with_progress(form_data, {
+            let progress = progress.clone();
+            move |sent| progress.inc(sent)
+        })
+        .await;
+    progress.finish_and_clear();
+    result.map_err(explain)?;
+    println!("uploaded {}", file_name);
+
+    Ok(())
+}
+
+async fn download(
+    client: &Client,
+    shared_id: String,
+    password: Option<String>,
+    output: Option<PathBuf>,
+    force: bool,
+) -> CliResult {
+    let body = RetrieveFileDto {
+        shared_id,
+        password: password_or_prompt(password, "Share password")?,
+    };
+    let download = client.retrieve_stream(&body).await.map_err(explain)?;
+    match download.signature_status {
+        Some(SignatureStatus::Verified) | None => {}
+        Some(SignatureStatus::Unsigned) => {
+            eprintln!("warning: the file is not signed, so its sender cannot be confirmed")
+        }
+        Some(SignatureStatus::Invalid) => eprintln!(
+            "warning: the sender's signature does not match, the file may not come from them"
+        ),
+    }
+
+    let output = match output {
+        Some(output) => output,
+        None => PathBuf::from(
+            download
+                .file_name
+                .clone()
+                .ok_or("the server sent no file name")?,
+        ),
+    };
+    let mut file = create_output(&output, force)?;
+    let progress = progress_bar(download.content_length().unwrap_or(0));
+    let mut chunks = download.bytes_stream();
+    while let Some(chunk) = chunks.next().await {
+        let chunk = chunk?;
+        file.write_all(&chunk)?;
+        progress.inc(chunk.len() as u64);
+    }
+    progress.finish_and_clear();
+    println!("saved {}", output.display());
+
+    Ok(())
+}
+
+/// Opens `path` for a download, refusing to replace an existing file unless
+/// `force` is set.
+fn create_output(path: &Path, force: bool) -> CliResult<fs::File> {
+    let mut options = fs::OpenOptions::new();
+    options.write(true);
+    if force {
+        options.create(true).truncate(true);
+    } else {
+        options.create_new(true);
+    }
+    options.open(path).map_err(|err| match err.kind() {
+        io::ErrorKind::AlreadyExists => format!(
+            "{} already exists, pass --force to replace it",
+            path.display()
+        )
+        .into(),
+        _ => err.into(),
+    })
+}
+
+fn progress_bar(length: u64) -> ProgressBar {
+    let progress = ProgressBar::new(length);
+    progress.set_style(
+        ProgressStyle::with_template("{bar:40} {bytes}/{total_bytes} {bytes_per_sec} {eta}")
+            .expect("progress template is valid"),
+    );
+    progress
+}
+
+fn password_or_prompt(password: Option<String>, prompt: &str) -> CliResult<String> {
+    if let Some(password) = password {
+        return Ok(password);
+    }
+    eprint!("{}: ", prompt);
+    io::stderr().flush()?;
+    let mut line = String::new();
+    io::stdin().read_line(&mut line)?;
+    Ok(line.trim_end_matches(['\r', '\n']).to_string())
+}
+
+/// Points at `login` when there is no usable token.
+fn explain(err: ClientError) -> Box<dyn Error> {
+    match (&err, err.code()) {
+        (ClientError::NotLoggedIn, _) => "not logged in, run `secure-share-cli login` first".into(),
+        (_, Some("invalid_token" | "token_not_provided")) => {
+            "the stored token is missing or expired, run `secure-share-cli login`".into()
+        }
+        _ => err.into(),
+    }
+}
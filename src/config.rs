@@ -1,27 +1,514 @@
-use std::env;
+use std::{collections::HashMap, env, fmt, fs, path::Path};
 
-#[derive(Debug, Clone)]
+use secure_share_types::dtos::KeyType;
+use serde::Deserialize;
+use toml::{Table, Value};
+use tracing_subscriber::EnvFilter;
+
+/// Prefix for environment variables overriding nested settings, e.g.
+/// `SECURE_SHARE__SERVER__PORT=9000` sets `server.port`.
+const ENV_PREFIX: &str = "SECURE_SHARE__";
+const DEFAULT_CONFIG_FILE: &str = "config.toml";
+
+/// Environment variables that predate the config file, mapped to their setting.
+const LEGACY_ENV_VARS: [(&str, &str); 4] = [
+    ("DATABASE_URL", "database_url"),
+    ("JWT_SECRET_KEY", "jwt_secret"),
+    ("JWT_MAXAGE", "jwt_maxage"),
+    ("RATE_LIMIT_STORE", "rate_limit.store"),
+];
+
+#[derive(Debug, Clone, Copy, PartialEq, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum RateLimitBackend {
+    Memory,
+    Postgres,
+}
+
+/// Where the key encryption key wrapping stored key material comes from.
+#[derive(Debug, Clone, Copy, PartialEq, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum KekProvider {
+    /// Key material is stored as is
+    None,
+    /// A key file on local disk
+    Local,
+    /// An environment variable
+    Env,
+    /// A Vault transit engine, or anything speaking its API
+    Transit,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum LogFormat {
+    Json,
+    Pretty,
+}
+
+#[derive(Debug, Clone, Deserialize)]
+#[serde(default, deny_unknown_fields)]
 pub struct Config {
     pub database_url: String,
     pub jwt_secret: String,
     pub jwt_maxage: i64,
-    pub port: u64,
+    pub server: ServerConfig,
+    pub database: DatabaseConfig,
+    pub storage: StorageConfig,
+    pub kek: KekConfig,
+    pub jobs: JobsConfig,
+    pub rate_limit: RateLimitConfig,
+    pub limits: LimitsConfig,
+    pub logging: LoggingConfig,
+    pub metrics: MetricsConfig,
 }
 
-impl Config {
-    pub fn init() -> Self {
-        let database_url = env::var("DATABASE_URL").expect("DATABASE_URL must be set");
-        let jwt_secret = env::var("JWT_SECRET_KEY").expect("JWT_SECRET_KEY must be set");
-        let jwt_maxage = env::var("JWT_MAXAGE")
-            .expect("JWT_MAXAGE must be set")
-            .parse::<i64>()
-            .expect("JWT_MAXAGE must be a number");
-
-        Self {
-            database_url,
-            jwt_secret,
-            jwt_maxage,
+#[derive(Debug, Clone, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct ServerConfig {
+    pub host: String,
+    pub port: u16,
+    pub cors_origins: Vec<String>,
+    pub shutdown_timeout_secs: u64,
+}
+
+#[derive(Debug, Clone, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct DatabaseConfig {
+    pub max_connections: u32,
+    pub min_connections: u32,
+    pub acquire_timeout_secs: u64,
+    /// Apply pending migrations on startup instead of refusing to serve
+    pub auto_migrate: bool,
+}
+
+#[derive(Debug, Clone, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct StorageConfig {
+    pub private_key_dir: String,
+    /// Type of the key pairs generated when a user does not pick one
+    pub key_type: KeyType,
+}
+
+#[derive(Debug, Clone, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct KekConfig {
+    pub provider: KekProvider,
+    /// `local`: one `id:base64 key` per line, the last one wraps new material
+    pub key_file: String,
+    /// `env`: variable holding keys like `key_file`, separated by commas
+    pub env_var: String,
+    /// `transit`: base URL of the Vault server
+    pub transit_url: String,
+    /// `transit`: name of the transit key
+    pub transit_key: String,
+    /// `transit`: variable holding the Vault token
+    pub transit_token_env: String,
+}
+
+#[derive(Debug, Clone, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct JobsConfig {
+    pub cleanup_schedule: String,
+}
+
+#[derive(Debug, Clone, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct RateLimitConfig {
+    pub store: RateLimitBackend,
+    pub auth_per_minute: u32,
+    pub file_per_minute: u32,
+    /// Key pair rotations, which generate a key pair each
+    pub key_rotation_per_minute: u32,
+}
+
+#[derive(Debug, Clone, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct LimitsConfig {
+    pub max_upload_bytes: usize,
+    /// Newest shares a list sorted or filtered by file name is taken from,
+    /// as each name has to be decrypted first
+    pub name_search_window: usize,
+}
+
+#[derive(Debug, Clone, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct LoggingConfig {
+    pub level: String,
+    pub format: LogFormat,
+    pub otlp_endpoint: Option<String>,
+}
+
+#[derive(Debug, Clone, Default, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct MetricsConfig {
+    /// Bearer token scrapers present; `/metrics` is not served without one
+    pub token: Option<String>,
+}
+
+impl Default for Config {
+    fn default() -> Self {
+        Self {
+            database_url: String::new(),
+            jwt_secret: String::new(),
+            jwt_maxage: 60,
+            server: ServerConfig::default(),
+            database: DatabaseConfig::default(),
+            storage: StorageConfig::default(),
+            kek: KekConfig::default(),
+            jobs: JobsConfig::default(),
+            rate_limit: RateLimitConfig::default(),
+            limits: LimitsConfig::default(),
+            logging: LoggingConfig::default(),
+            metrics: MetricsConfig::default(),
+        }
+    }
+}
+
+impl Default for ServerConfig {
+    fn default() -> Self {
+        Self {
+            host: "0.0.0.0".to_string(),
             port: 8000,
+            cors_origins: vec!["http://localhost:3000".to_string()],
+            shutdown_timeout_secs: 30,
+        }
+    }
+}
+
+impl Default for DatabaseConfig {
+    fn default() -> Self {
+        Self {
+            max_connections: 10,
+            min_connections: 0,
+            acquire_timeout_secs: 30,
+            auto_migrate: false,
+        }
+    }
+}
+
+impl Default for StorageConfig {
+    fn default() -> Self {
+        Self {
+            private_key_dir: "assets/private_keys".to_string(),
+            key_type: KeyType::X25519,
+        }
+    }
+}
+
+impl Default for KekConfig {
+    fn default() -> Self {
+        Self {
+            provider: KekProvider::None,
+            key_file: "assets/kek.keys".to_string(),
+            env_var: "SECURE_SHARE_KEK".to_string(),
+            transit_url: "http://127.0.0.1:8200".to_string(),
+            transit_key: "secure-share".to_string(),
+            transit_token_env: "VAULT_TOKEN".to_string(),
+        }
+    }
+}
+
+impl Default for JobsConfig {
+    fn default() -> Self {
+        Self {
+            cleanup_schedule: "0 0 * * * *".to_string(),
+        }
+    }
+}
+
+impl Default for RateLimitConfig {
+    fn default() -> Self {
+        Self {
+            store: RateLimitBackend::Memory,
+            auth_per_minute: 10,
+            file_per_minute: 30,
+            key_rotation_per_minute: 2,
+        }
+    }
+}
+
+impl Default for LimitsConfig {
+    fn default() -> Self {
+        Self {
+            max_upload_bytes: 10 * 1024 * 1024,
+            name_search_window: 1000,
+        }
+    }
+}
+
+impl Default for LoggingConfig {
+    fn default() -> Self {
+        Self {
+            level: "info".to_string(),
+            format: LogFormat::Json,
+            otlp_endpoint: None,
+        }
+    }
+}
+
+/// Every problem found while loading the configuration.
+#[derive(Debug)]
+pub struct ConfigError(pub Vec<String>);
+
+impl fmt::Display for ConfigError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        writeln!(f, "invalid configuration:")?;
+        for problem in &self.0 {
+            writeln!(f, "  - {}", problem)?;
+        }
+        Ok(())
+    }
+}
+
+impl std::error::Error for ConfigError {}
+
+impl Config {
+    /// Loads the configuration from defaults, then the TOML file named by
+    /// `CONFIG_FILE` (or `config.toml` if present), then environment variables.
+    pub fn init() -> Result<Self, ConfigError> {
+        Self::from_vars(env::vars().collect())
+    }
+
+    /// Like [`Config::init`], with `vars` in place of the process environment.
+    pub fn from_vars(vars: HashMap<String, String>) -> Result<Self, ConfigError> {
+        let mut table = Table::new();
+        let mut problems = Vec::new();
+
+        let (path, required) = match vars.get("CONFIG_FILE") {
+            Some(path) => (path.clone(), true),
+            None => (DEFAULT_CONFIG_FILE.to_string(), false),
+        };
+        if required || Path::new(&path).exists() {
+            match fs::read_to_string(&path) {
+                Ok(content) => match content.parse::<Table>() {
+                    Ok(file_table) => table = file_table,
+                    Err(err) => problems.push(format!("{}: {}", path, err)),
+                },
+                Err(err) => problems.push(format!("{}: {}", path, err)),
+            }
+        }
+
+        for (name, key) in LEGACY_ENV_VARS {
+            if let Some(raw) = vars.get(name) {
+                set_path(&mut table, key, env_value(key, raw.clone()));
+            }
+        }
+        for (name, raw) in vars {
+            if let Some(key) = name.strip_prefix(ENV_PREFIX) {
+                let key = key.to_lowercase().replace("__", ".");
+                let value = env_value(&key, raw);
+                set_path(&mut table, &key, value);
+            }
+        }
+
+        // Serde stops at the first bad value, so check each one on its own
+        // and fall back to the default for those that fail
+        let mut leaves = Vec::new();
+        collect_leaves(&table, &mut Vec::new(), &mut leaves);
+        for (path, value) in leaves {
+            if let Some(problem) = leaf_problem(&path, value) {
+                problems.push(problem);
+                remove_leaf(&mut table, &path);
+            }
+        }
+        let config = match Value::Table(table).try_into::<Config>() {
+            Ok(config) => config,
+            Err(err) => {
+                problems.push(err.message().to_string());
+                return Err(ConfigError(problems));
+            }
+        };
+
+        problems.extend(config.validate());
+        if problems.is_empty() {
+            Ok(config)
+        } else {
+            Err(ConfigError(problems))
+        }
+    }
+
+    fn validate(&self) -> Vec<String> {
+        let mut problems = Vec::new();
+
+        let sqlite = self.database_url.starts_with("sqlite:");
+        if self.database_url.is_empty() {
+            problems.push("database_url must be set (DATABASE_URL)".to_string());
+        } else if !(sqlite
+            || self.database_url.starts_with("postgres://")
+            || self.database_url.starts_with("postgresql://"))
+        {
+            problems.push("database_url must be a postgres:// or sqlite: URL".to_string());
+        }
+        if self.jwt_secret.is_empty() {
+            problems.push("jwt_secret must be set (JWT_SECRET_KEY)".to_string());
+        }
+        if self.jwt_maxage <= 0 {
+            problems.push("jwt_maxage must be positive".to_string());
+        }
+        if self.server.port == 0 {
+            problems.push("server.port must not be 0".to_string());
+        }
+        for origin in &self.server.cors_origins {
+            if !(origin.starts_with("http://") || origin.starts_with("https://")) {
+                problems.push(format!(
+                    "server.cors_origins: {} is not an http(s) origin",
+                    origin
+                ));
+            }
+        }
+        if self.database.max_connections == 0 {
+            problems.push("database.max_connections must be at least 1".to_string());
+        }
+        if self.database.min_connections > self.database.max_connections {
+            problems.push(
+                "database.min_connections must not exceed database.max_connections".to_string(),
+            );
+        }
+        if self.storage.private_key_dir.is_empty() {
+            problems.push("storage.private_key_dir must not be empty".to_string());
+        }
+        match self.kek.provider {
+            KekProvider::None => {}
+            KekProvider::Local if self.kek.key_file.is_empty() => {
+                problems.push("kek.key_file must be set for the local provider".to_string());
+            }
+            KekProvider::Env if self.kek.env_var.is_empty() => {
+                problems.push("kek.env_var must be set for the env provider".to_string());
+            }
+            KekProvider::Transit => {
+                if !(self.kek.transit_url.starts_with("http://")
+                    || self.kek.transit_url.starts_with("https://"))
+                {
+                    problems.push(format!(
+                        "kek.transit_url: {} is not an http(s) URL",
+                        self.kek.transit_url
+                    ));
+                }
+                if self.kek.transit_key.is_empty() {
+                    problems
+                        .push("kek.transit_key must be set for the transit provider".to_string());
+                }
+            }
+            KekProvider::Local | KekProvider::Env => {}
+        }
+        if !is_cron_expression(&self.jobs.cleanup_schedule) {
+            problems.push(format!(
+                "jobs.cleanup_schedule: {} is not a 6 or 7 field cron expression",
+                self.jobs.cleanup_schedule
+            ));
+        }
+        if sqlite && self.rate_limit.store == RateLimitBackend::Postgres {
+            problems
+                .push("rate_limit.store = \"postgres\" needs a Postgres database_url".to_string());
+        }
+        if self.rate_limit.auth_per_minute == 0
+            || self.rate_limit.file_per_minute == 0
+            || self.rate_limit.key_rotation_per_minute == 0
+        {
+            problems.push("rate_limit quotas must be at least 1 request per minute".to_string());
+        }
+        if self.limits.max_upload_bytes == 0 {
+            problems.push("limits.max_upload_bytes must be positive".to_string());
+        }
+        if self.limits.name_search_window == 0 {
+            problems.push("limits.name_search_window must be positive".to_string());
+        }
+        if let Err(err) = EnvFilter::try_new(&self.logging.level) {
+            problems.push(format!("logging.level: {}", err));
+        }
+        if let Some(endpoint) = &self.logging.otlp_endpoint
+            && !(endpoint.starts_with("http://") || endpoint.starts_with("https://"))
+        {
+            problems.push(format!(
+                "logging.otlp_endpoint: {} is not an http(s) URL",
+                endpoint
+            ));
+        }
+
+        if self.metrics.token.as_deref() == Some("") {
+            problems.push("metrics.token must not be empty when set".to_string());
+        }
+
+        problems
+    }
+}
+
+fn is_cron_expression(schedule: &str) -> bool {
+    let fields = schedule.split_whitespace().count();
+    fields == 6 || fields == 7
+}
+
+/// Keeps an environment value a string unless the setting `key` only takes
+/// it as a TOML literal, such as a number, boolean or array. A secret of
+/// `123456` stays a string while a port of `9000` becomes a number.
+fn env_value(key: &str, raw: String) -> Value {
+    let path: Vec<String> = key.split('.').map(str::to_string).collect();
+    if leaf_problem(&path, Value::String(raw.clone())).is_none() {
+        return Value::String(raw);
+    }
+    let literal = format!("value = {}", raw)
+        .parse::<Table>()
+        .ok()
+        .and_then(|mut table| table.remove("value"));
+    match literal {
+        Some(value) if leaf_problem(&path, value.clone()).is_none() => value,
+        // Reported with the setting's name when the whole table is checked
+        _ => Value::String(raw),
+    }
+}
+
+/// Every value of `table` that is not a table, with its path.
+fn collect_leaves(table: &Table, path: &mut Vec<String>, leaves: &mut Vec<(Vec<String>, Value)>) {
+    for (key, value) in table {
+        path.push(key.clone());
+        match value {
+            Value::Table(inner) => collect_leaves(inner, path, leaves),
+            _ => leaves.push((path.clone(), value.clone())),
+        }
+        path.pop();
+    }
+}
+
+/// What is wrong with setting `path` to `value`, with every other setting
+/// left at its default.
+fn leaf_problem(path: &[String], value: Value) -> Option<String> {
+    let value = path.iter().rev().fold(value, |value, key| {
+        Value::Table(Table::from_iter([(key.clone(), value)]))
+    });
+    let err = value.try_into::<Config>().err()?;
+    Some(format!("{}: {}", path.join("."), err.message()))
+}
+
+fn remove_leaf(table: &mut Table, path: &[String]) {
+    match path {
+        [key] => {
+            table.remove(key);
+        }
+        [section, rest @ ..] => {
+            if let Some(Value::Table(inner)) = table.get_mut(section) {
+                remove_leaf(inner, rest);
+            }
+        }
+        [] => {}
+    }
+}
+
+fn set_path(table: &mut Table, key: &str, value: Value) {
+    match key.split_once('.') {
+        Some((section, rest)) => {
+            let entry = table
+                .entry(section)
+                .or_insert_with(|| Value::Table(Table::new()));
+            if !entry.is_table() {
+                *entry = Value::Table(Table::new());
+            }
+            if let Value::Table(inner) = entry {
+                set_path(inner, rest, value);
+            }
+        }
+        None => {
+            table.insert(key.to_string(), value);
         }
     }
 }
@@ -0,0 +1,356 @@
+use std::cmp::Ordering;
+
+use chrono::{DateTime, Utc};
+use serde::{Deserialize, Serialize};
+use sqlx::{Database, Encode, QueryBuilder, Type};
+use uuid::Uuid;
+
+use crate::{
+    dtos::{ListSort, ShareStatus, SortOrder},
+    models::{ReceiveFileDetails, SentFileDetails},
+};
+
+/// Which page of the sent or received list to return and what to leave out.
+#[derive(Debug, Clone)]
+pub struct ListQuery {
+    pub limit: usize,
+    /// Rows to skip; only used without `after`
+    pub offset: usize,
+    pub sort: ListSort,
+    pub order: SortOrder,
+    /// Sort value and id of the last row of the previous page
+    pub after: Option<(SortValue, Uuid)>,
+    pub status: Option<ShareStatus>,
+    /// Recipient of sent files or sender of received ones
+    pub email: Option<String>,
+    /// Case-insensitive substring of the file name. Like sorting by name,
+    /// the SQL queries only see the clear-text names of files uploaded before
+    /// names were encrypted; see [`ListQuery::needs_file_names`].
+    pub file_name: Option<String>,
+    pub created_after: Option<DateTime<Utc>>,
+    pub created_before: Option<DateTime<Utc>>,
+}
+
+impl Default for ListQuery {
+    fn default() -> Self {
+        Self {
+            limit: 10,
+            offset: 0,
+            sort: ListSort::CreatedAt,
+            order: SortOrder::Desc,
+            after: None,
+            status: None,
+            email: None,
+            file_name: None,
+            created_after: None,
+            created_before: None,
+        }
+    }
+}
+
+impl ListQuery {
+    /// Whether the query sorts or filters by file name, which is encrypted, so
+    /// it has to be applied with [`page`] once the names are decrypted.
+    pub fn needs_file_names(&self) -> bool {
+        self.sort == ListSort::FileName || self.file_name.is_some()
+    }
+
+    /// The newest `window` rows matching the filters that do not depend on
+    /// the file name. Each of them is decrypted before [`page`] sorts and
+    /// filters by name, so `window` bounds that work.
+    pub fn without_file_names(&self, window: usize) -> ListQuery {
+        ListQuery {
+            limit: window,
+            offset: 0,
+            sort: ListSort::CreatedAt,
+            order: SortOrder::Desc,
+            after: None,
+            file_name: None,
+            ..self.clone()
+        }
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, PartialOrd, Serialize, Deserialize)]
+pub enum SortValue {
+    Time(DateTime<Utc>),
+    Text(String),
+    Number(i64),
+}
+
+/// A row of the sent or received list.
+pub trait ListedFile {
+    /// Unique per row, breaks ties between equal sort values
+    fn id(&self) -> Uuid;
+
+    fn file_name(&self) -> &str;
+
+    fn sort_value(&self, sort: ListSort) -> Option<SortValue>;
+}
+
+impl ListedFile for SentFileDetails {
+    fn id(&self) -> Uuid {
+        self.file_id
+    }
+
+    fn file_name(&self) -> &str {
+        &self.file_name
+    }
+
+    fn sort_value(&self, sort: ListSort) -> Option<SortValue> {
+        sort_value(
+            sort,
+            &self.file_name,
+            self.file_size,
+            self.expiration_date,
+            self.created_at,
+        )
+    }
+}
+
+impl ListedFile for ReceiveFileDetails {
+    fn id(&self) -> Uuid {
+        self.share_id
+    }
+
+    fn file_name(&self) -> &str {
+        &self.file_name
+    }
+
+    fn sort_value(&self, sort: ListSort) -> Option<SortValue> {
+        sort_value(
+            sort,
+            &self.file_name,
+            self.file_size,
+            self.expiration_date,
+            self.created_at,
+        )
+    }
+}
+
+fn sort_value(
+    sort: ListSort,
+    file_name: &str,
+    file_size: i64,
+    expiration_date: Option<DateTime<Utc>>,
+    created_at: Option<DateTime<Utc>>,
+) -> Option<SortValue> {
+    match sort {
+        ListSort::CreatedAt => created_at.map(SortValue::Time),
+        ListSort::FileName => Some(SortValue::Text(file_name.to_string())),
+        ListSort::FileSize => Some(SortValue::Number(file_size)),
+        ListSort::ExpirationDate => expiration_date.map(SortValue::Time),
+    }
+}
+
+/// Filters rows by file name, sorts them and cuts out the requested page,
+/// like the `LIKE`, `ORDER BY` and keyset or `OFFSET` of the SQL queries.
+/// Returns the page and the number of rows matching the filter.
+pub fn page<T: ListedFile>(rows: Vec<T>, query: &ListQuery) -> (Vec<T>, i64) {
+    let mut rows: Vec<T> = match &query.file_name {
+        Some(file_name) => {
+            let file_name = file_name.to_lowercase();
+            rows.into_iter()
+                .filter(|row| row.file_name().to_lowercase().contains(&file_name))
+                .collect()
+        }
+        None => rows,
+    };
+    let total_count = rows.len() as i64;
+    let key = |row: &T| (row.sort_value(query.sort), row.id());
+    rows.sort_by(|a, b| {
+        let ordering = key(a).partial_cmp(&key(b)).unwrap_or(Ordering::Equal);
+        match query.order {
+            SortOrder::Asc => ordering,
+            SortOrder::Desc => ordering.reverse(),
+        }
+    });
+
+    let rows: Vec<T> = match &query.after {
+        Some((value, id)) => {
+            let after = (Some(value.clone()), *id);
+            rows.into_iter()
+                .filter(|row| match query.order {
+                    SortOrder::Asc => key(row) > after,
+                    SortOrder::Desc => key(row) < after,
+                })
+                .take(query.limit)
+                .collect()
+        }
+        None => rows
+            .into_iter()
+            .skip(query.offset)
+            .take(query.limit)
+            .collect(),
+    };
+
+    (rows, total_count)
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub(super) enum ListSide {
+    Sent,
+    Received,
+}
+
+impl ListSide {
+    /// Column breaking ties between equal sort values, as [`ListedFile::id`]
+    /// does: the file for the sender, the share for the recipient.
+    fn id_column(self) -> &'static str {
+        match self {
+            ListSide::Sent => "f.id",
+            ListSide::Received => "sl.id",
+        }
+    }
+}
+
+fn sort_column(sort: ListSort) -> &'static str {
+    match sort {
+        ListSort::CreatedAt => "sl.created_at",
+        ListSort::FileName => "f.file_name",
+        ListSort::FileSize => "f.file_size",
+        ListSort::ExpirationDate => "sl.expiration_date",
+    }
+}
+
+/// `LIKE` pattern matching `value` anywhere, with its wildcards escaped.
+fn contains_pattern(value: &str) -> String {
+    let mut pattern = String::from("%");
+    for c in value.to_lowercase().chars() {
+        if matches!(c, '%' | '_' | '\\') {
+            pattern.push('\\');
+        }
+        pattern.push(c);
+    }
+    pattern.push('%');
+    pattern
+}
+
+/// Builds the page query for Postgres or SQLite, which differ only in their
+/// placeholders.
+pub(super) fn page_query<'a, DB>(
+    side: ListSide,
+    user_id: Uuid,
+    query: &ListQuery,
+    now: DateTime<Utc>,
+) -> QueryBuilder<'a, DB>
+where
+    DB: Database,
+    DB::Arguments<'a>: Default,
+    Uuid: Encode<'a, DB> + Type<DB>,
+    DateTime<Utc>: Encode<'a, DB> + Type<DB>,
+    String: Encode<'a, DB> + Type<DB>,
+    i64: Encode<'a, DB> + Type<DB>,
+{
+    let id_column = side.id_column();
+    let (counterpart, key_column, version_column) = match side {
+        ListSide::Sent => (
+            "recipient",
+            "f.sender_encrypted_aes_key",
+            "f.sender_key_version",
+        ),
+        ListSide::Received => ("sender", "f.encrypted_aes_key", "f.recipient_key_version"),
+    };
+    let mut builder = QueryBuilder::new(format!(
+        "SELECT sl.id AS share_id, f.id AS file_id, f.file_name, f.file_size, f.content_type, \
+         u.email AS {counterpart}_email, u.name AS {counterpart}_name, sl.download_count, \
+         sl.last_downloaded_at, sl.password <> '' AS password_protected, sl.expiration_date, \
+         sl.created_at, {key_column} AS encrypted_aes_key, f.encrypted_metadata, \
+         {version_column} AS key_version",
+    ));
+    push_filters(&mut builder, side, user_id, query, now);
+
+    let column = sort_column(query.sort);
+    let (direction, comparison) = match query.order {
+        SortOrder::Asc => ("ASC", ">"),
+        SortOrder::Desc => ("DESC", "<"),
+    };
+    if let Some((value, id)) = &query.after {
+        builder.push(format!(" AND ({}, {}) {} (", column, id_column, comparison));
+        match value.clone() {
+            SortValue::Time(value) => builder.push_bind(value),
+            SortValue::Text(value) => builder.push_bind(value),
+            SortValue::Number(value) => builder.push_bind(value),
+        };
+        builder.push(", ").push_bind(*id).push(")");
+    }
+
+    builder.push(format!(
+        " ORDER BY {} {}, {} {} LIMIT ",
+        column, direction, id_column, direction
+    ));
+    builder.push_bind(query.limit as i64);
+    if query.after.is_none() {
+        builder.push(" OFFSET ").push_bind(query.offset as i64);
+    }
+
+    builder
+}
+
+/// Counts every row matching the filters, regardless of the page.
+pub(super) fn count_query<'a, DB>(
+    side: ListSide,
+    user_id: Uuid,
+    query: &ListQuery,
+    now: DateTime<Utc>,
+) -> QueryBuilder<'a, DB>
+where
+    DB: Database,
+    DB::Arguments<'a>: Default,
+    Uuid: Encode<'a, DB> + Type<DB>,
+    DateTime<Utc>: Encode<'a, DB> + Type<DB>,
+    String: Encode<'a, DB> + Type<DB>,
+{
+    let mut builder = QueryBuilder::new("SELECT COUNT(*)");
+    push_filters(&mut builder, side, user_id, query, now);
+    builder
+}
+
+fn push_filters<'a, DB>(
+    builder: &mut QueryBuilder<'a, DB>,
+    side: ListSide,
+    user_id: Uuid,
+    query: &ListQuery,
+    now: DateTime<Utc>,
+) where
+    DB: Database,
+    Uuid: Encode<'a, DB> + Type<DB>,
+    DateTime<Utc>: Encode<'a, DB> + Type<DB>,
+    String: Encode<'a, DB> + Type<DB>,
+{
+    builder.push(" FROM shared_links sl JOIN files f ON sl.file_id = f.id");
+    builder.push(match side {
+        ListSide::Sent => " JOIN users u ON sl.recipient_user_id = u.id WHERE f.user_id = ",
+        ListSide::Received => " JOIN users u ON f.user_id = u.id WHERE sl.recipient_user_id = ",
+    });
+    builder.push_bind(user_id);
+
+    match query.status {
+        Some(ShareStatus::Active) => {
+            builder.push(" AND sl.expiration_date > ").push_bind(now);
+        }
+        Some(ShareStatus::Expired) => {
+            builder.push(" AND sl.expiration_date <= ").push_bind(now);
+        }
+        None => {}
+    }
+    if let Some(email) = &query.email {
+        builder.push(" AND u.email = ").push_bind(email.clone());
+    }
+    if let Some(file_name) = &query.file_name {
+        builder
+            .push(" AND LOWER(f.file_name) LIKE ")
+            .push_bind(contains_pattern(file_name))
+            .push(" ESCAPE '\\'");
+    }
+    if let Some(created_after) = query.created_after {
+        builder
+            .push(" AND sl.created_at >= ")
+            .push_bind(created_after);
+    }
+    if let Some(created_before) = query.created_before {
+        builder
+            .push(" AND sl.created_at < ")
+            .push_bind(created_before);
+    }
+}
@@ -0,0 +1,588 @@
+use std::{
+    borrow::Cow,
+    error::Error as StdError,
+    fmt,
+    sync::{Mutex, MutexGuard},
+};
+
+use async_trait::async_trait;
+use chrono::{DateTime, Utc};
+use sqlx::error::{DatabaseError, ErrorKind};
+use uuid::Uuid;
+
+use super::{FileRepository, ListQuery, ShareRepository, UserRepository, list::page};
+use crate::{
+    dtos::ShareStatus,
+    models::{
+        File, NewFile, ReceiveFileDetails, SentFileDetails, ShareDetails, SharedLink, User,
+        UserKey, UserSigningKey,
+    },
+};
+
+/// Repositories kept in memory, so handlers can be tested without a database.
+/// Mirrors the queries of [`super::DbClient`], including the unique email.
+#[derive(Debug, Default)]
+pub struct MemoryDb {
+    tables: Mutex<Tables>,
+}
+
+#[derive(Debug, Default)]
+struct Tables {
+    users: Vec<User>,
+    user_keys: Vec<UserKey>,
+    signing_keys: Vec<UserSigningKey>,
+    files: Vec<File>,
+    shared_links: Vec<SharedLink>,
+}
+
+impl MemoryDb {
+    fn tables(&self) -> MutexGuard<'_, Tables> {
+        self.tables.lock().unwrap()
+    }
+}
+
+impl Tables {
+    fn update_user(
+        &mut self,
+        user_id: Uuid,
+        update: impl FnOnce(&mut User),
+    ) -> Result<User, sqlx::Error> {
+        let user = self
+            .users
+            .iter_mut()
+            .find(|user| user.id == user_id)
+            .ok_or(sqlx::Error::RowNotFound)?;
+        update(user);
+        user.updated_at = Some(Utc::now());
+        Ok(user.clone())
+    }
+
+    fn user(&self, user_id: Option<Uuid>) -> Option<&User> {
+        self.users.iter().find(|user| Some(user.id) == user_id)
+    }
+
+    fn file(&self, file_id: Option<Uuid>) -> Option<&File> {
+        self.files.iter().find(|file| Some(file.id) == file_id)
+    }
+
+    fn signing_key(&self, user_id: Uuid, version: Option<i32>) -> Option<&UserSigningKey> {
+        self.signing_keys
+            .iter()
+            .find(|key| key.user_id == user_id && Some(key.version) == version)
+    }
+}
+
+#[async_trait]
+impl UserRepository for MemoryDb {
+    async fn get_user(
+        &self,
+        user_id: Option<Uuid>,
+        name: Option<&str>,
+        email: Option<&str>,
+    ) -> Result<Option<User>, sqlx::Error> {
+        let tables = self.tables();
+        let user = if let Some(user_id) = user_id {
+            tables.users.iter().find(|user| user.id == user_id)
+        } else if let Some(name) = name {
+            tables.users.iter().find(|user| user.name == name)
+        } else if let Some(email) = email {
+            tables.users.iter().find(|user| user.email == email)
+        } else {
+            None
+        };
+
+        Ok(user.cloned())
+    }
+
+    async fn save_user(
+        &self,
+        name: String,
+        email: String,
+        password: String,
+    ) -> Result<User, sqlx::Error> {
+        let mut tables = self.tables();
+        if tables.users.iter().any(|user| user.email == email) {
+            return Err(sqlx::Error::Database(Box::new(UniqueViolation(
+                "users_email_key",
+            ))));
+        }
+
+        let now = Utc::now();
+        let user = User {
+            id: Uuid::new_v4(),
+            email,
+            name,
+            password,
+            public_key: None,
+            signing_key: None,
+            locale: None,
+            disabled_at: None,
+            created_at: Some(now),
+            updated_at: Some(now),
+        };
+        tables.users.push(user.clone());
+
+        Ok(user)
+    }
+
+    async fn update_user_name(&self, user_id: Uuid, name: String) -> Result<User, sqlx::Error> {
+        self.tables().update_user(user_id, |user| user.name = name)
+    }
+
+    async fn update_user_password(
+        &self,
+        user_id: Uuid,
+        password: String,
+    ) -> Result<User, sqlx::Error> {
+        self.tables()
+            .update_user(user_id, |user| user.password = password)
+    }
+
+    async fn save_user_key(
+        &self,
+        user_id: Uuid,
+        version: i32,
+        key_type: String,
+        public_key: String,
+    ) -> Result<UserKey, sqlx::Error> {
+        let mut tables = self.tables();
+        if tables
+            .user_keys
+            .iter()
+            .any(|key| key.user_id == user_id && key.version == version)
+        {
+            return Err(sqlx::Error::Database(Box::new(UniqueViolation(
+                "user_keys_pkey",
+            ))));
+        }
+
+        let now = Utc::now();
+        tables.update_user(user_id, |user| user.public_key = Some(public_key.clone()))?;
+        for key in tables
+            .user_keys
+            .iter_mut()
+            .filter(|key| key.user_id == user_id && key.status == "active")
+        {
+            key.status = "retired".to_string();
+            key.retired_at = Some(now);
+        }
+        let key = UserKey {
+            user_id,
+            version,
+            key_type,
+            public_key,
+            status: "active".to_string(),
+            created_at: Some(now),
+            retired_at: None,
+        };
+        tables.user_keys.push(key.clone());
+
+        Ok(key)
+    }
+
+    async fn get_user_keys(&self, user_id: Uuid) -> Result<Vec<UserKey>, sqlx::Error> {
+        let mut keys: Vec<UserKey> = self
+            .tables()
+            .user_keys
+            .iter()
+            .filter(|key| key.user_id == user_id)
+            .cloned()
+            .collect();
+        keys.sort_by_key(|key| key.version);
+
+        Ok(keys)
+    }
+
+    async fn get_active_user_key(&self, user_id: Uuid) -> Result<Option<UserKey>, sqlx::Error> {
+        let key = self
+            .tables()
+            .user_keys
+            .iter()
+            .find(|key| key.user_id == user_id && key.status == "active")
+            .cloned();
+
+        Ok(key)
+    }
+
+    async fn save_signing_key(
+        &self,
+        user_id: Uuid,
+        version: i32,
+        held_by: String,
+        public_key: String,
+    ) -> Result<UserSigningKey, sqlx::Error> {
+        let mut tables = self.tables();
+        if tables.signing_key(user_id, Some(version)).is_some() {
+            return Err(sqlx::Error::Database(Box::new(UniqueViolation(
+                "user_signing_keys_pkey",
+            ))));
+        }
+
+        let now = Utc::now();
+        tables.update_user(user_id, |user| user.signing_key = Some(public_key.clone()))?;
+        for key in tables
+            .signing_keys
+            .iter_mut()
+            .filter(|key| key.user_id == user_id && key.status == "active")
+        {
+            key.status = "retired".to_string();
+            key.retired_at = Some(now);
+        }
+        let key = UserSigningKey {
+            user_id,
+            version,
+            public_key,
+            held_by,
+            status: "active".to_string(),
+            created_at: Some(now),
+            retired_at: None,
+        };
+        tables.signing_keys.push(key.clone());
+
+        Ok(key)
+    }
+
+    async fn get_signing_keys(&self, user_id: Uuid) -> Result<Vec<UserSigningKey>, sqlx::Error> {
+        let mut keys: Vec<UserSigningKey> = self
+            .tables()
+            .signing_keys
+            .iter()
+            .filter(|key| key.user_id == user_id)
+            .cloned()
+            .collect();
+        keys.sort_by_key(|key| key.version);
+
+        Ok(keys)
+    }
+
+    async fn update_user_locale(
+        &self,
+        user_id: Uuid,
+        locale: Option<String>,
+    ) -> Result<User, sqlx::Error> {
+        self.tables()
+            .update_user(user_id, |user| user.locale = locale)
+    }
+
+    async fn search_by_email(
+        &self,
+        user_id: Uuid,
+        query: String,
+    ) -> Result<Vec<User>, sqlx::Error> {
+        let users = self
+            .tables()
+            .users
+            .iter()
+            .filter(|user| {
+                user.email.contains(&query) && user.public_key.is_some() && user.id != user_id
+            })
+            .cloned()
+            .collect();
+
+        Ok(users)
+    }
+}
+
+#[async_trait]
+impl FileRepository for MemoryDb {
+    async fn save_encrypted_file(&self, file: NewFile) -> Result<(), sqlx::Error> {
+        let mut tables = self.tables();
+        let now = Utc::now();
+        let file_id = Uuid::new_v4();
+        tables.files.push(File {
+            id: file_id,
+            user_id: Some(file.user_id),
+            file_name: file.file_name,
+            file_size: file.file_size,
+            content_type: file.content_type,
+            encrypted_aes_key: file.encrypted_aes_key,
+            encrypted_file: file.encrypted_file,
+            iv: file.iv,
+            encrypted_metadata: file.encrypted_metadata,
+            sender_encrypted_aes_key: file.sender_encrypted_aes_key,
+            created_at: Some(now),
+            recipient_key_version: file.recipient_key_version,
+            sender_key_version: file.sender_key_version,
+            content_digest: file.content_digest,
+            signature: file.signature,
+            signing_key_version: file.signing_key_version,
+        });
+        tables.shared_links.push(SharedLink {
+            id: Uuid::new_v4(),
+            file_id: Some(file_id),
+            recipient_user_id: Some(file.recipient_user_id),
+            password: file.password,
+            download_count: 0,
+            last_downloaded_at: None,
+            expiration_date: Some(file.expiration_date),
+            created_at: Some(now),
+        });
+
+        Ok(())
+    }
+
+    async fn get_file(&self, file_id: Uuid) -> Result<Option<File>, sqlx::Error> {
+        Ok(self.tables().file(Some(file_id)).cloned())
+    }
+}
+
+#[async_trait]
+impl ShareRepository for MemoryDb {
+    async fn get_shared(
+        &self,
+        shared_id: Uuid,
+        user_id: Uuid,
+    ) -> Result<Option<SharedLink>, sqlx::Error> {
+        let now = Utc::now();
+        let shared_link = self
+            .tables()
+            .shared_links
+            .iter()
+            .find(|link| {
+                link.id == shared_id
+                    && link.recipient_user_id == Some(user_id)
+                    && link.expiration_date.is_some_and(|date| date > now)
+            })
+            .cloned();
+
+        Ok(shared_link)
+    }
+
+    async fn get_share_details(
+        &self,
+        share_id: Uuid,
+        user_id: Uuid,
+    ) -> Result<Option<ShareDetails>, sqlx::Error> {
+        let tables = self.tables();
+        let details = tables
+            .shared_links
+            .iter()
+            .find(|link| link.id == share_id)
+            .and_then(|link| {
+                let file = tables.file(link.file_id)?;
+                let sender = tables.user(file.user_id)?;
+                let recipient = tables.user(link.recipient_user_id)?;
+                if sender.id != user_id && recipient.id != user_id {
+                    return None;
+                }
+                Some(ShareDetails {
+                    share_id: link.id,
+                    file_id: file.id,
+                    file_name: file.file_name.clone(),
+                    file_size: file.file_size,
+                    content_type: file.content_type.clone(),
+                    sender_email: sender.email.clone(),
+                    sender_name: sender.name.clone(),
+                    recipient_email: recipient.email.clone(),
+                    recipient_name: recipient.name.clone(),
+                    download_count: link.download_count,
+                    last_downloaded_at: link.last_downloaded_at,
+                    password_protected: !link.password.is_empty(),
+                    expiration_date: link.expiration_date,
+                    created_at: link.created_at,
+                    encrypted_aes_key: file.encrypted_aes_key.clone(),
+                    sender_encrypted_aes_key: file.sender_encrypted_aes_key.clone(),
+                    encrypted_metadata: file.encrypted_metadata.clone(),
+                    recipient_key_version: file.recipient_key_version,
+                    sender_key_version: file.sender_key_version,
+                    sender_id: sender.id,
+                    recipient_id: recipient.id,
+                    content_digest: file.content_digest.clone(),
+                    signature: file.signature.clone(),
+                    signing_key_version: file.signing_key_version,
+                    sender_signing_key: tables
+                        .signing_key(sender.id, file.signing_key_version)
+                        .map(|key| key.public_key.clone()),
+                })
+            });
+
+        Ok(details)
+    }
+
+    async fn record_download(&self, share_id: Uuid) -> Result<(), sqlx::Error> {
+        let mut tables = self.tables();
+        if let Some(link) = tables
+            .shared_links
+            .iter_mut()
+            .find(|link| link.id == share_id)
+        {
+            link.download_count += 1;
+            link.last_downloaded_at = Some(Utc::now());
+        }
+
+        Ok(())
+    }
+
+    async fn get_sent_files(
+        &self,
+        user_id: Uuid,
+        query: &ListQuery,
+    ) -> Result<(Vec<SentFileDetails>, i64), sqlx::Error> {
+        let tables = self.tables();
+        let now = Utc::now();
+        let files = tables
+            .shared_links
+            .iter()
+            .filter_map(|link| {
+                let file = tables.file(link.file_id)?;
+                if file.user_id != Some(user_id) {
+                    return None;
+                }
+                let recipient = tables.user(link.recipient_user_id)?;
+                if !matches_filters(query, link, &recipient.email, now) {
+                    return None;
+                }
+                Some(SentFileDetails {
+                    encrypted_aes_key: file.sender_encrypted_aes_key.clone(),
+                    encrypted_metadata: file.encrypted_metadata.clone(),
+                    key_version: file.sender_key_version,
+                    share_id: link.id,
+                    file_id: file.id,
+                    file_name: file.file_name.clone(),
+                    file_size: file.file_size,
+                    content_type: file.content_type.clone(),
+                    recipient_email: recipient.email.clone(),
+                    recipient_name: recipient.name.clone(),
+                    download_count: link.download_count,
+                    last_downloaded_at: link.last_downloaded_at,
+                    password_protected: !link.password.is_empty(),
+                    expiration_date: link.expiration_date,
+                    created_at: link.created_at,
+                })
+            })
+            .collect();
+
+        Ok(page(files, query))
+    }
+
+    async fn get_receive_files(
+        &self,
+        user_id: Uuid,
+        query: &ListQuery,
+    ) -> Result<(Vec<ReceiveFileDetails>, i64), sqlx::Error> {
+        let tables = self.tables();
+        let now = Utc::now();
+        let files = tables
+            .shared_links
+            .iter()
+            .filter(|link| link.recipient_user_id == Some(user_id))
+            .filter_map(|link| {
+                let file = tables.file(link.file_id)?;
+                let sender = tables.user(file.user_id)?;
+                if !matches_filters(query, link, &sender.email, now) {
+                    return None;
+                }
+                Some(ReceiveFileDetails {
+                    encrypted_aes_key: Some(file.encrypted_aes_key.clone()),
+                    encrypted_metadata: file.encrypted_metadata.clone(),
+                    key_version: file.recipient_key_version,
+                    share_id: link.id,
+                    file_id: file.id,
+                    file_name: file.file_name.clone(),
+                    file_size: file.file_size,
+                    content_type: file.content_type.clone(),
+                    sender_email: sender.email.clone(),
+                    sender_name: sender.name.clone(),
+                    download_count: link.download_count,
+                    last_downloaded_at: link.last_downloaded_at,
+                    password_protected: !link.password.is_empty(),
+                    expiration_date: link.expiration_date,
+                    created_at: link.created_at,
+                })
+            })
+            .collect();
+
+        Ok(page(files, query))
+    }
+
+    async fn delete_expired_files(&self) -> Result<u64, sqlx::Error> {
+        let mut tables = self.tables();
+        let now = Utc::now();
+        let (expired, active): (Vec<_>, Vec<_>) = tables
+            .shared_links
+            .drain(..)
+            .partition(|link| link.expiration_date.is_some_and(|date| date < now));
+        tables.shared_links = active;
+
+        let before = tables.files.len();
+        tables
+            .files
+            .retain(|file| !expired.iter().any(|link| link.file_id == Some(file.id)));
+
+        Ok((before - tables.files.len()) as u64)
+    }
+}
+
+/// The filters of `query` except the file name, which [`page`] applies.
+fn matches_filters(query: &ListQuery, link: &SharedLink, email: &str, now: DateTime<Utc>) -> bool {
+    let expired = link.expiration_date.is_none_or(|date| date <= now);
+    match query.status {
+        Some(ShareStatus::Active) if expired => return false,
+        Some(ShareStatus::Expired) if !expired => return false,
+        _ => {}
+    }
+    if query.email.as_ref().is_some_and(|filter| filter != email) {
+        return false;
+    }
+    let created_at = link.created_at;
+    if query
+        .created_after
+        .is_some_and(|after| created_at.is_none_or(|date| date < after))
+    {
+        return false;
+    }
+    if query
+        .created_before
+        .is_some_and(|before| created_at.is_none_or(|date| date >= before))
+    {
+        return false;
+    }
+    true
+}
+
+/// What Postgres reports for a duplicate key, so callers can keep matching on
+/// [`DatabaseError::is_unique_violation`].
+#[derive(Debug)]
+struct UniqueViolation(&'static str);
+
+impl fmt::Display for UniqueViolation {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(
+            f,
+            "duplicate key value violates unique constraint \"{}\"",
+            self.0
+        )
+    }
+}
+
+impl StdError for UniqueViolation {}
+
+impl DatabaseError for UniqueViolation {
+    fn message(&self) -> &str {
+        "duplicate key value violates unique constraint"
+    }
+
+    fn code(&self) -> Option<Cow<'_, str>> {
+        Some(Cow::Borrowed("23505"))
+    }
+
+    fn as_error(&self) -> &(dyn StdError + Send + Sync + 'static) {
+        self
+    }
+
+    fn as_error_mut(&mut self) -> &mut (dyn StdError + Send + Sync + 'static) {
+        self
+    }
+
+    fn into_error(self: Box<Self>) -> Box<dyn StdError + Send + Sync + 'static> {
+        self
+    }
+
+    fn constraint(&self) -> Option<&str> {
+        Some(self.0)
+    }
+
+    fn kind(&self) -> ErrorKind {
+        ErrorKind::UniqueViolation
+    }
+}
@@ -0,0 +1,243 @@
+//! Storage behind the handlers, split by what it stores. [`DbClient`]
+//! implements every repository on Postgres, [`SqliteClient`] on SQLite for
+//! single-node deployments, and [`MemoryDb`] keeps everything in memory for
+//! tests.
+
+mod list;
+mod memory;
+mod postgres;
+mod sqlite;
+
+use std::{fmt, str::FromStr, sync::Arc, time::Duration};
+
+use async_trait::async_trait;
+use sqlx::{
+    Pool, Postgres, Sqlite,
+    postgres::PgPoolOptions,
+    sqlite::{SqliteConnectOptions, SqliteJournalMode, SqlitePoolOptions},
+};
+use uuid::Uuid;
+
+use crate::{
+    config::DatabaseConfig,
+    models::{
+        File, NewFile, ReceiveFileDetails, SentFileDetails, ShareDetails, SharedLink, User,
+        UserKey, UserSigningKey,
+    },
+};
+
+pub use list::{ListQuery, ListedFile, SortValue, page};
+pub use memory::MemoryDb;
+pub use postgres::{AdminExt, DbClient};
+pub use sqlite::SqliteClient;
+
+/// Connection pool for the database named by `database_url`.
+#[derive(Debug, Clone)]
+pub enum DbPool {
+    Postgres(Pool<Postgres>),
+    Sqlite(Pool<Sqlite>),
+}
+
+impl DbPool {
+    /// Connects to a `postgres://` or `sqlite:` URL. SQLite databases are
+    /// created when missing.
+    pub async fn connect(url: &str, config: &DatabaseConfig) -> Result<Self, sqlx::Error> {
+        let acquire_timeout = Duration::from_secs(config.acquire_timeout_secs);
+
+        if url.starts_with("sqlite:") {
+            // WAL lets readers continue while the single writer holds the lock
+            let options = SqliteConnectOptions::from_str(url)?
+                .create_if_missing(true)
+                .journal_mode(SqliteJournalMode::Wal);
+            let pool = SqlitePoolOptions::new()
+                .max_connections(config.max_connections)
+                .min_connections(config.min_connections)
+                .acquire_timeout(acquire_timeout)
+                .connect_with(options)
+                .await?;
+            Ok(DbPool::Sqlite(pool))
+        } else {
+            let pool = PgPoolOptions::new()
+                .max_connections(config.max_connections)
+                .min_connections(config.min_connections)
+                .acquire_timeout(acquire_timeout)
+                .connect(url)
+                .await?;
+            Ok(DbPool::Postgres(pool))
+        }
+    }
+
+    /// The repositories backed by this pool.
+    pub fn repositories(
+        &self,
+    ) -> (
+        Arc<dyn UserRepository>,
+        Arc<dyn FileRepository>,
+        Arc<dyn ShareRepository>,
+    ) {
+        match self {
+            DbPool::Postgres(pool) => {
+                let db_client = DbClient::new(pool.clone());
+                (
+                    Arc::new(db_client.clone()),
+                    Arc::new(db_client.clone()),
+                    Arc::new(db_client),
+                )
+            }
+            DbPool::Sqlite(pool) => {
+                let sqlite_client = SqliteClient::new(pool.clone());
+                (
+                    Arc::new(sqlite_client.clone()),
+                    Arc::new(sqlite_client.clone()),
+                    Arc::new(sqlite_client),
+                )
+            }
+        }
+    }
+
+    pub async fn ping(&self) -> Result<(), sqlx::Error> {
+        match self {
+            DbPool::Postgres(pool) => sqlx::query("SELECT 1").execute(pool).await.map(|_| ()),
+            DbPool::Sqlite(pool) => sqlx::query("SELECT 1").execute(pool).await.map(|_| ()),
+        }
+    }
+
+    pub fn size(&self) -> u32 {
+        match self {
+            DbPool::Postgres(pool) => pool.size(),
+            DbPool::Sqlite(pool) => pool.size(),
+        }
+    }
+
+    pub fn num_idle(&self) -> usize {
+        match self {
+            DbPool::Postgres(pool) => pool.num_idle(),
+            DbPool::Sqlite(pool) => pool.num_idle(),
+        }
+    }
+
+    pub async fn close(&self) {
+        match self {
+            DbPool::Postgres(pool) => pool.close().await,
+            DbPool::Sqlite(pool) => pool.close().await,
+        }
+    }
+}
+
+#[async_trait]
+pub trait UserRepository: fmt::Debug + Send + Sync {
+    /// Looks a user up by the first of `user_id`, `name` and `email` that is set.
+    async fn get_user(
+        &self,
+        user_id: Option<Uuid>,
+        name: Option<&str>,
+        email: Option<&str>,
+    ) -> Result<Option<User>, sqlx::Error>;
+
+    /// Fails with a unique violation when the email is taken.
+    async fn save_user(
+        &self,
+        name: String,
+        email: String,
+        password: String,
+    ) -> Result<User, sqlx::Error>;
+
+    async fn update_user_name(&self, user_id: Uuid, name: String) -> Result<User, sqlx::Error>;
+
+    async fn update_user_password(
+        &self,
+        user_id: Uuid,
+        password: String,
+    ) -> Result<User, sqlx::Error>;
+
+    /// Makes `public_key`, of type `key_type`, the active key pair of
+    /// `user_id` as `version`, retiring the previous one and copying it to
+    /// `users.public_key`. Fails with a unique violation when `version` is
+    /// taken.
+    async fn save_user_key(
+        &self,
+        user_id: Uuid,
+        version: i32,
+        key_type: String,
+        public_key: String,
+    ) -> Result<UserKey, sqlx::Error>;
+
+    /// Every key pair `user_id` has had, oldest first.
+    async fn get_user_keys(&self, user_id: Uuid) -> Result<Vec<UserKey>, sqlx::Error>;
+
+    /// The key pair new files for `user_id` are wrapped to.
+    async fn get_active_user_key(&self, user_id: Uuid) -> Result<Option<UserKey>, sqlx::Error>;
+
+    /// Makes `public_key` the active signing key of `user_id` as `version`,
+    /// retiring the previous one and copying it to `users.signing_key`.
+    /// `held_by` is `server` or `client`. Fails with a unique violation when
+    /// `version` is taken.
+    async fn save_signing_key(
+        &self,
+        user_id: Uuid,
+        version: i32,
+        held_by: String,
+        public_key: String,
+    ) -> Result<UserSigningKey, sqlx::Error>;
+
+    /// Every signing key `user_id` has had, oldest first.
+    async fn get_signing_keys(&self, user_id: Uuid) -> Result<Vec<UserSigningKey>, sqlx::Error>;
+
+    async fn update_user_locale(
+        &self,
+        user_id: Uuid,
+        locale: Option<String>,
+    ) -> Result<User, sqlx::Error>;
+
+    /// Other users with a key pair whose email contains `query`.
+    async fn search_by_email(&self, user_id: Uuid, query: String)
+    -> Result<Vec<User>, sqlx::Error>;
+}
+
+#[async_trait]
+pub trait FileRepository: fmt::Debug + Send + Sync {
+    /// Stores an encrypted file together with the share for its recipient.
+    async fn save_encrypted_file(&self, file: NewFile) -> Result<(), sqlx::Error>;
+
+    async fn get_file(&self, file_id: Uuid) -> Result<Option<File>, sqlx::Error>;
+}
+
+#[async_trait]
+pub trait ShareRepository: fmt::Debug + Send + Sync {
+    /// An unexpired share addressed to `user_id`.
+    async fn get_shared(
+        &self,
+        shared_id: Uuid,
+        user_id: Uuid,
+    ) -> Result<Option<SharedLink>, sqlx::Error>;
+
+    /// A share sent by or addressed to `user_id`, expired or not. Never reads
+    /// the file contents.
+    async fn get_share_details(
+        &self,
+        share_id: Uuid,
+        user_id: Uuid,
+    ) -> Result<Option<ShareDetails>, sqlx::Error>;
+
+    /// Counts a successful download of the share.
+    async fn record_download(&self, share_id: Uuid) -> Result<(), sqlx::Error>;
+
+    /// One page of the files `user_id` has sent, together with the number
+    /// matching the filters of `query` across all pages.
+    async fn get_sent_files(
+        &self,
+        user_id: Uuid,
+        query: &ListQuery,
+    ) -> Result<(Vec<SentFileDetails>, i64), sqlx::Error>;
+
+    /// One page of the shares addressed to `user_id`, together with the number
+    /// matching the filters of `query` across all pages.
+    async fn get_receive_files(
+        &self,
+        user_id: Uuid,
+        query: &ListQuery,
+    ) -> Result<(Vec<ReceiveFileDetails>, i64), sqlx::Error>;
+
+    /// Deletes expired shares and their files, returning the number of files.
+    async fn delete_expired_files(&self) -> Result<u64, sqlx::Error>;
+}
@@ -0,0 +1,924 @@
+use async_trait::async_trait;
+use chrono::Utc;
+use sqlx::{Pool, Postgres};
+use uuid::Uuid;
+
+use super::{
+    FileRepository, ListQuery, ShareRepository, UserRepository,
+    list::{self, ListSide},
+};
+use crate::models::{
+    File, FileRecipient, NewFile, ReceiveFileDetails, SentFileDetails, ShareDetails, SharedLink,
+    StorageUsage, StoredFileKeys, UnsealedFile, User, UserKey, UserSigningKey,
+};
+
+#[derive(Debug, Clone)]
+pub struct DbClient {
+    pool: Pool<Postgres>,
+}
+
+impl DbClient {
+    pub fn new(pool: Pool<Postgres>) -> Self {
+        Self { pool }
+    }
+}
+
+#[async_trait]
+impl UserRepository for DbClient {
+    #[tracing::instrument(skip_all, err)]
+    async fn get_user(
+        &self,
+        user_id: Option<Uuid>,
+        name: Option<&str>,
+        email: Option<&str>,
+    ) -> Result<Option<User>, sqlx::Error> {
+        let mut user: Option<User> = None;
+
+        if let Some(user_id) = user_id {
+            user = sqlx::query_as!(
+                User,
+                r#"
+                SELECT id, name, email, password, public_key, signing_key, locale, disabled_at, created_at, updated_at
+                FROM users
+                WHERE id = $1
+                "#,
+                user_id
+            )
+            .fetch_optional(&self.pool)
+            .await?;
+        } else if let Some(name) = name {
+            user = sqlx::query_as!(
+                User,
+                r#"
+                SELECT id, name, email, password, public_key, signing_key, locale, disabled_at, created_at, updated_at
+                FROM users
+                WHERE name = $1
+                "#,
+                name
+            )
+            .fetch_optional(&self.pool)
+            .await?;
+        } else if let Some(email) = email {
+            user = sqlx::query_as!(
+                User,
+                r#"
+                SELECT id, name, email, password, public_key, signing_key, locale, disabled_at, created_at, updated_at
+                FROM users
+                WHERE email = $1
+                "#,
+                email
+            )
+            .fetch_optional(&self.pool)
+            .await?;
+        }
+        Ok(user)
+    }
+
+    #[tracing::instrument(skip_all, err)]
+    async fn save_user(
+        &self,
+        name: String,
+        email: String,
+        password: String,
+    ) -> Result<User, sqlx::Error> {
+        let user = sqlx::query_as!(
+            User,
+            r#"
+            INSERT INTO users (name, email, password)
+            VALUES ($1, $2, $3)
+            RETURNING id, name, email, password, public_key, signing_key, locale, disabled_at, created_at, updated_at
+            "#,
+            name,
+            email,
+            password
+        )
+        .fetch_one(&self.pool)
+        .await?;
+        Ok(user)
+    }
+
+    #[tracing::instrument(skip(self, name), err)]
+    async fn update_user_name(&self, user_id: Uuid, name: String) -> Result<User, sqlx::Error> {
+        let user = sqlx::query_as!(
+            User,
+            r#"
+            UPDATE users
+            SET name = $1, updated_at = NOW()
+            WHERE id = $2
+            RETURNING id, name, email, password, public_key, signing_key, locale, disabled_at, created_at, updated_at
+            "#,
+            name,
+            user_id
+        )
+        .fetch_one(&self.pool)
+        .await?;
+
+        Ok(user)
+    }
+
+    #[tracing::instrument(skip(self, password), err)]
+    async fn update_user_password(
+        &self,
+        user_id: Uuid,
+        password: String,
+    ) -> Result<User, sqlx::Error> {
+        let user = sqlx::query_as!(
+            User,
+            r#"
+            UPDATE users
+            SET password = $1, updated_at = NOW()
+            WHERE id = $2
+            RETURNING id, name, email, password, public_key, signing_key, locale, disabled_at, created_at, updated_at
+            "#,
+            password.into(),
+            user_id
+        )
+        .fetch_one(&self.pool)
+        .await?;
+
+        Ok(user)
+    }
+
+    #[tracing::instrument(skip(self, public_key), err)]
+    async fn save_user_key(
+        &self,
+        user_id: Uuid,
+        version: i32,
+        key_type: String,
+        public_key: String,
+    ) -> Result<UserKey, sqlx::Error> {
+        let mut tx = self.pool.begin().await?;
+
+        sqlx::query!(
+            r#"
+            UPDATE user_keys
+            SET status = 'retired', retired_at = NOW()
+            WHERE user_id = $1 AND status = 'active'
+            "#,
+            user_id
+        )
+        .execute(&mut *tx)
+        .await?;
+
+        let key = sqlx::query_as!(
+            UserKey,
+            r#"
+            INSERT INTO user_keys (user_id, version, key_type, public_key, status, created_at)
+            VALUES ($1, $2, $3, $4, 'active', NOW())
+            RETURNING user_id, version, key_type, public_key, status, created_at, retired_at
+            "#,
+            user_id,
+            version,
+            key_type,
+            public_key
+        )
+        .fetch_one(&mut *tx)
+        .await?;
+
+        sqlx::query!(
+            r#"
+            UPDATE users
+            SET public_key = $1, updated_at = NOW()
+            WHERE id = $2
+            "#,
+            key.public_key,
+            user_id
+        )
+        .execute(&mut *tx)
+        .await?;
+
+        tx.commit().await?;
+        Ok(key)
+    }
+
+    #[tracing::instrument(skip(self), err)]
+    async fn get_user_keys(&self, user_id: Uuid) -> Result<Vec<UserKey>, sqlx::Error> {
+        sqlx::query_as!(
+            UserKey,
+            r#"
+            SELECT user_id, version, key_type, public_key, status, created_at, retired_at
+            FROM user_keys
+            WHERE user_id = $1
+            ORDER BY version
+            "#,
+            user_id
+        )
+        .fetch_all(&self.pool)
+        .await
+    }
+
+    #[tracing::instrument(skip(self), err)]
+    async fn get_active_user_key(&self, user_id: Uuid) -> Result<Option<UserKey>, sqlx::Error> {
+        sqlx::query_as!(
+            UserKey,
+            r#"
+            SELECT user_id, version, key_type, public_key, status, created_at, retired_at
+            FROM user_keys
+            WHERE user_id = $1 AND status = 'active'
+            "#,
+            user_id
+        )
+        .fetch_optional(&self.pool)
+        .await
+    }
+
+    #[tracing::instrument(skip(self, public_key), err)]
+    async fn save_signing_key(
+        &self,
+        user_id: Uuid,
+        version: i32,
+        held_by: String,
+        public_key: String,
+    ) -> Result<UserSigningKey, sqlx::Error> {
+        let mut tx = self.pool.begin().await?;
+
+        sqlx::query!(
+            r#"
+            UPDATE user_signing_keys
+            SET status = 'retired', retired_at = NOW()
+            WHERE user_id = $1 AND status = 'active'
+            "#,
+            user_id
+        )
+        .execute(&mut *tx)
+        .await?;
+
+        let key = sqlx::query_as!(
+            UserSigningKey,
+            r#"
+            INSERT INTO user_signing_keys (user_id, version, public_key, held_by, status, created_at)
+            VALUES ($1, $2, $3, $4, 'active', NOW())
+            RETURNING user_id, version, public_key, held_by, status, created_at, retired_at
+            "#,
+            user_id,
+            version,
+            public_key,
+            held_by
+        )
+        .fetch_one(&mut *tx)
+        .await?;
+
+        sqlx::query!(
+            r#"
+            UPDATE users
+            SET signing_key = $1, updated_at = NOW()
+            WHERE id = $2
+            "#,
+            key.public_key,
+            user_id
+        )
+        .execute(&mut *tx)
+        .await?;
+
+        tx.commit().await?;
+        Ok(key)
+    }
+
+    #[tracing::instrument(skip(self), err)]
+    async fn get_signing_keys(&self, user_id: Uuid) -> Result<Vec<UserSigningKey>, sqlx::Error> {
+        sqlx::query_as!(
+            UserSigningKey,
+            r#"
+            SELECT user_id, version, public_key, held_by, status, created_at, retired_at
+            FROM user_signing_keys
+            WHERE user_id = $1
+            ORDER BY version
+            "#,
+            user_id
+        )
+        .fetch_all(&self.pool)
+        .await
+    }
+
+    #[tracing::instrument(skip(self), err)]
+    async fn update_user_locale(
+        &self,
+        user_id: Uuid,
+        locale: Option<String>,
+    ) -> Result<User, sqlx::Error> {
+        let user = sqlx::query_as!(
+            User,
+            r#"
+            UPDATE users
+            SET locale = $1, updated_at = NOW()
+            WHERE id = $2
+            RETURNING id, name, email, password, public_key, signing_key, locale, disabled_at, created_at, updated_at
+            "#,
+            locale,
+            user_id
+        )
+        .fetch_one(&self.pool)
+        .await?;
+
+        Ok(user)
+    }
+
+    #[tracing::instrument(skip(self, query), err)]
+    async fn search_by_email(
+        &self,
+        user_id: Uuid,
+        query: String,
+    ) -> Result<Vec<User>, sqlx::Error> {
+        let user = sqlx::query_as!(
+            User,
+            r#"
+            SELECT id, name, email, password, public_key, signing_key, locale, disabled_at, created_at, updated_at
+            FROM users
+            WHERE POSITION($1 IN email) > 0 AND public_key IS NOT NULL AND id != $2
+            "#,
+            query,
+            user_id
+        )
+        .fetch_all(&self.pool)
+        .await?;
+
+        Ok(user)
+    }
+}
+
+#[async_trait]
+impl FileRepository for DbClient {
+    #[tracing::instrument(
+        skip_all,
+        fields(user_id = %file.user_id, recipient_user_id = %file.recipient_user_id, file_size = file.file_size),
+        err
+    )]
+    async fn save_encrypted_file(&self, file: NewFile) -> Result<(), sqlx::Error> {
+        // Insert into the files table and get the file_id
+        let file_id: Uuid = sqlx::query_scalar!(
+            r#"
+            INSERT INTO files (user_id, file_name, file_size, content_type, encrypted_aes_key, encrypted_file, iv, encrypted_metadata, sender_encrypted_aes_key, recipient_key_version, sender_key_version, content_digest, signature, signing_key_version, created_at)
+            VALUES ($1, $2, $3, $4, $5, $6, $7, $8, $9, $10, $11, $12, $13, $14, NOW())
+            RETURNING id
+            "#,
+            file.user_id,
+            file.file_name,
+            file.file_size,
+            file.content_type,
+            file.encrypted_aes_key,
+            file.encrypted_file,
+            file.iv,
+            file.encrypted_metadata,
+            file.sender_encrypted_aes_key,
+            file.recipient_key_version,
+            file.sender_key_version,
+            file.content_digest,
+            file.signature,
+            file.signing_key_version
+        )
+        .fetch_one(&self.pool)
+        .await?;
+
+        // Insert into the shared_links table using the returned file_id
+        sqlx::query!(
+            r#"
+            INSERT INTO shared_links (file_id, recipient_user_id, password, expiration_date, created_at)
+            VALUES ($1, $2, $3, $4, NOW())
+            "#,
+            file_id,
+            file.recipient_user_id,
+            file.password,
+            file.expiration_date
+        )
+        .execute(&self.pool)
+        .await?;
+        Ok(())
+    }
+
+    #[tracing::instrument(skip(self), err)]
+    async fn get_file(&self, file_id: Uuid) -> Result<Option<File>, sqlx::Error> {
+        let file = sqlx::query_as!(
+            File,
+            r#"
+            SELECT id, user_id, file_name, file_size, content_type, encrypted_aes_key, encrypted_file, iv, encrypted_metadata, sender_encrypted_aes_key, created_at, recipient_key_version, sender_key_version, content_digest, signature, signing_key_version
+            FROM files
+            WHERE id = $1
+            "#,
+            file_id
+        )
+        .fetch_optional(&self.pool)
+        .await?;
+
+        Ok(file)
+    }
+}
+
+#[async_trait]
+impl ShareRepository for DbClient {
+    #[tracing::instrument(skip(self), err)]
+    async fn get_shared(
+        &self,
+        shared_id: Uuid,
+        user_id: Uuid,
+    ) -> Result<Option<SharedLink>, sqlx::Error> {
+        let shared_link = sqlx::query_as!(
+            SharedLink,
+            r#"
+            SELECT id, file_id, recipient_user_id, password, download_count, last_downloaded_at, expiration_date, created_at
+            FROM shared_links
+            WHERE id = $1
+            AND recipient_user_id = $2
+            AND expiration_date > NOW()
+            "#,
+            shared_id,
+            user_id
+        )
+        .fetch_optional(&self.pool)
+        .await?;
+
+        Ok(shared_link)
+    }
+
+    #[tracing::instrument(skip(self), err)]
+    async fn get_share_details(
+        &self,
+        share_id: Uuid,
+        user_id: Uuid,
+    ) -> Result<Option<ShareDetails>, sqlx::Error> {
+        let share = sqlx::query_as!(
+            ShareDetails,
+            r#"
+            SELECT
+                sl.id AS share_id,
+                f.id AS file_id,
+                f.file_name,
+                f.file_size,
+                f.content_type,
+                sender.email AS sender_email,
+                sender.name AS sender_name,
+                recipient.email AS recipient_email,
+                recipient.name AS recipient_name,
+                sl.download_count,
+                sl.last_downloaded_at,
+                sl.password <> '' AS "password_protected!",
+                sl.expiration_date,
+                sl.created_at,
+                f.encrypted_aes_key,
+                f.sender_encrypted_aes_key,
+                f.encrypted_metadata,
+                f.recipient_key_version,
+                f.sender_key_version,
+                sender.id AS sender_id,
+                recipient.id AS recipient_id,
+                f.content_digest,
+                f.signature,
+                f.signing_key_version,
+                sk.public_key AS "sender_signing_key?"
+            FROM
+                shared_links sl
+            JOIN
+                files f ON sl.file_id = f.id
+            JOIN
+                users sender ON f.user_id = sender.id
+            JOIN
+                users recipient ON sl.recipient_user_id = recipient.id
+            LEFT JOIN
+                user_signing_keys sk ON sk.user_id = f.user_id AND sk.version = f.signing_key_version
+            WHERE
+                sl.id = $1 AND (sender.id = $2 OR recipient.id = $2)
+            "#,
+            share_id,
+            user_id
+        )
+        .fetch_optional(&self.pool)
+        .await?;
+
+        Ok(share)
+    }
+
+    #[tracing::instrument(skip(self), err)]
+    async fn record_download(&self, share_id: Uuid) -> Result<(), sqlx::Error> {
+        sqlx::query!(
+            r#"
+            UPDATE shared_links
+            SET download_count = download_count + 1, last_downloaded_at = NOW()
+            WHERE id = $1
+            "#,
+            share_id
+        )
+        .execute(&self.pool)
+        .await?;
+
+        Ok(())
+    }
+
+    #[tracing::instrument(skip(self), err)]
+    async fn get_sent_files(
+        &self,
+        user_id: Uuid,
+        query: &ListQuery,
+    ) -> Result<(Vec<SentFileDetails>, i64), sqlx::Error> {
+        let now = Utc::now();
+
+        let files = list::page_query::<Postgres>(ListSide::Sent, user_id, query, now)
+            .build_query_as()
+            .fetch_all(&self.pool)
+            .await?;
+
+        let total_count = list::count_query::<Postgres>(ListSide::Sent, user_id, query, now)
+            .build_query_scalar()
+            .fetch_one(&self.pool)
+            .await?;
+
+        Ok((files, total_count))
+    }
+
+    #[tracing::instrument(skip(self), err)]
+    async fn get_receive_files(
+        &self,
+        user_id: Uuid,
+        query: &ListQuery,
+    ) -> Result<(Vec<ReceiveFileDetails>, i64), sqlx::Error> {
+        let now = Utc::now();
+
+        let files = list::page_query::<Postgres>(ListSide::Received, user_id, query, now)
+            .build_query_as()
+            .fetch_all(&self.pool)
+            .await?;
+
+        let total_count = list::count_query::<Postgres>(ListSide::Received, user_id, query, now)
+            .build_query_scalar()
+            .fetch_one(&self.pool)
+            .await?;
+
+        Ok((files, total_count))
+    }
+
+    #[tracing::instrument(skip(self), err)]
+    async fn delete_expired_files(&self) -> Result<u64, sqlx::Error> {
+        let expired_shared_links: Vec<Uuid> = sqlx::query_scalar!(
+            r#"
+                   SELECT sl.id
+                   FROM shared_links sl
+                   WHERE sl.expiration_date < NOW()
+                   "#,
+        )
+        .fetch_all(&self.pool)
+        .await?;
+
+        if expired_shared_links.is_empty() {
+            tracing::debug!("No expired files or shared links to delete");
+            return Ok(0);
+        }
+
+        let expired_file_ids: Vec<Uuid> = sqlx::query_scalar!(
+            r#"
+                   SELECT f.id
+                   FROM files f
+                   WHERE f.id IN (
+                       SELECT sl.file_id
+                       FROM shared_links sl
+                       WHERE sl.expiration_date < NOW()
+                   )
+                   "#,
+        )
+        .fetch_all(&self.pool)
+        .await?;
+
+        sqlx::query!(
+            r#"
+                   DELETE FROM shared_links
+                   WHERE id = ANY($1)
+                   "#,
+            &expired_shared_links[..] // Pass the list of expired shared link IDs
+        )
+        .execute(&self.pool)
+        .await?;
+
+        // Delete the expired files
+        let deleted_files = sqlx::query!(
+            r#"
+                   DELETE FROM files
+                   WHERE id = ANY($1)
+                   "#,
+            &expired_file_ids[..] // Pass the list of expired file IDs
+        )
+        .execute(&self.pool)
+        .await?
+        .rows_affected();
+
+        tracing::info!(
+            deleted_links = expired_shared_links.len(),
+            deleted_files,
+            "Deleted expired files and their shared links"
+        );
+
+        Ok(deleted_files)
+    }
+}
+
+/// Queries used by `secure-share-admin`; nothing in the HTTP API calls these.
+#[allow(async_fn_in_trait)]
+pub trait AdminExt {
+    async fn set_user_disabled(&self, user_id: Uuid, disabled: bool) -> Result<User, sqlx::Error>;
+    async fn list_shares(&self, email: Option<&str>) -> Result<Vec<ShareDetails>, sqlx::Error>;
+    async fn revoke_share(&self, share_id: Uuid) -> Result<bool, sqlx::Error>;
+    async fn storage_usage(&self) -> Result<Vec<StorageUsage>, sqlx::Error>;
+    async fn list_file_recipients(&self) -> Result<Vec<FileRecipient>, sqlx::Error>;
+    /// The stored keys of up to `limit` files following `after` in id order.
+    async fn list_file_keys(
+        &self,
+        after: Option<Uuid>,
+        limit: i64,
+    ) -> Result<Vec<StoredFileKeys>, sqlx::Error>;
+    /// Replaces the keys of a file unless they changed since `current` was
+    /// read, e.g. by a concurrent re-wrap.
+    async fn replace_file_keys(
+        &self,
+        current: &StoredFileKeys,
+        encrypted_aes_key: Vec<u8>,
+        sender_encrypted_aes_key: Option<Vec<u8>>,
+    ) -> Result<bool, sqlx::Error>;
+    /// Up to `limit` files following `after` in id order whose name and
+    /// content type are still stored in the clear.
+    async fn list_unsealed_files(
+        &self,
+        after: Option<Uuid>,
+        limit: i64,
+    ) -> Result<Vec<UnsealedFile>, sqlx::Error>;
+    /// Stores the sealed metadata of a file and blanks its clear-text name and
+    /// content type, unless it was sealed in the meantime. `sender_key` is
+    /// the AES key wrapped for the sender and the version of their key pair,
+    /// for files that have none yet.
+    async fn seal_file_metadata(
+        &self,
+        file_id: Uuid,
+        encrypted_metadata: Vec<u8>,
+        sender_key: Option<(Vec<u8>, i32)>,
+    ) -> Result<bool, sqlx::Error>;
+}
+
+impl AdminExt for DbClient {
+    #[tracing::instrument(skip(self), err)]
+    async fn set_user_disabled(&self, user_id: Uuid, disabled: bool) -> Result<User, sqlx::Error> {
+        let user = sqlx::query_as!(
+            User,
+            r#"
+            UPDATE users
+            SET disabled_at = CASE WHEN $1 THEN COALESCE(disabled_at, NOW()) END, updated_at = NOW()
+            WHERE id = $2
+            RETURNING id, name, email, password, public_key, signing_key, locale, disabled_at, created_at, updated_at
+            "#,
+            disabled,
+            user_id
+        )
+        .fetch_one(&self.pool)
+        .await?;
+
+        Ok(user)
+    }
+
+    #[tracing::instrument(skip(self), err)]
+    async fn list_shares(&self, email: Option<&str>) -> Result<Vec<ShareDetails>, sqlx::Error> {
+        let shares = sqlx::query_as!(
+            ShareDetails,
+            r#"
+            SELECT
+                sl.id AS share_id,
+                f.id AS file_id,
+                f.file_name,
+                f.file_size,
+                f.content_type,
+                sender.email AS sender_email,
+                sender.name AS sender_name,
+                recipient.email AS recipient_email,
+                recipient.name AS recipient_name,
+                sl.download_count,
+                sl.last_downloaded_at,
+                sl.password <> '' AS "password_protected!",
+                sl.expiration_date,
+                sl.created_at,
+                f.encrypted_aes_key,
+                f.sender_encrypted_aes_key,
+                f.encrypted_metadata,
+                f.recipient_key_version,
+                f.sender_key_version,
+                sender.id AS sender_id,
+                recipient.id AS recipient_id,
+                f.content_digest,
+                f.signature,
+                f.signing_key_version,
+                sk.public_key AS "sender_signing_key?"
+            FROM
+                shared_links sl
+            JOIN
+                files f ON sl.file_id = f.id
+            JOIN
+                users sender ON f.user_id = sender.id
+            JOIN
+                users recipient ON sl.recipient_user_id = recipient.id
+            LEFT JOIN
+                user_signing_keys sk ON sk.user_id = f.user_id AND sk.version = f.signing_key_version
+            WHERE
+                $1::TEXT IS NULL OR sender.email = $1 OR recipient.email = $1
+            ORDER BY
+                sl.created_at DESC
+            "#,
+            email
+        )
+        .fetch_all(&self.pool)
+        .await?;
+
+        Ok(shares)
+    }
+
+    #[tracing::instrument(skip(self), err)]
+    async fn revoke_share(&self, share_id: Uuid) -> Result<bool, sqlx::Error> {
+        let mut tx = self.pool.begin().await?;
+
+        let file_id = sqlx::query_scalar!(
+            r#"
+            DELETE FROM shared_links
+            WHERE id = $1
+            RETURNING file_id
+            "#,
+            share_id
+        )
+        .fetch_optional(&mut *tx)
+        .await?;
+
+        let Some(file_id) = file_id else {
+            return Ok(false);
+        };
+
+        // Each upload has a single share, so the file has no other readers left
+        sqlx::query!(
+            r#"
+            DELETE FROM files f
+            WHERE f.id = $1
+            AND NOT EXISTS (SELECT 1 FROM shared_links sl WHERE sl.file_id = f.id)
+            "#,
+            file_id
+        )
+        .execute(&mut *tx)
+        .await?;
+
+        tx.commit().await?;
+
+        Ok(true)
+    }
+
+    #[tracing::instrument(skip(self), err)]
+    async fn storage_usage(&self) -> Result<Vec<StorageUsage>, sqlx::Error> {
+        let usage = sqlx::query_as!(
+            StorageUsage,
+            r#"
+            SELECT
+                u.email,
+                COUNT(f.id) AS "file_count!",
+                COALESCE(SUM(f.file_size), 0)::BIGINT AS "plaintext_bytes!",
+                COALESCE(SUM(OCTET_LENGTH(f.encrypted_file)), 0)::BIGINT AS "stored_bytes!"
+            FROM
+                users u
+            LEFT JOIN
+                files f ON f.user_id = u.id
+            GROUP BY
+                u.email
+            ORDER BY
+                4 DESC, u.email
+            "#
+        )
+        .fetch_all(&self.pool)
+        .await?;
+
+        Ok(usage)
+    }
+
+    #[tracing::instrument(skip(self), err)]
+    async fn list_file_recipients(&self) -> Result<Vec<FileRecipient>, sqlx::Error> {
+        let files = sqlx::query_as!(
+            FileRecipient,
+            r#"
+            SELECT f.id AS file_id, sl.recipient_user_id
+            FROM files f
+            LEFT JOIN shared_links sl ON sl.file_id = f.id
+            ORDER BY f.created_at
+            "#
+        )
+        .fetch_all(&self.pool)
+        .await?;
+
+        Ok(files)
+    }
+
+    #[tracing::instrument(skip(self), err)]
+    async fn list_file_keys(
+        &self,
+        after: Option<Uuid>,
+        limit: i64,
+    ) -> Result<Vec<StoredFileKeys>, sqlx::Error> {
+        let keys = sqlx::query_as!(
+            StoredFileKeys,
+            r#"
+            SELECT id AS file_id, encrypted_aes_key, sender_encrypted_aes_key
+            FROM files
+            WHERE $1::UUID IS NULL OR id > $1
+            ORDER BY id
+            LIMIT $2
+            "#,
+            after,
+            limit
+        )
+        .fetch_all(&self.pool)
+        .await?;
+
+        Ok(keys)
+    }
+
+    #[tracing::instrument(
+        skip(self, current, encrypted_aes_key, sender_encrypted_aes_key),
+        fields(file_id = %current.file_id),
+        err
+    )]
+    async fn replace_file_keys(
+        &self,
+        current: &StoredFileKeys,
+        encrypted_aes_key: Vec<u8>,
+        sender_encrypted_aes_key: Option<Vec<u8>>,
+    ) -> Result<bool, sqlx::Error> {
+        let result = sqlx::query!(
+            r#"
+            UPDATE files
+            SET encrypted_aes_key = $1, sender_encrypted_aes_key = $2
+            WHERE id = $3
+            AND encrypted_aes_key = $4
+            AND sender_encrypted_aes_key IS NOT DISTINCT FROM $5
+            "#,
+            encrypted_aes_key,
+            sender_encrypted_aes_key,
+            current.file_id,
+            current.encrypted_aes_key,
+            current.sender_encrypted_aes_key
+        )
+        .execute(&self.pool)
+        .await?;
+
+        Ok(result.rows_affected() == 1)
+    }
+
+    #[tracing::instrument(skip(self), err)]
+    async fn list_unsealed_files(
+        &self,
+        after: Option<Uuid>,
+        limit: i64,
+    ) -> Result<Vec<UnsealedFile>, sqlx::Error> {
+        let files = sqlx::query_as!(
+            UnsealedFile,
+            r#"
+            SELECT
+                f.id AS file_id,
+                f.user_id AS sender_user_id,
+                sl.recipient_user_id AS "recipient_user_id?",
+                f.file_name,
+                f.content_type,
+                f.encrypted_aes_key,
+                f.recipient_key_version,
+                f.sender_encrypted_aes_key
+            FROM files f
+            LEFT JOIN shared_links sl ON sl.file_id = f.id
+            WHERE f.encrypted_metadata IS NULL
+            AND ($1::UUID IS NULL OR f.id > $1)
+            ORDER BY f.id
+            LIMIT $2
+            "#,
+            after,
+            limit
+        )
+        .fetch_all(&self.pool)
+        .await?;
+
+        Ok(files)
+    }
+
+    #[tracing::instrument(skip(self, encrypted_metadata, sender_key), err)]
+    async fn seal_file_metadata(
+        &self,
+        file_id: Uuid,
+        encrypted_metadata: Vec<u8>,
+        sender_key: Option<(Vec<u8>, i32)>,
+    ) -> Result<bool, sqlx::Error> {
+        let (sender_encrypted_aes_key, sender_key_version) = sender_key.unzip();
+        let result = sqlx::query!(
+            r#"
+            UPDATE files
+            SET encrypted_metadata = $1,
+                file_name = '',
+                content_type = 'application/octet-stream',
+                sender_encrypted_aes_key = COALESCE(sender_encrypted_aes_key, $2),
+                sender_key_version = CASE
+                    WHEN sender_encrypted_aes_key IS NULL THEN COALESCE($3, sender_key_version)
+                    ELSE sender_key_version
+                END
+            WHERE id = $4 AND encrypted_metadata IS NULL
+            "#,
+            encrypted_metadata,
+            sender_encrypted_aes_key,
+            sender_key_version,
+            file_id
+        )
+        .execute(&self.pool)
+        .await?;
+
+        Ok(result.rows_affected() == 1)
+    }
+}
@@ -0,0 +1,768 @@
+use async_trait::async_trait;
+use chrono::Utc;
+use sqlx::{Pool, Sqlite};
+use uuid::Uuid;
+
+use super::{
+    AdminExt, FileRepository, ListQuery, ShareRepository, UserRepository,
+    list::{self, ListSide},
+};
+use crate::models::{
+    File, FileRecipient, NewFile, ReceiveFileDetails, SentFileDetails, ShareDetails, SharedLink,
+    StorageUsage, StoredFileKeys, UnsealedFile, User, UserKey, UserSigningKey,
+};
+
+// The query macros are checked against the Postgres DATABASE_URL, so these
+// queries are plain strings. Ids and timestamps are bound from Rust because
+// SQLite has no UUIDs and `CURRENT_TIMESTAMP` would not compare with RFC 3339.
+
+const USER_COLUMNS: &str = "id, name, email, password, public_key, signing_key, locale, \
+    disabled_at, created_at, updated_at";
+
+const USER_KEY_COLUMNS: &str =
+    "user_id, version, key_type, public_key, status, created_at, retired_at";
+
+const SIGNING_KEY_COLUMNS: &str =
+    "user_id, version, public_key, held_by, status, created_at, retired_at";
+
+const SHARE_DETAILS_SELECT: &str = r#"
+    SELECT
+        sl.id AS share_id,
+        f.id AS file_id,
+        f.file_name,
+        f.file_size,
+        f.content_type,
+        sender.email AS sender_email,
+        sender.name AS sender_name,
+        recipient.email AS recipient_email,
+        recipient.name AS recipient_name,
+        sl.download_count,
+        sl.last_downloaded_at,
+        sl.password <> '' AS password_protected,
+        sl.expiration_date,
+        sl.created_at,
+        f.encrypted_aes_key,
+        f.sender_encrypted_aes_key,
+        f.encrypted_metadata,
+        f.recipient_key_version,
+        f.sender_key_version,
+        sender.id AS sender_id,
+        recipient.id AS recipient_id,
+        f.content_digest,
+        f.signature,
+        f.signing_key_version,
+        sk.public_key AS sender_signing_key
+    FROM
+        shared_links sl
+    JOIN
+        files f ON sl.file_id = f.id
+    JOIN
+        users sender ON f.user_id = sender.id
+    JOIN
+        users recipient ON sl.recipient_user_id = recipient.id
+    LEFT JOIN
+        user_signing_keys sk ON sk.user_id = f.user_id AND sk.version = f.signing_key_version
+"#;
+
+#[derive(Debug, Clone)]
+pub struct SqliteClient {
+    pool: Pool<Sqlite>,
+}
+
+impl SqliteClient {
+    pub fn new(pool: Pool<Sqlite>) -> Self {
+        Self { pool }
+    }
+
+    async fn update_user(
+        &self,
+        user_id: Uuid,
+        column: &str,
+        value: Option<String>,
+    ) -> Result<User, sqlx::Error> {
+        let query = format!(
+            "UPDATE users SET {} = $1, updated_at = $2 WHERE id = $3 RETURNING {}",
+            column, USER_COLUMNS
+        );
+        sqlx::query_as(&query)
+            .bind(value)
+            .bind(Utc::now())
+            .bind(user_id)
+            .fetch_one(&self.pool)
+            .await
+    }
+}
+
+#[async_trait]
+impl UserRepository for SqliteClient {
+    #[tracing::instrument(skip_all, err)]
+    async fn get_user(
+        &self,
+        user_id: Option<Uuid>,
+        name: Option<&str>,
+        email: Option<&str>,
+    ) -> Result<Option<User>, sqlx::Error> {
+        let select = format!("SELECT {} FROM users", USER_COLUMNS);
+        let user = if let Some(user_id) = user_id {
+            sqlx::query_as(&format!("{} WHERE id = $1", select))
+                .bind(user_id)
+                .fetch_optional(&self.pool)
+                .await?
+        } else if let Some(name) = name {
+            sqlx::query_as(&format!("{} WHERE name = $1", select))
+                .bind(name)
+                .fetch_optional(&self.pool)
+                .await?
+        } else if let Some(email) = email {
+            sqlx::query_as(&format!("{} WHERE email = $1", select))
+                .bind(email)
+                .fetch_optional(&self.pool)
+                .await?
+        } else {
+            None
+        };
+
+        Ok(user)
+    }
+
+    #[tracing::instrument(skip_all, err)]
+    async fn save_user(
+        &self,
+        name: String,
+        email: String,
+        password: String,
+    ) -> Result<User, sqlx::Error> {
+        let now = Utc::now();
+        let query = format!(
+            r#"
+            INSERT INTO users (id, name, email, password, created_at, updated_at)
+            VALUES ($1, $2, $3, $4, $5, $5)
+            RETURNING {}
+            "#,
+            USER_COLUMNS
+        );
+        sqlx::query_as(&query)
+            .bind(Uuid::new_v4())
+            .bind(name)
+            .bind(email)
+            .bind(password)
+            .bind(now)
+            .fetch_one(&self.pool)
+            .await
+    }
+
+    #[tracing::instrument(skip(self, name), err)]
+    async fn update_user_name(&self, user_id: Uuid, name: String) -> Result<User, sqlx::Error> {
+        self.update_user(user_id, "name", Some(name)).await
+    }
+
+    #[tracing::instrument(skip(self, password), err)]
+    async fn update_user_password(
+        &self,
+        user_id: Uuid,
+        password: String,
+    ) -> Result<User, sqlx::Error> {
+        self.update_user(user_id, "password", Some(password)).await
+    }
+
+    #[tracing::instrument(skip(self, public_key), err)]
+    async fn save_user_key(
+        &self,
+        user_id: Uuid,
+        version: i32,
+        key_type: String,
+        public_key: String,
+    ) -> Result<UserKey, sqlx::Error> {
+        let now = Utc::now();
+        let mut tx = self.pool.begin().await?;
+
+        sqlx::query(
+            r#"
+            UPDATE user_keys
+            SET status = 'retired', retired_at = $1
+            WHERE user_id = $2 AND status = 'active'
+            "#,
+        )
+        .bind(now)
+        .bind(user_id)
+        .execute(&mut *tx)
+        .await?;
+
+        let query = format!(
+            r#"
+            INSERT INTO user_keys (user_id, version, key_type, public_key, status, created_at)
+            VALUES ($1, $2, $3, $4, 'active', $5)
+            RETURNING {}
+            "#,
+            USER_KEY_COLUMNS
+        );
+        let key: UserKey = sqlx::query_as(&query)
+            .bind(user_id)
+            .bind(version)
+            .bind(key_type)
+            .bind(&public_key)
+            .bind(now)
+            .fetch_one(&mut *tx)
+            .await?;
+
+        sqlx::query("UPDATE users SET public_key = $1, updated_at = $2 WHERE id = $3")
+            .bind(public_key)
+            .bind(now)
+            .bind(user_id)
+            .execute(&mut *tx)
+            .await?;
+
+        tx.commit().await?;
+        Ok(key)
+    }
+
+    #[tracing::instrument(skip(self), err)]
+    async fn get_user_keys(&self, user_id: Uuid) -> Result<Vec<UserKey>, sqlx::Error> {
+        let query = format!(
+            "SELECT {} FROM user_keys WHERE user_id = $1 ORDER BY version",
+            USER_KEY_COLUMNS
+        );
+        sqlx::query_as(&query)
+            .bind(user_id)
+            .fetch_all(&self.pool)
+            .await
+    }
+
+    #[tracing::instrument(skip(self), err)]
+    async fn get_active_user_key(&self, user_id: Uuid) -> Result<Option<UserKey>, sqlx::Error> {
+        let query = format!(
+            "SELECT {} FROM user_keys WHERE user_id = $1 AND status = 'active'",
+            USER_KEY_COLUMNS
+        );
+        sqlx::query_as(&query)
+            .bind(user_id)
+            .fetch_optional(&self.pool)
+            .await
+    }
+
+    #[tracing::instrument(skip(self, public_key), err)]
+    async fn save_signing_key(
+        &self,
+        user_id: Uuid,
+        version: i32,
+        held_by: String,
+        public_key: String,
+    ) -> Result<UserSigningKey, sqlx::Error> {
+        let now = Utc::now();
+        let mut tx = self.pool.begin().await?;
+
+        sqlx::query(
+            r#"
+            UPDATE user_signing_keys
+            SET status = 'retired', retired_at = $1
+            WHERE user_id = $2 AND status = 'active'
+            "#,
+        )
+        .bind(now)
+        .bind(user_id)
+        .execute(&mut *tx)
+        .await?;
+
+        let query = format!(
+            r#"
+            INSERT INTO user_signing_keys (user_id, version, public_key, held_by, status, created_at)
+            VALUES ($1, $2, $3, $4, 'active', $5)
+            RETURNING {}
+            "#,
+            SIGNING_KEY_COLUMNS
+        );
+        let key: UserSigningKey = sqlx::query_as(&query)
+            .bind(user_id)
+            .bind(version)
+            .bind(&public_key)
+            .bind(held_by)
+            .bind(now)
+            .fetch_one(&mut *tx)
+            .await?;
+
+        sqlx::query("UPDATE users SET signing_key = $1, updated_at = $2 WHERE id = $3")
+            .bind(public_key)
+            .bind(now)
+            .bind(user_id)
+            .execute(&mut *tx)
+            .await?;
+
+        tx.commit().await?;
+        Ok(key)
+    }
+
+    #[tracing::instrument(skip(self), err)]
+    async fn get_signing_keys(&self, user_id: Uuid) -> Result<Vec<UserSigningKey>, sqlx::Error> {
+        let query = format!(
+            "SELECT {} FROM user_signing_keys WHERE user_id = $1 ORDER BY version",
+            SIGNING_KEY_COLUMNS
+        );
+        sqlx::query_as(&query)
+            .bind(user_id)
+            .fetch_all(&self.pool)
+            .await
+    }
+
+    #[tracing::instrument(skip(self), err)]
+    async fn update_user_locale(
+        &self,
+        user_id: Uuid,
+        locale: Option<String>,
+    ) -> Result<User, sqlx::Error> {
+        self.update_user(user_id, "locale", locale).await
+    }
+
+    #[tracing::instrument(skip(self, query), err)]
+    async fn search_by_email(
+        &self,
+        user_id: Uuid,
+        query: String,
+    ) -> Result<Vec<User>, sqlx::Error> {
+        let select = format!(
+            r#"
+            SELECT {}
+            FROM users
+            WHERE INSTR(email, $1) > 0 AND public_key IS NOT NULL AND id != $2
+            "#,
+            USER_COLUMNS
+        );
+        sqlx::query_as(&select)
+            .bind(query)
+            .bind(user_id)
+            .fetch_all(&self.pool)
+            .await
+    }
+}
+
+#[async_trait]
+impl FileRepository for SqliteClient {
+    #[tracing::instrument(
+        skip_all,
+        fields(user_id = %file.user_id, recipient_user_id = %file.recipient_user_id, file_size = file.file_size),
+        err
+    )]
+    async fn save_encrypted_file(&self, file: NewFile) -> Result<(), sqlx::Error> {
+        let now = Utc::now();
+        let file_id = Uuid::new_v4();
+        let mut tx = self.pool.begin().await?;
+
+        sqlx::query(
+            r#"
+            INSERT INTO files (id, user_id, file_name, file_size, content_type, encrypted_aes_key, encrypted_file, iv, encrypted_metadata, sender_encrypted_aes_key, recipient_key_version, sender_key_version, content_digest, signature, signing_key_version, created_at)
+            VALUES ($1, $2, $3, $4, $5, $6, $7, $8, $9, $10, $11, $12, $13, $14, $15, $16)
+            "#,
+        )
+        .bind(file_id)
+        .bind(file.user_id)
+        .bind(file.file_name)
+        .bind(file.file_size)
+        .bind(file.content_type)
+        .bind(file.encrypted_aes_key)
+        .bind(file.encrypted_file)
+        .bind(file.iv)
+        .bind(file.encrypted_metadata)
+        .bind(file.sender_encrypted_aes_key)
+        .bind(file.recipient_key_version)
+        .bind(file.sender_key_version)
+        .bind(file.content_digest)
+        .bind(file.signature)
+        .bind(file.signing_key_version)
+        .bind(now)
+        .execute(&mut *tx)
+        .await?;
+
+        sqlx::query(
+            r#"
+            INSERT INTO shared_links (id, file_id, recipient_user_id, password, expiration_date, created_at)
+            VALUES ($1, $2, $3, $4, $5, $6)
+            "#,
+        )
+        .bind(Uuid::new_v4())
+        .bind(file_id)
+        .bind(file.recipient_user_id)
+        .bind(file.password)
+        .bind(file.expiration_date)
+        .bind(now)
+        .execute(&mut *tx)
+        .await?;
+
+        tx.commit().await
+    }
+
+    #[tracing::instrument(skip(self), err)]
+    async fn get_file(&self, file_id: Uuid) -> Result<Option<File>, sqlx::Error> {
+        sqlx::query_as(
+            r#"
+            SELECT id, user_id, file_name, file_size, content_type, encrypted_aes_key, encrypted_file, iv, encrypted_metadata, sender_encrypted_aes_key, created_at, recipient_key_version, sender_key_version, content_digest, signature, signing_key_version
+            FROM files
+            WHERE id = $1
+            "#,
+        )
+        .bind(file_id)
+        .fetch_optional(&self.pool)
+        .await
+    }
+}
+
+#[async_trait]
+impl ShareRepository for SqliteClient {
+    #[tracing::instrument(skip(self), err)]
+    async fn get_shared(
+        &self,
+        shared_id: Uuid,
+        user_id: Uuid,
+    ) -> Result<Option<SharedLink>, sqlx::Error> {
+        sqlx::query_as(
+            r#"
+            SELECT id, file_id, recipient_user_id, password, download_count, last_downloaded_at, expiration_date, created_at
+            FROM shared_links
+            WHERE id = $1
+            AND recipient_user_id = $2
+            AND expiration_date > $3
+            "#,
+        )
+        .bind(shared_id)
+        .bind(user_id)
+        .bind(Utc::now())
+        .fetch_optional(&self.pool)
+        .await
+    }
+
+    #[tracing::instrument(skip(self), err)]
+    async fn get_share_details(
+        &self,
+        share_id: Uuid,
+        user_id: Uuid,
+    ) -> Result<Option<ShareDetails>, sqlx::Error> {
+        let query = format!(
+            "{} WHERE sl.id = $1 AND (sender.id = $2 OR recipient.id = $2)",
+            SHARE_DETAILS_SELECT
+        );
+        sqlx::query_as(&query)
+            .bind(share_id)
+            .bind(user_id)
+            .fetch_optional(&self.pool)
+            .await
+    }
+
+    #[tracing::instrument(skip(self), err)]
+    async fn record_download(&self, share_id: Uuid) -> Result<(), sqlx::Error> {
+        sqlx::query(
+            r#"
+            UPDATE shared_links
+            SET download_count = download_count + 1, last_downloaded_at = $1
+            WHERE id = $2
+            "#,
+        )
+        .bind(Utc::now())
+        .bind(share_id)
+        .execute(&self.pool)
+        .await?;
+
+        Ok(())
+    }
+
+    #[tracing::instrument(skip(self), err)]
+    async fn get_sent_files(
+        &self,
+        user_id: Uuid,
+        query: &ListQuery,
+    ) -> Result<(Vec<SentFileDetails>, i64), sqlx::Error> {
+        let now = Utc::now();
+
+        let files = list::page_query::<Sqlite>(ListSide::Sent, user_id, query, now)
+            .build_query_as()
+            .fetch_all(&self.pool)
+            .await?;
+
+        let total_count = list::count_query::<Sqlite>(ListSide::Sent, user_id, query, now)
+            .build_query_scalar()
+            .fetch_one(&self.pool)
+            .await?;
+
+        Ok((files, total_count))
+    }
+
+    #[tracing::instrument(skip(self), err)]
+    async fn get_receive_files(
+        &self,
+        user_id: Uuid,
+        query: &ListQuery,
+    ) -> Result<(Vec<ReceiveFileDetails>, i64), sqlx::Error> {
+        let now = Utc::now();
+
+        let files = list::page_query::<Sqlite>(ListSide::Received, user_id, query, now)
+            .build_query_as()
+            .fetch_all(&self.pool)
+            .await?;
+
+        let total_count = list::count_query::<Sqlite>(ListSide::Received, user_id, query, now)
+            .build_query_scalar()
+            .fetch_one(&self.pool)
+            .await?;
+
+        Ok((files, total_count))
+    }
+
+    #[tracing::instrument(skip(self), err)]
+    async fn delete_expired_files(&self) -> Result<u64, sqlx::Error> {
+        let now = Utc::now();
+        let mut tx = self.pool.begin().await?;
+
+        // Deleting a file cascades to its shared links
+        let deleted_files = sqlx::query(
+            r#"
+            DELETE FROM files
+            WHERE id IN (
+                SELECT sl.file_id
+                FROM shared_links sl
+                WHERE sl.expiration_date < $1
+            )
+            "#,
+        )
+        .bind(now)
+        .execute(&mut *tx)
+        .await?
+        .rows_affected();
+
+        let deleted_links = sqlx::query("DELETE FROM shared_links WHERE expiration_date < $1")
+            .bind(now)
+            .execute(&mut *tx)
+            .await?
+            .rows_affected();
+
+        tx.commit().await?;
+
+        if deleted_files == 0 && deleted_links == 0 {
+            tracing::debug!("No expired files or shared links to delete");
+        } else {
+            tracing::info!(
+                deleted_files,
+                "Deleted expired files and their shared links"
+            );
+        }
+
+        Ok(deleted_files)
+    }
+}
+
+impl AdminExt for SqliteClient {
+    #[tracing::instrument(skip(self), err)]
+    async fn set_user_disabled(&self, user_id: Uuid, disabled: bool) -> Result<User, sqlx::Error> {
+        let query = format!(
+            r#"
+            UPDATE users
+            SET disabled_at = CASE WHEN $1 THEN COALESCE(disabled_at, $2) END, updated_at = $2
+            WHERE id = $3
+            RETURNING {}
+            "#,
+            USER_COLUMNS
+        );
+        sqlx::query_as(&query)
+            .bind(disabled)
+            .bind(Utc::now())
+            .bind(user_id)
+            .fetch_one(&self.pool)
+            .await
+    }
+
+    #[tracing::instrument(skip(self), err)]
+    async fn list_shares(&self, email: Option<&str>) -> Result<Vec<ShareDetails>, sqlx::Error> {
+        let query = format!(
+            "{} WHERE $1 IS NULL OR sender.email = $1 OR recipient.email = $1 ORDER BY sl.created_at DESC",
+            SHARE_DETAILS_SELECT
+        );
+        sqlx::query_as(&query)
+            .bind(email)
+            .fetch_all(&self.pool)
+            .await
+    }
+
+    #[tracing::instrument(skip(self), err)]
+    async fn revoke_share(&self, share_id: Uuid) -> Result<bool, sqlx::Error> {
+        let mut tx = self.pool.begin().await?;
+
+        let file_id: Option<Option<Uuid>> = sqlx::query_scalar(
+            r#"
+            DELETE FROM shared_links
+            WHERE id = $1
+            RETURNING file_id
+            "#,
+        )
+        .bind(share_id)
+        .fetch_optional(&mut *tx)
+        .await?;
+
+        let Some(file_id) = file_id else {
+            return Ok(false);
+        };
+
+        // Each upload has a single share, so the file has no other readers left
+        sqlx::query(
+            r#"
+            DELETE FROM files
+            WHERE id = $1
+            AND NOT EXISTS (SELECT 1 FROM shared_links sl WHERE sl.file_id = files.id)
+            "#,
+        )
+        .bind(file_id)
+        .execute(&mut *tx)
+        .await?;
+
+        tx.commit().await?;
+
+        Ok(true)
+    }
+
+    #[tracing::instrument(skip(self), err)]
+    async fn storage_usage(&self) -> Result<Vec<StorageUsage>, sqlx::Error> {
+        sqlx::query_as(
+            r#"
+            SELECT
+                u.email,
+                COUNT(f.id) AS file_count,
+                COALESCE(SUM(f.file_size), 0) AS plaintext_bytes,
+                COALESCE(SUM(LENGTH(f.encrypted_file)), 0) AS stored_bytes
+            FROM
+                users u
+            LEFT JOIN
+                files f ON f.user_id = u.id
+            GROUP BY
+                u.email
+            ORDER BY
+                4 DESC, u.email
+            "#,
+        )
+        .fetch_all(&self.pool)
+        .await
+    }
+
+    #[tracing::instrument(skip(self), err)]
+    async fn list_file_recipients(&self) -> Result<Vec<FileRecipient>, sqlx::Error> {
+        sqlx::query_as(
+            r#"
+            SELECT f.id AS file_id, sl.recipient_user_id
+            FROM files f
+            LEFT JOIN shared_links sl ON sl.file_id = f.id
+            ORDER BY f.created_at
+            "#,
+        )
+        .fetch_all(&self.pool)
+        .await
+    }
+
+    #[tracing::instrument(skip(self), err)]
+    async fn list_file_keys(
+        &self,
+        after: Option<Uuid>,
+        limit: i64,
+    ) -> Result<Vec<StoredFileKeys>, sqlx::Error> {
+        sqlx::query_as(
+            r#"
+            SELECT id AS file_id, encrypted_aes_key, sender_encrypted_aes_key
+            FROM files
+            WHERE $1 IS NULL OR id > $1
+            ORDER BY id
+            LIMIT $2
+            "#,
+        )
+        .bind(after)
+        .bind(limit)
+        .fetch_all(&self.pool)
+        .await
+    }
+
+    #[tracing::instrument(
+        skip(self, current, encrypted_aes_key, sender_encrypted_aes_key),
+        fields(file_id = %current.file_id),
+        err
+    )]
+    async fn replace_file_keys(
+        &self,
+        current: &StoredFileKeys,
+        encrypted_aes_key: Vec<u8>,
+        sender_encrypted_aes_key: Option<Vec<u8>>,
+    ) -> Result<bool, sqlx::Error> {
+        let result = sqlx::query(
+            r#"
+            UPDATE files
+            SET encrypted_aes_key = $1, sender_encrypted_aes_key = $2
+            WHERE id = $3
+            AND encrypted_aes_key = $4
+            AND sender_encrypted_aes_key IS $5
+            "#,
+        )
+        .bind(encrypted_aes_key)
+        .bind(sender_encrypted_aes_key)
+        .bind(current.file_id)
+        .bind(&current.encrypted_aes_key)
+        .bind(&current.sender_encrypted_aes_key)
+        .execute(&self.pool)
+        .await?;
+
+        Ok(result.rows_affected() == 1)
+    }
+
+    #[tracing::instrument(skip(self), err)]
+    async fn list_unsealed_files(
+        &self,
+        after: Option<Uuid>,
+        limit: i64,
+    ) -> Result<Vec<UnsealedFile>, sqlx::Error> {
+        sqlx::query_as(
+            r#"
+            SELECT
+                f.id AS file_id,
+                f.user_id AS sender_user_id,
+                sl.recipient_user_id,
+                f.file_name,
+                f.content_type,
+                f.encrypted_aes_key,
+                f.recipient_key_version,
+                f.sender_encrypted_aes_key
+            FROM files f
+            LEFT JOIN shared_links sl ON sl.file_id = f.id
+            WHERE f.encrypted_metadata IS NULL
+            AND ($1 IS NULL OR f.id > $1)
+            ORDER BY f.id
+            LIMIT $2
+            "#,
+        )
+        .bind(after)
+        .bind(limit)
+        .fetch_all(&self.pool)
+        .await
+    }
+
+    #[tracing::instrument(skip(self, encrypted_metadata, sender_key), err)]
+    async fn seal_file_metadata(
+        &self,
+        file_id: Uuid,
+        encrypted_metadata: Vec<u8>,
+        sender_key: Option<(Vec<u8>, i32)>,
+    ) -> Result<bool, sqlx::Error> {
+        let (sender_encrypted_aes_key, sender_key_version) = sender_key.unzip();
+        let result = sqlx::query(
+            r#"
+            UPDATE files
+            SET encrypted_metadata = $1,
+                file_name = '',
+                content_type = 'application/octet-stream',
+                sender_encrypted_aes_key = COALESCE(sender_encrypted_aes_key, $2),
+                sender_key_version = CASE
+                    WHEN sender_encrypted_aes_key IS NULL THEN COALESCE($3, sender_key_version)
+                    ELSE sender_key_version
+                END
+            WHERE id = $4 AND encrypted_metadata IS NULL
+            "#,
+        )
+        .bind(encrypted_metadata)
+        .bind(sender_encrypted_aes_key)
+        .bind(sender_key_version)
+        .bind(file_id)
+        .execute(&self.pool)
+        .await?;
+
+        Ok(result.rows_affected() == 1)
+    }
+}
@@ -1,261 +1,211 @@
-use crate::models::*;
-use chrono::{DateTime, Utc};
-use serde::{Deserialize, Serialize};
-use validator::{Validate, ValidationError};
-
-#[derive(Debug, Clone, Default, Serialize, Deserialize, Validate)]
-pub struct RegisterUserDto {
-    #[validate(length(min = 10, message = "Name must be at least 10 characters"))]
-    pub name: String,
-    #[validate(
-        length(min = 1, message = "Email is required"),
-        email(message = "Invalid email")
-    )]
-    pub email: String,
-    #[validate(length(min = 8, message = "Password must be at least 8 characters"))]
+//! The request and response bodies live in `secure-share-types` so clients can
+//! share them; this module adds what only the server needs.
+
+use chrono::Utc;
+pub use secure_share_types::dtos::*;
+use utoipa::ToSchema;
+
+use crate::{extractors::ValidatedDto, models::*};
+
+/// Multipart form accepted by `POST /api/file/upload`. Only used for the API
+/// documentation; the form is read into [`FileUploadDto`].
+#[derive(ToSchema)]
+#[allow(dead_code)]
+pub struct FileUploadFormDto {
+    /// The part's `Content-Type` is stored as the file's content type
+    #[schema(rename = "fileUpload", value_type = String, format = Binary)]
+    pub file_upload: Vec<u8>,
+    #[schema(format = Email)]
+    pub recipient_email: String,
+    #[schema(format = Password, min_length = 8)]
     pub password: String,
-    #[validate(
-        length(min = 1, message = "Confirm password is required"),
-        must_match(other = "password", message = "Passwords do not match")
-    )]
-    #[serde(rename = "passwordConfirm")]
-    pub password_confirm: String,
+    #[schema(format = DateTime)]
+    pub expiration_date: String,
 }
 
-#[derive(Debug, Clone, Default, Serialize, Deserialize, Validate)]
-pub struct LoginUserDto {
-    #[validate(
-        length(min = 1, message = "Email is required"),
-        email(message = "Invalid email")
-    )]
-    pub email: String,
-    #[validate(length(min = 8, message = "Password must be at least 8 characters"))]
+/// Multipart form accepted by `POST /api/file/envelope`, the fields of a
+/// sealed file. Only used for the API documentation; the form is read into
+/// [`EnvelopeUploadDto`].
+#[derive(ToSchema)]
+#[allow(dead_code)]
+pub struct EnvelopeUploadFormDto {
+    #[schema(value_type = String, format = Binary)]
+    pub ciphertext: Vec<u8>,
+    #[schema(value_type = String, format = Binary)]
+    pub iv: Vec<u8>,
+    #[schema(value_type = String, format = Binary)]
+    pub encrypted_key: Vec<u8>,
+    #[schema(value_type = String, format = Binary)]
+    pub sender_encrypted_key: Vec<u8>,
+    #[schema(value_type = String, format = Binary)]
+    pub encrypted_metadata: Vec<u8>,
+    #[schema(value_type = String, format = Binary)]
+    pub signature: Vec<u8>,
+    /// Size of the original file in bytes
+    pub file_size: i64,
+    /// Active key pair versions the key was wrapped to
+    pub recipient_key_version: i32,
+    pub sender_key_version: i32,
+    /// The sender's active signing key
+    pub signing_key_version: i32,
+    #[schema(format = Email)]
+    pub recipient_email: String,
+    #[schema(format = Password, min_length = 8)]
     pub password: String,
+    #[schema(format = DateTime)]
+    pub expiration_date: String,
 }
 
-#[derive(Debug, Clone, Default, Serialize, Deserialize, Validate)]
-pub struct RequestQueryDto {
-    #[validate(range(min = 1))]
-    pub page: Option<usize>,
-    #[validate(range(min = 1, max = 50))]
-    pub limit: Option<usize>,
-}
-
-#[derive(Debug, Serialize, Deserialize)]
-pub struct FilterUserDto {
-    pub id: String,
-    pub name: String,
-    pub email: String,
-    pub public_key: Option<String>,
-    pub created_at: DateTime<Utc>,
-    pub updated_at: DateTime<Utc>,
-}
-
-#[derive(Debug, Serialize, Deserialize)]
-pub struct UserData {
-    pub user: FilterUserDto,
-}
-
-#[derive(Debug, Serialize, Deserialize)]
-pub struct UserResponseDto {
-    pub status: String,
-    pub data: UserData,
+impl ValidatedDto for RegisterUserDto {
+    fn field_name(field: &str) -> &str {
+        match field {
+            "password_confirm" => "passwordConfirm",
+            field => field,
+        }
+    }
 }
 
-#[derive(Debug, Serialize, Deserialize)]
-pub struct UserSendFileDto {
-    pub file_id: String,
-    pub file_name: String,
-    pub recipient_email: String,
-    pub expiration_date: DateTime<Utc>,
-    pub created_at: DateTime<Utc>,
-}
+impl ValidatedDto for LoginUserDto {}
 
-#[derive(Debug, Serialize, Deserialize)]
-pub struct UserSendFileListResponseDto {
-    pub status: String,
-    pub files: Vec<UserSendFileDto>,
-    pub results: i64,
-}
+impl ValidatedDto for RequestQueryDto {}
 
-#[derive(Debug, Serialize, Deserialize)]
-pub struct UserReceiveFileDto {
-    pub file_id: String,
-    pub file_name: String,
-    pub sender_email: String,
-    pub expiration_date: DateTime<Utc>,
-    pub created_at: DateTime<Utc>,
-}
+impl ValidatedDto for NamedUpdateDto {}
 
-#[derive(Debug, Serialize, Deserialize)]
-pub struct UserReceiveFileListResponseDto {
-    pub status: String,
-    pub files: Vec<UserReceiveFileDto>,
-    pub results: i64,
-}
+impl ValidatedDto for LocaleUpdateDto {}
 
-#[derive(Debug, Serialize, Deserialize)]
-pub struct UserLoginResponseDto {
-    pub status: String,
-    pub token: String,
-}
+impl ValidatedDto for UserPasswordUpdateDto {}
 
-#[derive(Debug, Serialize, Deserialize)]
-pub struct Response {
-    pub status: &'static str,
-    pub message: String,
-}
+impl ValidatedDto for SearchQueryByEmailDto {}
 
-#[derive(Debug, Clone, Default, Serialize, Deserialize, Validate)]
-pub struct NamedUpdateDto {
-    #[validate(length(min = 1, message = "Name is required"))]
-    pub name: String,
+impl ValidatedDto for FileUploadDto {
+    fn field_name(field: &str) -> &str {
+        match field {
+            "file_data" => "fileUpload",
+            field => field,
+        }
+    }
 }
 
-#[derive(Debug, Default, Clone, Serialize, Deserialize, Validate)]
-pub struct UserPasswordUpdateDto {
-    #[validate(length(min = 8, message = "Password must be at least 8 characters long"))]
-    pub new_password: String,
-    #[validate(must_match(other = "new_password", message = "Passwords do not match"))]
-    pub new_password_confirm: String,
-    #[validate(length(min = 8, message = "Old password must be at least 8 characters long"))]
-    pub old_password: String,
-}
+impl ValidatedDto for EnvelopeUploadDto {}
 
-#[derive(Debug, Clone, Default, Serialize, Deserialize, Validate)]
-pub struct SearchQueryByEmailDto {
-    #[validate(length(min = 1, message = "Query is required"))]
-    pub query: String,
-}
+impl ValidatedDto for RetrieveFileDto {}
 
-#[derive(Debug, Serialize, Deserialize)]
-pub struct FilterEmailDto {
-    pub email: String,
-}
+impl ValidatedDto for RotateKeyDto {}
 
-#[derive(Debug, Serialize, Deserialize)]
-pub struct EmailListResponseDto {
-    pub status: String,
-    pub emails: Vec<FilterEmailDto>,
-}
+impl ValidatedDto for RegisterSigningKeyDto {}
 
-#[derive(Debug, Clone, Default, Serialize, Deserialize, Validate)]
-pub struct FileUploadDto {
-    #[validate(email(message = "Invalid email"))]
-    pub recipient_email: String,
-    #[validate(length(min = 8, message = "Password must be at least 8 characters long"))]
-    pub password: String,
-    #[validate(custom(function = "validate_expiration_date"))]
-    pub expiration_date: String,
-}
-
-#[derive(Debug, Clone, Default, Serialize, Deserialize, Validate)]
-pub struct RetrieveFileDto {
-    #[validate(length(min = 1, message = "Shared ID must not be empty"))]
-    pub shared_id: String,
-    #[validate(length(min = 1, message = "Password must not be empty"))]
-    pub password: String,
-}
-
-impl FilterUserDto {
-    pub fn filter_user(user: &User) -> Self {
+impl From<&User> for FilterUserDto {
+    fn from(user: &User) -> Self {
         Self {
             id: user.id.to_string(),
             name: user.name.to_owned(),
             email: user.email.to_owned(),
             public_key: user.public_key.to_owned(),
+            signing_key: user.signing_key.to_owned(),
+            locale: user.locale.to_owned(),
             created_at: user.created_at.unwrap(),
             updated_at: user.updated_at.unwrap(),
         }
     }
 }
 
-impl UserSendFileDto {
-    pub fn filter_send_user_file(file_data: &SentFileDetails) -> Self {
+impl From<&UserKey> for UserKeyDto {
+    fn from(key: &UserKey) -> Self {
         Self {
+            version: key.version,
+            key_type: key.key_type.parse().unwrap_or(KeyType::Rsa),
+            public_key: key.public_key.to_owned(),
+            status: key.status.to_owned(),
+            created_at: key.created_at.unwrap(),
+            retired_at: key.retired_at,
+        }
+    }
+}
+
+impl From<&UserSigningKey> for SigningKeyDto {
+    fn from(key: &UserSigningKey) -> Self {
+        Self {
+            version: key.version,
+            public_key: key.public_key.to_owned(),
+            held_by: key.held_by.to_owned(),
+            status: key.status.to_owned(),
+            created_at: key.created_at.unwrap(),
+            retired_at: key.retired_at,
+        }
+    }
+}
+
+impl From<&SentFileDetails> for UserSendFileDto {
+    fn from(file_data: &SentFileDetails) -> Self {
+        let expiration_date = file_data.expiration_date.unwrap();
+        Self {
+            share_id: file_data.share_id.to_string(),
             file_id: file_data.file_id.to_string(),
             file_name: file_data.file_name.to_owned(),
+            file_size: file_data.file_size,
+            content_type: file_data.content_type.to_owned(),
             recipient_email: file_data.recipient_email.to_owned(),
-            expiration_date: file_data.expiration_date.unwrap(),
+            recipient_name: file_data.recipient_name.to_owned(),
+            download_count: file_data.download_count,
+            last_downloaded_at: file_data.last_downloaded_at,
+            password_protected: file_data.password_protected,
+            expired: expiration_date <= Utc::now(),
+            expiration_date,
             created_at: file_data.created_at.unwrap(),
         }
     }
-
-    pub fn filter_send_user_files(user: &[SentFileDetails]) -> Vec<UserSendFileDto> {
-        user.iter()
-            .map(UserSendFileDto::filter_send_user_file)
-            .collect()
-    }
 }
 
-impl UserReceiveFileDto {
-    pub fn filter_receive_user_file(file_data: &ReceiveFileDetails) -> Self {
+impl From<&ReceiveFileDetails> for UserReceiveFileDto {
+    fn from(file_data: &ReceiveFileDetails) -> Self {
+        let expiration_date = file_data.expiration_date.unwrap();
         Self {
+            share_id: file_data.share_id.to_string(),
             file_id: file_data.file_id.to_string(),
             file_name: file_data.file_name.to_owned(),
+            file_size: file_data.file_size,
+            content_type: file_data.content_type.to_owned(),
             sender_email: file_data.sender_email.to_owned(),
-            expiration_date: file_data.expiration_date.unwrap(),
+            sender_name: file_data.sender_name.to_owned(),
+            download_count: file_data.download_count,
+            last_downloaded_at: file_data.last_downloaded_at,
+            password_protected: file_data.password_protected,
+            expired: expiration_date <= Utc::now(),
+            expiration_date,
             created_at: file_data.created_at.unwrap(),
         }
     }
-
-    pub fn filter_receive_user_files(user: &[ReceiveFileDetails]) -> Vec<UserReceiveFileDto> {
-        user.iter()
-            .map(UserReceiveFileDto::filter_receive_user_file)
-            .collect()
-    }
 }
 
-impl UserReceiveFileDto {
-    pub fn new(
-        file_id: String,
-        file_name: String,
-        sender_email: String,
-        expiration_date: DateTime<Utc>,
-        created_at: DateTime<Utc>,
-    ) -> Self {
+impl From<&ShareDetails> for ShareDetailsDto {
+    fn from(share: &ShareDetails) -> Self {
+        let expiration_date = share.expiration_date.unwrap();
         Self {
-            file_id,
-            file_name,
-            sender_email,
+            share_id: share.share_id.to_string(),
+            file_id: share.file_id.to_string(),
+            file_name: share.file_name.to_owned(),
+            file_size: share.file_size,
+            content_type: share.content_type.to_owned(),
+            sender_email: share.sender_email.to_owned(),
+            sender_name: share.sender_name.to_owned(),
+            recipient_email: share.recipient_email.to_owned(),
+            recipient_name: share.recipient_name.to_owned(),
+            download_count: share.download_count,
+            last_downloaded_at: share.last_downloaded_at,
+            password_protected: share.password_protected,
+            expired: expiration_date <= Utc::now(),
             expiration_date,
-            created_at,
+            created_at: share.created_at.unwrap(),
+            signature_status: None,
+            signing_key_version: share.signing_key_version,
         }
     }
 }
 
-impl FilterEmailDto {
-    pub fn filter_email(user: &User) -> Self {
+impl From<&User> for FilterEmailDto {
+    fn from(user: &User) -> Self {
         FilterEmailDto {
             email: user.email.to_owned(),
         }
     }
-
-    pub fn filter_emails(users: &[User]) -> Vec<FilterEmailDto> {
-        users.iter().map(FilterEmailDto::filter_email).collect()
-    }
-}
-
-fn validate_expiration_date(expiration_date: &str) -> Result<(), ValidationError> {
-    if expiration_date.is_empty() {
-        let mut error = ValidationError::new("expiration date required");
-        error.message = Some("Expiration date is required.".into());
-        return Err(error);
-    }
-
-    let parsed_date = DateTime::parse_from_rfc3339(expiration_date).map_err(|_| {
-        let mut error = ValidationError::new("invalid date format");
-        error.message =
-            Some("Invalid date format. Expected format is YYYY-MM-DDTHH:MM:ssssssZ.".into());
-        error
-    })?;
-
-    let now = Utc::now();
-    if parsed_date <= now {
-        let mut error = ValidationError::new("expiration date future");
-        error.message = Some("Expiration date must be in the future.".into());
-        return Err(error);
-    }
-
-    Ok(())
 }
@@ -1,25 +1,21 @@
-use std::fmt;
+use std::{collections::BTreeMap, fmt};
 
 use axum::{
     Json,
-    http::StatusCode,
+    http::{HeaderValue, StatusCode, header},
     response::{IntoResponse, Response},
 };
-use serde::{Deserialize, Serialize};
+use uuid::Uuid;
+use validator::ValidationError;
 
-#[derive(Debug, Serialize, Deserialize)]
-pub struct ErrorResponse {
-    pub status: String,
-    pub message: String,
-}
+pub use secure_share_types::error::{ErrorResponse, FieldError, PROBLEM_JSON};
 
-impl fmt::Display for ErrorResponse {
-    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
-        write!(f, "{}", serde_json::to_string(&self).unwrap())
-    }
-}
+use crate::i18n::{self, Locale};
 
-#[derive(Debug, PartialEq)]
+/// Failed validation rules keyed by the field name clients send.
+pub type FieldErrors = BTreeMap<String, Vec<ValidationError>>;
+
+#[derive(Debug, Clone, PartialEq)]
 pub enum ErrorMessage {
     EmptyPassword,
     ExceededMaxPasswordLength(usize),
@@ -31,81 +27,182 @@ pub enum ErrorMessage {
     UserNoLongerExists,
     EmailDoesNotExist,
     TokenNotProvided,
+    TooManyRequests,
+    /// A body or query string that could not be read, with the message key
+    /// saying why. Parser output is only logged, it is not localized.
+    InvalidInput(&'static str),
+    Validation(FieldErrors),
+    InvalidMultipart,
+    FileTooLarge,
+    RecipientNotFound,
+    RecipientHasNoKey,
+    SenderHasNoKey,
+    InvalidSharedId,
+    InvalidCursor,
+    ShareNotFound,
+    WrongSharePassword,
+    FileNotFound,
+    OldPasswordIncorrect,
+    AccountDisabled,
+    KeyRotationInProgress,
+    InvalidEnvelope,
+    StaleEnvelopeKey,
+    InternalServerError,
 }
 
 impl ErrorMessage {
-    fn to_str(&self) -> String {
+    /// Message shown to the client, looked up in the catalog for `locale`.
+    pub fn localized(&self, locale: Locale) -> String {
+        match self {
+            ErrorMessage::ExceededMaxPasswordLength(max_length) => match locale {
+                Locale::En => format!("Password length exceeds maximum of {}", max_length),
+                Locale::Zh => format!("密码长度超过上限 {}", max_length),
+            },
+            ErrorMessage::InvalidInput(key) => i18n::message(key, locale)
+                .unwrap_or(self.code())
+                .to_string(),
+            _ => i18n::message(self.code(), locale)
+                .unwrap_or(self.code())
+                .to_string(),
+        }
+    }
+
+    /// Stable identifier clients can match on; never change an existing code.
+    pub fn code(&self) -> &'static str {
         match self {
-            ErrorMessage::EmptyPassword => "Password cannot be empty".to_string(),
-            ErrorMessage::ExceededMaxPasswordLength(max_length) => {
-                format!("Password length exceeds maximum of {}", max_length)
-            }
-            ErrorMessage::InvalidHashFormat => "Invalid hash format".to_string(),
-            ErrorMessage::HashingError => "Error while hashing password".to_string(),
-            ErrorMessage::InvalidToken => "Authentication token is invalid or expired".to_string(),
-            ErrorMessage::WrongCredentials => "Wrong credentials".to_string(),
-            ErrorMessage::EmailAlreadyExists => "Email already exists".to_string(),
-            ErrorMessage::UserNoLongerExists => "User no longer exists".to_string(),
-            ErrorMessage::EmailDoesNotExist => "Email does not exist".to_string(),
-            ErrorMessage::TokenNotProvided => "Token not provided".to_string(),
+            ErrorMessage::EmptyPassword => "empty_password",
+            ErrorMessage::ExceededMaxPasswordLength(_) => "password_too_long",
+            ErrorMessage::InvalidHashFormat => "invalid_hash_format",
+            ErrorMessage::HashingError => "hashing_error",
+            ErrorMessage::InvalidToken => "invalid_token",
+            ErrorMessage::WrongCredentials => "wrong_credentials",
+            ErrorMessage::EmailAlreadyExists => "email_already_exists",
+            ErrorMessage::UserNoLongerExists => "user_no_longer_exists",
+            ErrorMessage::EmailDoesNotExist => "email_does_not_exist",
+            ErrorMessage::TokenNotProvided => "token_not_provided",
+            ErrorMessage::TooManyRequests => "too_many_requests",
+            ErrorMessage::InvalidInput(_) => "invalid_input",
+            ErrorMessage::Validation(_) => "validation_failed",
+            ErrorMessage::InvalidMultipart => "invalid_multipart",
+            ErrorMessage::FileTooLarge => "file_too_large",
+            ErrorMessage::RecipientNotFound => "recipient_not_found",
+            ErrorMessage::RecipientHasNoKey => "recipient_has_no_key",
+            ErrorMessage::SenderHasNoKey => "sender_has_no_key",
+            ErrorMessage::InvalidSharedId => "invalid_shared_id",
+            ErrorMessage::InvalidCursor => "invalid_cursor",
+            ErrorMessage::ShareNotFound => "share_not_found",
+            ErrorMessage::WrongSharePassword => "wrong_share_password",
+            ErrorMessage::FileNotFound => "file_not_found",
+            ErrorMessage::OldPasswordIncorrect => "old_password_incorrect",
+            ErrorMessage::AccountDisabled => "account_disabled",
+            ErrorMessage::KeyRotationInProgress => "key_rotation_in_progress",
+            ErrorMessage::InvalidEnvelope => "invalid_envelope",
+            ErrorMessage::StaleEnvelopeKey => "stale_envelope_key",
+            ErrorMessage::InternalServerError => "internal_error",
         }
     }
 }
 
-impl ToString for ErrorMessage {
-    fn to_string(&self) -> String {
-        self.to_str().to_owned()
+impl fmt::Display for ErrorMessage {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}", self.localized(Locale::En))
     }
 }
 
 #[derive(Debug, Clone)]
 pub struct HttpError {
     pub status: StatusCode,
-    pub message: String,
+    pub error: ErrorMessage,
+    /// Cause of an internal error; logged but never sent to the client.
+    pub internal: Option<String>,
 }
 
 impl HttpError {
-    pub fn new(message: impl Into<String>, status: StatusCode) -> Self {
+    pub fn new(error: ErrorMessage, status: StatusCode) -> Self {
         Self {
             status,
-            message: message.into(),
+            error,
+            internal: None,
         }
     }
 
-    pub fn server_error(message: impl Into<String>) -> Self {
+    pub fn server_error(internal: impl Into<String>) -> Self {
         Self {
             status: StatusCode::INTERNAL_SERVER_ERROR,
-            message: message.into(),
+            error: ErrorMessage::InternalServerError,
+            internal: Some(internal.into()),
         }
     }
 
-    pub fn bad_request(message: impl Into<String>) -> Self {
-        Self {
-            status: StatusCode::BAD_REQUEST,
-            message: message.into(),
-        }
+    pub fn bad_request(error: ErrorMessage) -> Self {
+        Self::new(error, StatusCode::BAD_REQUEST)
     }
 
-    pub fn unique_constraint_violation(message: impl Into<String>) -> Self {
-        Self {
-            status: StatusCode::CONFLICT,
-            message: message.into(),
-        }
+    pub fn unique_constraint_violation(error: ErrorMessage) -> Self {
+        Self::new(error, StatusCode::CONFLICT)
     }
 
-    pub fn unauthorized(message: impl Into<String>) -> Self {
-        Self {
-            status: StatusCode::UNAUTHORIZED,
-            message: message.into(),
-        }
+    pub fn unauthorized(error: ErrorMessage) -> Self {
+        Self::new(error, StatusCode::UNAUTHORIZED)
+    }
+
+    pub fn forbidden(error: ErrorMessage) -> Self {
+        Self::new(error, StatusCode::FORBIDDEN)
+    }
+
+    pub fn unprocessable_entity(error: ErrorMessage) -> Self {
+        Self::new(error, StatusCode::UNPROCESSABLE_ENTITY)
+    }
+
+    pub fn too_many_requests(error: ErrorMessage) -> Self {
+        Self::new(error, StatusCode::TOO_MANY_REQUESTS)
     }
 
     pub fn into_http_response(self) -> Response {
-        let json_response = Json(ErrorResponse {
-            status: "fail".to_string(),
-            message: self.message,
+        let correlation_id = self.internal.as_ref().map(|internal| {
+            let correlation_id = Uuid::new_v4().to_string();
+            tracing::error!(%correlation_id, error = %internal, "Internal server error");
+            correlation_id
         });
-        (self.status, json_response).into_response()
+        let locale = i18n::current();
+        let code = self.error.code();
+        let errors = match &self.error {
+            ErrorMessage::Validation(fields) => Some(
+                fields
+                    .iter()
+                    .map(|(field, errors)| {
+                        let errors = errors
+                            .iter()
+                            .map(|error| FieldError {
+                                code: error.code.to_string(),
+                                message: i18n::validation_message(error, locale),
+                            })
+                            .collect();
+                        (field.clone(), errors)
+                    })
+                    .collect(),
+            ),
+            _ => None,
+        };
+        let body = Json(ErrorResponse {
+            problem_type: format!("urn:secure-share:problem:{}", code),
+            title: self
+                .status
+                .canonical_reason()
+                .unwrap_or("Error")
+                .to_string(),
+            status: self.status.as_u16(),
+            detail: self.error.localized(locale),
+            code: code.to_string(),
+            correlation_id,
+            errors,
+        });
+
+        let mut response = (self.status, body).into_response();
+        response
+            .headers_mut()
+            .insert(header::CONTENT_TYPE, HeaderValue::from_static(PROBLEM_JSON));
+        response
     }
 }
 
@@ -113,9 +210,15 @@ impl fmt::Display for HttpError {
     fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
         write!(
             f,
-            "HttpError: status: {}: message:{}",
-            self.status, self.message
-        )
+            "HttpError: status: {}: code: {}: message:{}",
+            self.status,
+            self.error.code(),
+            self.error
+        )?;
+        if let Some(internal) = &self.internal {
+            write!(f, ": cause: {}", internal)?;
+        }
+        Ok(())
     }
 }
 
@@ -0,0 +1,157 @@
+use std::collections::BTreeMap;
+
+use axum::{
+    Json,
+    extract::{
+        FromRequest, FromRequestParts, Multipart, Query, Request,
+        multipart::MultipartError,
+        rejection::{JsonRejection, QueryRejection},
+    },
+    http::{StatusCode, request::Parts},
+};
+use serde::de::DeserializeOwned;
+use validator::{Validate, ValidationErrors, ValidationErrorsKind};
+
+use crate::error::{ErrorMessage, FieldErrors, HttpError};
+
+/// A DTO checked by the validated extractors. validator reports Rust field
+/// names, so DTOs renaming fields with serde map them back to the names
+/// clients send.
+pub trait ValidatedDto: Validate {
+    fn field_name(field: &str) -> &str {
+        field
+    }
+}
+
+/// A DTO read from `multipart/form-data`.
+pub trait MultipartForm: Sized {
+    fn from_multipart(multipart: Multipart)
+    -> impl Future<Output = Result<Self, HttpError>> + Send;
+}
+
+/// JSON body that has passed `validate()`; failures become a 422 listing the
+/// errors of each field.
+#[derive(Debug, Clone, Copy, Default)]
+pub struct ValidatedJson<T>(pub T);
+
+/// Query string that has passed `validate()`.
+#[derive(Debug, Clone, Copy, Default)]
+pub struct ValidatedQuery<T>(pub T);
+
+/// Multipart form that has passed `validate()`.
+#[derive(Debug, Clone, Copy, Default)]
+pub struct ValidatedMultipart<T>(pub T);
+
+impl<S, T> FromRequest<S> for ValidatedJson<T>
+where
+    S: Send + Sync,
+    T: DeserializeOwned + ValidatedDto,
+{
+    type Rejection = HttpError;
+
+    async fn from_request(req: Request, state: &S) -> Result<Self, Self::Rejection> {
+        let Json(value) = Json::<T>::from_request(req, state)
+            .await
+            .map_err(json_error)?;
+        validate(&value)?;
+        Ok(Self(value))
+    }
+}
+
+impl<S, T> FromRequestParts<S> for ValidatedQuery<T>
+where
+    S: Send + Sync,
+    T: DeserializeOwned + ValidatedDto,
+{
+    type Rejection = HttpError;
+
+    async fn from_request_parts(parts: &mut Parts, state: &S) -> Result<Self, Self::Rejection> {
+        let Query(value) = Query::<T>::from_request_parts(parts, state)
+            .await
+            .map_err(query_error)?;
+        validate(&value)?;
+        Ok(Self(value))
+    }
+}
+
+impl<S, T> FromRequest<S> for ValidatedMultipart<T>
+where
+    S: Send + Sync,
+    T: MultipartForm + ValidatedDto,
+{
+    type Rejection = HttpError;
+
+    async fn from_request(req: Request, state: &S) -> Result<Self, Self::Rejection> {
+        let multipart = Multipart::from_request(req, state)
+            .await
+            .map_err(|_| HttpError::bad_request(ErrorMessage::InvalidMultipart))?;
+        let value = T::from_multipart(multipart).await?;
+        validate(&value)?;
+        Ok(Self(value))
+    }
+}
+
+/// Maps a multipart read failure, distinguishing bodies over the upload limit.
+pub fn multipart_error(err: MultipartError) -> HttpError {
+    if err.status() == StatusCode::PAYLOAD_TOO_LARGE {
+        HttpError::new(ErrorMessage::FileTooLarge, StatusCode::PAYLOAD_TOO_LARGE)
+    } else {
+        HttpError::bad_request(ErrorMessage::InvalidMultipart)
+    }
+}
+
+/// Maps a JSON body rejection to a message key, keeping its status.
+fn json_error(rejection: JsonRejection) -> HttpError {
+    tracing::debug!(error = %rejection.body_text(), "Rejected JSON body");
+    let key = match &rejection {
+        JsonRejection::JsonDataError(_) => "invalid_json_data",
+        JsonRejection::JsonSyntaxError(_) => "invalid_json_syntax",
+        JsonRejection::MissingJsonContentType(_) => "missing_json_content_type",
+        _ => "invalid_body",
+    };
+    HttpError::new(ErrorMessage::InvalidInput(key), rejection.status())
+}
+
+fn query_error(rejection: QueryRejection) -> HttpError {
+    tracing::debug!(error = %rejection.body_text(), "Rejected query string");
+    HttpError::bad_request(ErrorMessage::InvalidInput("invalid_query"))
+}
+
+fn validate<T: ValidatedDto>(value: &T) -> Result<(), HttpError> {
+    value.validate().map_err(|errors| {
+        let mut fields = BTreeMap::new();
+        collect_field_errors::<T>(&errors, None, &mut fields);
+        HttpError::unprocessable_entity(ErrorMessage::Validation(fields))
+    })
+}
+
+/// Flattens nested validation errors into `parent.child` and `list[0]` paths.
+fn collect_field_errors<T: ValidatedDto>(
+    errors: &ValidationErrors,
+    prefix: Option<&str>,
+    fields: &mut FieldErrors,
+) {
+    for (field, kind) in errors.errors() {
+        let path = match prefix {
+            Some(prefix) => format!("{}.{}", prefix, field),
+            None => T::field_name(field).to_string(),
+        };
+        match kind {
+            ValidationErrorsKind::Field(field_errors) => {
+                fields
+                    .entry(path)
+                    .or_default()
+                    .extend(field_errors.iter().cloned());
+            }
+            ValidationErrorsKind::Struct(nested) => {
+                collect_field_errors::<T>(nested, Some(&path), fields);
+            }
+            ValidationErrorsKind::List(items) => {
+                for (index, nested) in items {
+                    let path = format!("{}[{}]", path, index);
+                    collect_field_errors::<T>(nested, Some(&path), fields);
+                }
+            }
+        }
+    }
+}
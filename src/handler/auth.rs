@@ -7,13 +7,12 @@ use axum::{
     routing::post,
 };
 use axum_extra::extract::cookie::Cookie;
-use validator::Validate;
 
 use crate::{
     AppState,
-    db::UserExt,
     dtos::{LoginUserDto, RegisterUserDto, Response, UserLoginResponseDto},
-    error::{ErrorMessage, HttpError},
+    error::{ErrorMessage, ErrorResponse, HttpError},
+    extractors::ValidatedJson,
     utils::{keys, password, token},
 };
 
@@ -23,28 +22,42 @@ pub fn auth_handler() -> Router {
         .route("/login", post(login))
 }
 
+#[utoipa::path(
+    post,
+    path = "/api/auth/register",
+    tag = "auth",
+    request_body = RegisterUserDto,
+    responses(
+        (status = 201, description = "User registered and key pair generated", body = Response),
+        (status = 400, description = "Malformed JSON body", body = ErrorResponse, content_type = "application/problem+json"),
+        (status = 409, description = "Email already exists", body = ErrorResponse, content_type = "application/problem+json"),
+        (status = 422, description = "Invalid registration data", body = ErrorResponse, content_type = "application/problem+json"),
+        (status = 429, description = "Too many requests", body = ErrorResponse, content_type = "application/problem+json")
+    )
+)]
+#[tracing::instrument(skip_all)]
 pub async fn register(
     Extension(app_state): Extension<Arc<AppState>>,
-    Json(user): Json<RegisterUserDto>,
+    ValidatedJson(user): ValidatedJson<RegisterUserDto>,
 ) -> Result<impl IntoResponse, HttpError> {
-    user.validate()
-        .map_err(|err| HttpError::bad_request(err.to_string()))?;
-
+    let key_type = user.key_type;
     let hash_password =
         password::hash(&user.password).map_err(|err| HttpError::server_error(err.to_string()))?;
 
     let user = app_state
-        .db_client
+        .users
         .save_user(user.name, user.email, hash_password)
         .await;
 
     match user {
         Ok(user) => {
-            let _key_result = keys::generete_key(app_state, user).await?;
+            let key_type = key_type.unwrap_or(app_state.env.storage.key_type);
+            keys::signing_key(&app_state, &user).await?;
+            let _key_result = keys::generete_key(app_state, user, key_type).await?;
             Ok((
                 StatusCode::CREATED,
                 Json(Response {
-                    status: "successful",
+                    status: "successful".to_string(),
                     message: "User registered successfully".to_string(),
                 }),
             ))
@@ -52,7 +65,7 @@ pub async fn register(
         Err(sqlx::Error::Database(db_err)) => {
             if db_err.is_unique_violation() {
                 Err(HttpError::unique_constraint_violation(
-                    ErrorMessage::EmailAlreadyExists.to_string(),
+                    ErrorMessage::EmailAlreadyExists,
                 ))
             } else {
                 Err(HttpError::server_error(db_err.to_string()))
@@ -62,29 +75,47 @@ pub async fn register(
     }
 }
 
+#[utoipa::path(
+    post,
+    path = "/api/auth/login",
+    tag = "auth",
+    request_body = LoginUserDto,
+    responses(
+        (status = 200, description = "Logged in, token also set as the `token` cookie", body = UserLoginResponseDto),
+        (status = 400, description = "Wrong credentials", body = ErrorResponse, content_type = "application/problem+json"),
+        (status = 403, description = "Account disabled", body = ErrorResponse, content_type = "application/problem+json"),
+        (status = 422, description = "Invalid login data", body = ErrorResponse, content_type = "application/problem+json"),
+        (status = 429, description = "Too many requests", body = ErrorResponse, content_type = "application/problem+json")
+    )
+)]
+#[tracing::instrument(skip_all)]
 pub async fn login(
     Extension(app_state): Extension<Arc<AppState>>,
-    Json(body): Json<LoginUserDto>,
+    ValidatedJson(body): ValidatedJson<LoginUserDto>,
 ) -> Result<impl IntoResponse, HttpError> {
-    body.validate()
-        .map_err(|err| HttpError::bad_request(err.to_string()))?;
     let user = app_state
-        .db_client
+        .users
         .get_user(None, None, Some(body.email.as_str()))
         .await
         .map_err(|err| HttpError::server_error(err.to_string()))?;
 
-    let user = user.ok_or(HttpError::bad_request(
-        ErrorMessage::WrongCredentials.to_string(),
-    ))?;
+    let user = user.ok_or_else(|| {
+        metrics::counter!("login_failures_total").increment(1);
+        HttpError::bad_request(ErrorMessage::WrongCredentials)
+    })?;
 
     let password_matched = password::compare(&body.password, &user.password)
         .map_err(|err| HttpError::server_error(err.to_string()))?;
 
+    // Only reveal that an account is disabled to someone who knows its password
+    if password_matched && user.disabled_at.is_some() {
+        return Err(HttpError::forbidden(ErrorMessage::AccountDisabled));
+    }
+
     if password_matched {
         let token = token::create_token(
             &user.id.to_string(),
-            &app_state.env.jwt_secret.as_bytes(),
+            app_state.env.jwt_secret.as_bytes(),
             app_state.env.jwt_maxage,
         )
         .map_err(|err| HttpError::server_error(err.to_string()))?;
@@ -107,8 +138,7 @@ pub async fn login(
 
         Ok(response)
     } else {
-        Err(HttpError::bad_request(
-            ErrorMessage::WrongCredentials.to_string(),
-        ))
+        metrics::counter!("login_failures_total").increment(1);
+        Err(HttpError::bad_request(ErrorMessage::WrongCredentials))
     }
 }
@@ -0,0 +1,15 @@
+use axum::{Json, Router, response::IntoResponse, routing::get};
+use utoipa::OpenApi;
+use utoipa_redoc::{Redoc, Servable};
+
+use crate::openapi::ApiDoc;
+
+pub fn docs_handler() -> Router {
+    Router::new()
+        .route("/openapi.json", get(openapi_json))
+        .merge(Redoc::with_url("/docs", ApiDoc::openapi()))
+}
+
+pub async fn openapi_json() -> impl IntoResponse {
+    Json(ApiDoc::openapi())
+}
@@ -1,199 +1,714 @@
-use std::{fs, path::PathBuf, sync::Arc};
+use std::{str::FromStr, sync::Arc};
 
 use axum::{
     Extension, Json, Router,
     body::Body,
-    extract::Multipart,
-    http::StatusCode,
+    extract::{Multipart, Path, multipart::Field},
+    http::{HeaderValue, StatusCode, header},
     response::{IntoResponse, Response},
-    routing::post,
+    routing::{get, post},
 };
 use base64::{Engine, prelude::BASE64_STANDARD};
 use chrono::{DateTime, Utc};
-use rsa::{
-    RsaPrivateKey, RsaPublicKey,
-    pkcs1::{DecodeRsaPrivateKey, DecodeRsaPublicKey},
-};
+use secure_share_crypto::content_digest;
 use uuid::Uuid;
-use validator::Validate;
 
 use crate::{
     AppState,
-    db::UserExt,
-    dtos::{FileUploadDto, Response as ResponseDto, RetrieveFileDto},
-    error::HttpError,
+    dtos::{
+        EnvelopeDto, EnvelopeResponseDto, EnvelopeUploadDto, EnvelopeUploadFormDto, FileUploadDto,
+        FileUploadFormDto, Response as ResponseDto, RetrieveFileDto, SIGNATURE_STATUS_HEADER,
+        ShareDetailsDto, ShareDetailsResponseDto, SignatureStatus,
+    },
+    error::{ErrorMessage, ErrorResponse, HttpError},
+    extractors::{MultipartForm, ValidatedJson, ValidatedMultipart, multipart_error},
     middleware::JwtAuthMiddleware,
-    utils::{decrypt, encrypt, password},
+    models::{File, FileMetadata, NewFile, SharedLink, UserSigningKey},
+    utils::{decrypt, encrypt, keys, media_type, password, signature},
 };
 
 pub fn file_handle() -> Router {
     Router::new()
         .route("/upload", post(upload_file))
         .route("/register", post(retrieve_file))
+        .route("/envelope", post(upload_envelope))
+        .route("/envelope/retrieve", post(retrieve_envelope))
+        .route("/shares/{id}", get(get_share_details))
 }
 
+#[utoipa::path(
+    post,
+    path = "/api/file/upload",
+    tag = "file",
+    security(("bearer_token" = []), ("cookie_token" = [])),
+    request_body(content = FileUploadFormDto, content_type = "multipart/form-data"),
+    responses(
+        (status = 200, description = "File encrypted for the recipient and stored", body = ResponseDto),
+        (status = 400, description = "Malformed form, unknown recipient, or a sender without a key pair", body = ErrorResponse, content_type = "application/problem+json"),
+        (status = 401, description = "Missing or invalid token", body = ErrorResponse, content_type = "application/problem+json"),
+        (status = 403, description = "Account disabled", body = ErrorResponse, content_type = "application/problem+json"),
+        (status = 413, description = "File exceeds limits.max_upload_bytes", body = ErrorResponse, content_type = "application/problem+json"),
+        (status = 422, description = "Invalid form fields", body = ErrorResponse, content_type = "application/problem+json"),
+        (status = 429, description = "Too many requests", body = ErrorResponse, content_type = "application/problem+json")
+    )
+)]
+#[tracing::instrument(skip_all)]
 pub async fn upload_file(
     Extension(app_state): Extension<Arc<AppState>>,
     Extension(middleware): Extension<JwtAuthMiddleware>,
-    mut multipart: Multipart,
+    ValidatedMultipart(form_data): ValidatedMultipart<FileUploadDto>,
 ) -> Result<impl IntoResponse, HttpError> {
-    let mut file_data = Vec::new();
-    let mut file_name = String::new();
-    let mut file_size: i64 = 0;
-    let mut form_data = FileUploadDto {
-        recipient_email: String::new(),
-        password: String::new(),
-        expiration_date: String::new(),
-    };
-    while let Some(field) = multipart.next_field().await.unwrap() {
-        let name = field.name().unwrap().to_string();
-
-        match name.as_str() {
-            "fileUpload" => {
-                file_name = field.file_name().unwrap_or("unknow_file").to_string();
-                file_data = field.bytes().await.unwrap().to_vec();
-                file_size = file_data.len() as i64;
-            }
-            "recipient_email" => {
-                form_data.recipient_email = field.text().await.unwrap();
-            }
-            "password" => {
-                form_data.password = field.text().await.unwrap();
-            }
-            "expiration_date" => {
-                form_data.expiration_date = field.text().await.unwrap();
-            }
-            _ => {}
-        }
-    }
-
-    form_data
-        .validate()
-        .map_err(|err| HttpError::bad_request(err.to_string()))?;
+    let file_size = form_data.file_data.len() as i64;
+    let file_name = media_type::sanitize_file_name(&form_data.file_name);
+    let content_type =
+        media_type::detect(&form_data.file_data, &file_name, &form_data.content_type);
 
     let user = app_state
-        .db_client
+        .users
         .get_user(None, None, Some(&form_data.recipient_email))
         .await
         .map_err(|err| HttpError::server_error(err.to_string()))?;
 
-    let recipient_user = user.ok_or(HttpError::bad_request("Recipient user not found"))?;
-    let public_key_str = match &recipient_user.public_key {
-        Some(public_key) => public_key,
-        None => return Err(HttpError::bad_request("Recipient user has no public key")),
-    };
-    let public_key_bytes = BASE64_STANDARD
-        .decode(public_key_str)
-        .map_err(|err| HttpError::server_error(err.to_string()))?;
-    let public_key = String::from_utf8(public_key_bytes)
-        .map_err(|err| HttpError::server_error(err.to_string()))?;
-    let public_key_pem = RsaPublicKey::from_pkcs1_pem(&public_key)
-        .map_err(|err| HttpError::server_error(err.to_string()))?;
+    let recipient_user = user.ok_or(HttpError::bad_request(ErrorMessage::RecipientNotFound))?;
+    let recipient_key = app_state
+        .users
+        .get_active_user_key(recipient_user.id)
+        .await
+        .map_err(|err| HttpError::server_error(err.to_string()))?
+        .ok_or(HttpError::bad_request(ErrorMessage::RecipientHasNoKey))?;
+    let public_key = keys::decode_public_key(&recipient_key.public_key)?;
+    // Lets the sender read the file name back in their sent list
+    let sender_key = app_state
+        .users
+        .get_active_user_key(middleware.user.id)
+        .await
+        .map_err(|err| HttpError::server_error(err.to_string()))?
+        .ok_or(HttpError::bad_request(ErrorMessage::SenderHasNoKey))?;
+    let sender_public_key = keys::decode_public_key(&sender_key.public_key)?;
 
-    let (encrypted_aes_key, encrypted_data, iv) =
-        encrypt::encrypt_file(file_data, &public_key_pem).await?;
+    let metadata = FileMetadata {
+        file_name,
+        content_type,
+    };
+    let encrypted = encrypt::encrypt_file(
+        form_data.file_data,
+        &metadata,
+        &public_key,
+        Some(&sender_public_key),
+    )
+    .await?;
     let user_id = Uuid::parse_str(&middleware.user.id.to_string()).unwrap();
     let hash_password = password::hash(&form_data.password)
         .map_err(|err| HttpError::server_error(err.to_string()))?;
     let expiration_date = DateTime::parse_from_rfc3339(&form_data.expiration_date)
-        .map_err(|err| HttpError::bad_request(err.to_string()))?
+        .map_err(|_| HttpError::bad_request(ErrorMessage::InvalidInput("invalid_date_format")))?
         .with_timezone(&Utc);
     let recipient_user_id = Uuid::parse_str(&recipient_user.id.to_string()).unwrap();
+    let encrypted_aes_key = keys::wrap_stored(&app_state, &encrypted.encrypted_aes_key).await?;
+    let sender_encrypted_aes_key = match &encrypted.sender_encrypted_aes_key {
+        Some(key) => Some(keys::wrap_stored(&app_state, key).await?),
+        None => None,
+    };
+    let digest = content_digest(&encrypted.iv, &encrypted.encrypted_file);
+    // Left unsigned for users who registered a signing key of their own
+    let signing_key = keys::signing_key(&app_state, &middleware.user).await?;
+    let signed_file =
+        signature::signed_file(user_id, recipient_user_id, digest, &metadata, file_size);
+    let (signing_key_version, file_signature) = match signing_key {
+        Some((version, signing_key)) => (
+            Some(version),
+            Some(signature::sign(&signed_file, &signing_key)),
+        ),
+        None => (None, None),
+    };
 
     app_state
-        .db_client
-        .save_encrypted_file(
+        .files
+        .save_encrypted_file(NewFile {
             user_id,
-            file_name,
+            file_name: String::new(),
             file_size,
+            content_type: media_type::OCTET_STREAM.to_string(),
             recipient_user_id,
-            hash_password,
+            password: hash_password,
             expiration_date,
             encrypted_aes_key,
-            encrypted_data,
-            iv,
-        )
+            encrypted_file: encrypted.encrypted_file,
+            iv: encrypted.iv,
+            encrypted_metadata: Some(encrypted.encrypted_metadata),
+            sender_encrypted_aes_key,
+            recipient_key_version: recipient_key.version,
+            sender_key_version: sender_key.version,
+            content_digest: Some(digest.to_vec()),
+            signature: file_signature,
+            signing_key_version,
+        })
         .await
         .map_err(|err| HttpError::server_error(err.to_string()))?;
 
+    metrics::counter!("file_bytes_uploaded_total").increment(file_size as u64);
+
     let response = ResponseDto {
-        status: "successful",
+        status: "successful".to_string(),
         message: "File uploaded and encrypted successfully".to_string(),
     };
 
     Ok(Json(response))
 }
 
+/// Downloads and decrypts a file shared with the current user. Despite the
+/// path this does not register anything; the name is kept for compatibility.
+#[utoipa::path(
+    post,
+    path = "/api/file/register",
+    tag = "file",
+    security(("bearer_token" = []), ("cookie_token" = [])),
+    request_body = RetrieveFileDto,
+    responses(
+        (status = 200, description = "Decrypted file contents, served with the content type detected at upload", content_type = "application/octet-stream", body = Vec<u8>,
+            headers(
+                ("Content-Disposition" = String, description = "`inline` for PDFs, plain text and common images, `attachment` otherwise, with the name in `filename*`"),
+                ("X-Signature-Status" = SignatureStatus, description = "Check of the sender's signature over the file as served"),
+            )),
+        (status = 400, description = "Share expired, missing or wrong password", body = ErrorResponse, content_type = "application/problem+json"),
+        (status = 401, description = "Missing or invalid token", body = ErrorResponse, content_type = "application/problem+json"),
+        (status = 403, description = "Account disabled", body = ErrorResponse, content_type = "application/problem+json"),
+        (status = 422, description = "Invalid request data", body = ErrorResponse, content_type = "application/problem+json"),
+        (status = 429, description = "Too many requests", body = ErrorResponse, content_type = "application/problem+json")
+    )
+)]
+#[tracing::instrument(skip_all)]
 pub async fn retrieve_file(
     Extension(app_state): Extension<Arc<AppState>>,
     Extension(middleware): Extension<JwtAuthMiddleware>,
-    Json(body): Json<RetrieveFileDto>,
+    ValidatedJson(body): ValidatedJson<RetrieveFileDto>,
 ) -> Result<impl IntoResponse, HttpError> {
-    body.validate()
+    let user_id = Uuid::parse_str(&middleware.user.id.to_string()).unwrap();
+    let (shared_link, file) = shared_file(&app_state, user_id, &body).await?;
+
+    let private_key =
+        keys::read_private_key(&app_state, user_id, file.recipient_key_version).await?;
+    let encrypted_aes_key = keys::unwrap_stored(&app_state, &file.encrypted_aes_key).await?;
+    let metadata = match &file.encrypted_metadata {
+        Some(encrypted_metadata) => {
+            decrypt::decrypt_metadata(&encrypted_aes_key, encrypted_metadata, &private_key)?
+        }
+        None => FileMetadata {
+            file_name: file.file_name.clone(),
+            content_type: file.content_type.clone(),
+        },
+    };
+
+    // Checked against what is served rather than the stored digest
+    let signing_key = file_signing_key(&app_state, &file).await?;
+    let signed_file = signature::signed_file(
+        file.user_id.unwrap_or_default(),
+        user_id,
+        content_digest(&file.iv, &file.encrypted_file),
+        &metadata,
+        file.file_size,
+    );
+    let signature_status = signature::verify(
+        &signed_file,
+        file.signature.as_deref(),
+        signing_key.as_ref().map(|key| key.public_key.as_str()),
+    );
+
+    let decrypted_file = decrypt::decrypt_file(
+        encrypted_aes_key,
+        file.encrypted_file,
+        file.iv,
+        &private_key,
+    )
+    .await?;
+
+    app_state
+        .shares
+        .record_download(shared_link.id)
+        .await
+        .map_err(|err| HttpError::server_error(err.to_string()))?;
+    metrics::counter!("file_bytes_downloaded_total").increment(decrypted_file.len() as u64);
+
+    // Rows from before detection may hold whatever the uploader declared
+    let content_type = HeaderValue::from_str(&metadata.content_type)
+        .unwrap_or(HeaderValue::from_static(media_type::OCTET_STREAM));
+    let disposition = media_type::content_disposition(&metadata.file_name, &metadata.content_type);
+    let response = Response::builder()
+        .status(StatusCode::OK)
+        .header(header::CONTENT_DISPOSITION, disposition)
+        .header(header::CONTENT_TYPE, content_type)
+        .header(header::X_CONTENT_TYPE_OPTIONS, "nosniff")
+        .header(SIGNATURE_STATUS_HEADER, signature_status.as_str())
+        .body(Body::from(decrypted_file))
         .map_err(|err| HttpError::server_error(err.to_string()))?;
+
+    Ok(response)
+}
+
+/// Stores a version 2 envelope its sender sealed, see `seal_file` of
+/// `secure-share-crypto`. It has to be sealed with the active keys of both
+/// parties and signed with the sender's active signing key; the server opens
+/// the metadata to check the signature but never sees the contents in clear.
+#[utoipa::path(
+    post,
+    path = "/api/file/envelope",
+    tag = "file",
+    security(("bearer_token" = []), ("cookie_token" = [])),
+    request_body(content = EnvelopeUploadFormDto, content_type = "multipart/form-data"),
+    responses(
+        (status = 200, description = "Envelope stored for the recipient", body = ResponseDto),
+        (status = 400, description = "Malformed form, unknown recipient, or an envelope that does not open or verify", body = ErrorResponse, content_type = "application/problem+json"),
+        (status = 401, description = "Missing or invalid token", body = ErrorResponse, content_type = "application/problem+json"),
+        (status = 403, description = "Account disabled", body = ErrorResponse, content_type = "application/problem+json"),
+        (status = 409, description = "Sealed with a key pair or signing key that is no longer active", body = ErrorResponse, content_type = "application/problem+json"),
+        (status = 413, description = "Envelope or its file_size exceeds limits.max_upload_bytes", body = ErrorResponse, content_type = "application/problem+json"),
+        (status = 422, description = "Invalid form fields", body = ErrorResponse, content_type = "application/problem+json"),
+        (status = 429, description = "Too many requests", body = ErrorResponse, content_type = "application/problem+json")
+    )
+)]
+#[tracing::instrument(skip_all)]
+pub async fn upload_envelope(
+    Extension(app_state): Extension<Arc<AppState>>,
+    Extension(middleware): Extension<JwtAuthMiddleware>,
+    ValidatedMultipart(envelope): ValidatedMultipart<EnvelopeUploadDto>,
+) -> Result<impl IntoResponse, HttpError> {
     let user_id = Uuid::parse_str(&middleware.user.id.to_string()).unwrap();
-    let shared_id = Uuid::parse_str(&body.shared_id.to_string()).unwrap();
+    let stale = || HttpError::new(ErrorMessage::StaleEnvelopeKey, StatusCode::CONFLICT);
+    let invalid = || HttpError::bad_request(ErrorMessage::InvalidEnvelope);
+
+    // Validated to be at least 0
+    if envelope.file_size as u64 > app_state.env.limits.max_upload_bytes as u64 {
+        return Err(HttpError::new(
+            ErrorMessage::FileTooLarge,
+            StatusCode::PAYLOAD_TOO_LARGE,
+        ));
+    }
+    // AES-256-CBC with PKCS#7 pads every file by 1 to 16 bytes
+    let padded_size = (envelope.file_size / 16)
+        .checked_add(1)
+        .and_then(|blocks| blocks.checked_mul(16))
+        .ok_or_else(invalid)?;
+    if envelope.iv.len() != 16 || envelope.ciphertext.len() as i64 != padded_size {
+        return Err(invalid());
+    }
+
+    let recipient_user = app_state
+        .users
+        .get_user(None, None, Some(&envelope.recipient_email))
+        .await
+        .map_err(|err| HttpError::server_error(err.to_string()))?
+        .ok_or(HttpError::bad_request(ErrorMessage::RecipientNotFound))?;
+    let recipient_key = app_state
+        .users
+        .get_active_user_key(recipient_user.id)
+        .await
+        .map_err(|err| HttpError::server_error(err.to_string()))?
+        .ok_or(HttpError::bad_request(ErrorMessage::RecipientHasNoKey))?;
+    let sender_key = app_state
+        .users
+        .get_active_user_key(user_id)
+        .await
+        .map_err(|err| HttpError::server_error(err.to_string()))?;
+    let signing_key = app_state
+        .users
+        .get_signing_keys(user_id)
+        .await
+        .map_err(|err| HttpError::server_error(err.to_string()))?
+        .into_iter()
+        .find(|key| key.status == "active");
+    if recipient_key.version != envelope.recipient_key_version
+        || sender_key.is_none_or(|key| key.version != envelope.sender_key_version)
+    {
+        return Err(stale());
+    }
+    let signing_key = signing_key
+        .filter(|key| key.version == envelope.signing_key_version)
+        .ok_or_else(stale)?;
+
+    let private_key =
+        keys::read_private_key(&app_state, recipient_user.id, recipient_key.version).await?;
+    let metadata = decrypt::decrypt_metadata(
+        &envelope.encrypted_key,
+        &envelope.encrypted_metadata,
+        &private_key,
+    )
+    .map_err(|_| invalid())?;
+    let digest = content_digest(&envelope.iv, &envelope.ciphertext);
+    let signed_file = signature::signed_file(
+        user_id,
+        recipient_user.id,
+        digest,
+        &metadata,
+        envelope.file_size,
+    );
+    let signature_status = signature::verify(
+        &signed_file,
+        Some(&envelope.signature),
+        Some(&signing_key.public_key),
+    );
+    if signature_status != SignatureStatus::Verified {
+        return Err(invalid());
+    }
+
+    let hash_password = password::hash(&envelope.password)
+        .map_err(|err| HttpError::server_error(err.to_string()))?;
+    let expiration_date = DateTime::parse_from_rfc3339(&envelope.expiration_date)
+        .map_err(|_| HttpError::bad_request(ErrorMessage::InvalidInput("invalid_date_format")))?
+        .with_timezone(&Utc);
+    let encrypted_aes_key = keys::wrap_stored(&app_state, &envelope.encrypted_key).await?;
+    let sender_encrypted_aes_key =
+        keys::wrap_stored(&app_state, &envelope.sender_encrypted_key).await?;
+
+    app_state
+        .files
+        .save_encrypted_file(NewFile {
+            user_id,
+            file_name: String::new(),
+            file_size: envelope.file_size,
+            content_type: media_type::OCTET_STREAM.to_string(),
+            recipient_user_id: recipient_user.id,
+            password: hash_password,
+            expiration_date,
+            encrypted_aes_key,
+            encrypted_file: envelope.ciphertext,
+            iv: envelope.iv,
+            encrypted_metadata: Some(envelope.encrypted_metadata),
+            sender_encrypted_aes_key: Some(sender_encrypted_aes_key),
+            recipient_key_version: envelope.recipient_key_version,
+            sender_key_version: envelope.sender_key_version,
+            content_digest: Some(digest.to_vec()),
+            signature: Some(envelope.signature),
+            signing_key_version: Some(signing_key.version),
+        })
+        .await
+        .map_err(|err| HttpError::server_error(err.to_string()))?;
+
+    metrics::counter!("file_bytes_uploaded_total").increment(envelope.file_size as u64);
+
+    let response = ResponseDto {
+        status: "successful".to_string(),
+        message: "Envelope stored successfully".to_string(),
+    };
+
+    Ok(Json(response))
+}
+
+/// Downloads a file shared with the current user as stored, for them to
+/// decrypt themselves. Counts as a download like `/api/file/register`.
+#[utoipa::path(
+    post,
+    path = "/api/file/envelope/retrieve",
+    tag = "file",
+    security(("bearer_token" = []), ("cookie_token" = [])),
+    request_body = RetrieveFileDto,
+    responses(
+        (status = 200, description = "The stored envelope", body = EnvelopeResponseDto),
+        (status = 400, description = "Share expired, missing or wrong password", body = ErrorResponse, content_type = "application/problem+json"),
+        (status = 401, description = "Missing or invalid token", body = ErrorResponse, content_type = "application/problem+json"),
+        (status = 403, description = "Account disabled", body = ErrorResponse, content_type = "application/problem+json"),
+        (status = 422, description = "Invalid request data", body = ErrorResponse, content_type = "application/problem+json"),
+        (status = 429, description = "Too many requests", body = ErrorResponse, content_type = "application/problem+json")
+    )
+)]
+#[tracing::instrument(skip_all)]
+pub async fn retrieve_envelope(
+    Extension(app_state): Extension<Arc<AppState>>,
+    Extension(middleware): Extension<JwtAuthMiddleware>,
+    ValidatedJson(body): ValidatedJson<RetrieveFileDto>,
+) -> Result<impl IntoResponse, HttpError> {
+    let user_id = Uuid::parse_str(&middleware.user.id.to_string()).unwrap();
+    let (shared_link, file) = shared_file(&app_state, user_id, &body).await?;
+
+    let encrypted_aes_key = keys::unwrap_stored(&app_state, &file.encrypted_aes_key).await?;
+    let metadata = match &file.encrypted_metadata {
+        Some(encrypted_metadata) => {
+            let private_key =
+                keys::read_private_key(&app_state, user_id, file.recipient_key_version).await?;
+            decrypt::decrypt_metadata(&encrypted_aes_key, encrypted_metadata, &private_key)?
+        }
+        None => FileMetadata {
+            file_name: file.file_name.clone(),
+            content_type: file.content_type.clone(),
+        },
+    };
+    let signing_key = file_signing_key(&app_state, &file).await?;
+    let signed_file = signature::signed_file(
+        file.user_id.unwrap_or_default(),
+        user_id,
+        content_digest(&file.iv, &file.encrypted_file),
+        &metadata,
+        file.file_size,
+    );
+    let signature_status = signature::verify(
+        &signed_file,
+        file.signature.as_deref(),
+        signing_key.as_ref().map(|key| key.public_key.as_str()),
+    );
+
+    app_state
+        .shares
+        .record_download(shared_link.id)
+        .await
+        .map_err(|err| HttpError::server_error(err.to_string()))?;
+    metrics::counter!("file_bytes_downloaded_total").increment(file.file_size as u64);
+
+    let envelope = EnvelopeDto {
+        share_id: shared_link.id.to_string(),
+        file_id: file.id.to_string(),
+        sender_id: file.user_id.unwrap_or_default().to_string(),
+        file_size: file.file_size,
+        ciphertext: BASE64_STANDARD.encode(&file.encrypted_file),
+        iv: BASE64_STANDARD.encode(&file.iv),
+        encrypted_key: BASE64_STANDARD.encode(&encrypted_aes_key),
+        recipient_key_version: file.recipient_key_version,
+        encrypted_metadata: file
+            .encrypted_metadata
+            .as_ref()
+            .map(|metadata| BASE64_STANDARD.encode(metadata)),
+        signature: file
+            .signature
+            .as_ref()
+            .map(|signature| BASE64_STANDARD.encode(signature)),
+        signing_key_version: signing_key.as_ref().map(|key| key.version),
+        sender_signing_key: signing_key.map(|key| key.public_key),
+        signature_status,
+    };
+    let response = EnvelopeResponseDto {
+        status: "successful".to_string(),
+        envelope,
+    };
+    Ok(Json(response))
+}
+
+/// Details of a share for its sender or recipient. Reads only the metadata,
+/// never the encrypted contents.
+#[utoipa::path(
+    get,
+    path = "/api/file/shares/{id}",
+    tag = "file",
+    security(("bearer_token" = []), ("cookie_token" = [])),
+    params(("id" = String, Path, format = Uuid, description = "`share_id` of a sent or received list entry")),
+    responses(
+        (status = 200, description = "The share, expired or not", body = ShareDetailsResponseDto),
+        (status = 400, description = "Malformed share id", body = ErrorResponse, content_type = "application/problem+json"),
+        (status = 401, description = "Missing or invalid token", body = ErrorResponse, content_type = "application/problem+json"),
+        (status = 403, description = "Account disabled", body = ErrorResponse, content_type = "application/problem+json"),
+        (status = 404, description = "No such share sent by or to the current user", body = ErrorResponse, content_type = "application/problem+json"),
+        (status = 429, description = "Too many requests", body = ErrorResponse, content_type = "application/problem+json")
+    )
+)]
+#[tracing::instrument(skip_all)]
+pub async fn get_share_details(
+    Extension(app_state): Extension<Arc<AppState>>,
+    Extension(middleware): Extension<JwtAuthMiddleware>,
+    Path(share_id): Path<String>,
+) -> Result<impl IntoResponse, HttpError> {
+    let user_id = Uuid::parse_str(&middleware.user.id.to_string()).unwrap();
+    let share_id = Uuid::parse_str(&share_id)
+        .map_err(|_| HttpError::bad_request(ErrorMessage::InvalidSharedId))?;
+    let share = app_state
+        .shares
+        .get_share_details(share_id, user_id)
+        .await
+        .map_err(|err| HttpError::server_error(err.to_string()))?;
+    let mut share =
+        share.ok_or_else(|| HttpError::new(ErrorMessage::ShareNotFound, StatusCode::NOT_FOUND))?;
+
+    let mut metadata_readable = share.encrypted_metadata.is_none();
+    if let Some(encrypted_metadata) = &share.encrypted_metadata {
+        let (encrypted_aes_key, key_version) = if share.recipient_id == user_id {
+            (Some(&share.encrypted_aes_key), share.recipient_key_version)
+        } else {
+            (
+                share.sender_encrypted_aes_key.as_ref(),
+                share.sender_key_version,
+            )
+        };
+        // Files sent before the sender had a key pair stay unnamed for them
+        if let Some(encrypted_aes_key) = encrypted_aes_key {
+            let private_key = keys::read_private_key(&app_state, user_id, key_version).await?;
+            let encrypted_aes_key = keys::unwrap_stored(&app_state, encrypted_aes_key).await?;
+            let metadata =
+                decrypt::decrypt_metadata(&encrypted_aes_key, encrypted_metadata, &private_key)?;
+            share.file_name = metadata.file_name;
+            share.content_type = metadata.content_type;
+            metadata_readable = true;
+        }
+    }
+
+    let mut details = ShareDetailsDto::from(&share);
+    // The signature covers the metadata, so only those who can read it can check
+    if metadata_readable {
+        let metadata = FileMetadata {
+            file_name: share.file_name.clone(),
+            content_type: share.content_type.clone(),
+        };
+        let digest = share
+            .content_digest
+            .as_deref()
+            .and_then(|digest| digest.try_into().ok())
+            .unwrap_or_default();
+        let signed_file = signature::signed_file(
+            share.sender_id,
+            share.recipient_id,
+            digest,
+            &metadata,
+            share.file_size,
+        );
+        details.signature_status = Some(signature::verify(
+            &signed_file,
+            share.signature.as_deref(),
+            share.sender_signing_key.as_deref(),
+        ));
+    }
+
+    let response = ShareDetailsResponseDto {
+        status: "successful".to_string(),
+        share: details,
+    };
+    Ok(Json(response))
+}
+
+/// The share `body` names for the recipient `user_id`, once its password
+/// matched, and the file behind it.
+async fn shared_file(
+    app_state: &AppState,
+    user_id: Uuid,
+    body: &RetrieveFileDto,
+) -> Result<(SharedLink, File), HttpError> {
+    let shared_id = Uuid::parse_str(&body.shared_id)
+        .map_err(|_| HttpError::bad_request(ErrorMessage::InvalidSharedId))?;
     let shared_link = app_state
-        .db_client
-        .get_shared(shared_id.clone(), user_id.clone())
+        .shares
+        .get_shared(shared_id, user_id)
         .await
         .map_err(|err| HttpError::server_error(err.to_string()))?;
-    let shared_link = shared_link.ok_or_else(|| {
-        HttpError::bad_request(
-            "The requested shared link either does not exist or has expired".to_string(),
-        )
-    })?;
+    let shared_link = shared_link.ok_or(HttpError::bad_request(ErrorMessage::ShareNotFound))?;
 
     let match_password = password::compare(&body.password, &shared_link.password)
         .map_err(|err| HttpError::server_error(err.to_string()))?;
     if !match_password {
-        return Err(HttpError::bad_request(
-            "The provided password is incorrect.".to_string(),
-        ));
+        metrics::counter!("share_password_failures_total").increment(1);
+        return Err(HttpError::bad_request(ErrorMessage::WrongSharePassword));
     }
 
     let file_id = match shared_link.file_id {
         Some(id) => id,
-        None => return Err(HttpError::bad_request("File ID not found".to_string())),
+        None => return Err(HttpError::bad_request(ErrorMessage::FileNotFound)),
     };
     let file = app_state
-        .db_client
-        .get_file(file_id.clone())
+        .files
+        .get_file(file_id)
         .await
         .map_err(|err| HttpError::server_error(err.to_string()))?;
 
-    let file = file.ok_or_else(|| {
-        HttpError::bad_request(
-            "The requested file either does not exist or has expired".to_string(),
-        )
-    })?;
+    let file = file.ok_or(HttpError::bad_request(ErrorMessage::FileNotFound))?;
+    Ok((shared_link, file))
+}
 
-    let mut path = PathBuf::from("assets/private_keys");
-    path.push(format!("{}.pem", user_id.clone()));
+/// The sender's signing key of the version recorded for `file`.
+async fn file_signing_key(
+    app_state: &AppState,
+    file: &File,
+) -> Result<Option<UserSigningKey>, HttpError> {
+    let (Some(sender_id), Some(version)) = (file.user_id, file.signing_key_version) else {
+        return Ok(None);
+    };
+    let signing_key = app_state
+        .users
+        .get_signing_keys(sender_id)
+        .await
+        .map_err(|err| HttpError::server_error(err.to_string()))?
+        .into_iter()
+        .find(|key| key.version == version);
+    Ok(signing_key)
+}
 
-    let private_key =
-        fs::read_to_string(&path).map_err(|err| HttpError::server_error(err.to_string()))?;
+impl MultipartForm for FileUploadDto {
+    async fn from_multipart(mut multipart: Multipart) -> Result<Self, HttpError> {
+        let mut form_data = FileUploadDto::default();
+        while let Some(field) = multipart.next_field().await.map_err(multipart_error)? {
+            let name = field.name().unwrap_or_default().to_string();
 
-    let private_key_pem = RsaPrivateKey::from_pkcs1_pem(&private_key)
-        .map_err(|err| HttpError::server_error(err.to_string()))?;
+            match name.as_str() {
+                "fileUpload" => {
+                    form_data.file_name = field.file_name().unwrap_or("unknow_file").to_string();
+                    form_data.content_type = field
+                        .content_type()
+                        .unwrap_or("application/octet-stream")
+                        .to_string();
+                    form_data.file_data = field.bytes().await.map_err(multipart_error)?.to_vec();
+                }
+                "recipient_email" => {
+                    form_data.recipient_email = field.text().await.map_err(multipart_error)?;
+                }
+                "password" => {
+                    form_data.password = field.text().await.map_err(multipart_error)?;
+                }
+                "expiration_date" => {
+                    form_data.expiration_date = field.text().await.map_err(multipart_error)?;
+                }
+                _ => {}
+            }
+        }
 
-    let decrypted_file = decrypt::decrypt_file(
-        file.encrypted_aes_key,
-        file.encrypted_file,
-        file.iv,
-        &private_key_pem,
-    )
-    .await?;
+        Ok(form_data)
+    }
+}
 
-    let response = Response::builder()
-        .status(StatusCode::OK)
-        .header(
-            "Content-Disposition",
-            format!("attachment; filename=\"{}\"", file.file_name),
-        )
-        .header("Content-Type", "application/octet-stream")
-        .body(Body::from(decrypted_file))
-        .map_err(|err| HttpError::server_error(err.to_string()))?;
+impl MultipartForm for EnvelopeUploadDto {
+    async fn from_multipart(mut multipart: Multipart) -> Result<Self, HttpError> {
+        let mut form_data = EnvelopeUploadDto::default();
+        while let Some(field) = multipart.next_field().await.map_err(multipart_error)? {
+            let name = field.name().unwrap_or_default().to_string();
 
-    Ok(response)
+            match name.as_str() {
+                "ciphertext"
+                | "iv"
+                | "encrypted_key"
+                | "sender_encrypted_key"
+                | "encrypted_metadata"
+                | "signature" => {
+                    let bytes = field.bytes().await.map_err(multipart_error)?.to_vec();
+                    match name.as_str() {
+                        "ciphertext" => form_data.ciphertext = bytes,
+                        "iv" => form_data.iv = bytes,
+                        "encrypted_key" => form_data.encrypted_key = bytes,
+                        "sender_encrypted_key" => form_data.sender_encrypted_key = bytes,
+                        "encrypted_metadata" => form_data.encrypted_metadata = bytes,
+                        _ => form_data.signature = bytes,
+                    }
+                }
+                "file_size" => form_data.file_size = number_field(field).await?,
+                "recipient_key_version" => {
+                    form_data.recipient_key_version = number_field(field).await?;
+                }
+                "sender_key_version" => {
+                    form_data.sender_key_version = number_field(field).await?;
+                }
+                "signing_key_version" => {
+                    form_data.signing_key_version = number_field(field).await?;
+                }
+                "recipient_email" => {
+                    form_data.recipient_email = field.text().await.map_err(multipart_error)?;
+                }
+                "password" => {
+                    form_data.password = field.text().await.map_err(multipart_error)?;
+                }
+                "expiration_date" => {
+                    form_data.expiration_date = field.text().await.map_err(multipart_error)?;
+                }
+                _ => {}
+            }
+        }
+
+        Ok(form_data)
+    }
+}
+
+async fn number_field<T: FromStr>(field: Field<'_>) -> Result<T, HttpError> {
+    let text = field.text().await.map_err(multipart_error)?;
+    text.trim()
+        .parse()
+        .map_err(|_| HttpError::bad_request(ErrorMessage::InvalidMultipart))
 }
@@ -1,18 +1,25 @@
-use std::sync::Arc;
+use std::{
+    collections::{HashMap, hash_map::Entry},
+    sync::Arc,
+};
 
-use axum::{Extension, Json, Router, extract::Query, response::IntoResponse, routing::get};
+use axum::{Extension, Json, Router, response::IntoResponse, routing::get};
+use base64::{Engine, engine::general_purpose::URL_SAFE_NO_PAD};
+use serde::{Deserialize, Serialize};
 use uuid::Uuid;
-use validator::Validate;
 
 use crate::{
     AppState,
-    db::UserExt,
+    db::{self, ListQuery, ListedFile, SortValue},
     dtos::{
-        RequestQueryDto, UserReceiveFileDto, UserReceiveFileListResponseDto, UserSendFileDto,
-        UserSendFileListResponseDto,
+        ListSort, RequestQueryDto, SortOrder, UserReceiveFileDto, UserReceiveFileListResponseDto,
+        UserSendFileDto, UserSendFileListResponseDto,
     },
-    error::HttpError,
+    error::{ErrorMessage, ErrorResponse, HttpError},
+    extractors::ValidatedQuery,
     middleware::JwtAuthMiddleware,
+    models::{FileMetadata, ReceiveFileDetails, SentFileDetails},
+    utils::{decrypt, keys},
 };
 
 pub fn get_file_list_handler() -> Router {
@@ -21,58 +28,266 @@ pub fn get_file_list_handler() -> Router {
         .route("/receive", get(get_receive_shared_files))
 }
 
+/// Position after the last row of a page. Opaque to clients, and tied to the
+/// sort it was issued for so it cannot be replayed against another one.
+#[derive(Debug, Serialize, Deserialize)]
+struct Cursor {
+    sort: ListSort,
+    order: SortOrder,
+    value: SortValue,
+    id: Uuid,
+}
+
+impl Cursor {
+    fn encode(&self) -> String {
+        URL_SAFE_NO_PAD.encode(serde_json::to_vec(self).unwrap())
+    }
+
+    fn decode(cursor: &str, sort: ListSort, order: SortOrder) -> Result<Self, HttpError> {
+        let invalid = || HttpError::bad_request(ErrorMessage::InvalidCursor);
+        let bytes = URL_SAFE_NO_PAD.decode(cursor).map_err(|_| invalid())?;
+        let cursor: Cursor = serde_json::from_slice(&bytes).map_err(|_| invalid())?;
+        if cursor.sort != sort || cursor.order != order {
+            return Err(invalid());
+        }
+        Ok(cursor)
+    }
+}
+
+fn list_query(params: &RequestQueryDto) -> Result<ListQuery, HttpError> {
+    let page = params.page.unwrap_or(1);
+    let limit = params.limit.unwrap_or(10);
+    let sort = params.sort.unwrap_or_default();
+    let order = params.order.unwrap_or(sort.default_order());
+    let after = match &params.cursor {
+        Some(cursor) => {
+            let cursor = Cursor::decode(cursor, sort, order)?;
+            Some((cursor.value, cursor.id))
+        }
+        None => None,
+    };
+
+    Ok(ListQuery {
+        // One row more than the page, to tell whether another page follows
+        limit: limit + 1,
+        offset: (page - 1) * limit,
+        sort,
+        order,
+        after,
+        status: params.status,
+        email: params.email.clone(),
+        file_name: params.file_name.clone(),
+        created_after: params.created_after,
+        created_before: params.created_before,
+    })
+}
+
+/// Drops the extra row fetched by [`list_query`] and returns the cursor for
+/// the page after it, if there is one.
+fn next_cursor<T: ListedFile>(rows: &mut Vec<T>, query: &ListQuery) -> Option<String> {
+    let limit = query.limit - 1;
+    if rows.len() <= limit {
+        return None;
+    }
+    rows.truncate(limit);
+
+    let last = rows.last()?;
+    let cursor = Cursor {
+        sort: query.sort,
+        order: query.order,
+        value: last.sort_value(query.sort)?,
+        id: last.id(),
+    };
+    Some(cursor.encode())
+}
+
+/// A list row whose name and content type may be sealed in its metadata.
+trait SealedRow: ListedFile {
+    /// The AES key as wrapped for the viewer and the encrypted metadata
+    fn sealed(&self) -> Option<(&[u8], &[u8])>;
+
+    /// Version of the viewer's key pair the AES key is wrapped to
+    fn key_version(&self) -> i32;
+
+    fn reveal(&mut self, metadata: FileMetadata);
+}
+
+impl SealedRow for SentFileDetails {
+    fn sealed(&self) -> Option<(&[u8], &[u8])> {
+        Some((
+            self.encrypted_aes_key.as_deref()?,
+            self.encrypted_metadata.as_deref()?,
+        ))
+    }
+
+    fn key_version(&self) -> i32 {
+        self.key_version
+    }
+
+    fn reveal(&mut self, metadata: FileMetadata) {
+        self.file_name = metadata.file_name;
+        self.content_type = metadata.content_type;
+    }
+}
+
+impl SealedRow for ReceiveFileDetails {
+    fn sealed(&self) -> Option<(&[u8], &[u8])> {
+        Some((
+            self.encrypted_aes_key.as_deref()?,
+            self.encrypted_metadata.as_deref()?,
+        ))
+    }
+
+    fn key_version(&self) -> i32 {
+        self.key_version
+    }
+
+    fn reveal(&mut self, metadata: FileMetadata) {
+        self.file_name = metadata.file_name;
+        self.content_type = metadata.content_type;
+    }
+}
+
+/// Decrypts the names of the rows with sealed metadata using the private keys
+/// of `user_id`. Rows without an AES key wrapped for the user, sent before the
+/// sender had a key pair, keep an empty name; a private key that cannot be
+/// read fails the whole list.
+async fn decrypt_names<T: SealedRow>(
+    rows: &mut [T],
+    app_state: &AppState,
+    user_id: Uuid,
+) -> Result<(), HttpError> {
+    if !rows.iter().any(|row| row.sealed().is_some()) {
+        return Ok(());
+    }
+
+    // Rows wrapped to the same key pair share its private key
+    let mut private_keys = HashMap::new();
+    for row in rows {
+        if let Some((encrypted_aes_key, encrypted_metadata)) = row.sealed() {
+            let private_key = match private_keys.entry(row.key_version()) {
+                Entry::Occupied(entry) => entry.into_mut(),
+                Entry::Vacant(entry) => entry
+                    .insert(keys::read_private_key(app_state, user_id, row.key_version()).await?),
+            };
+            let encrypted_aes_key = keys::unwrap_stored(app_state, encrypted_aes_key).await?;
+            let metadata =
+                decrypt::decrypt_metadata(&encrypted_aes_key, encrypted_metadata, private_key)?;
+            row.reveal(metadata);
+        }
+    }
+    Ok(())
+}
+
+#[utoipa::path(
+    get,
+    path = "/api/list/send",
+    tag = "list",
+    security(("bearer_token" = []), ("cookie_token" = [])),
+    params(RequestQueryDto),
+    responses(
+        (status = 200, description = "Files the current user has sent", body = UserSendFileListResponseDto),
+        (status = 400, description = "Malformed query parameters or cursor", body = ErrorResponse, content_type = "application/problem+json"),
+        (status = 401, description = "Missing or invalid token", body = ErrorResponse, content_type = "application/problem+json"),
+        (status = 403, description = "Account disabled", body = ErrorResponse, content_type = "application/problem+json"),
+        (status = 422, description = "Invalid query parameters", body = ErrorResponse, content_type = "application/problem+json")
+    )
+)]
+#[tracing::instrument(skip_all)]
 pub async fn get_user_shared_files(
-    Query(query_params): Query<RequestQueryDto>,
+    ValidatedQuery(query_params): ValidatedQuery<RequestQueryDto>,
     Extension(app_state): Extension<Arc<AppState>>,
     Extension(middleware): Extension<JwtAuthMiddleware>,
 ) -> Result<impl IntoResponse, HttpError> {
-    query_params
-        .validate()
-        .map_err(|err| HttpError::bad_request(err.to_string()))?;
-
     let user = &middleware.user;
-    let page = query_params.page.unwrap_or(1);
-    let limit = query_params.limit.unwrap_or(10);
+    let query = list_query(&query_params)?;
     let user_id = Uuid::parse_str(&user.id.to_string()).unwrap();
-    let (shared_files, total_count) = app_state
-        .db_client
-        .get_sent_files(user_id.clone(), page as u32, limit)
-        .await
-        .map_err(|err| HttpError::server_error(err.to_string()))?;
+    // Names are only known once decrypted, so sorting or filtering by them
+    // pages the newest matching rows here instead of in the database
+    let (mut shared_files, total_count) = if query.needs_file_names() {
+        let (mut shared_files, _) = app_state
+            .shares
+            .get_sent_files(
+                user_id,
+                &query.without_file_names(app_state.env.limits.name_search_window),
+            )
+            .await
+            .map_err(|err| HttpError::server_error(err.to_string()))?;
+        decrypt_names(&mut shared_files, &app_state, user_id).await?;
+        db::page(shared_files, &query)
+    } else {
+        let (mut shared_files, total_count) = app_state
+            .shares
+            .get_sent_files(user_id, &query)
+            .await
+            .map_err(|err| HttpError::server_error(err.to_string()))?;
+        decrypt_names(&mut shared_files, &app_state, user_id).await?;
+        (shared_files, total_count)
+    };
+    let next_cursor = next_cursor(&mut shared_files, &query);
 
-    let filter_send_files = UserSendFileDto::filter_send_user_files(&shared_files);
+    let filter_send_files = shared_files.iter().map(UserSendFileDto::from).collect();
     let response = UserSendFileListResponseDto {
         status: "successful".to_string(),
         files: filter_send_files,
         results: total_count,
+        next_cursor,
     };
     Ok(Json(response))
 }
 
+#[utoipa::path(
+    get,
+    path = "/api/list/receive",
+    tag = "list",
+    security(("bearer_token" = []), ("cookie_token" = [])),
+    params(RequestQueryDto),
+    responses(
+        (status = 200, description = "Files shared with the current user", body = UserReceiveFileListResponseDto),
+        (status = 400, description = "Malformed query parameters or cursor", body = ErrorResponse, content_type = "application/problem+json"),
+        (status = 401, description = "Missing or invalid token", body = ErrorResponse, content_type = "application/problem+json"),
+        (status = 403, description = "Account disabled", body = ErrorResponse, content_type = "application/problem+json"),
+        (status = 422, description = "Invalid query parameters", body = ErrorResponse, content_type = "application/problem+json")
+    )
+)]
+#[tracing::instrument(skip_all)]
 pub async fn get_receive_shared_files(
-    Query(query_params): Query<RequestQueryDto>,
+    ValidatedQuery(query_params): ValidatedQuery<RequestQueryDto>,
     Extension(app_state): Extension<Arc<AppState>>,
     Extension(middleware): Extension<JwtAuthMiddleware>,
 ) -> Result<impl IntoResponse, HttpError> {
-    query_params
-        .validate()
-        .map_err(|err| HttpError::bad_request(err.to_string()))?;
     let user = &middleware.user;
-    let page = query_params.page.unwrap_or(1);
-    let limit = query_params.limit.unwrap_or(10);
+    let query = list_query(&query_params)?;
     let user_id = Uuid::parse_str(&user.id.to_string()).unwrap();
 
-    let (receive_files, total_count) = app_state
-        .db_client
-        .get_receive_files(user_id.clone(), page as u32, limit)
-        .await
-        .map_err(|err| HttpError::server_error(err.to_string()))?;
+    let (mut receive_files, total_count) = if query.needs_file_names() {
+        let (mut receive_files, _) = app_state
+            .shares
+            .get_receive_files(
+                user_id,
+                &query.without_file_names(app_state.env.limits.name_search_window),
+            )
+            .await
+            .map_err(|err| HttpError::server_error(err.to_string()))?;
+        decrypt_names(&mut receive_files, &app_state, user_id).await?;
+        db::page(receive_files, &query)
+    } else {
+        let (mut receive_files, total_count) = app_state
+            .shares
+            .get_receive_files(user_id, &query)
+            .await
+            .map_err(|err| HttpError::server_error(err.to_string()))?;
+        decrypt_names(&mut receive_files, &app_state, user_id).await?;
+        (receive_files, total_count)
+    };
+    let next_cursor = next_cursor(&mut receive_files, &query);
 
-    let filter_receive_files = UserReceiveFileDto::filter_receive_user_files(&receive_files);
+    let filter_receive_files = receive_files.iter().map(UserReceiveFileDto::from).collect();
     let response = UserReceiveFileListResponseDto {
         status: "successful".to_string(),
         files: filter_receive_files,
         results: total_count,
+        next_cursor,
     };
 
     Ok(Json(response))
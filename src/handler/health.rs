@@ -0,0 +1,91 @@
+use std::{fs, path::Path, sync::Arc};
+
+use axum::{Extension, Json, Router, http::StatusCode, response::IntoResponse, routing::get};
+use uuid::Uuid;
+
+use crate::{
+    AppState,
+    dtos::{ReadinessChecksDto, ReadinessResponseDto, Response},
+};
+
+pub fn health_handler() -> Router {
+    Router::new()
+        .route("/healthz", get(liveness))
+        .route("/readyz", get(readiness))
+}
+
+#[utoipa::path(
+    get,
+    path = "/healthz",
+    tag = "operations",
+    responses((status = 200, description = "Process is alive", body = Response))
+)]
+pub async fn liveness() -> impl IntoResponse {
+    Json(Response {
+        status: "successful".to_string(),
+        message: "Server is alive".to_string(),
+    })
+}
+
+#[utoipa::path(
+    get,
+    path = "/readyz",
+    tag = "operations",
+    responses(
+        (status = 200, description = "Database reachable, key directory writable and key encryption key available", body = ReadinessResponseDto),
+        (status = 503, description = "A dependency check failed", body = ReadinessResponseDto)
+    )
+)]
+pub async fn readiness(Extension(app_state): Extension<Arc<AppState>>) -> impl IntoResponse {
+    let database = match &app_state.db_pool {
+        Some(db_pool) => check_status("database", db_pool.ping().await),
+        None => "ok",
+    };
+    let key_directory = check_status(
+        "key_directory",
+        check_writable(&app_state.env.storage.private_key_dir),
+    );
+
+    // Proves a remote provider is reachable and the token valid
+    let kek = match &app_state.kek {
+        Some(kek) => check_status("kek", kek.current_key_id().await),
+        None => "disabled",
+    };
+
+    let ready = database == "ok" && key_directory == "ok" && kek != "fail";
+    let status = if ready {
+        StatusCode::OK
+    } else {
+        StatusCode::SERVICE_UNAVAILABLE
+    };
+    let response = ReadinessResponseDto {
+        status: if ready { "successful" } else { "fail" }.to_string(),
+        checks: ReadinessChecksDto {
+            database: database.to_string(),
+            key_directory: key_directory.to_string(),
+            kek: kek.to_string(),
+        },
+    };
+
+    (status, Json(response))
+}
+
+/// `ok` or `fail`. The probe is unauthenticated, so what went wrong is only
+/// logged.
+fn check_status<T, E: std::fmt::Display>(check: &str, result: Result<T, E>) -> &'static str {
+    match result {
+        Ok(_) => "ok",
+        Err(err) => {
+            tracing::warn!(check, error = %err, "Readiness check failed");
+            "fail"
+        }
+    }
+}
+
+/// Creates and removes a probe file to prove private keys can be written.
+fn check_writable(dir: &str) -> std::io::Result<()> {
+    fs::create_dir_all(dir)?;
+    let probe = Path::new(dir).join(format!(".readyz-{}", Uuid::new_v4()));
+    fs::write(&probe, b"")?;
+    fs::remove_file(&probe)
+}
@@ -0,0 +1,56 @@
+use std::sync::Arc;
+
+use axum::{
+    Extension, Router,
+    http::{HeaderMap, header},
+    response::IntoResponse,
+    routing::get,
+};
+use sha2::{Digest, Sha256};
+
+use crate::{
+    AppState,
+    error::{ErrorMessage, ErrorResponse, HttpError},
+};
+
+pub fn metrics_handler() -> Router {
+    Router::new().route("/metrics", get(render_metrics))
+}
+
+#[utoipa::path(
+    get,
+    path = "/metrics",
+    tag = "operations",
+    responses(
+        (status = 200, description = "Prometheus text exposition format", content_type = "text/plain", body = String),
+        (status = 401, description = "Missing or wrong `metrics.token`", body = ErrorResponse, content_type = "application/problem+json")
+    ),
+    security(("metrics_token" = []))
+)]
+pub async fn render_metrics(
+    Extension(app_state): Extension<Arc<AppState>>,
+    headers: HeaderMap,
+) -> Result<impl IntoResponse, HttpError> {
+    let token = headers
+        .get(header::AUTHORIZATION)
+        .and_then(|value| value.to_str().ok())
+        .and_then(|value| value.strip_prefix("Bearer "))
+        .ok_or_else(|| HttpError::unauthorized(ErrorMessage::TokenNotProvided))?;
+    // Comparing digests keeps the time taken independent of the token
+    let expected = app_state.env.metrics.token.as_deref().unwrap_or_default();
+    if Sha256::digest(token) != Sha256::digest(expected) || expected.is_empty() {
+        return Err(HttpError::unauthorized(ErrorMessage::InvalidToken));
+    }
+
+    // Pool utilization is sampled at scrape time rather than tracked on every checkout
+    if let Some(db_pool) = &app_state.db_pool {
+        metrics::gauge!("db_pool_connections").set(db_pool.size() as f64);
+        metrics::gauge!("db_pool_idle_connections").set(db_pool.num_idle() as f64);
+    }
+    metrics::gauge!("db_pool_max_connections").set(app_state.env.database.max_connections as f64);
+
+    Ok((
+        [(header::CONTENT_TYPE, "text/plain; version=0.0.4")],
+        app_state.metrics.render(),
+    ))
+}
@@ -1,4 +1,7 @@
 pub mod auth;
+pub mod docs;
 pub mod file;
 pub mod file_query;
+pub mod health;
+pub mod metrics;
 pub mod user;
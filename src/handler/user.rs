@@ -2,38 +2,69 @@ use std::sync::Arc;
 
 use axum::{
     Extension, Json, Router,
-    extract::Query,
     response::IntoResponse,
-    routing::{get, put},
+    routing::{get, post, put},
 };
+use base64::{Engine, prelude::BASE64_STANDARD};
+use secure_share_crypto::verifying_key_from_pem;
 use uuid::Uuid;
-use validator::Validate;
 
 use crate::{
     AppState,
-    db::UserExt,
     dtos::{
-        EmailListResponseDto, FilterEmailDto, FilterUserDto, NamedUpdateDto, Response,
-        SearchQueryByEmailDto, UserData, UserPasswordUpdateDto, UserResponseDto,
+        EmailListResponseDto, FilterEmailDto, FilterUserDto, LocaleUpdateDto, NamedUpdateDto,
+        RegisterSigningKeyDto, Response, RotateKeyDto, SearchQueryByEmailDto, SigningKeyDto,
+        SigningKeyResponseDto, UserData, UserKeyDto, UserKeyListResponseDto, UserKeyResponseDto,
+        UserPasswordUpdateDto, UserResponseDto,
     },
-    error::{ErrorMessage, HttpError},
+    error::{ErrorMessage, ErrorResponse, HttpError},
+    extractors::{ValidatedJson, ValidatedQuery},
     middleware::JwtAuthMiddleware,
-    utils::password,
+    rate_limit::{self, RateLimiter},
+    utils::{keys, password},
 };
 
-pub fn users_handler() -> Router {
+pub fn users_handler(key_rotation_limit: RateLimiter) -> Router {
     Router::new()
         .route("/me", get(get_me))
         .route("/name", put(update_user_name))
         .route("/password", put(update_user_password))
+        .route("/locale", put(update_user_locale))
         .route("/search-emails", get(search_by_email))
+        .route("/keys", get(get_user_keys))
+        .route(
+            "/keys/rotate",
+            post(rotate_user_key).layer(axum::middleware::from_fn_with_state(
+                key_rotation_limit.clone(),
+                rate_limit::limit,
+            )),
+        )
+        .route(
+            "/signing-key",
+            put(register_signing_key).layer(axum::middleware::from_fn_with_state(
+                key_rotation_limit,
+                rate_limit::limit,
+            )),
+        )
 }
 
+#[utoipa::path(
+    get,
+    path = "/api/users/me",
+    tag = "users",
+    security(("bearer_token" = []), ("cookie_token" = [])),
+    responses(
+        (status = 200, description = "Current user", body = UserResponseDto),
+        (status = 401, description = "Missing or invalid token", body = ErrorResponse, content_type = "application/problem+json"),
+        (status = 403, description = "Account disabled", body = ErrorResponse, content_type = "application/problem+json")
+    )
+)]
+#[tracing::instrument(skip_all)]
 pub async fn get_me(
     Extension(_app_state): Extension<Arc<AppState>>,
     Extension(middleware): Extension<JwtAuthMiddleware>,
 ) -> Result<impl IntoResponse, HttpError> {
-    let filtered_user = FilterUserDto::filter_user(&middleware.user);
+    let filtered_user = FilterUserDto::from(&middleware.user);
     let response = UserResponseDto {
         status: "successful".to_string(),
         data: UserData {
@@ -43,22 +74,35 @@ pub async fn get_me(
     Ok(Json(response))
 }
 
+#[utoipa::path(
+    put,
+    path = "/api/users/name",
+    tag = "users",
+    security(("bearer_token" = []), ("cookie_token" = [])),
+    request_body = NamedUpdateDto,
+    responses(
+        (status = 200, description = "Updated user", body = UserResponseDto),
+        (status = 400, description = "Malformed JSON body", body = ErrorResponse, content_type = "application/problem+json"),
+        (status = 401, description = "Missing or invalid token", body = ErrorResponse, content_type = "application/problem+json"),
+        (status = 403, description = "Account disabled", body = ErrorResponse, content_type = "application/problem+json"),
+        (status = 422, description = "Invalid name", body = ErrorResponse, content_type = "application/problem+json")
+    )
+)]
+#[tracing::instrument(skip_all)]
 pub async fn update_user_name(
     Extension(app_state): Extension<Arc<AppState>>,
     Extension(middleware): Extension<JwtAuthMiddleware>,
-    Json(body): Json<NamedUpdateDto>,
+    ValidatedJson(body): ValidatedJson<NamedUpdateDto>,
 ) -> Result<impl IntoResponse, HttpError> {
-    body.validate()
-        .map_err(|err| HttpError::bad_request(err.to_string()))?;
     let user = &middleware.user;
     let user_id = Uuid::parse_str(&user.id.to_string()).unwrap();
     let user = app_state
-        .db_client
-        .update_user_name(user_id.clone(), body.name)
+        .users
+        .update_user_name(user_id, body.name)
         .await
         .map_err(|err| HttpError::server_error(err.to_string()))?;
 
-    let filtered_user = FilterUserDto::filter_user(&user);
+    let filtered_user = FilterUserDto::from(&user);
     let response = UserResponseDto {
         status: "successful".to_string(),
         data: UserData {
@@ -69,69 +113,231 @@ pub async fn update_user_name(
     Ok(Json(response))
 }
 
+#[utoipa::path(
+    put,
+    path = "/api/users/password",
+    tag = "users",
+    security(("bearer_token" = []), ("cookie_token" = [])),
+    request_body = UserPasswordUpdateDto,
+    responses(
+        (status = 200, description = "Password updated", body = Response),
+        (status = 400, description = "Wrong old password", body = ErrorResponse, content_type = "application/problem+json"),
+        (status = 401, description = "Missing or invalid token", body = ErrorResponse, content_type = "application/problem+json"),
+        (status = 403, description = "Account disabled", body = ErrorResponse, content_type = "application/problem+json"),
+        (status = 422, description = "Invalid password data", body = ErrorResponse, content_type = "application/problem+json")
+    )
+)]
+#[tracing::instrument(skip_all)]
 pub async fn update_user_password(
     Extension(app_state): Extension<Arc<AppState>>,
     Extension(middleware): Extension<JwtAuthMiddleware>,
-    Json(body): Json<UserPasswordUpdateDto>,
+    ValidatedJson(body): ValidatedJson<UserPasswordUpdateDto>,
 ) -> Result<impl IntoResponse, HttpError> {
-    body.validate()
-        .map_err(|err| HttpError::bad_request(err.to_string()))?;
     let user = &middleware.user;
     let user_id = Uuid::parse_str(&user.id.to_string()).unwrap();
     let user = app_state
-        .db_client
-        .get_user(Some(user_id.clone()), None, None)
+        .users
+        .get_user(Some(user_id), None, None)
         .await
         .map_err(|err| HttpError::server_error(err.to_string()))?;
-    let user = user.ok_or(HttpError::unauthorized(
-        ErrorMessage::InvalidToken.to_string(),
-    ))?;
+    let user = user.ok_or(HttpError::unauthorized(ErrorMessage::InvalidToken))?;
 
     let password_match = password::compare(&body.old_password, &user.password)
         .map_err(|err| HttpError::server_error(err.to_string()))?;
     if !password_match {
-        return Err(HttpError::bad_request(
-            "Old password is incorrect".to_string(),
-        ));
+        return Err(HttpError::bad_request(ErrorMessage::OldPasswordIncorrect));
     }
 
     let hashed_password = password::hash(&body.new_password)
         .map_err(|err| HttpError::server_error(err.to_string()))?;
 
     app_state
-        .db_client
-        .update_user_password(user_id.clone(), hashed_password)
+        .users
+        .update_user_password(user_id, hashed_password)
         .await
         .map_err(|err| HttpError::server_error(err.to_string()))?;
 
     let response = Response {
-        status: "successful",
+        status: "successful".to_string(),
         message: "Password updated successfully".to_string(),
     };
 
     Ok(Json(response))
 }
 
+#[utoipa::path(
+    put,
+    path = "/api/users/locale",
+    tag = "users",
+    security(("bearer_token" = []), ("cookie_token" = [])),
+    request_body = LocaleUpdateDto,
+    responses(
+        (status = 200, description = "Updated user", body = UserResponseDto),
+        (status = 400, description = "Malformed JSON body", body = ErrorResponse, content_type = "application/problem+json"),
+        (status = 401, description = "Missing or invalid token", body = ErrorResponse, content_type = "application/problem+json"),
+        (status = 403, description = "Account disabled", body = ErrorResponse, content_type = "application/problem+json"),
+        (status = 422, description = "Unsupported locale", body = ErrorResponse, content_type = "application/problem+json")
+    )
+)]
+#[tracing::instrument(skip_all)]
+pub async fn update_user_locale(
+    Extension(app_state): Extension<Arc<AppState>>,
+    Extension(middleware): Extension<JwtAuthMiddleware>,
+    ValidatedJson(body): ValidatedJson<LocaleUpdateDto>,
+) -> Result<impl IntoResponse, HttpError> {
+    let user = app_state
+        .users
+        .update_user_locale(middleware.user.id, body.locale)
+        .await
+        .map_err(|err| HttpError::server_error(err.to_string()))?;
+
+    let filtered_user = FilterUserDto::from(&user);
+    let response = UserResponseDto {
+        status: "successful".to_string(),
+        data: UserData {
+            user: filtered_user,
+        },
+    };
+
+    Ok(Json(response))
+}
+
+#[utoipa::path(
+    get,
+    path = "/api/users/search-emails",
+    tag = "users",
+    security(("bearer_token" = []), ("cookie_token" = [])),
+    params(SearchQueryByEmailDto),
+    responses(
+        (status = 200, description = "Emails of users with a public key", body = EmailListResponseDto),
+        (status = 401, description = "Missing or invalid token", body = ErrorResponse, content_type = "application/problem+json"),
+        (status = 403, description = "Account disabled", body = ErrorResponse, content_type = "application/problem+json"),
+        (status = 422, description = "Missing query", body = ErrorResponse, content_type = "application/problem+json")
+    )
+)]
+#[tracing::instrument(skip_all)]
 pub async fn search_by_email(
-    Query(params): Query<SearchQueryByEmailDto>,
+    ValidatedQuery(params): ValidatedQuery<SearchQueryByEmailDto>,
     Extension(app_state): Extension<Arc<AppState>>,
     Extension(user): Extension<JwtAuthMiddleware>,
 ) -> Result<impl IntoResponse, HttpError> {
-    params
-        .validate()
-        .map_err(|err| HttpError::server_error(err.to_string()))?;
-    let query_pattern = format!("%{}%", params.query);
     let user_id = Uuid::parse_str(&user.user.id.to_string()).unwrap();
     let users = app_state
-        .db_client
-        .search_by_email(user_id.clone(), query_pattern.clone())
+        .users
+        .search_by_email(user_id, params.query)
         .await
         .map_err(|err| HttpError::server_error(err.to_string()))?;
 
-    let filtered_email = FilterEmailDto::filter_emails(&users);
+    let filtered_email = users.iter().map(FilterEmailDto::from).collect();
     let response = EmailListResponseDto {
         status: "successful".to_string(),
         emails: filtered_email,
     };
     Ok(Json(response))
 }
+
+#[utoipa::path(
+    get,
+    path = "/api/users/keys",
+    tag = "users",
+    security(("bearer_token" = []), ("cookie_token" = [])),
+    responses(
+        (status = 200, description = "Every key pair of the current user", body = UserKeyListResponseDto),
+        (status = 401, description = "Missing or invalid token", body = ErrorResponse, content_type = "application/problem+json"),
+        (status = 403, description = "Account disabled", body = ErrorResponse, content_type = "application/problem+json")
+    )
+)]
+#[tracing::instrument(skip_all)]
+pub async fn get_user_keys(
+    Extension(app_state): Extension<Arc<AppState>>,
+    Extension(middleware): Extension<JwtAuthMiddleware>,
+) -> Result<impl IntoResponse, HttpError> {
+    let keys = app_state
+        .users
+        .get_user_keys(middleware.user.id)
+        .await
+        .map_err(|err| HttpError::server_error(err.to_string()))?;
+
+    let response = UserKeyListResponseDto {
+        status: "successful".to_string(),
+        keys: keys.iter().map(UserKeyDto::from).collect(),
+    };
+    Ok(Json(response))
+}
+
+/// Replaces the active key pair with a new one, of the server's default type
+/// unless `key_type` says otherwise. Files already shared keep decrypting
+/// with the retired pair; new uploads are wrapped to the new one.
+#[utoipa::path(
+    post,
+    path = "/api/users/keys/rotate",
+    tag = "users",
+    security(("bearer_token" = []), ("cookie_token" = [])),
+    request_body = RotateKeyDto,
+    responses(
+        (status = 200, description = "The new active key pair", body = UserKeyResponseDto),
+        (status = 400, description = "Malformed JSON body", body = ErrorResponse, content_type = "application/problem+json"),
+        (status = 401, description = "Missing or invalid token", body = ErrorResponse, content_type = "application/problem+json"),
+        (status = 403, description = "Account disabled", body = ErrorResponse, content_type = "application/problem+json"),
+        (status = 409, description = "Another rotation is in progress", body = ErrorResponse, content_type = "application/problem+json"),
+        (status = 429, description = "Too many requests", body = ErrorResponse, content_type = "application/problem+json")
+    )
+)]
+#[tracing::instrument(skip_all)]
+pub async fn rotate_user_key(
+    Extension(app_state): Extension<Arc<AppState>>,
+    Extension(middleware): Extension<JwtAuthMiddleware>,
+    ValidatedJson(body): ValidatedJson<RotateKeyDto>,
+) -> Result<impl IntoResponse, HttpError> {
+    let key_type = body.key_type.unwrap_or(app_state.env.storage.key_type);
+    let key = keys::generete_key(app_state, middleware.user, key_type).await?;
+
+    let response = UserKeyResponseDto {
+        status: "successful".to_string(),
+        key: UserKeyDto::from(&key),
+    };
+    Ok(Json(response))
+}
+
+/// Registers an Ed25519 key whose private half only the user holds as the
+/// key their uploads are signed with. The server deletes the signing key it
+/// generated for them, so their later uploads are signed by the client or
+/// not at all, and files signed before keep the key they were signed with.
+#[utoipa::path(
+    put,
+    path = "/api/users/signing-key",
+    tag = "users",
+    security(("bearer_token" = []), ("cookie_token" = [])),
+    request_body = RegisterSigningKeyDto,
+    responses(
+        (status = 200, description = "The new active signing key", body = SigningKeyResponseDto),
+        (status = 400, description = "Malformed JSON body or not an Ed25519 public key", body = ErrorResponse, content_type = "application/problem+json"),
+        (status = 401, description = "Missing or invalid token", body = ErrorResponse, content_type = "application/problem+json"),
+        (status = 403, description = "Account disabled", body = ErrorResponse, content_type = "application/problem+json"),
+        (status = 409, description = "Another key is being registered", body = ErrorResponse, content_type = "application/problem+json"),
+        (status = 422, description = "Invalid request data", body = ErrorResponse, content_type = "application/problem+json"),
+        (status = 429, description = "Too many requests", body = ErrorResponse, content_type = "application/problem+json")
+    )
+)]
+#[tracing::instrument(skip_all)]
+pub async fn register_signing_key(
+    Extension(app_state): Extension<Arc<AppState>>,
+    Extension(middleware): Extension<JwtAuthMiddleware>,
+    ValidatedJson(body): ValidatedJson<RegisterSigningKeyDto>,
+) -> Result<impl IntoResponse, HttpError> {
+    let verifying_key = BASE64_STANDARD
+        .decode(&body.public_key)
+        .ok()
+        .and_then(|pem| String::from_utf8(pem).ok())
+        .and_then(|pem| verifying_key_from_pem(&pem).ok())
+        .ok_or(HttpError::bad_request(ErrorMessage::InvalidInput(
+            "invalid_signing_key",
+        )))?;
+    let key = keys::register_signing_key(&app_state, &middleware.user, &verifying_key).await?;
+
+    let response = SigningKeyResponseDto {
+        status: "successful".to_string(),
+        key: SigningKeyDto::from(&key),
+    };
+    Ok(Json(response))
+}
@@ -0,0 +1,250 @@
+use std::{borrow::Cow, cell::Cell};
+
+use axum::{
+    extract::Request,
+    http::{HeaderValue, header},
+    middleware::Next,
+    response::Response,
+};
+use validator::ValidationError;
+
+use crate::error::PROBLEM_JSON;
+
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub enum Locale {
+    #[default]
+    En,
+    Zh,
+}
+
+impl Locale {
+    /// Matches a BCP 47 language tag on its primary subtag, so `zh-CN` and
+    /// `zh-Hant` both select Chinese.
+    pub fn parse(tag: &str) -> Option<Self> {
+        let primary = tag.trim().split(['-', '_']).next()?;
+        if primary.eq_ignore_ascii_case("en") {
+            Some(Locale::En)
+        } else if primary.eq_ignore_ascii_case("zh") {
+            Some(Locale::Zh)
+        } else {
+            None
+        }
+    }
+
+    pub fn code(&self) -> &'static str {
+        match self {
+            Locale::En => "en",
+            Locale::Zh => "zh",
+        }
+    }
+
+    /// Picks the supported language with the highest `q` weight from an
+    /// `Accept-Language` header.
+    pub fn from_accept_language(value: &HeaderValue) -> Option<Self> {
+        let value = value.to_str().ok()?;
+        let mut candidates: Vec<(Locale, f32)> = value
+            .split(',')
+            .filter_map(|entry| {
+                let mut parts = entry.split(';');
+                let locale = Locale::parse(parts.next()?)?;
+                let quality = parts
+                    .find_map(|param| param.trim().strip_prefix("q="))
+                    .map_or(Some(1.0), |q| q.trim().parse::<f32>().ok())?;
+                (quality > 0.0).then_some((locale, quality))
+            })
+            .collect();
+        // Stable sort keeps the header order between equal weights
+        candidates.sort_by(|a, b| b.1.total_cmp(&a.1));
+        candidates.first().map(|(locale, _)| *locale)
+    }
+}
+
+tokio::task_local! {
+    static LOCALE: Cell<Locale>;
+}
+
+/// Locale of the request being handled, English outside of a request.
+pub fn current() -> Locale {
+    LOCALE.try_with(Cell::get).unwrap_or_default()
+}
+
+/// Overrides the negotiated locale for the rest of the request, used once the
+/// authenticated user's preference is known.
+pub fn set(locale: Locale) {
+    let _ = LOCALE.try_with(|cell| cell.set(locale));
+}
+
+/// Negotiates the locale from `Accept-Language` and makes it available to
+/// everything rendering a response further down the stack.
+pub async fn negotiate(req: Request, next: Next) -> Response {
+    let locale = req
+        .headers()
+        .get(header::ACCEPT_LANGUAGE)
+        .and_then(Locale::from_accept_language)
+        .unwrap_or_default();
+
+    LOCALE
+        .scope(Cell::new(locale), async move {
+            let mut response = next.run(req).await;
+            let is_problem = response
+                .headers()
+                .get(header::CONTENT_TYPE)
+                .is_some_and(|content_type| content_type == PROBLEM_JSON);
+            if is_problem {
+                response.headers_mut().insert(
+                    header::CONTENT_LANGUAGE,
+                    HeaderValue::from_static(current().code()),
+                );
+            }
+            response
+        })
+        .await
+}
+
+/// Looks up a message by key. Keys are `ErrorMessage` codes and the `code` of
+/// DTO validation rules.
+pub fn message(key: &str, locale: Locale) -> Option<&'static str> {
+    let (en, zh) = match key {
+        // Errors
+        "empty_password" => ("Password cannot be empty", "密码不能为空"),
+        "invalid_hash_format" => ("Invalid hash format", "哈希格式无效"),
+        "hashing_error" => ("Error while hashing password", "密码哈希时出错"),
+        "invalid_token" => (
+            "Authentication token is invalid or expired",
+            "身份验证令牌无效或已过期",
+        ),
+        "wrong_credentials" => ("Wrong credentials", "邮箱或密码错误"),
+        "email_already_exists" => ("Email already exists", "该邮箱已被注册"),
+        "user_no_longer_exists" => ("User no longer exists", "用户已不存在"),
+        "email_does_not_exist" => ("Email does not exist", "邮箱不存在"),
+        "token_not_provided" => ("Token not provided", "未提供令牌"),
+        "too_many_requests" => (
+            "Too many requests, try again later",
+            "请求过于频繁，请稍后再试",
+        ),
+        "invalid_multipart" => (
+            "Malformed multipart form data",
+            "multipart 表单数据格式错误",
+        ),
+        "file_too_large" => (
+            "File exceeds the maximum upload size",
+            "文件超过上传大小上限",
+        ),
+        "recipient_not_found" => ("Recipient user not found", "未找到接收用户"),
+        "recipient_has_no_key" => ("Recipient user has no public key", "接收用户没有公钥"),
+        "sender_has_no_key" => (
+            "You have no key pair yet; rotating your key creates one",
+            "你还没有密钥对，轮换密钥即可创建",
+        ),
+        "invalid_shared_id" => (
+            "Shared ID is not a valid identifier",
+            "共享 ID 不是有效的标识符",
+        ),
+        "invalid_cursor" => (
+            "Cursor is malformed or belongs to a different sort order",
+            "游标格式错误或与当前排序方式不匹配",
+        ),
+        "share_not_found" => (
+            "The requested shared link either does not exist or has expired",
+            "请求的共享链接不存在或已过期",
+        ),
+        "wrong_share_password" => ("The provided password is incorrect.", "提供的密码不正确。"),
+        "file_not_found" => (
+            "The requested file either does not exist or has expired",
+            "请求的文件不存在或已过期",
+        ),
+        "old_password_incorrect" => ("Old password is incorrect", "旧密码不正确"),
+        "account_disabled" => ("This account has been disabled", "该账户已被停用"),
+        "key_rotation_in_progress" => ("The key pair is already being rotated", "密钥对正在轮换中"),
+        "invalid_envelope" => (
+            "The envelope does not open or its signature does not verify",
+            "信封无法打开或其签名验证失败",
+        ),
+        "stale_envelope_key" => (
+            "The envelope was sealed with keys that are no longer active",
+            "信封使用的密钥已不再有效",
+        ),
+        "internal_error" => ("An internal server error occurred", "服务器内部错误"),
+        "validation_failed" => ("One or more fields are invalid", "一个或多个字段无效"),
+        // Unreadable input
+        "invalid_json_data" => (
+            "The JSON body has missing fields or values of the wrong type",
+            "JSON 请求体缺少字段或字段类型错误",
+        ),
+        "invalid_json_syntax" => (
+            "The request body is not valid JSON",
+            "请求体不是有效的 JSON",
+        ),
+        "missing_json_content_type" => (
+            "Expected a request with Content-Type: application/json",
+            "请求的 Content-Type 应为 application/json",
+        ),
+        "invalid_body" => ("The request body could not be read", "无法读取请求体"),
+        "invalid_query" => (
+            "The query string is malformed or has values of the wrong type",
+            "查询字符串格式错误或取值类型错误",
+        ),
+        // Validation rules
+        "name_too_short" => (
+            "Name must be at least 10 characters",
+            "姓名至少需要 10 个字符",
+        ),
+        "name_required" => ("Name is required", "姓名不能为空"),
+        "email_required" => ("Email is required", "邮箱不能为空"),
+        "email_invalid" => ("Invalid email", "邮箱格式无效"),
+        "password_too_short" => (
+            "Password must be at least 8 characters",
+            "密码至少需要 8 个字符",
+        ),
+        "password_required" => ("Password must not be empty", "密码不能为空"),
+        "old_password_too_short" => (
+            "Old password must be at least 8 characters long",
+            "旧密码至少需要 8 个字符",
+        ),
+        "confirm_password_required" => ("Confirm password is required", "请确认密码"),
+        "passwords_do_not_match" => ("Passwords do not match", "两次输入的密码不一致"),
+        "query_required" => ("Query is required", "查询内容不能为空"),
+        "file_required" => ("A file is required", "必须上传文件"),
+        "shared_id_required" => ("Shared ID must not be empty", "共享 ID 不能为空"),
+        "expiration_date_required" => ("Expiration date is required.", "过期时间不能为空。"),
+        "invalid_date_format" => (
+            "Invalid date format. Expected format is YYYY-MM-DDTHH:MM:ssssssZ.",
+            "日期格式无效，应为 YYYY-MM-DDTHH:MM:ssssssZ。",
+        ),
+        "signing_key_required" => ("Signing key is required", "签名密钥不能为空"),
+        "invalid_signing_key" => (
+            "Signing key must be a base64 encoded Ed25519 public key PEM",
+            "签名密钥必须是 Base64 编码的 Ed25519 公钥 PEM",
+        ),
+        "expiration_date_past" => (
+            "Expiration date must be in the future.",
+            "过期时间必须晚于当前时间。",
+        ),
+        "file_name_length" => (
+            "File name filter must be 1 to 255 characters",
+            "文件名筛选条件必须为 1 到 255 个字符",
+        ),
+        "content_type_too_long" => (
+            "Content type must be at most 255 characters",
+            "内容类型不能超过 255 个字符",
+        ),
+        "unsupported_locale" => ("Unsupported locale", "不支持的语言"),
+        "range" => ("Value is out of range", "取值超出范围"),
+        _ => return None,
+    };
+
+    Some(match locale {
+        Locale::En => en,
+        Locale::Zh => zh,
+    })
+}
+
+/// Message of a failed validation rule, falling back to the DTO's own
+/// message for rules missing from the catalog.
+pub fn validation_message(error: &ValidationError, locale: Locale) -> String {
+    message(&error.code, locale)
+        .map(Cow::Borrowed)
+        .or_else(|| error.message.clone())
+        .unwrap_or_else(|| error.code.clone())
+        .into_owned()
+}
@@ -0,0 +1,416 @@
+//! Key encryption keys (KEKs) wrap every key the server keeps at rest: the
+//! users' private keys on disk and the RSA-wrapped AES keys in the database.
+//! Wrapped material names the key that wrapped it, so rotating the KEK only
+//! re-wraps these small blobs and never touches file ciphertext, and material
+//! stored before a KEK was configured keeps working as is.
+
+use std::{env, fmt, fs, sync::Arc, time::Duration};
+
+use aes::Aes256;
+use async_trait::async_trait;
+use base64::{Engine, prelude::BASE64_STANDARD};
+use cbc::cipher::{BlockDecryptMut, BlockEncryptMut, KeyIvInit, block_padding::Pkcs7};
+use hkdf::Hkdf;
+use hmac::{Hmac, Mac};
+use rand::RngCore;
+use serde::{Deserialize, de::DeserializeOwned};
+use serde_json::json;
+use sha2::Sha256;
+
+use crate::config::{KekConfig, KekProvider};
+
+/// Starts every wrapped blob. RSA ciphertexts stored without a KEK start with
+/// it by chance once in 2^64.
+const MAGIC: &[u8] = b"SSKEK01\0";
+const KEY_SIZE: usize = 32;
+const IV_SIZE: usize = 16;
+const TAG_SIZE: usize = 32;
+
+#[derive(Debug)]
+pub enum KekError {
+    /// The material names a key the provider does not have
+    UnknownKey(String),
+    /// The material was tampered with, truncated or wrapped by another key
+    Integrity,
+    /// Keys or wrapped material that cannot be parsed
+    Malformed(String),
+    /// The provider could not be reached or refused the request
+    Provider(String),
+    /// The material is wrapped but no KEK is configured
+    NotConfigured,
+}
+
+impl fmt::Display for KekError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            KekError::UnknownKey(key_id) => write!(f, "unknown key encryption key {}", key_id),
+            KekError::Integrity => write!(f, "wrapped key failed its integrity check"),
+            KekError::Malformed(problem) => write!(f, "malformed key material: {}", problem),
+            KekError::Provider(problem) => write!(f, "key encryption key provider: {}", problem),
+            KekError::NotConfigured => {
+                write!(f, "key material is wrapped but no KEK is configured")
+            }
+        }
+    }
+}
+
+impl std::error::Error for KekError {}
+
+/// Key material wrapped by a [`KeyEncryptionKey`], with the id of the key
+/// that wrapped it.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct WrappedKey {
+    pub key_id: String,
+    pub ciphertext: Vec<u8>,
+}
+
+impl WrappedKey {
+    /// `MAGIC`, the length of the key id in one byte, the key id and the
+    /// ciphertext.
+    pub fn to_bytes(&self) -> Vec<u8> {
+        let mut bytes = MAGIC.to_vec();
+        bytes.push(self.key_id.len() as u8);
+        bytes.extend_from_slice(self.key_id.as_bytes());
+        bytes.extend_from_slice(&self.ciphertext);
+        bytes
+    }
+
+    /// Parses stored material; `None` when it was stored without a KEK.
+    pub fn from_bytes(stored: &[u8]) -> Result<Option<Self>, KekError> {
+        let Some(rest) = stored.strip_prefix(MAGIC) else {
+            return Ok(None);
+        };
+        let truncated = || KekError::Malformed("truncated wrapped key".to_string());
+        let (&id_len, rest) = rest.split_first().ok_or_else(truncated)?;
+        if rest.len() < id_len as usize {
+            return Err(truncated());
+        }
+        let (key_id, ciphertext) = rest.split_at(id_len as usize);
+        let key_id = String::from_utf8(key_id.to_vec())
+            .map_err(|_| KekError::Malformed("key id is not UTF-8".to_string()))?;
+
+        Ok(Some(WrappedKey {
+            key_id,
+            ciphertext: ciphertext.to_vec(),
+        }))
+    }
+}
+
+#[async_trait]
+pub trait KeyEncryptionKey: fmt::Debug + Send + Sync {
+    /// Id of the key new material is wrapped with. Material wrapped by any
+    /// other key is due for re-wrapping.
+    async fn current_key_id(&self) -> Result<String, KekError>;
+
+    async fn wrap(&self, key: &[u8]) -> Result<WrappedKey, KekError>;
+
+    async fn unwrap(&self, wrapped: &WrappedKey) -> Result<Vec<u8>, KekError>;
+}
+
+/// The provider named by the configuration; `None` stores material as is.
+pub fn from_config(config: &KekConfig) -> Result<Option<Arc<dyn KeyEncryptionKey>>, KekError> {
+    let kek: Arc<dyn KeyEncryptionKey> = match config.provider {
+        KekProvider::None => return Ok(None),
+        KekProvider::Local => {
+            let keys = fs::read_to_string(&config.key_file)
+                .map_err(|err| KekError::Malformed(format!("{}: {}", config.key_file, err)))?;
+            Arc::new(Keyring::parse(&keys)?)
+        }
+        KekProvider::Env => {
+            let keys = env::var(&config.env_var)
+                .map_err(|err| KekError::Malformed(format!("{}: {}", config.env_var, err)))?;
+            Arc::new(Keyring::parse(&keys)?)
+        }
+        KekProvider::Transit => {
+            let token = env::var(&config.transit_token_env).map_err(|err| {
+                KekError::Provider(format!("{}: {}", config.transit_token_env, err))
+            })?;
+            Arc::new(TransitKek::new(
+                &config.transit_url,
+                &config.transit_key,
+                token,
+            ))
+        }
+    };
+
+    Ok(Some(kek))
+}
+
+/// Wraps `key` for storage, or returns it unchanged without a KEK.
+pub async fn wrap_stored(
+    kek: Option<&dyn KeyEncryptionKey>,
+    key: &[u8],
+) -> Result<Vec<u8>, KekError> {
+    match kek {
+        Some(kek) => Ok(kek.wrap(key).await?.to_bytes()),
+        None => Ok(key.to_vec()),
+    }
+}
+
+/// Recovers what [`wrap_stored`] was given, with or without a KEK.
+pub async fn unwrap_stored(
+    kek: Option<&dyn KeyEncryptionKey>,
+    stored: &[u8],
+) -> Result<Vec<u8>, KekError> {
+    match (WrappedKey::from_bytes(stored)?, kek) {
+        (None, _) => Ok(stored.to_vec()),
+        (Some(wrapped), Some(kek)) => kek.unwrap(&wrapped).await,
+        (Some(_), None) => Err(KekError::NotConfigured),
+    }
+}
+
+/// Id of the key `stored` is wrapped with; `None` when stored as is.
+pub fn stored_key_id(stored: &[u8]) -> Result<Option<String>, KekError> {
+    Ok(WrappedKey::from_bytes(stored)?.map(|wrapped| wrapped.key_id))
+}
+
+/// A fresh random key as a line for a [`Keyring`].
+pub fn generate_key_line(key_id: &str) -> String {
+    let mut key = [0u8; KEY_SIZE];
+    rand::thread_rng().fill_bytes(&mut key);
+    format!("{}:{}", key_id, BASE64_STANDARD.encode(key))
+}
+
+/// KEKs held by the server itself, from a key file or an environment variable.
+/// Wraps with AES-256-CBC and authenticates with HMAC-SHA256, under subkeys
+/// derived from the KEK with HKDF.
+pub struct Keyring {
+    /// In the order given; the last one wraps new material
+    keys: Vec<(String, [u8; KEY_SIZE])>,
+}
+
+impl fmt::Debug for Keyring {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let ids: Vec<&str> = self.keys.iter().map(|(id, _)| id.as_str()).collect();
+        f.debug_struct("Keyring").field("keys", &ids).finish()
+    }
+}
+
+impl Keyring {
+    /// Reads `id:base64 key` entries separated by newlines or commas, skipping
+    /// blank lines and `#` comments.
+    pub fn parse(keys: &str) -> Result<Self, KekError> {
+        let mut parsed = Vec::new();
+        for entry in keys.split(['\n', ',']) {
+            let entry = entry.trim();
+            if entry.is_empty() || entry.starts_with('#') {
+                continue;
+            }
+            let (key_id, key) = entry
+                .split_once(':')
+                .ok_or_else(|| KekError::Malformed("expected id:base64 key".to_string()))?;
+            if key_id.is_empty() || key_id.len() > u8::MAX as usize {
+                return Err(KekError::Malformed(format!(
+                    "key id must be 1 to 255 bytes: {}",
+                    key_id
+                )));
+            }
+            let key: [u8; KEY_SIZE] = BASE64_STANDARD
+                .decode(key)
+                .ok()
+                .and_then(|key| key.try_into().ok())
+                .ok_or_else(|| {
+                    KekError::Malformed(format!("key {} is not 32 bytes of base64", key_id))
+                })?;
+            if parsed.iter().any(|(id, _)| id == key_id) {
+                return Err(KekError::Malformed(format!("key {} given twice", key_id)));
+            }
+            parsed.push((key_id.to_string(), key));
+        }
+
+        if parsed.is_empty() {
+            return Err(KekError::Malformed("no keys".to_string()));
+        }
+        Ok(Keyring { keys: parsed })
+    }
+
+    fn current(&self) -> &(String, [u8; KEY_SIZE]) {
+        self.keys.last().expect("a keyring has at least one key")
+    }
+
+    fn key(&self, key_id: &str) -> Option<&[u8; KEY_SIZE]> {
+        self.keys
+            .iter()
+            .find(|(id, _)| id == key_id)
+            .map(|(_, key)| key)
+    }
+}
+
+/// Encryption and MAC keys derived from one KEK.
+fn subkeys(key: &[u8; KEY_SIZE]) -> ([u8; KEY_SIZE], [u8; KEY_SIZE]) {
+    let mut okm = [0u8; 2 * KEY_SIZE];
+    Hkdf::<Sha256>::new(None, key)
+        .expand(b"secure-share kek v1", &mut okm)
+        .expect("64 bytes is a valid HKDF-SHA256 output length");
+    let (enc_key, mac_key) = okm.split_at(KEY_SIZE);
+    (enc_key.try_into().unwrap(), mac_key.try_into().unwrap())
+}
+
+/// MAC over the key id and the IV with the ciphertext, so material cannot be
+/// relabelled as wrapped by another key.
+fn mac(mac_key: &[u8; KEY_SIZE], key_id: &str, sealed: &[u8]) -> Hmac<Sha256> {
+    let mut mac = Hmac::<Sha256>::new_from_slice(mac_key).expect("HMAC takes any key length");
+    mac.update(&[key_id.len() as u8]);
+    mac.update(key_id.as_bytes());
+    mac.update(sealed);
+    mac
+}
+
+#[async_trait]
+impl KeyEncryptionKey for Keyring {
+    async fn current_key_id(&self) -> Result<String, KekError> {
+        Ok(self.current().0.clone())
+    }
+
+    async fn wrap(&self, key: &[u8]) -> Result<WrappedKey, KekError> {
+        let (key_id, kek) = self.current();
+        let (enc_key, mac_key) = subkeys(kek);
+        let mut iv = [0u8; IV_SIZE];
+        rand::thread_rng().fill_bytes(&mut iv);
+
+        let mut ciphertext = iv.to_vec();
+        ciphertext.extend_from_slice(
+            &cbc::Encryptor::<Aes256>::new(&enc_key.into(), &iv.into())
+                .encrypt_padded_vec_mut::<Pkcs7>(key),
+        );
+        let tag = mac(&mac_key, key_id, &ciphertext).finalize().into_bytes();
+        ciphertext.extend_from_slice(&tag);
+
+        Ok(WrappedKey {
+            key_id: key_id.clone(),
+            ciphertext,
+        })
+    }
+
+    async fn unwrap(&self, wrapped: &WrappedKey) -> Result<Vec<u8>, KekError> {
+        let kek = self
+            .key(&wrapped.key_id)
+            .ok_or_else(|| KekError::UnknownKey(wrapped.key_id.clone()))?;
+        let (enc_key, mac_key) = subkeys(kek);
+        if wrapped.ciphertext.len() < IV_SIZE + TAG_SIZE {
+            return Err(KekError::Integrity);
+        }
+        let (sealed, tag) = wrapped
+            .ciphertext
+            .split_at(wrapped.ciphertext.len() - TAG_SIZE);
+        mac(&mac_key, &wrapped.key_id, sealed)
+            .verify_slice(tag)
+            .map_err(|_| KekError::Integrity)?;
+
+        let (iv, ciphertext) = sealed.split_at(IV_SIZE);
+        cbc::Decryptor::<Aes256>::new_from_slices(&enc_key, iv)
+            .map_err(|_| KekError::Integrity)?
+            .decrypt_padded_vec_mut::<Pkcs7>(ciphertext)
+            .map_err(|_| KekError::Integrity)
+    }
+}
+
+/// The transit secrets engine of Vault, or any stand-in speaking its API. The
+/// KEK never leaves Vault; key ids are the transit key name and version, e.g.
+/// `secure-share:v2`, and Vault's own key rotation starts a new version.
+#[derive(Debug)]
+pub struct TransitKek {
+    client: reqwest::Client,
+    url: String,
+    key: String,
+    token: String,
+}
+
+#[derive(Deserialize)]
+struct TransitResponse<T> {
+    data: T,
+}
+
+#[derive(Deserialize)]
+struct TransitKeyInfo {
+    latest_version: u32,
+}
+
+#[derive(Deserialize)]
+struct TransitCiphertext {
+    ciphertext: String,
+}
+
+#[derive(Deserialize)]
+struct TransitPlaintext {
+    plaintext: String,
+}
+
+impl TransitKek {
+    pub fn new(url: &str, key: &str, token: String) -> Self {
+        let client = reqwest::Client::builder()
+            .timeout(Duration::from_secs(10))
+            .build()
+            .expect("a client without custom TLS settings builds");
+        Self {
+            client,
+            url: url.trim_end_matches('/').to_string(),
+            key: key.to_string(),
+            token,
+        }
+    }
+
+    fn key_id(&self, version: u32) -> String {
+        format!("{}:v{}", self.key, version)
+    }
+
+    async fn call<T: DeserializeOwned>(
+        &self,
+        request: reqwest::RequestBuilder,
+    ) -> Result<T, KekError> {
+        let provider = |err: reqwest::Error| KekError::Provider(err.to_string());
+        let response: TransitResponse<T> = request
+            .header("X-Vault-Token", &self.token)
+            .send()
+            .await
+            .map_err(provider)?
+            .error_for_status()
+            .map_err(provider)?
+            .json()
+            .await
+            .map_err(provider)?;
+        Ok(response.data)
+    }
+}
+
+#[async_trait]
+impl KeyEncryptionKey for TransitKek {
+    async fn current_key_id(&self) -> Result<String, KekError> {
+        let url = format!("{}/v1/transit/keys/{}", self.url, self.key);
+        let info: TransitKeyInfo = self.call(self.client.get(url)).await?;
+        Ok(self.key_id(info.latest_version))
+    }
+
+    async fn wrap(&self, key: &[u8]) -> Result<WrappedKey, KekError> {
+        let url = format!("{}/v1/transit/encrypt/{}", self.url, self.key);
+        let body = json!({ "plaintext": BASE64_STANDARD.encode(key) });
+        let response: TransitCiphertext = self.call(self.client.post(url).json(&body)).await?;
+
+        // Vault ciphertexts look like `vault:v3:...`
+        let version = response
+            .ciphertext
+            .strip_prefix("vault:v")
+            .and_then(|rest| rest.split_once(':'))
+            .and_then(|(version, _)| version.parse().ok())
+            .ok_or_else(|| KekError::Provider("unexpected transit ciphertext".to_string()))?;
+        Ok(WrappedKey {
+            key_id: self.key_id(version),
+            ciphertext: response.ciphertext.into_bytes(),
+        })
+    }
+
+    async fn unwrap(&self, wrapped: &WrappedKey) -> Result<Vec<u8>, KekError> {
+        if !wrapped.key_id.starts_with(&format!("{}:v", self.key)) {
+            return Err(KekError::UnknownKey(wrapped.key_id.clone()));
+        }
+        let ciphertext = std::str::from_utf8(&wrapped.ciphertext)
+            .map_err(|_| KekError::Malformed("transit ciphertext is not UTF-8".to_string()))?;
+        let url = format!("{}/v1/transit/decrypt/{}", self.url, self.key);
+        let body = json!({ "ciphertext": ciphertext });
+        let response: TransitPlaintext = self.call(self.client.post(url).json(&body)).await?;
+
+        BASE64_STANDARD
+            .decode(response.plaintext)
+            .map_err(|_| KekError::Provider("transit plaintext is not base64".to_string()))
+    }
+}
@@ -0,0 +1,43 @@
+use std::sync::Arc;
+
+use metrics_exporter_prometheus::PrometheusHandle;
+
+use crate::{
+    config::Config,
+    db::{DbPool, FileRepository, ShareRepository, UserRepository},
+    kek::KeyEncryptionKey,
+    rate_limit::RateLimitStore,
+};
+
+pub mod config;
+pub mod db;
+pub mod dtos;
+pub mod error;
+pub mod extractors;
+pub mod handler;
+pub mod i18n;
+pub mod kek;
+pub mod middleware;
+pub mod migrations;
+pub mod models;
+pub mod monitoring;
+pub mod openapi;
+pub mod rate_limit;
+pub mod router;
+pub mod telemetry;
+pub mod utils;
+
+#[derive(Debug, Clone)]
+pub struct AppState {
+    pub env: Config,
+    pub users: Arc<dyn UserRepository>,
+    pub files: Arc<dyn FileRepository>,
+    pub shares: Arc<dyn ShareRepository>,
+    /// Backs the repositories in production; `None` when they live elsewhere,
+    /// e.g. in a [`db::MemoryDb`] under test
+    pub db_pool: Option<DbPool>,
+    /// Wraps stored key material; `None` stores it as is
+    pub kek: Option<Arc<dyn KeyEncryptionKey>>,
+    pub rate_limit_store: Arc<dyn RateLimitStore>,
+    pub metrics: PrometheusHandle,
+}
@@ -1,98 +1,208 @@
-use std::sync::Arc;
+use std::{future::IntoFuture, net::SocketAddr, sync::Arc, time::Duration};
 
 use axum::http::{
     HeaderValue, Method,
     header::{ACCEPT, AUTHORIZATION, CONTENT_TYPE},
 };
-use sqlx::postgres::PgPoolOptions;
+use tokio::sync::watch;
 use tokio_cron_scheduler::{Job, JobScheduler};
 use tower_http::cors::CorsLayer;
-use tracing_subscriber::filter::LevelFilter;
+use tracing::Instrument;
 
-use crate::{
-    config::Config,
-    db::{DbClient, UserExt},
+use secure_share::{
+    AppState,
+    config::{Config, RateLimitBackend},
+    db::DbPool,
+    kek, migrations, monitoring,
+    rate_limit::{MemoryRateLimitStore, PgRateLimitStore, RateLimitStore},
     router::create_router,
+    telemetry,
 };
 
-mod config;
-mod db;
-mod dtos;
-mod error;
-mod handler;
-mod middleware;
-mod models;
-mod router;
-mod utils;
-
-#[derive(Debug, Clone)]
-pub struct AppState {
-    pub env: Config,
-    pub db_client: DbClient,
-}
-
 #[tokio::main]
 async fn main() {
-    tracing_subscriber::fmt()
-        .with_max_level(LevelFilter::DEBUG)
-        .init();
-
     dotenv::dotenv().ok();
 
-    let config = Config::init();
-    let pool = match PgPoolOptions::new()
-        .max_connections(10)
-        .connect(&config.database_url)
-        .await
-    {
+    // The subscriber depends on the config, so problems loading it go straight to stderr
+    let config = match Config::init() {
+        Ok(config) => config,
+        Err(err) => {
+            eprintln!("🔥 {}", err);
+            std::process::exit(1);
+        }
+    };
+    let telemetry = telemetry::init(&config.logging);
+    let pool = match DbPool::connect(&config.database_url, &config.database).await {
         Ok(pool) => {
-            println!("✅ Connection to the database is successful!");
+            tracing::info!("Connection to the database is successful");
             pool
         }
         Err(err) => {
-            println!("🔥 Failed to connect to the database: {:?}", err);
+            tracing::error!(error = %err, "Failed to connect to the database");
+            telemetry.shutdown();
             std::process::exit(1);
         }
     };
+
+    let mut args = std::env::args().skip(1);
+    if args.next().as_deref() == Some("migrate") {
+        let code = migrations::command(&pool, args.next().as_deref()).await;
+        pool.close().await;
+        telemetry.shutdown();
+        std::process::exit(code);
+    }
+    if let Err(err) = migrations::prepare(&pool, config.database.auto_migrate).await {
+        tracing::error!(error = %err, "Database schema is not ready");
+        telemetry.shutdown();
+        std::process::exit(1);
+    }
+
+    let origins = config
+        .server
+        .cors_origins
+        .iter()
+        .map(|origin| origin.parse::<HeaderValue>().unwrap())
+        .collect::<Vec<_>>();
     let cors = CorsLayer::new()
-        .allow_origin("http://localhost:3000".parse::<HeaderValue>().unwrap())
+        .allow_origin(origins)
         .allow_headers([AUTHORIZATION, ACCEPT, CONTENT_TYPE])
         .allow_credentials(true)
-        .allow_methods([Method::GET, Method::POST, Method::PUT]);
-    let db_client = DbClient::new(pool);
+        .allow_methods([Method::GET, Method::POST, Method::PUT])
+        .expose_headers([telemetry::REQUEST_ID_HEADER]);
+    let rate_limit_store: Arc<dyn RateLimitStore> = match (config.rate_limit.store, &pool) {
+        (RateLimitBackend::Postgres, DbPool::Postgres(pool)) => {
+            Arc::new(PgRateLimitStore::new(pool.clone()))
+        }
+        // Config::validate only allows the Postgres store with a Postgres database
+        _ => Arc::new(MemoryRateLimitStore::default()),
+    };
+    let kek = match kek::from_config(&config.kek) {
+        Ok(kek) => kek,
+        Err(err) => {
+            tracing::error!(error = %err, "Failed to set up the key encryption key");
+            pool.close().await;
+            telemetry.shutdown();
+            std::process::exit(1);
+        }
+    };
+    let (users, files, shares) = pool.repositories();
     let app_state = AppState {
         env: config.clone(),
-        db_client: db_client.clone(),
+        users,
+        files,
+        shares: shares.clone(),
+        db_pool: Some(pool.clone()),
+        kek,
+        rate_limit_store,
+        metrics: monitoring::install_recorder(),
     };
-    let sched = JobScheduler::new().await.unwrap();
-    let job = Job::new_async("0 0 * * * *", {
+    let mut sched = JobScheduler::new().await.unwrap();
+    let job = Job::new_async(config.jobs.cleanup_schedule.as_str(), {
+        let rate_limit_store = app_state.rate_limit_store.clone();
         move |_, _| {
-            let db_client = db_client.clone();
-            Box::pin(async move {
-                println!("Running scheduler task to delete expirated files...");
-                if let Err(err) = db_client.delete_expired_files().await {
-                    eprintln!("Error deleting expired files: {:?}", err);
-                } else {
-                    println!("Successfully deleted expired files.");
+            let shares = shares.clone();
+            let rate_limit_store = rate_limit_store.clone();
+            Box::pin(
+                async move {
+                    tracing::info!("Running scheduler task to delete expired files");
+                    match shares.delete_expired_files().await {
+                        Ok(deleted_files) => {
+                            metrics::counter!("cleanup_runs_total", "result" => "success")
+                                .increment(1);
+                            metrics::counter!("expired_files_deleted_total")
+                                .increment(deleted_files);
+                            tracing::info!(deleted_files, "Successfully deleted expired files");
+                        }
+                        Err(err) => {
+                            metrics::counter!("cleanup_runs_total", "result" => "failure")
+                                .increment(1);
+                            tracing::error!(error = %err, "Error deleting expired files");
+                        }
+                    }
+                    match rate_limit_store.prune().await {
+                        Ok(pruned_buckets) => {
+                            tracing::info!(pruned_buckets, "Pruned idle rate limit buckets")
+                        }
+                        Err(err) => {
+                            tracing::error!(error = %err, "Error pruning rate limit buckets")
+                        }
+                    }
                 }
-            })
+                .instrument(tracing::info_span!("expired_file_cleanup")),
+            )
         }
     })
     .unwrap();
 
     sched.add(job).await.unwrap();
-
-    tokio::spawn(async move {
-        sched.start().await.unwrap();
-    });
+    sched.start().await.unwrap();
 
     let app = create_router(Arc::new(app_state.clone())).layer(cors.clone());
-    println!(
-        "{}",
-        format!("🚀 Server is running on http://localhost:{}", config.port)
+    tracing::info!(
+        host = %config.server.host,
+        port = config.server.port,
+        "Server is running"
     );
-    let listener = tokio::net::TcpListener::bind(format!("0.0.0.0:{}", &config.port))
+    let listener = tokio::net::TcpListener::bind((config.server.host.as_str(), config.server.port))
         .await
         .unwrap();
-    axum::serve(listener, app).await.unwrap();
+
+    let (shutdown_tx, shutdown_rx) = watch::channel(false);
+    tokio::spawn(async move {
+        shutdown_signal().await;
+        let _ = shutdown_tx.send(true);
+    });
+
+    let mut server_shutdown = shutdown_rx.clone();
+    let server = axum::serve(
+        listener,
+        app.into_make_service_with_connect_info::<SocketAddr>(),
+    )
+    .with_graceful_shutdown(async move {
+        let _ = server_shutdown.wait_for(|stopping| *stopping).await;
+        tracing::info!("Shutdown signal received, draining in-flight requests");
+    });
+
+    let mut drain_shutdown = shutdown_rx;
+    let drain_timeout = Duration::from_secs(config.server.shutdown_timeout_secs);
+    tokio::select! {
+        result = server.into_future() => result.unwrap(),
+        _ = async {
+            let _ = drain_shutdown.wait_for(|stopping| *stopping).await;
+            tokio::time::sleep(drain_timeout).await;
+        } => {
+            tracing::warn!(?drain_timeout, "Requests still running, shutting down anyway");
+        }
+    }
+
+    if let Err(err) = sched.shutdown().await {
+        tracing::error!(error = %err, "Error stopping scheduler");
+    }
+    pool.close().await;
+    tracing::info!("Server stopped");
+    telemetry.shutdown();
+}
+
+async fn shutdown_signal() {
+    let ctrl_c = async {
+        tokio::signal::ctrl_c()
+            .await
+            .expect("failed to install Ctrl+C handler");
+    };
+
+    #[cfg(unix)]
+    let terminate = async {
+        tokio::signal::unix::signal(tokio::signal::unix::SignalKind::terminate())
+            .expect("failed to install SIGTERM handler")
+            .recv()
+            .await;
+    };
+
+    #[cfg(not(unix))]
+    let terminate = std::future::pending::<()>();
+
+    tokio::select! {
+        _ = ctrl_c => {},
+        _ = terminate => {},
+    }
 }
@@ -1,3 +1,5 @@
+use std::sync::Arc;
+
 use axum::{Extension, extract::Request, http::header, middleware::Next, response::IntoResponse};
 use axum_extra::extract::CookieJar;
 use serde::{Deserialize, Serialize};
@@ -5,8 +7,8 @@ use uuid::Uuid;
 
 use crate::{
     AppState,
-    db::UserExt,
     error::{ErrorMessage, HttpError},
+    i18n::{self, Locale},
     models::User,
     utils::token,
 };
@@ -18,7 +20,7 @@ pub struct JwtAuthMiddleware {
 
 pub async fn auth(
     cookie_jar: CookieJar,
-    Extension(app_state): Extension<AppState>,
+    Extension(app_state): Extension<Arc<AppState>>,
     mut req: Request,
     next: Next,
 ) -> Result<impl IntoResponse, HttpError> {
@@ -29,35 +31,34 @@ pub async fn auth(
             req.headers()
                 .get(header::AUTHORIZATION)
                 .and_then(|auth_header| auth_header.to_str().ok())
-                .and_then(|auth_value| {
-                    if auth_value.starts_with("Bearer ") {
-                        Some(auth_value[7..].to_owned())
-                    } else {
-                        None
-                    }
-                })
+                .and_then(|auth_value| auth_value.strip_prefix("Bearer "))
+                .map(|token| token.to_owned())
         });
 
-    let token = cookies
-        .ok_or_else(|| HttpError::unauthorized(ErrorMessage::TokenNotProvided.to_string()))?;
+    let token = cookies.ok_or_else(|| HttpError::unauthorized(ErrorMessage::TokenNotProvided))?;
 
     let token_details = match token::decode_token(token, app_state.env.jwt_secret.as_bytes()) {
         Ok(token_details) => token_details,
         Err(_) => {
-            return Err(HttpError::unauthorized(
-                ErrorMessage::InvalidToken.to_string(),
-            ));
+            return Err(HttpError::unauthorized(ErrorMessage::InvalidToken));
         }
     };
 
-    let user_id = Uuid::parse_str(&token_details.to_string()).unwrap();
+    let user_id = Uuid::parse_str(&token_details)
+        .map_err(|_| HttpError::unauthorized(ErrorMessage::InvalidToken))?;
     let user = app_state
-        .db_client
+        .users
         .get_user(Some(user_id), None, None)
         .await
         .map_err(|err| HttpError::server_error(err.to_string()))?;
-    let user =
-        user.ok_or_else(|| HttpError::unauthorized(ErrorMessage::UserNoLongerExists.to_string()))?;
+    let user = user.ok_or_else(|| HttpError::unauthorized(ErrorMessage::UserNoLongerExists))?;
+    if user.disabled_at.is_some() {
+        return Err(HttpError::forbidden(ErrorMessage::AccountDisabled));
+    }
+
+    if let Some(locale) = user.locale.as_deref().and_then(Locale::parse) {
+        i18n::set(locale);
+    }
 
     req.extensions_mut()
         .insert(JwtAuthMiddleware { user: user.clone() });
@@ -0,0 +1,235 @@
+use std::fmt;
+
+use sqlx::{
+    Database, Pool,
+    migrate::{Migrate, MigrateError, Migrator},
+};
+
+use crate::db::DbPool;
+
+/// Migrations from `migrations/`, compiled into the binary.
+pub static MIGRATOR: Migrator = sqlx::migrate!();
+
+/// The same schema for SQLite, from `migrations/sqlite/`.
+pub static SQLITE_MIGRATOR: Migrator = sqlx::migrate!("migrations/sqlite");
+
+async fn run(pool: &DbPool) -> Result<(), MigrateError> {
+    match pool {
+        DbPool::Postgres(pool) => MIGRATOR.run(pool).await,
+        DbPool::Sqlite(pool) => SQLITE_MIGRATOR.run(pool).await,
+    }
+}
+
+async fn undo(pool: &DbPool, target: i64) -> Result<(), MigrateError> {
+    match pool {
+        DbPool::Postgres(pool) => MIGRATOR.undo(pool, target).await,
+        DbPool::Sqlite(pool) => SQLITE_MIGRATOR.undo(pool, target).await,
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum MigrationState {
+    Applied,
+    Pending,
+    /// Applied, but the file changed since
+    Modified,
+    /// Recorded in the database but unknown to this binary
+    Unknown,
+}
+
+impl fmt::Display for MigrationState {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let state = match self {
+            MigrationState::Applied => "applied",
+            MigrationState::Pending => "pending",
+            MigrationState::Modified => "modified",
+            MigrationState::Unknown => "unknown",
+        };
+        f.pad(state)
+    }
+}
+
+#[derive(Debug, Clone)]
+pub struct MigrationStatus {
+    pub version: i64,
+    pub description: String,
+    pub state: MigrationState,
+}
+
+#[derive(Debug, Clone)]
+pub struct SchemaStatus {
+    pub migrations: Vec<MigrationStatus>,
+    /// Version of a migration that failed halfway
+    pub dirty: Option<i64>,
+}
+
+impl SchemaStatus {
+    fn versions(&self, state: MigrationState) -> Vec<i64> {
+        self.migrations
+            .iter()
+            .filter(|migration| migration.state == state)
+            .map(|migration| migration.version)
+            .collect()
+    }
+}
+
+#[derive(Debug)]
+pub enum SchemaError {
+    Migrate(MigrateError),
+    Dirty(i64),
+    Modified(Vec<i64>),
+    Behind(Vec<i64>),
+}
+
+impl fmt::Display for SchemaError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            SchemaError::Migrate(err) => write!(f, "migration failed: {}", err),
+            SchemaError::Dirty(version) => write!(
+                f,
+                "migration {} failed partway; fix the schema by hand and remove its row from _sqlx_migrations",
+                version
+            ),
+            SchemaError::Modified(versions) => {
+                write!(f, "applied migrations were modified: {:?}", versions)
+            }
+            SchemaError::Behind(versions) => write!(
+                f,
+                "database schema is behind, pending migrations: {:?}; run `secure-share migrate run` or set database.auto_migrate",
+                versions
+            ),
+        }
+    }
+}
+
+impl std::error::Error for SchemaError {}
+
+impl From<MigrateError> for SchemaError {
+    fn from(err: MigrateError) -> Self {
+        SchemaError::Migrate(err)
+    }
+}
+
+/// Compares the embedded migrations with the ones recorded in the database.
+pub async fn status(pool: &DbPool) -> Result<SchemaStatus, MigrateError> {
+    match pool {
+        DbPool::Postgres(pool) => status_of(pool, &MIGRATOR).await,
+        DbPool::Sqlite(pool) => status_of(pool, &SQLITE_MIGRATOR).await,
+    }
+}
+
+async fn status_of<DB>(pool: &Pool<DB>, migrator: &Migrator) -> Result<SchemaStatus, MigrateError>
+where
+    DB: Database,
+    DB::Connection: Migrate,
+{
+    let mut conn = pool.acquire().await?;
+    conn.ensure_migrations_table().await?;
+    let dirty = conn.dirty_version().await?;
+    let applied = conn.list_applied_migrations().await?;
+
+    let mut migrations: Vec<MigrationStatus> = migrator
+        .iter()
+        .filter(|migration| !migration.migration_type.is_down_migration())
+        .map(|migration| {
+            let state = match applied.iter().find(|a| a.version == migration.version) {
+                Some(a) if a.checksum == migration.checksum => MigrationState::Applied,
+                Some(_) => MigrationState::Modified,
+                None => MigrationState::Pending,
+            };
+            MigrationStatus {
+                version: migration.version,
+                description: migration.description.to_string(),
+                state,
+            }
+        })
+        .collect();
+    for applied in &applied {
+        if !migrator.version_exists(applied.version) {
+            migrations.push(MigrationStatus {
+                version: applied.version,
+                description: String::new(),
+                state: MigrationState::Unknown,
+            });
+        }
+    }
+    migrations.sort_by_key(|migration| migration.version);
+
+    Ok(SchemaStatus { migrations, dirty })
+}
+
+/// Brings the schema up to date when `auto_migrate` is set, otherwise refuses
+/// to continue while migrations are pending.
+pub async fn prepare(pool: &DbPool, auto_migrate: bool) -> Result<(), SchemaError> {
+    let schema = status(pool).await?;
+    if let Some(version) = schema.dirty {
+        return Err(SchemaError::Dirty(version));
+    }
+    let modified = schema.versions(MigrationState::Modified);
+    if !modified.is_empty() {
+        return Err(SchemaError::Modified(modified));
+    }
+
+    let pending = schema.versions(MigrationState::Pending);
+    if pending.is_empty() {
+        return Ok(());
+    }
+    if !auto_migrate {
+        return Err(SchemaError::Behind(pending));
+    }
+
+    tracing::info!(?pending, "Applying database migrations");
+    run(pool).await?;
+    Ok(())
+}
+
+/// Reverts the most recently applied migration.
+pub async fn revert_latest(pool: &DbPool) -> Result<Option<i64>, MigrateError> {
+    let schema = status(pool).await?;
+    let applied: Vec<i64> = schema
+        .migrations
+        .iter()
+        .filter(|migration| migration.state != MigrationState::Pending)
+        .map(|migration| migration.version)
+        .collect();
+    let Some((&latest, rest)) = applied.split_last() else {
+        return Ok(None);
+    };
+
+    undo(pool, rest.last().copied().unwrap_or(0)).await?;
+    Ok(Some(latest))
+}
+
+/// Handles `secure-share migrate <status|run|revert>` and returns the exit code.
+pub async fn command(pool: &DbPool, action: Option<&str>) -> i32 {
+    let result = match action {
+        Some("status") | None => status(pool).await.map(|schema| {
+            for migration in &schema.migrations {
+                println!(
+                    "{}  {:<8}  {}",
+                    migration.version, migration.state, migration.description
+                );
+            }
+            if let Some(version) = schema.dirty {
+                println!("migration {} is dirty", version);
+            }
+        }),
+        Some("run") => run(pool).await.map(|_| println!("schema is up to date")),
+        Some("revert") => revert_latest(pool).await.map(|reverted| match reverted {
+            Some(version) => println!("reverted {}", version),
+            None => println!("nothing to revert"),
+        }),
+        Some(other) => {
+            eprintln!("unknown migrate action `{}`, expected status, run or revert", other);
+            return 2;
+        }
+    };
+
+    match result {
+        Ok(()) => 0,
+        Err(err) => {
+            eprintln!("🔥 {}", err);
+            1
+        }
+    }
+}
@@ -9,20 +9,97 @@ pub struct User {
     pub name: String,
     pub password: String,
     pub public_key: Option<String>,
+    /// Base64 encoded PEM of the Ed25519 key the user's uploads are signed
+    /// with, a copy of the active [`UserSigningKey`]
+    pub signing_key: Option<String>,
+    pub locale: Option<String>,
+    pub disabled_at: Option<DateTime<Utc>>,
     pub created_at: Option<DateTime<Utc>>,
     pub updated_at: Option<DateTime<Utc>>,
 }
 
+/// One of a user's key pairs. Only the public half is stored; the private
+/// half is a file in the key directory, see `utils::keys`.
+#[derive(Debug, Clone, Deserialize, Serialize, sqlx::FromRow)]
+pub struct UserKey {
+    pub user_id: Uuid,
+    pub version: i32,
+    /// `rsa` or `x25519`
+    pub key_type: String,
+    pub public_key: String,
+    /// `active` for the one new files are wrapped to, otherwise `retired`
+    pub status: String,
+    pub created_at: Option<DateTime<Utc>>,
+    pub retired_at: Option<DateTime<Utc>>,
+}
+
+/// One of the Ed25519 keys a user has signed uploads with.
+#[derive(Debug, Clone, Deserialize, Serialize, sqlx::FromRow)]
+pub struct UserSigningKey {
+    pub user_id: Uuid,
+    pub version: i32,
+    /// Base64 encoded PEM of the public key
+    pub public_key: String,
+    /// `server` when the private key is a file in the key directory, `client`
+    /// when only the user has it
+    pub held_by: String,
+    /// `active` for the one new uploads are signed with, otherwise `retired`
+    pub status: String,
+    pub created_at: Option<DateTime<Utc>>,
+    pub retired_at: Option<DateTime<Utc>>,
+}
+
 #[derive(Debug, Clone, Deserialize, Serialize, sqlx::FromRow, sqlx::Type)]
 pub struct File {
     pub id: Uuid,
     pub user_id: Option<Uuid>,
+    /// Empty when `encrypted_metadata` is set
     pub file_name: String,
     pub file_size: i64,
+    pub content_type: String,
     pub encrypted_aes_key: Vec<u8>,
     pub encrypted_file: Vec<u8>,
     pub iv: Vec<u8>,
+    /// [`FileMetadata`] as JSON, sealed with the file's AES key
+    pub encrypted_metadata: Option<Vec<u8>>,
+    /// The file's AES key wrapped for the sender
+    pub sender_encrypted_aes_key: Option<Vec<u8>>,
     pub created_at: Option<DateTime<Utc>>,
+    /// Version of the recipient's key pair `encrypted_aes_key` is wrapped to
+    pub recipient_key_version: i32,
+    /// Version of the sender's key pair `sender_encrypted_aes_key` is wrapped to
+    pub sender_key_version: i32,
+    /// SHA-256 of `iv` and `encrypted_file`, see [`secure_share_crypto::content_digest`]
+    pub content_digest: Option<Vec<u8>>,
+    /// The sender's signature over a [`secure_share_crypto::SignedFile`]
+    pub signature: Option<Vec<u8>>,
+    /// Version of the sender's [`UserSigningKey`] `signature` was made with
+    pub signing_key_version: Option<i32>,
+}
+
+/// A file to store together with the share for its recipient, see
+/// [`FileRepository::save_encrypted_file`](crate::db::FileRepository::save_encrypted_file).
+#[derive(Clone)]
+pub struct NewFile {
+    pub user_id: Uuid,
+    /// Empty when `encrypted_metadata` is set
+    pub file_name: String,
+    pub file_size: i64,
+    pub content_type: String,
+    pub recipient_user_id: Uuid,
+    /// Hash of the share password
+    pub password: String,
+    pub expiration_date: DateTime<Utc>,
+    pub encrypted_aes_key: Vec<u8>,
+    pub encrypted_file: Vec<u8>,
+    pub iv: Vec<u8>,
+    pub encrypted_metadata: Option<Vec<u8>>,
+    pub sender_encrypted_aes_key: Option<Vec<u8>>,
+    pub recipient_key_version: i32,
+    pub sender_key_version: i32,
+    pub content_digest: Option<Vec<u8>>,
+    pub signature: Option<Vec<u8>>,
+    pub signing_key_version: Option<i32>,
 }
 
 #[derive(Debug, Clone, Deserialize, Serialize, sqlx::FromRow, sqlx::Type)]
@@ -31,24 +108,124 @@ pub struct SharedLink {
     pub file_id: Option<Uuid>,
     pub recipient_user_id: Option<Uuid>,
     pub password: String,
+    pub download_count: i64,
+    pub last_downloaded_at: Option<DateTime<Utc>>,
     pub expiration_date: Option<DateTime<Utc>>,
     pub created_at: Option<DateTime<Utc>>,
 }
 
 #[derive(sqlx::FromRow)]
 pub struct SentFileDetails {
+    pub share_id: Uuid,
     pub file_id: Uuid,
     pub file_name: String,
+    pub file_size: i64,
+    pub content_type: String,
     pub recipient_email: String,
+    pub recipient_name: String,
+    pub download_count: i64,
+    pub last_downloaded_at: Option<DateTime<Utc>>,
+    pub password_protected: bool,
     pub expiration_date: Option<DateTime<Utc>>,
     pub created_at: Option<DateTime<Utc>>,
+    /// The file's AES key wrapped for the sender
+    pub encrypted_aes_key: Option<Vec<u8>>,
+    pub encrypted_metadata: Option<Vec<u8>>,
+    /// Version of the sender's key pair `encrypted_aes_key` is wrapped to
+    pub key_version: i32,
 }
 
 #[derive(sqlx::FromRow)]
 pub struct ReceiveFileDetails {
+    /// What the recipient retrieves by
+    pub share_id: Uuid,
     pub file_id: Uuid,
     pub file_name: String,
+    pub file_size: i64,
+    pub content_type: String,
     pub sender_email: String,
+    pub sender_name: String,
+    pub download_count: i64,
+    pub last_downloaded_at: Option<DateTime<Utc>>,
+    pub password_protected: bool,
     pub expiration_date: Option<DateTime<Utc>>,
     pub created_at: Option<DateTime<Utc>>,
+    /// The file's AES key wrapped for the recipient
+    pub encrypted_aes_key: Option<Vec<u8>>,
+    pub encrypted_metadata: Option<Vec<u8>>,
+    /// Version of the recipient's key pair `encrypted_aes_key` is wrapped to
+    pub key_version: i32,
+}
+
+/// Everything about a share except the file contents.
+#[derive(sqlx::FromRow)]
+pub struct ShareDetails {
+    pub share_id: Uuid,
+    pub file_id: Uuid,
+    pub file_name: String,
+    pub file_size: i64,
+    pub content_type: String,
+    pub sender_email: String,
+    pub sender_name: String,
+    pub recipient_email: String,
+    pub recipient_name: String,
+    pub download_count: i64,
+    pub last_downloaded_at: Option<DateTime<Utc>>,
+    pub password_protected: bool,
+    pub expiration_date: Option<DateTime<Utc>>,
+    pub created_at: Option<DateTime<Utc>>,
+    pub encrypted_aes_key: Vec<u8>,
+    pub sender_encrypted_aes_key: Option<Vec<u8>>,
+    pub encrypted_metadata: Option<Vec<u8>>,
+    pub recipient_key_version: i32,
+    pub sender_key_version: i32,
+    pub sender_id: Uuid,
+    pub recipient_id: Uuid,
+    pub content_digest: Option<Vec<u8>>,
+    pub signature: Option<Vec<u8>>,
+    pub signing_key_version: Option<i32>,
+    /// Public key of the sender's signing key of `signing_key_version`
+    pub sender_signing_key: Option<String>,
+}
+
+/// Descriptive fields of a file, stored only in `encrypted_metadata`. Shared
+/// with clients that seal it themselves.
+pub use secure_share_crypto::FileMetadata;
+
+#[derive(sqlx::FromRow)]
+pub struct StorageUsage {
+    pub email: String,
+    pub file_count: i64,
+    /// Sum of the original file sizes
+    pub plaintext_bytes: i64,
+    /// Sum of the encrypted file contents as stored
+    pub stored_bytes: i64,
+}
+
+#[derive(sqlx::FromRow)]
+pub struct FileRecipient {
+    pub file_id: Uuid,
+    pub recipient_user_id: Option<Uuid>,
+}
+
+/// A file uploaded before names were encrypted, with what sealing its name
+/// takes.
+#[derive(sqlx::FromRow)]
+pub struct UnsealedFile {
+    pub file_id: Uuid,
+    pub sender_user_id: Option<Uuid>,
+    pub recipient_user_id: Option<Uuid>,
+    pub file_name: String,
+    pub content_type: String,
+    pub encrypted_aes_key: Vec<u8>,
+    pub recipient_key_version: i32,
+    pub sender_encrypted_aes_key: Option<Vec<u8>>,
+}
+
+/// The stored AES keys of a file, as read for re-wrapping under a new KEK.
+#[derive(sqlx::FromRow)]
+pub struct StoredFileKeys {
+    pub file_id: Uuid,
+    pub encrypted_aes_key: Vec<u8>,
+    pub sender_encrypted_aes_key: Option<Vec<u8>>,
 }
@@ -0,0 +1,54 @@
+use std::time::Instant;
+
+use axum::{
+    extract::{MatchedPath, Request},
+    middleware::Next,
+    response::IntoResponse,
+};
+use metrics_exporter_prometheus::{Matcher, PrometheusBuilder, PrometheusHandle};
+
+const DURATION_BUCKETS: [f64; 11] = [
+    0.005, 0.01, 0.025, 0.05, 0.1, 0.25, 0.5, 1.0, 2.5, 5.0, 10.0,
+];
+
+/// Installs the global Prometheus recorder and returns the handle used to render `/metrics`.
+pub fn install_recorder() -> PrometheusHandle {
+    PrometheusBuilder::new()
+        .set_buckets_for_metric(
+            Matcher::Suffix("duration_seconds".to_string()),
+            &DURATION_BUCKETS,
+        )
+        .expect("duration buckets must not be empty")
+        .install_recorder()
+        .expect("failed to install Prometheus recorder")
+}
+
+/// Records request counts and latencies labelled by the matched route template,
+/// so path parameters don't create a series per value.
+pub async fn track_requests(req: Request, next: Next) -> impl IntoResponse {
+    let start = Instant::now();
+    let method = req.method().to_string();
+    let path = match req.extensions().get::<MatchedPath>() {
+        Some(matched) => matched.as_str().to_string(),
+        None => "unmatched".to_string(),
+    };
+
+    let response = next.run(req).await;
+
+    let status = response.status().as_u16().to_string();
+    metrics::counter!(
+        "http_requests_total",
+        "method" => method.clone(),
+        "path" => path.clone(),
+        "status" => status
+    )
+    .increment(1);
+    metrics::histogram!(
+        "http_request_duration_seconds",
+        "method" => method,
+        "path" => path
+    )
+    .record(start.elapsed().as_secs_f64());
+
+    response
+}
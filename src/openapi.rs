@@ -0,0 +1,114 @@
+use utoipa::{
+    Modify, OpenApi,
+    openapi::security::{ApiKey, ApiKeyValue, HttpAuthScheme, HttpBuilder, SecurityScheme},
+};
+
+use crate::{
+    dtos,
+    error::{ErrorResponse, FieldError},
+    handler,
+};
+
+#[derive(OpenApi)]
+#[openapi(
+    info(
+        title = "Secure Share API",
+        description = "File sharing between registered users. The server encrypts each upload to the recipient's key pair and stores only ciphertext."
+    ),
+    paths(
+        handler::auth::register,
+        handler::auth::login,
+        handler::user::get_me,
+        handler::user::update_user_name,
+        handler::user::update_user_password,
+        handler::user::update_user_locale,
+        handler::user::search_by_email,
+        handler::user::get_user_keys,
+        handler::user::rotate_user_key,
+        handler::user::register_signing_key,
+        handler::file::upload_file,
+        handler::file::upload_envelope,
+        handler::file::retrieve_envelope,
+        handler::file::retrieve_file,
+        handler::file::get_share_details,
+        handler::file_query::get_user_shared_files,
+        handler::file_query::get_receive_shared_files,
+        handler::health::liveness,
+        handler::health::readiness,
+        handler::metrics::render_metrics,
+    ),
+    components(schemas(
+        ErrorResponse,
+        FieldError,
+        dtos::Response,
+        dtos::RegisterUserDto,
+        dtos::LoginUserDto,
+        dtos::UserLoginResponseDto,
+        dtos::FilterUserDto,
+        dtos::UserData,
+        dtos::UserResponseDto,
+        dtos::KeyType,
+        dtos::UserKeyDto,
+        dtos::RotateKeyDto,
+        dtos::UserKeyResponseDto,
+        dtos::UserKeyListResponseDto,
+        dtos::SigningKeyDto,
+        dtos::RegisterSigningKeyDto,
+        dtos::SigningKeyResponseDto,
+        dtos::NamedUpdateDto,
+        dtos::UserPasswordUpdateDto,
+        dtos::LocaleUpdateDto,
+        dtos::FilterEmailDto,
+        dtos::EmailListResponseDto,
+        dtos::FileUploadFormDto,
+        dtos::EnvelopeUploadFormDto,
+        dtos::EnvelopeDto,
+        dtos::EnvelopeResponseDto,
+        dtos::RetrieveFileDto,
+        dtos::UserSendFileDto,
+        dtos::UserSendFileListResponseDto,
+        dtos::UserReceiveFileDto,
+        dtos::UserReceiveFileListResponseDto,
+        dtos::SignatureStatus,
+        dtos::ShareDetailsDto,
+        dtos::ShareDetailsResponseDto,
+        dtos::ReadinessChecksDto,
+        dtos::ReadinessResponseDto,
+    )),
+    modifiers(&SecurityAddon),
+    tags(
+        (name = "auth", description = "Registration and login"),
+        (name = "users", description = "Current user profile and key pairs"),
+        (name = "file", description = "Encrypted upload and download"),
+        (name = "list", description = "Sent and received shares"),
+        (name = "operations", description = "Health checks and metrics"),
+    )
+)]
+pub struct ApiDoc;
+
+/// Registers the two ways `middleware::auth` accepts a JWT, and the
+/// token scrapers send for `/metrics`.
+struct SecurityAddon;
+
+impl Modify for SecurityAddon {
+    fn modify(&self, openapi: &mut utoipa::openapi::OpenApi) {
+        let components = openapi.components.get_or_insert_with(Default::default);
+        components.add_security_scheme(
+            "bearer_token",
+            SecurityScheme::Http(
+                HttpBuilder::new()
+                    .scheme(HttpAuthScheme::Bearer)
+                    .bearer_format("JWT")
+                    .build(),
+            ),
+        );
+        components.add_security_scheme(
+            "cookie_token",
+            SecurityScheme::ApiKey(ApiKey::Cookie(ApiKeyValue::new("token"))),
+        );
+        components.add_security_scheme(
+            "metrics_token",
+            SecurityScheme::Http(HttpBuilder::new().scheme(HttpAuthScheme::Bearer).build()),
+        );
+    }
+}
@@ -0,0 +1,230 @@
+use std::{
+    collections::HashMap,
+    fmt,
+    net::SocketAddr,
+    sync::{Arc, Mutex},
+    time::{Duration, Instant},
+};
+
+use async_trait::async_trait;
+use axum::{
+    extract::{ConnectInfo, Request, State},
+    http::{HeaderValue, header},
+    middleware::Next,
+    response::{IntoResponse, Response},
+};
+use sqlx::{Pool, Postgres};
+
+use crate::{
+    error::{ErrorMessage, HttpError},
+    middleware::JwtAuthMiddleware,
+};
+
+/// Buckets idle for longer than this are dropped. Quotas are per minute, so
+/// any bucket idle this long has refilled and dropping it changes nothing.
+const MAX_IDLE: Duration = Duration::from_secs(60 * 60);
+const PRUNE_THRESHOLD: usize = 10_000;
+
+/// Token bucket parameters for one route group.
+#[derive(Debug, Clone, Copy)]
+pub struct Quota {
+    pub burst: u32,
+    pub refill_per_second: f64,
+}
+
+impl Quota {
+    pub fn per_minute(requests: u32) -> Self {
+        Self {
+            burst: requests,
+            refill_per_second: requests as f64 / 60.0,
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Decision {
+    Allowed,
+    Limited { retry_after: Duration },
+}
+
+/// Refills a bucket holding `tokens` for `elapsed` seconds and tries to take one token.
+/// Returns the new token count together with the decision.
+pub fn take_token(tokens: f64, elapsed: f64, quota: &Quota) -> (f64, Decision) {
+    let tokens = (tokens + elapsed.max(0.0) * quota.refill_per_second).min(quota.burst as f64);
+    if tokens >= 1.0 {
+        (tokens - 1.0, Decision::Allowed)
+    } else {
+        let retry_after = Duration::from_secs_f64((1.0 - tokens) / quota.refill_per_second);
+        (tokens, Decision::Limited { retry_after })
+    }
+}
+
+#[async_trait]
+pub trait RateLimitStore: fmt::Debug + Send + Sync {
+    async fn take(&self, key: &str, quota: &Quota) -> Result<Decision, sqlx::Error>;
+
+    /// Drops buckets that have been idle long enough to be full again.
+    /// Returns how many were dropped.
+    async fn prune(&self) -> Result<u64, sqlx::Error>;
+}
+
+#[derive(Debug, Default)]
+pub struct MemoryRateLimitStore {
+    buckets: Mutex<HashMap<String, (f64, Instant)>>,
+}
+
+#[async_trait]
+impl RateLimitStore for MemoryRateLimitStore {
+    async fn take(&self, key: &str, quota: &Quota) -> Result<Decision, sqlx::Error> {
+        let now = Instant::now();
+        let mut buckets = self.buckets.lock().unwrap();
+
+        if buckets.len() > PRUNE_THRESHOLD {
+            buckets.retain(|_, (_, updated_at)| now.duration_since(*updated_at) < MAX_IDLE);
+        }
+
+        let (tokens, updated_at) = buckets
+            .get(key)
+            .copied()
+            .unwrap_or((quota.burst as f64, now));
+        let elapsed = now.duration_since(updated_at).as_secs_f64();
+        let (tokens, decision) = take_token(tokens, elapsed, quota);
+        buckets.insert(key.to_string(), (tokens, now));
+
+        Ok(decision)
+    }
+
+    async fn prune(&self) -> Result<u64, sqlx::Error> {
+        let now = Instant::now();
+        let mut buckets = self.buckets.lock().unwrap();
+        let before = buckets.len();
+        buckets.retain(|_, (_, updated_at)| now.duration_since(*updated_at) < MAX_IDLE);
+        Ok((before - buckets.len()) as u64)
+    }
+}
+
+/// Shares buckets between instances through the `rate_limit_buckets` table.
+#[derive(Debug, Clone)]
+pub struct PgRateLimitStore {
+    pool: Pool<Postgres>,
+}
+
+impl PgRateLimitStore {
+    pub fn new(pool: Pool<Postgres>) -> Self {
+        Self { pool }
+    }
+}
+
+#[async_trait]
+impl RateLimitStore for PgRateLimitStore {
+    async fn take(&self, key: &str, quota: &Quota) -> Result<Decision, sqlx::Error> {
+        // The upsert holds the row lock, so concurrent instances see each
+        // other's withdrawals. A limited request leaves the row as it was,
+        // which refills the same as writing it back, so an `updated_at` of
+        // now tells that a token was taken.
+        let bucket = sqlx::query!(
+            r#"
+            INSERT INTO rate_limit_buckets AS bucket (key, tokens, updated_at)
+            VALUES ($1, $2::DOUBLE PRECISION - 1, NOW())
+            ON CONFLICT (key) DO UPDATE
+            SET
+                tokens = CASE
+                    WHEN LEAST($2, bucket.tokens + EXTRACT(EPOCH FROM NOW() - bucket.updated_at)::DOUBLE PRECISION * $3::DOUBLE PRECISION) >= 1
+                    THEN LEAST($2, bucket.tokens + EXTRACT(EPOCH FROM NOW() - bucket.updated_at)::DOUBLE PRECISION * $3::DOUBLE PRECISION) - 1
+                    ELSE bucket.tokens
+                END,
+                updated_at = CASE
+                    WHEN LEAST($2, bucket.tokens + EXTRACT(EPOCH FROM NOW() - bucket.updated_at)::DOUBLE PRECISION * $3::DOUBLE PRECISION) >= 1
+                    THEN NOW()
+                    ELSE bucket.updated_at
+                END
+            RETURNING
+                tokens,
+                updated_at = NOW() AS "taken!",
+                EXTRACT(EPOCH FROM NOW() - updated_at)::DOUBLE PRECISION AS "elapsed!"
+            "#,
+            key,
+            quota.burst as f64,
+            quota.refill_per_second
+        )
+        .fetch_one(&self.pool)
+        .await?;
+
+        if bucket.taken {
+            return Ok(Decision::Allowed);
+        }
+        let (_, decision) = take_token(bucket.tokens, bucket.elapsed, quota);
+        Ok(decision)
+    }
+
+    async fn prune(&self) -> Result<u64, sqlx::Error> {
+        let result = sqlx::query!(
+            r#"
+            DELETE FROM rate_limit_buckets
+            WHERE updated_at < NOW() - make_interval(secs => $1)
+            "#,
+            MAX_IDLE.as_secs_f64()
+        )
+        .execute(&self.pool)
+        .await?;
+
+        Ok(result.rows_affected())
+    }
+}
+
+/// Rate limit for a single route group, used as the state of the [`limit`] middleware.
+#[derive(Debug, Clone)]
+pub struct RateLimiter {
+    store: Arc<dyn RateLimitStore>,
+    group: &'static str,
+    quota: Quota,
+}
+
+impl RateLimiter {
+    pub fn new(store: Arc<dyn RateLimitStore>, group: &'static str, quota: Quota) -> Self {
+        Self {
+            store,
+            group,
+            quota,
+        }
+    }
+}
+
+/// Keys requests by the authenticated user when the auth middleware ran first,
+/// otherwise by the client IP address.
+fn client_key(req: &Request) -> String {
+    if let Some(auth) = req.extensions().get::<JwtAuthMiddleware>() {
+        return format!("user:{}", auth.user.id);
+    }
+
+    match req.extensions().get::<ConnectInfo<SocketAddr>>() {
+        Some(ConnectInfo(addr)) => format!("ip:{}", addr.ip()),
+        None => "ip:unknown".to_string(),
+    }
+}
+
+pub async fn limit(
+    State(limiter): State<RateLimiter>,
+    req: Request,
+    next: Next,
+) -> Result<Response, HttpError> {
+    let key = format!("{}:{}", limiter.group, client_key(&req));
+    let decision = limiter
+        .store
+        .take(&key, &limiter.quota)
+        .await
+        .map_err(|err| HttpError::server_error(err.to_string()))?;
+
+    match decision {
+        Decision::Allowed => Ok(next.run(req).await),
+        Decision::Limited { retry_after } => {
+            let seconds = retry_after.as_secs_f64().ceil().max(1.0) as u64;
+            let mut response =
+                HttpError::too_many_requests(ErrorMessage::TooManyRequests).into_response();
+            response
+                .headers_mut()
+                .insert(header::RETRY_AFTER, HeaderValue::from(seconds));
+            Ok(response)
+        }
+    }
+}
@@ -1,32 +1,91 @@
 use crate::{
     AppState,
     handler::{
-        auth::auth_handler, file::file_handle, file_query::get_file_list_handler,
+        auth::auth_handler, docs::docs_handler, file::file_handle,
+        file_query::get_file_list_handler, health::health_handler, metrics::metrics_handler,
         user::users_handler,
     },
-    middleware,
+    i18n, middleware, monitoring,
+    rate_limit::{self, Quota, RateLimiter},
+    telemetry,
 };
-use axum::{Extension, Router};
+use axum::{Extension, Router, extract::DefaultBodyLimit};
 use std::sync::Arc;
-use tower_http::trace::TraceLayer;
+use tower_http::{
+    request_id::{MakeRequestUuid, PropagateRequestIdLayer, SetRequestIdLayer},
+    trace::{DefaultOnResponse, TraceLayer},
+};
+use tracing::Level;
 
 pub fn create_router(app_state: Arc<AppState>) -> Router {
+    let store = app_state.rate_limit_store.clone();
+    let limits = &app_state.env.rate_limit;
+    // Unauthenticated routes are keyed by client IP, the rest by user
+    let auth_limit = RateLimiter::new(
+        store.clone(),
+        "auth",
+        Quota::per_minute(limits.auth_per_minute),
+    );
+    let file_limit = RateLimiter::new(
+        store.clone(),
+        "file",
+        Quota::per_minute(limits.file_per_minute),
+    );
+    let key_rotation_limit = RateLimiter::new(
+        store,
+        "key_rotation",
+        Quota::per_minute(limits.key_rotation_per_minute),
+    );
+    let max_upload_bytes = app_state.env.limits.max_upload_bytes;
+
     let api_router = Router::new()
-        .nest("/auth", auth_handler())
+        .merge(docs_handler())
+        .nest(
+            "/auth",
+            auth_handler().layer(axum::middleware::from_fn_with_state(
+                auth_limit,
+                rate_limit::limit,
+            )),
+        )
         .nest(
             "/users",
-            users_handler().layer(axum::middleware::from_fn(middleware::auth)),
+            users_handler(key_rotation_limit).layer(axum::middleware::from_fn(middleware::auth)),
         )
         .nest(
             "/file",
-            file_handle().layer(axum::middleware::from_fn(middleware::auth)),
+            file_handle()
+                .layer(DefaultBodyLimit::max(max_upload_bytes))
+                .layer(axum::middleware::from_fn_with_state(
+                    file_limit,
+                    rate_limit::limit,
+                ))
+                .layer(axum::middleware::from_fn(middleware::auth)),
         )
         .nest(
             "/list",
             get_file_list_handler().layer(axum::middleware::from_fn(middleware::auth)),
-        )
-        .layer(TraceLayer::new_for_http())
-        .layer(Extension(app_state));
+        );
+
+    let mut router = Router::new()
+        .nest("/api", api_router)
+        .merge(health_handler());
+    // Metrics reveal traffic and user counts, so they are opt-in
+    if app_state.env.metrics.token.is_some() {
+        router = router.merge(metrics_handler());
+    }
 
-    Router::new().nest("/api", api_router)
+    router
+        .layer(axum::middleware::from_fn(i18n::negotiate))
+        .layer(axum::middleware::from_fn(monitoring::track_requests))
+        .layer(
+            TraceLayer::new_for_http()
+                .make_span_with(telemetry::make_request_span)
+                .on_response(DefaultOnResponse::new().level(Level::INFO)),
+        )
+        .layer(PropagateRequestIdLayer::new(telemetry::REQUEST_ID_HEADER))
+        .layer(SetRequestIdLayer::new(
+            telemetry::REQUEST_ID_HEADER,
+            MakeRequestUuid,
+        ))
+        .layer(Extension(app_state))
 }
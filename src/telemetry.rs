@@ -0,0 +1,88 @@
+use axum::{
+    extract::{MatchedPath, Request},
+    http::HeaderName,
+};
+use opentelemetry::trace::TracerProvider;
+use opentelemetry_otlp::{SpanExporter, WithExportConfig};
+use opentelemetry_sdk::{Resource, trace::SdkTracerProvider};
+use tracing::Span;
+use tracing_subscriber::{EnvFilter, Layer, layer::SubscriberExt, util::SubscriberInitExt};
+
+use crate::config::{LogFormat, LoggingConfig};
+
+pub const REQUEST_ID_HEADER: HeaderName = HeaderName::from_static("x-request-id");
+const SERVICE_NAME: &str = "secure-share";
+
+/// Keeps the OTLP exporter alive; spans still buffered are flushed by [`TelemetryGuard::shutdown`].
+pub struct TelemetryGuard {
+    tracer_provider: Option<SdkTracerProvider>,
+}
+
+impl TelemetryGuard {
+    pub fn shutdown(self) {
+        if let Some(provider) = self.tracer_provider
+            && let Err(err) = provider.shutdown()
+        {
+            eprintln!("Failed to flush OpenTelemetry spans: {}", err);
+        }
+    }
+}
+
+/// Installs the global subscriber. `RUST_LOG` takes precedence over `logging.level`.
+pub fn init(config: &LoggingConfig) -> TelemetryGuard {
+    let filter =
+        EnvFilter::try_from_default_env().unwrap_or_else(|_| EnvFilter::new(config.level.as_str()));
+
+    let fmt_layer = match config.format {
+        LogFormat::Json => tracing_subscriber::fmt::layer()
+            .json()
+            .with_current_span(true)
+            .with_span_list(false)
+            .boxed(),
+        LogFormat::Pretty => tracing_subscriber::fmt::layer().boxed(),
+    };
+
+    let tracer_provider = config.otlp_endpoint.as_deref().map(|endpoint| {
+        let exporter = SpanExporter::builder()
+            .with_http()
+            .with_endpoint(endpoint)
+            .build()
+            .expect("failed to build OTLP span exporter");
+        SdkTracerProvider::builder()
+            .with_batch_exporter(exporter)
+            .with_resource(Resource::builder().with_service_name(SERVICE_NAME).build())
+            .build()
+    });
+    let otel_layer = tracer_provider
+        .as_ref()
+        .map(|provider| tracing_opentelemetry::layer().with_tracer(provider.tracer(SERVICE_NAME)));
+
+    tracing_subscriber::registry()
+        .with(fmt_layer)
+        .with(otel_layer)
+        .with(filter)
+        .init();
+
+    TelemetryGuard { tracer_provider }
+}
+
+/// Root span for every HTTP request, tagged with the id set by `SetRequestIdLayer`.
+pub fn make_request_span(req: &Request) -> Span {
+    let request_id = req
+        .headers()
+        .get(REQUEST_ID_HEADER)
+        .and_then(|value| value.to_str().ok())
+        .unwrap_or_default();
+    let path = req
+        .extensions()
+        .get::<MatchedPath>()
+        .map(|matched| matched.as_str())
+        .unwrap_or_else(|| req.uri().path());
+
+    tracing::info_span!(
+        "http_request",
+        method = %req.method(),
+        path,
+        request_id,
+    )
+}
@@ -1,26 +1,54 @@
-use aes::Aes256;
-use cbc::cipher::{BlockDecryptMut, KeyIvInit, block_padding::Pkcs7};
-use rsa::{Pkcs1v15Encrypt, RsaPrivateKey};
+use std::time::Instant;
 
-use crate::error::HttpError;
+use secure_share_crypto::{FileKey, PrivateKey};
 
+use crate::{error::HttpError, models::FileMetadata};
+
+#[tracing::instrument(skip_all, fields(bytes = encrypted_file.len()))]
 pub async fn decrypt_file(
     encrypted_aes_key: Vec<u8>,
     encrypted_file: Vec<u8>,
     iv: Vec<u8>,
-    user_private_key: &RsaPrivateKey,
+    user_private_key: &PrivateKey,
 ) -> Result<Vec<u8>, HttpError> {
-    let aes_key = user_private_key
-        .decrypt(Pkcs1v15Encrypt, &encrypted_aes_key)
-        .map_err(|err| HttpError::server_error(err.to_string()))?;
-    let iv = iv;
-    let cipher = cbc::Decryptor::<Aes256>::new_from_slices(&aes_key, &iv)
-        .map_err(|err| HttpError::server_error(err.to_string()))?;
-    let mut buffer = encrypted_file.clone();
+    let start = Instant::now();
+    let result = decrypt(encrypted_aes_key, encrypted_file, iv, user_private_key);
+    metrics::histogram!("crypto_duration_seconds", "operation" => "decrypt")
+        .record(start.elapsed().as_secs_f64());
+    if result.is_err() {
+        metrics::counter!("crypto_failures_total", "operation" => "decrypt").increment(1);
+    }
+
+    result
+}
+
+fn decrypt(
+    encrypted_aes_key: Vec<u8>,
+    encrypted_file: Vec<u8>,
+    iv: Vec<u8>,
+    user_private_key: &PrivateKey,
+) -> Result<Vec<u8>, HttpError> {
+    secure_share_crypto::decrypt(&encrypted_aes_key, &encrypted_file, &iv, user_private_key)
+        .map_err(|err| HttpError::server_error(err.to_string()))
+}
 
-    let decrypted_data = cipher
-        .decrypt_padded_mut::<Pkcs7>(&mut buffer)
-        .map_err(|err| HttpError::server_error(err.to_string()))?;
+/// Opens the `encrypted_metadata` of a file with the AES key as wrapped for
+/// the holder of `user_private_key`.
+pub fn decrypt_metadata(
+    encrypted_aes_key: &[u8],
+    encrypted_metadata: &[u8],
+    user_private_key: &PrivateKey,
+) -> Result<FileMetadata, HttpError> {
+    let start = Instant::now();
+    let result = FileKey::unwrap_with(encrypted_aes_key, user_private_key)
+        .and_then(|key| key.open(encrypted_metadata))
+        .and_then(|metadata| FileMetadata::from_json(&metadata))
+        .map_err(|err| HttpError::server_error(err.to_string()));
+    metrics::histogram!("crypto_duration_seconds", "operation" => "decrypt_metadata")
+        .record(start.elapsed().as_secs_f64());
+    if result.is_err() {
+        metrics::counter!("crypto_failures_total", "operation" => "decrypt_metadata").increment(1);
+    }
 
-    Ok(decrypted_data.to_vec())
+    result
 }
@@ -1,27 +1,62 @@
-use crate::error::HttpError;
-use aes::Aes256;
-use cbc::cipher::{BlockEncryptMut, KeyIvInit, block_padding::Pkcs7};
-use rand::Rng;
-use rsa::{Pkcs1v15Encrypt, RsaPublicKey};
+use std::time::Instant;
 
+use crate::{error::HttpError, models::FileMetadata};
+use secure_share_crypto::{FileKey, PublicKey};
+
+/// What is stored for an uploaded file.
+pub struct EncryptedFile {
+    /// AES key encrypted for the recipient
+    pub encrypted_aes_key: Vec<u8>,
+    /// The same key encrypted for the sender, when they have a key pair
+    pub sender_encrypted_aes_key: Option<Vec<u8>>,
+    pub encrypted_file: Vec<u8>,
+    pub iv: Vec<u8>,
+    /// The [`FileMetadata`] as JSON, sealed with the AES key
+    pub encrypted_metadata: Vec<u8>,
+}
+
+#[tracing::instrument(skip_all, fields(bytes = file_data.len()))]
 pub async fn encrypt_file(
     file_data: Vec<u8>,
-    user_public_key: &RsaPublicKey,
-) -> Result<(Vec<u8>, Vec<u8>, Vec<u8>), HttpError> {
-    let mut aes_key = [0u8; 32];
-    let mut iv = [0u8; 16];
-    rand::thread_rng().fill(&mut aes_key);
-    rand::thread_rng().fill(&mut iv);
+    metadata: &FileMetadata,
+    user_public_key: &PublicKey,
+    sender_public_key: Option<&PublicKey>,
+) -> Result<EncryptedFile, HttpError> {
+    let start = Instant::now();
+    let result = encrypt(file_data, metadata, user_public_key, sender_public_key);
+    metrics::histogram!("crypto_duration_seconds", "operation" => "encrypt")
+        .record(start.elapsed().as_secs_f64());
+    if result.is_err() {
+        metrics::counter!("crypto_failures_total", "operation" => "encrypt").increment(1);
+    }
+
+    result
+}
 
-    let cipher = cbc::Encryptor::<Aes256>::new(&aes_key.into(), &iv.into());
-    let mut buffer = file_data.clone();
-    let encrypted_data = cipher
-        .encrypt_padded_mut::<Pkcs7>(&mut buffer, file_data.len())
-        .map_err(|e| HttpError::server_error(e.to_string()))?
-        .to_vec();
-    let encrypted_aes_key = user_public_key
-        .encrypt(&mut rand::thread_rng(), Pkcs1v15Encrypt, &aes_key)
+fn encrypt(
+    file_data: Vec<u8>,
+    metadata: &FileMetadata,
+    user_public_key: &PublicKey,
+    sender_public_key: Option<&PublicKey>,
+) -> Result<EncryptedFile, HttpError> {
+    let mut rng = rand::thread_rng();
+    let key = FileKey::generate(&mut rng);
+    let envelope = key
+        .envelope(&mut rng, &file_data, user_public_key)
+        .map_err(|err| HttpError::server_error(err.to_string()))?;
+    let sender_encrypted_aes_key = sender_public_key
+        .map(|public_key| key.wrap_for(&mut rng, public_key))
+        .transpose()
+        .map_err(|err| HttpError::server_error(err.to_string()))?;
+    let metadata = metadata
+        .to_json()
         .map_err(|err| HttpError::server_error(err.to_string()))?;
 
-    Ok((encrypted_aes_key, encrypted_data, iv.to_vec()))
+    Ok(EncryptedFile {
+        encrypted_aes_key: envelope.encrypted_key,
+        sender_encrypted_aes_key,
+        encrypted_file: envelope.ciphertext,
+        iv: envelope.iv,
+        encrypted_metadata: key.seal(&mut rng, &metadata),
+    })
 }
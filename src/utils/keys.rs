@@ -1,52 +1,435 @@
 use std::{
-    fs::{self, File},
-    io::Write,
+    fs,
+    io::{self, Write},
+    path::{Path, PathBuf},
     sync::Arc,
+    time::Duration,
 };
 
-use axum::{http::StatusCode, response::IntoResponse};
+use axum::http::StatusCode;
 use base64::{Engine, prelude::BASE64_STANDARD};
 use rand::rngs::OsRng;
-use rsa::{
-    RsaPrivateKey, RsaPublicKey,
-    pkcs1::{EncodeRsaPrivateKey, EncodeRsaPublicKey},
+use secure_share_crypto::{
+    PrivateKey, PublicKey, SigningKey, VerifyingKey, signing_key_from_pem, signing_key_to_pem,
+    verifying_key_to_pem,
 };
 use uuid::Uuid;
 
-use crate::{AppState, db::UserExt, error::HttpError, models::User};
+use crate::{
+    AppState,
+    dtos::KeyType,
+    error::{ErrorMessage, HttpError},
+    kek,
+    models::{User, UserKey, UserSigningKey},
+};
+
+const WRAPPED_BEGIN: &str = "-----BEGIN SECURE-SHARE WRAPPED KEY-----";
+const WRAPPED_END: &str = "-----END SECURE-SHARE WRAPPED KEY-----";
+/// A private key file without a public key for longer than this was left by
+/// a rotation that never finished, which takes well under a second.
+const ABANDONED_KEY_FILE_AGE: Duration = Duration::from_secs(60);
 
+/// Generates a key pair of `key_type` for `user` and makes it their active
+/// one. An earlier pair is retired rather than replaced, so files wrapped to
+/// it stay readable.
+#[tracing::instrument(skip_all, fields(user_id = %user.id, key_type = key_type.as_str()))]
 pub async fn generete_key(
     app_state: Arc<AppState>,
     user: User,
-) -> Result<impl IntoResponse, HttpError> {
-    let mut rng = OsRng;
-    let private_key = RsaPrivateKey::new(&mut rng, 2048)
-        .map_err(|err| HttpError::server_error(err.to_string()))?;
-    let public_key = RsaPublicKey::from(private_key.clone());
+    key_type: KeyType,
+) -> Result<UserKey, HttpError> {
+    // RSA key generation takes long enough to stall other requests
+    let private_key = tokio::task::spawn_blocking(move || match key_type {
+        KeyType::Rsa => PrivateKey::generate_rsa(&mut OsRng),
+        KeyType::X25519 => Ok(PrivateKey::generate_x25519(&mut OsRng)),
+    })
+    .await
+    .map_err(|err| HttpError::server_error(err.to_string()))?
+    .map_err(|err| HttpError::server_error(err.to_string()))?;
     let private_key_pem = private_key
-        .to_pkcs1_pem(rsa::pkcs1::LineEnding::LF)
+        .to_pem()
         .map_err(|err| HttpError::server_error(err.to_string()))?;
-    let public_key_prm = public_key
-        .to_pkcs1_pem(rsa::pkcs1::LineEnding::LF)
+    let public_key_pem = private_key
+        .public_key()
+        .to_pem()
         .map_err(|err| HttpError::server_error(err.to_string()))?;
-    let public_key_b64 = BASE64_STANDARD.encode(public_key_prm.as_bytes());
+    let public_key_b64 = BASE64_STANDARD.encode(public_key_pem.as_bytes());
     let user_id = Uuid::parse_str(&user.id.to_string()).unwrap();
 
+    let version = app_state
+        .users
+        .get_user_keys(user_id)
+        .await
+        .map_err(|err| HttpError::server_error(err.to_string()))?
+        .last()
+        .map_or(1, |key| key.version + 1);
+
+    // The private key goes first, so an active public key always has one
+    let path = private_key_path(&app_state.env.storage.private_key_dir, user_id, version);
+    let public_key_b64 = if create_key_file(&app_state, &path, &private_key_pem).await? {
+        public_key_b64
+    } else {
+        claim_key_file(&app_state, &path, key_type, &private_key_pem)
+            .await?
+            .unwrap_or(public_key_b64)
+    };
+    // The file stays when saving fails; the next rotation claims it
     app_state
-        .db_client
-        .save_user_key(user_id.clone(), public_key_b64.clone())
+        .users
+        .save_user_key(
+            user_id,
+            version,
+            key_type.as_str().to_string(),
+            public_key_b64,
+        )
         .await
+        .map_err(|err| match err.as_database_error() {
+            // A concurrent rotation saved this version first
+            Some(db_err) if db_err.is_unique_violation() => {
+                HttpError::new(ErrorMessage::KeyRotationInProgress, StatusCode::CONFLICT)
+            }
+            _ => HttpError::server_error(err.to_string()),
+        })
+}
+
+/// Deals with a private key file for a version that has no public key yet,
+/// left by a rotation that stopped before saving it or written by one still
+/// running. A key of `key_type` is taken over and its public key returned;
+/// one of another type is replaced by `private_key_pem` once abandoned.
+async fn claim_key_file(
+    app_state: &AppState,
+    path: &Path,
+    key_type: KeyType,
+    private_key_pem: &str,
+) -> Result<Option<String>, HttpError> {
+    let pem = read_key_file_pem(app_state, path).await?;
+    let existing =
+        PrivateKey::from_pem(&pem).map_err(|err| HttpError::server_error(err.to_string()))?;
+    let existing_type = match existing {
+        PrivateKey::Rsa(_) => KeyType::Rsa,
+        PrivateKey::X25519(_) => KeyType::X25519,
+    };
+    if existing_type == key_type {
+        let public_key_pem = existing
+            .public_key()
+            .to_pem()
+            .map_err(|err| HttpError::server_error(err.to_string()))?;
+        return Ok(Some(BASE64_STANDARD.encode(public_key_pem.as_bytes())));
+    }
+
+    let modified = fs::metadata(path)
+        .and_then(|metadata| metadata.modified())
         .map_err(|err| HttpError::server_error(err.to_string()))?;
+    let abandoned = modified
+        .elapsed()
+        .is_ok_and(|age| age > ABANDONED_KEY_FILE_AGE);
+    if !abandoned {
+        return Err(HttpError::new(
+            ErrorMessage::KeyRotationInProgress,
+            StatusCode::CONFLICT,
+        ));
+    }
+    tracing::warn!(path = %path.display(), "Replacing an abandoned private key file");
+    write_key_file(app_state, path, private_key_pem).await?;
+    Ok(None)
+}
 
-    let private_keys_dir = "assets/private_keys";
-    fs::create_dir_all(private_keys_dir).map_err(|err| HttpError::server_error(err.to_string()))?;
+/// The key the server signs `user`'s uploads with and its version, `None`
+/// once the user registered a key of their own. Accounts created before
+/// uploads were signed get one on first use.
+#[tracing::instrument(skip_all, fields(user_id = %user.id))]
+pub async fn signing_key(
+    app_state: &AppState,
+    user: &User,
+) -> Result<Option<(i32, SigningKey)>, HttpError> {
+    let path = signing_key_path(&app_state.env.storage.private_key_dir, user.id);
+    let signing_keys = app_state
+        .users
+        .get_signing_keys(user.id)
+        .await
+        .map_err(|err| HttpError::server_error(err.to_string()))?;
+    if let Some(active) = signing_keys.iter().find(|key| key.status == "active") {
+        if active.held_by != "server" {
+            return Ok(None);
+        }
+        let pem = read_key_file_pem(app_state, &path).await?;
+        let signing_key =
+            signing_key_from_pem(&pem).map_err(|err| HttpError::server_error(err.to_string()))?;
+        return Ok(Some((active.version, signing_key)));
+    }
 
-    let pem_file_path = format!("{}/{}.pem", private_keys_dir, user_id.clone());
-    let mut file =
-        File::create(&pem_file_path).map_err(|err| HttpError::server_error(err.to_string()))?;
+    // Keys are only ever retired for a newer one, so this is the first
+    let version = 1;
+    let signing_key = SigningKey::generate(&mut OsRng);
+    let pem =
+        signing_key_to_pem(&signing_key).map_err(|err| HttpError::server_error(err.to_string()))?;
+    // A concurrent upload may have created one first; both then publish it
+    let signing_key = if create_key_file(app_state, &path, &pem).await? {
+        signing_key
+    } else {
+        let pem = read_key_file_pem(app_state, &path).await?;
+        signing_key_from_pem(&pem).map_err(|err| HttpError::server_error(err.to_string()))?
+    };
+    let verifying_key_pem = verifying_key_to_pem(&signing_key.verifying_key())
+        .map_err(|err| HttpError::server_error(err.to_string()))?;
+    let public_key = BASE64_STANDARD.encode(verifying_key_pem);
+    let saved = app_state
+        .users
+        .save_signing_key(user.id, version, "server".to_string(), public_key.clone())
+        .await;
+    match saved {
+        Ok(_) => Ok(Some((version, signing_key))),
+        // A concurrent upload saved first, which must have read the same file
+        Err(err)
+            if err
+                .as_database_error()
+                .is_some_and(|err| err.is_unique_violation()) =>
+        {
+            let stored = app_state
+                .users
+                .get_signing_keys(user.id)
+                .await
+                .map_err(|err| HttpError::server_error(err.to_string()))?
+                .into_iter()
+                .find(|key| key.version == version);
+            match stored {
+                Some(stored) if stored.public_key == public_key => Ok(Some((version, signing_key))),
+                _ => Err(HttpError::server_error(
+                    "signing key file does not match the stored signing key",
+                )),
+            }
+        }
+        Err(err) => Err(HttpError::server_error(err.to_string())),
+    }
+}
 
-    file.write_all(private_key_pem.as_bytes())
+/// Makes `verifying_key`, whose private half only the user holds, the key
+/// their uploads are signed with. The server deletes its own signing key for
+/// them, so it can no longer sign in their name.
+#[tracing::instrument(skip_all, fields(user_id = %user.id))]
+pub async fn register_signing_key(
+    app_state: &AppState,
+    user: &User,
+    verifying_key: &VerifyingKey,
+) -> Result<UserSigningKey, HttpError> {
+    let verifying_key_pem = verifying_key_to_pem(verifying_key)
         .map_err(|err| HttpError::server_error(err.to_string()))?;
+    let version = app_state
+        .users
+        .get_signing_keys(user.id)
+        .await
+        .map_err(|err| HttpError::server_error(err.to_string()))?
+        .last()
+        .map_or(1, |key| key.version + 1);
+    let key = app_state
+        .users
+        .save_signing_key(
+            user.id,
+            version,
+            "client".to_string(),
+            BASE64_STANDARD.encode(verifying_key_pem),
+        )
+        .await
+        .map_err(|err| match err.as_database_error() {
+            Some(db_err) if db_err.is_unique_violation() => {
+                HttpError::new(ErrorMessage::KeyRotationInProgress, StatusCode::CONFLICT)
+            }
+            _ => HttpError::server_error(err.to_string()),
+        })?;
+
+    let path = signing_key_path(&app_state.env.storage.private_key_dir, user.id);
+    match fs::remove_file(&path) {
+        Ok(()) => Ok(key),
+        Err(err) if err.kind() == io::ErrorKind::NotFound => Ok(key),
+        Err(err) => Err(HttpError::server_error(err.to_string())),
+    }
+}
+
+/// Path of the private key file of version `version` of `user_id`'s key
+/// pair. Version 1 keeps the name from before key pairs were versioned.
+pub fn private_key_path(key_dir: &str, user_id: Uuid, version: i32) -> PathBuf {
+    let mut path = PathBuf::from(key_dir);
+    if version == 1 {
+        path.push(format!("{}.pem", user_id));
+    } else {
+        path.push(format!("{}.v{}.pem", user_id, version));
+    }
+    path
+}
+
+/// Path of the file holding `user_id`'s signing key.
+pub fn signing_key_path(key_dir: &str, user_id: Uuid) -> PathBuf {
+    PathBuf::from(key_dir).join(format!("{}.signing.pem", user_id))
+}
+
+/// Owner of a file named by [`signing_key_path`].
+pub fn parse_signing_key_file_name(name: &str) -> Option<Uuid> {
+    Uuid::parse_str(name.strip_suffix(".signing.pem")?).ok()
+}
+
+/// Owner and version of a file named by [`private_key_path`].
+pub fn parse_private_key_file_name(name: &str) -> Option<(Uuid, i32)> {
+    let stem = name.strip_suffix(".pem")?;
+    match stem.split_once(".v") {
+        Some((user_id, version)) => {
+            let version = version.parse().ok().filter(|version| *version > 1)?;
+            Some((Uuid::parse_str(user_id).ok()?, version))
+        }
+        None => Some((Uuid::parse_str(stem).ok()?, 1)),
+    }
+}
+
+/// Writes a PEM private key to `path`, wrapped with the KEK when one is
+/// configured. An existing key is replaced atomically.
+pub async fn write_key_file(app_state: &AppState, path: &Path, pem: &str) -> Result<(), HttpError> {
+    let temp_path = stage_key_file(app_state, path, pem).await?;
+    fs::rename(&temp_path, path).map_err(|err| {
+        let _ = fs::remove_file(&temp_path);
+        HttpError::server_error(err.to_string())
+    })
+}
+
+/// Like [`write_key_file`], but leaves an existing key alone and returns
+/// `false`.
+async fn create_key_file(app_state: &AppState, path: &Path, pem: &str) -> Result<bool, HttpError> {
+    let temp_path = stage_key_file(app_state, path, pem).await?;
+    let linked = fs::hard_link(&temp_path, path);
+    let _ = fs::remove_file(&temp_path);
+    match linked {
+        Ok(()) => Ok(true),
+        Err(err) if err.kind() == io::ErrorKind::AlreadyExists => Ok(false),
+        Err(err) => Err(HttpError::server_error(err.to_string())),
+    }
+}
+
+/// Writes the private key next to `path` under a name of its own, so
+/// concurrent writers of the same key never share a staging file, returning
+/// where.
+async fn stage_key_file(
+    app_state: &AppState,
+    path: &Path,
+    pem: &str,
+) -> Result<PathBuf, HttpError> {
+    let contents = match &app_state.kek {
+        Some(kek) => {
+            let wrapped = kek
+                .wrap(pem.as_bytes())
+                .await
+                .map_err(|err| HttpError::server_error(err.to_string()))?;
+            armor(&wrapped.to_bytes())
+        }
+        None => pem.to_string(),
+    };
+
+    if let Some(dir) = path.parent() {
+        fs::create_dir_all(dir).map_err(|err| HttpError::server_error(err.to_string()))?;
+    }
+    let file_name = path.file_name().unwrap_or_default().to_string_lossy();
+    let temp_path = path.with_file_name(format!("{}.{}.tmp", file_name, Uuid::new_v4()));
+    if let Err(err) = write_private_file(&temp_path, contents.as_bytes()) {
+        let _ = fs::remove_file(&temp_path);
+        return Err(HttpError::server_error(err.to_string()));
+    }
+    Ok(temp_path)
+}
+
+/// Creates `path` readable by the owner only before anything is written to
+/// it.
+fn write_private_file(path: &Path, contents: &[u8]) -> io::Result<()> {
+    let mut options = fs::OpenOptions::new();
+    options.write(true).create_new(true);
+    #[cfg(unix)]
+    {
+        use std::os::unix::fs::OpenOptionsExt;
+        options.mode(0o600);
+    }
+    options.open(path)?.write_all(contents)
+}
+
+/// Decodes a public key as stored for a user: PEM, base64 encoded.
+pub fn decode_public_key(public_key: &str) -> Result<PublicKey, HttpError> {
+    let public_key_bytes = BASE64_STANDARD
+        .decode(public_key)
+        .map_err(|err| HttpError::server_error(err.to_string()))?;
+    let public_key = String::from_utf8(public_key_bytes)
+        .map_err(|err| HttpError::server_error(err.to_string()))?;
+    PublicKey::from_pem(&public_key).map_err(|err| HttpError::server_error(err.to_string()))
+}
+
+/// Reads a PEM private key from `path`, unwrapping it when needed.
+pub async fn read_key_file_pem(app_state: &AppState, path: &Path) -> Result<String, HttpError> {
+    let contents =
+        fs::read_to_string(path).map_err(|err| HttpError::server_error(err.to_string()))?;
+    let Some(stored) = dearmor(&contents)? else {
+        return Ok(contents);
+    };
+
+    let pem = unwrap_stored(app_state, &stored).await?;
+    String::from_utf8(pem).map_err(|err| HttpError::server_error(err.to_string()))
+}
+
+/// Reads the private key of version `version` of `user_id`'s key pair.
+pub async fn read_private_key(
+    app_state: &AppState,
+    user_id: Uuid,
+    version: i32,
+) -> Result<PrivateKey, HttpError> {
+    let path = private_key_path(&app_state.env.storage.private_key_dir, user_id, version);
+    let pem = read_key_file_pem(app_state, &path).await?;
+    PrivateKey::from_pem(&pem).map_err(|err| HttpError::server_error(err.to_string()))
+}
+
+/// Id of the KEK the private key file at `path` is wrapped with; `None` for
+/// a plain PEM.
+pub fn key_file_kek_id(path: &Path) -> Result<Option<String>, HttpError> {
+    let contents =
+        fs::read_to_string(path).map_err(|err| HttpError::server_error(err.to_string()))?;
+    match dearmor(&contents)? {
+        Some(stored) => {
+            kek::stored_key_id(&stored).map_err(|err| HttpError::server_error(err.to_string()))
+        }
+        None => Ok(None),
+    }
+}
+
+/// Wraps a key for the database with the KEK, if one is configured.
+pub async fn wrap_stored(app_state: &AppState, key: &[u8]) -> Result<Vec<u8>, HttpError> {
+    kek::wrap_stored(app_state.kek.as_deref(), key)
+        .await
+        .map_err(|err| HttpError::server_error(err.to_string()))
+}
+
+/// Reverses [`wrap_stored`], passing keys stored without a KEK through.
+pub async fn unwrap_stored(app_state: &AppState, stored: &[u8]) -> Result<Vec<u8>, HttpError> {
+    kek::unwrap_stored(app_state.kek.as_deref(), stored)
+        .await
+        .map_err(|err| HttpError::server_error(err.to_string()))
+}
+
+fn armor(wrapped: &[u8]) -> String {
+    let encoded = BASE64_STANDARD.encode(wrapped);
+    let mut armored = format!("{}\n", WRAPPED_BEGIN);
+    for line in encoded.as_bytes().chunks(64) {
+        armored.push_str(std::str::from_utf8(line).unwrap());
+        armored.push('\n');
+    }
+    armored.push_str(WRAPPED_END);
+    armored.push('\n');
+    armored
+}
 
-    Ok((StatusCode::OK, "true"))
+/// The wrapped key inside a file written by [`armor`]; `None` for a plain PEM.
+fn dearmor(contents: &str) -> Result<Option<Vec<u8>>, HttpError> {
+    let Some(body) = contents.trim().strip_prefix(WRAPPED_BEGIN) else {
+        return Ok(None);
+    };
+    let body = body
+        .trim_end()
+        .strip_suffix(WRAPPED_END)
+        .ok_or_else(|| HttpError::server_error("truncated wrapped private key"))?;
+    let encoded: String = body.split_whitespace().collect();
+    BASE64_STANDARD
+        .decode(encoded)
+        .map(Some)
+        .map_err(|err| HttpError::server_error(err.to_string()))
 }
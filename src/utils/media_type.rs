@@ -0,0 +1,134 @@
+//! What a file is and how to hand it back: content types are sniffed once at
+//! upload and stored with the file, and downloads describe the file name in a
+//! `Content-Disposition` that survives any bytes the sender chose.
+
+use mime::Mime;
+use percent_encoding::{AsciiSet, NON_ALPHANUMERIC, utf8_percent_encode};
+
+pub const OCTET_STREAM: &str = "application/octet-stream";
+
+/// Shown by browsers without running anything, so they may open inline.
+/// SVG is missing on purpose, as it can carry scripts.
+const INLINE_TYPES: &[&str] = &[
+    "application/pdf",
+    "image/gif",
+    "image/jpeg",
+    "image/png",
+    "image/webp",
+    "text/plain",
+];
+
+/// `attr-char` of RFC 5987; everything else is percent-encoded.
+const ATTR_CHAR: &AsciiSet = &NON_ALPHANUMERIC
+    .remove(b'!')
+    .remove(b'#')
+    .remove(b'$')
+    .remove(b'&')
+    .remove(b'+')
+    .remove(b'-')
+    .remove(b'.')
+    .remove(b'^')
+    .remove(b'_')
+    .remove(b'`')
+    .remove(b'|')
+    .remove(b'~');
+
+/// Signatures at the start of common formats.
+const SIGNATURES: &[(&[u8], &str)] = &[
+    (b"%PDF-", "application/pdf"),
+    (b"\x89PNG\r\n\x1a\n", "image/png"),
+    (b"\xff\xd8\xff", "image/jpeg"),
+    (b"GIF87a", "image/gif"),
+    (b"GIF89a", "image/gif"),
+    (b"\x1f\x8b", "application/gzip"),
+    (b"PK\x03\x04", "application/zip"),
+    (b"7z\xbc\xaf\x27\x1c", "application/x-7z-compressed"),
+    (b"OggS", "application/ogg"),
+    (b"ID3", "audio/mpeg"),
+];
+
+/// Content type of an upload: the file's signature when it has a known one,
+/// otherwise the type the uploader declared, otherwise a guess from the
+/// extension. Always a bare `type/subtype`.
+pub fn detect(data: &[u8], file_name: &str, declared: &str) -> String {
+    let guessed = mime_guess::from_path(file_name).first();
+
+    if let Some(sniffed) = sniff(data) {
+        // Office documents, EPUBs and JARs are all ZIP archives
+        if sniffed == "application/zip"
+            && let Some(guessed) = &guessed
+            && guessed.type_() == mime::APPLICATION
+            && guessed.essence_str() != OCTET_STREAM
+        {
+            return guessed.essence_str().to_string();
+        }
+        return sniffed.to_string();
+    }
+
+    let declared = declared
+        .parse::<Mime>()
+        .ok()
+        .filter(|declared| declared.essence_str() != OCTET_STREAM);
+    declared
+        .or(guessed)
+        .map(|mime| mime.essence_str().to_ascii_lowercase())
+        .unwrap_or_else(|| OCTET_STREAM.to_string())
+}
+
+fn sniff(data: &[u8]) -> Option<&'static str> {
+    if data.len() >= 12 && &data[..4] == b"RIFF" && &data[8..12] == b"WEBP" {
+        return Some("image/webp");
+    }
+    if data.len() >= 12 && &data[4..8] == b"ftyp" {
+        return Some("video/mp4");
+    }
+    SIGNATURES
+        .iter()
+        .find(|(signature, _)| data.starts_with(signature))
+        .map(|(_, content_type)| *content_type)
+}
+
+/// Whether browsers may display the file instead of saving it.
+pub fn is_inline(content_type: &str) -> bool {
+    content_type
+        .parse::<Mime>()
+        .is_ok_and(|mime| INLINE_TYPES.contains(&mime.essence_str()))
+}
+
+/// Drops directories and control characters, so the name is safe to store,
+/// list and offer as a download name.
+pub fn sanitize_file_name(file_name: &str) -> String {
+    let base_name = file_name.rsplit(['/', '\\']).next().unwrap_or_default();
+    let cleaned: String = base_name.chars().filter(|c| !c.is_control()).collect();
+    let cleaned = cleaned.trim();
+    if cleaned.is_empty() || cleaned == "." || cleaned == ".." {
+        return "download".to_string();
+    }
+    cleaned.to_string()
+}
+
+/// `Content-Disposition` after RFC 6266: an ASCII `filename` for old clients
+/// and the exact name as UTF-8 in `filename*`.
+pub fn content_disposition(file_name: &str, content_type: &str) -> String {
+    let file_name = sanitize_file_name(file_name);
+    let disposition = if is_inline(content_type) {
+        "inline"
+    } else {
+        "attachment"
+    };
+    let fallback: String = file_name
+        .chars()
+        .map(|c| match c {
+            '"' | '\\' => '_',
+            c if c.is_ascii() => c,
+            _ => '_',
+        })
+        .collect();
+
+    format!(
+        "{}; filename=\"{}\"; filename*=UTF-8''{}",
+        disposition,
+        fallback,
+        utf8_percent_encode(&file_name, ATTR_CHAR)
+    )
+}
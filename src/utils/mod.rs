@@ -1,5 +1,7 @@
 pub mod decrypt;
 pub mod encrypt;
 pub mod keys;
+pub mod media_type;
 pub mod password;
+pub mod signature;
 pub mod token;
@@ -36,7 +36,7 @@ pub fn compare(password: &str, hashed_password: &str) -> Result<bool, ErrorMessa
     let parsed_hash = PasswordHash::new(hashed_password).map_err(|_| ErrorMessage::HashingError)?;
     let password_matched = Argon2::default()
         .verify_password(password.as_bytes(), &parsed_hash)
-        .map_or(false, |_| true);
+        .is_ok();
 
     Ok(password_matched)
 }
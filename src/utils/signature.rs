@@ -0,0 +1,58 @@
+//! Sender signatures: uploads are signed with the sender's Ed25519 key over
+//! the stored ciphertext and the metadata the recipient is shown, so a
+//! download can prove who sent it without trusting the `files` row.
+
+use base64::{Engine, prelude::BASE64_STANDARD};
+use secure_share_crypto::{SignedFile, SigningKey, verifying_key_from_pem};
+use uuid::Uuid;
+
+use crate::{dtos::SignatureStatus, models::FileMetadata};
+
+/// What the sender of a file signs. `content_digest` is
+/// [`secure_share_crypto::content_digest`] of the stored IV and ciphertext.
+pub fn signed_file(
+    sender_id: Uuid,
+    recipient_id: Uuid,
+    content_digest: [u8; 32],
+    metadata: &FileMetadata,
+    file_size: i64,
+) -> SignedFile<'_> {
+    SignedFile {
+        sender_id: sender_id.into_bytes(),
+        recipient_id: recipient_id.into_bytes(),
+        content_digest,
+        file_name: &metadata.file_name,
+        content_type: &metadata.content_type,
+        file_size: file_size as u64,
+    }
+}
+
+pub fn sign(signed_file: &SignedFile, signing_key: &SigningKey) -> Vec<u8> {
+    signed_file.sign(signing_key).to_bytes().to_vec()
+}
+
+/// Checks `signature` against the sender's `signing_key` as stored for users:
+/// a base64 encoded PEM.
+pub fn verify(
+    signed_file: &SignedFile,
+    signature: Option<&[u8]>,
+    signing_key: Option<&str>,
+) -> SignatureStatus {
+    let status = match signature {
+        None => SignatureStatus::Unsigned,
+        Some(signature) => {
+            let verified = signing_key
+                .and_then(|signing_key| BASE64_STANDARD.decode(signing_key).ok())
+                .and_then(|pem| String::from_utf8(pem).ok())
+                .and_then(|pem| verifying_key_from_pem(&pem).ok())
+                .is_some_and(|verifying_key| signed_file.verify(&verifying_key, signature).is_ok());
+            if verified {
+                SignatureStatus::Verified
+            } else {
+                SignatureStatus::Invalid
+            }
+        }
+    };
+    metrics::counter!("signature_checks_total", "status" => status.as_str()).increment(1);
+    status
+}
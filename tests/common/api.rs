@@ -0,0 +1,236 @@
+use std::{
+    path::{Path, PathBuf},
+    sync::Arc,
+};
+
+use axum::{
+    Router,
+    body::{Body, to_bytes},
+    http::{Request, Response, StatusCode, header},
+};
+use chrono::{Duration, SecondsFormat, Utc};
+use metrics_exporter_prometheus::PrometheusBuilder;
+use secure_share::{
+    AppState,
+    config::Config,
+    db::{DbPool, FileRepository, MemoryDb, ShareRepository, UserRepository},
+    kek::KeyEncryptionKey,
+    rate_limit::MemoryRateLimitStore,
+    router::create_router,
+};
+use serde_json::{Value, json};
+use tower::ServiceExt;
+use uuid::Uuid;
+
+pub const PASSWORD: &str = "password123";
+pub const SHARE_PASSWORD: &str = "share-password";
+pub const BOUNDARY: &str = "secure-share-test-boundary";
+
+pub type Repositories = (
+    Arc<dyn UserRepository>,
+    Arc<dyn FileRepository>,
+    Arc<dyn ShareRepository>,
+);
+
+/// A private key directory of its own; remove it when the test is done.
+pub fn key_dir() -> PathBuf {
+    std::env::temp_dir().join(format!("secure-share-test-{}", Uuid::new_v4()))
+}
+
+pub fn config(database_url: &str, key_dir: &Path) -> Config {
+    let mut env = Config {
+        database_url: database_url.to_string(),
+        jwt_secret: "test-secret".to_string(),
+        ..Config::default()
+    };
+    env.storage.private_key_dir = key_dir.to_string_lossy().into_owned();
+    env
+}
+
+pub fn app_state(
+    env: Config,
+    (users, files, shares): Repositories,
+    db_pool: Option<DbPool>,
+    kek: Option<Arc<dyn KeyEncryptionKey>>,
+) -> AppState {
+    AppState {
+        env,
+        users,
+        files,
+        shares,
+        db_pool,
+        kek,
+        rate_limit_store: Arc::new(MemoryRateLimitStore::default()),
+        metrics: PrometheusBuilder::new().build_recorder().handle(),
+    }
+}
+
+/// The router over in-memory repositories, so no database is needed.
+pub struct TestApp {
+    pub router: Router,
+    pub db: Arc<MemoryDb>,
+    pub key_dir: PathBuf,
+}
+
+impl Drop for TestApp {
+    fn drop(&mut self) {
+        let _ = std::fs::remove_dir_all(&self.key_dir);
+    }
+}
+
+impl TestApp {
+    pub fn new() -> Self {
+        Self::with_kek(None)
+    }
+
+    pub fn with_kek(kek: Option<Arc<dyn KeyEncryptionKey>>) -> Self {
+        Self::with_config(|_| {}, kek)
+    }
+
+    /// Lets `configure` change the settings before the router is built.
+    pub fn with_config(
+        configure: impl FnOnce(&mut Config),
+        kek: Option<Arc<dyn KeyEncryptionKey>>,
+    ) -> Self {
+        let db = Arc::new(MemoryDb::default());
+        let key_dir = key_dir();
+        let mut env = config("", &key_dir);
+        configure(&mut env);
+        let repositories: Repositories = (db.clone(), db.clone(), db.clone());
+        let app_state = app_state(env, repositories, None, kek);
+
+        Self {
+            router: create_router(Arc::new(app_state)),
+            db,
+            key_dir,
+        }
+    }
+}
+
+impl TestClient for TestApp {
+    async fn respond(&self, request: Request<Vec<u8>>) -> Response<Vec<u8>> {
+        let response = self
+            .router
+            .clone()
+            .oneshot(request.map(Body::from))
+            .await
+            .unwrap();
+        let (parts, body) = response.into_parts();
+        let body = to_bytes(body, usize::MAX).await.unwrap();
+        Response::from_parts(parts, body.to_vec())
+    }
+}
+
+/// Drives the API, in process or over HTTP, as a browser or the CLI would.
+pub trait TestClient {
+    /// Sends `request`, whose URI is a path on the server.
+    async fn respond(&self, request: Request<Vec<u8>>) -> Response<Vec<u8>>;
+
+    async fn send(&self, request: Request<Vec<u8>>) -> (StatusCode, Vec<u8>) {
+        let response = self.respond(request).await;
+        (response.status(), response.into_body())
+    }
+
+    async fn json(&self, request: Request<Vec<u8>>) -> (StatusCode, Value) {
+        let (status, body) = self.send(request).await;
+        (status, serde_json::from_slice(&body).unwrap())
+    }
+
+    async fn register(&self, email: &str) -> (StatusCode, Value) {
+        let body = json!({
+            "name": "Test User Account",
+            "email": email,
+            "password": PASSWORD,
+            "passwordConfirm": PASSWORD,
+        });
+        self.json(post_json("/api/auth/register", None, &body))
+            .await
+    }
+
+    async fn login(&self, email: &str) -> String {
+        let body = json!({ "email": email, "password": PASSWORD });
+        let response = self
+            .respond(post_json("/api/auth/login", None, &body))
+            .await;
+        assert_eq!(response.status(), StatusCode::OK);
+        let cookie = response.headers()[header::SET_COOKIE].to_str().unwrap();
+        assert!(cookie.starts_with("token="), "{}", cookie);
+        let body: Value = serde_json::from_slice(response.body()).unwrap();
+        body["token"].as_str().unwrap().to_string()
+    }
+
+    async fn user_with_token(&self, email: &str) -> String {
+        let (status, body) = self.register(email).await;
+        assert_eq!(status, StatusCode::CREATED, "{}", body);
+        self.login(email).await
+    }
+
+    async fn upload(&self, token: &str, recipient_email: &str, data: &[u8]) -> (StatusCode, Value) {
+        self.upload_named(token, recipient_email, "notes.txt", "text/plain", data)
+            .await
+    }
+
+    async fn upload_named(
+        &self,
+        token: &str,
+        recipient_email: &str,
+        file_name: &str,
+        content_type: &str,
+        data: &[u8],
+    ) -> (StatusCode, Value) {
+        let expiration_date =
+            (Utc::now() + Duration::days(1)).to_rfc3339_opts(SecondsFormat::Secs, true);
+        let mut body = Vec::new();
+        for (name, value) in [
+            ("recipient_email", recipient_email),
+            ("password", SHARE_PASSWORD),
+            ("expiration_date", expiration_date.as_str()),
+        ] {
+            body.extend_from_slice(
+                format!(
+                    "--{BOUNDARY}\r\nContent-Disposition: form-data; name=\"{name}\"\r\n\r\n{value}\r\n"
+                )
+                .as_bytes(),
+            );
+        }
+        body.extend_from_slice(
+            format!(
+                "--{BOUNDARY}\r\nContent-Disposition: form-data; name=\"fileUpload\"; filename=\"{file_name}\"\r\nContent-Type: {content_type}\r\n\r\n"
+            )
+            .as_bytes(),
+        );
+        body.extend_from_slice(data);
+        body.extend_from_slice(format!("\r\n--{BOUNDARY}--\r\n").as_bytes());
+
+        let request = Request::post("/api/file/upload")
+            .header(header::AUTHORIZATION, format!("Bearer {}", token))
+            .header(
+                header::CONTENT_TYPE,
+                format!("multipart/form-data; boundary={}", BOUNDARY),
+            )
+            .body(body)
+            .unwrap();
+        self.json(request).await
+    }
+
+    async fn retrieve(&self, token: &str, shared_id: &Value, password: &str) -> Response<Vec<u8>> {
+        let body = json!({ "shared_id": shared_id, "password": password });
+        self.respond(post_json("/api/file/register", Some(token), &body))
+            .await
+    }
+}
+
+pub fn get(uri: &str, token: &str) -> Request<Vec<u8>> {
+    Request::get(uri)
+        .header(header::AUTHORIZATION, format!("Bearer {}", token))
+        .body(Vec::new())
+        .unwrap()
+}
+
+pub fn post_json(uri: &str, token: Option<&str>, body: &Value) -> Request<Vec<u8>> {
+    let mut request = Request::post(uri).header(header::CONTENT_TYPE, "application/json");
+    if let Some(token) = token {
+        request = request.header(header::AUTHORIZATION, format!("Bearer {}", token));
+    }
+    request.body(body.to_string().into_bytes()).unwrap()
+}
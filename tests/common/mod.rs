@@ -0,0 +1,44 @@
+//! Fixtures shared by the integration tests. Each test binary uses only part
+//! of them.
+#![allow(dead_code)]
+
+pub mod api;
+
+use sqlx::{Connection, Executor, PgConnection};
+use uuid::Uuid;
+
+/// A Postgres database created for one test next to the one named by
+/// `TEST_DATABASE_URL`, which is only used to create and drop it.
+pub struct TestDatabase {
+    pub url: String,
+    admin_url: String,
+    name: String,
+}
+
+impl TestDatabase {
+    /// `None` when `TEST_DATABASE_URL` is not set.
+    pub async fn create() -> Option<Self> {
+        let admin_url = std::env::var("TEST_DATABASE_URL").ok()?;
+        let name = format!("secure_share_test_{}", Uuid::new_v4().simple());
+        let mut conn = PgConnection::connect(&admin_url).await.unwrap();
+        conn.execute(format!("CREATE DATABASE \"{}\"", name).as_str())
+            .await
+            .unwrap();
+        conn.close().await.unwrap();
+
+        let (server_url, _) = admin_url.rsplit_once('/').unwrap();
+        Some(Self {
+            url: format!("{}/{}", server_url, name),
+            admin_url,
+            name,
+        })
+    }
+
+    /// Drops the database; close pools connected to it first.
+    pub async fn drop(self) {
+        let mut conn = PgConnection::connect(&self.admin_url).await.unwrap();
+        conn.execute(format!("DROP DATABASE \"{}\" WITH (FORCE)", self.name).as_str())
+            .await
+            .unwrap();
+    }
+}
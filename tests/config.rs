@@ -0,0 +1,84 @@
+//! Loading settings from a config file and environment variables.
+
+use std::{collections::HashMap, fs};
+
+use secure_share::config::Config;
+use uuid::Uuid;
+
+fn vars(pairs: &[(&str, &str)]) -> HashMap<String, String> {
+    let mut vars: HashMap<String, String> = pairs
+        .iter()
+        .map(|(name, value)| (name.to_string(), value.to_string()))
+        .collect();
+    // Never pick up a config.toml from the working directory
+    vars.entry("CONFIG_FILE".to_string())
+        .or_insert_with(|| "/dev/null".to_string());
+    vars
+}
+
+#[test]
+fn environment_values_take_the_type_of_their_setting() {
+    let config = Config::from_vars(vars(&[
+        ("DATABASE_URL", "sqlite://test.db"),
+        ("SECURE_SHARE__JWT_SECRET", "123456"),
+        ("JWT_MAXAGE", "30"),
+        ("SECURE_SHARE__SERVER__PORT", "9000"),
+        ("SECURE_SHARE__SERVER__HOST", "true"),
+        ("SECURE_SHARE__DATABASE__AUTO_MIGRATE", "true"),
+        (
+            "SECURE_SHARE__SERVER__CORS_ORIGINS",
+            r#"["https://a.example.com", "https://b.example.com"]"#,
+        ),
+    ]))
+    .unwrap();
+
+    assert_eq!(config.jwt_secret, "123456");
+    assert_eq!(config.jwt_maxage, 30);
+    assert_eq!(config.server.port, 9000);
+    assert_eq!(config.server.host, "true");
+    assert!(config.database.auto_migrate);
+    assert_eq!(config.server.cors_origins.len(), 2);
+}
+
+#[test]
+fn every_problem_is_reported_at_once() {
+    let path = std::env::temp_dir().join(format!("secure-share-config-{}.toml", Uuid::new_v4()));
+    fs::write(
+        &path,
+        r#"
+        jwt_maxage = "soon"
+
+        [server]
+        port = 70000
+        colour = "blue"
+        "#,
+    )
+    .unwrap();
+
+    let err = Config::from_vars(vars(&[
+        ("CONFIG_FILE", path.to_str().unwrap()),
+        ("SECURE_SHARE__DATABASE__MAX_CONNECTIONS", "many"),
+        ("SECURE_SHARE__RATE_LIMIT__AUTH_PER_MINUTE", "0"),
+    ]))
+    .unwrap_err();
+    fs::remove_file(&path).unwrap();
+
+    let problems = err.0.join("\n");
+    for expected in [
+        "jwt_maxage:",
+        "server.port:",
+        "server.colour:",
+        "database.max_connections:",
+        "database_url must be set",
+        "jwt_secret must be set",
+        "rate_limit quotas must be at least 1",
+    ] {
+        assert!(
+            problems.contains(expected),
+            "{} not in\n{}",
+            expected,
+            problems
+        );
+    }
+    assert_eq!(err.0.len(), 7, "{}", problems);
+}
@@ -0,0 +1,245 @@
+//! Serves the full router over TCP against a throwaway Postgres database and
+//! drives it with a real HTTP client. Skipped unless `TEST_DATABASE_URL` is set.
+
+mod common;
+
+use std::{fs, net::SocketAddr, path::PathBuf, sync::Arc};
+
+use axum::http::{Request, Response, header};
+use reqwest::StatusCode;
+use secure_share::{
+    db::{DbPool, ShareRepository},
+    migrations,
+    router::create_router,
+};
+use serde_json::Value;
+use tokio::sync::oneshot;
+use uuid::Uuid;
+
+use common::{
+    TestDatabase,
+    api::{self, SHARE_PASSWORD, TestClient, get},
+};
+
+struct TestServer {
+    base_url: String,
+    client: reqwest::Client,
+    pool: DbPool,
+    shares: Arc<dyn ShareRepository>,
+    database: TestDatabase,
+    key_dir: PathBuf,
+    shutdown: oneshot::Sender<()>,
+}
+
+impl TestServer {
+    async fn start() -> Option<Self> {
+        let Some(database) = TestDatabase::create().await else {
+            eprintln!("TEST_DATABASE_URL is not set, skipping");
+            return None;
+        };
+
+        let key_dir = api::key_dir();
+        let env = api::config(&database.url, &key_dir);
+        let pool = DbPool::connect(&env.database_url, &env.database)
+            .await
+            .unwrap();
+        migrations::prepare(&pool, true).await.unwrap();
+        let repositories = pool.repositories();
+        let shares = repositories.2.clone();
+        let app_state = api::app_state(env, repositories, Some(pool.clone()), None);
+
+        let listener = tokio::net::TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let addr = listener.local_addr().unwrap();
+        let app = create_router(Arc::new(app_state));
+        let (shutdown, stopped) = oneshot::channel();
+        tokio::spawn(async move {
+            axum::serve(
+                listener,
+                app.into_make_service_with_connect_info::<SocketAddr>(),
+            )
+            .with_graceful_shutdown(async {
+                let _ = stopped.await;
+            })
+            .await
+            .unwrap();
+        });
+
+        Some(Self {
+            base_url: format!("http://{}", addr),
+            client: reqwest::Client::new(),
+            pool,
+            shares,
+            database,
+            key_dir,
+            shutdown,
+        })
+    }
+
+    async fn stop(self) {
+        let _ = self.shutdown.send(());
+        self.pool.close().await;
+        self.database.drop().await;
+        let _ = fs::remove_dir_all(&self.key_dir);
+    }
+
+    async fn list(&self, token: &str, direction: &str) -> Value {
+        let (status, body) = self
+            .json(get(&format!("/api/list/{}", direction), token))
+            .await;
+        assert_eq!(status, StatusCode::OK);
+        body
+    }
+}
+
+impl TestClient for TestServer {
+    async fn respond(&self, request: Request<Vec<u8>>) -> Response<Vec<u8>> {
+        let (parts, body) = request.into_parts();
+        let response = self
+            .client
+            .request(parts.method, format!("{}{}", self.base_url, parts.uri))
+            .headers(parts.headers)
+            .body(body)
+            .send()
+            .await
+            .unwrap();
+
+        let mut builder = Response::builder().status(response.status());
+        for (name, value) in response.headers() {
+            builder = builder.header(name, value);
+        }
+        let body = response.bytes().await.unwrap();
+        builder.body(body.to_vec()).unwrap()
+    }
+}
+
+fn problem_code(response: Response<Vec<u8>>) -> Value {
+    assert_eq!(
+        response.headers()[header::CONTENT_TYPE],
+        "application/problem+json"
+    );
+    let body: Value = serde_json::from_slice(response.body()).unwrap();
+    body["code"].clone()
+}
+
+#[tokio::test]
+async fn shared_file_round_trips_over_http() {
+    let Some(server) = TestServer::start().await else {
+        return;
+    };
+    let alice = server.user_with_token("alice@example.com").await;
+    let bob = server.user_with_token("bob@example.com").await;
+    // Every byte value, and longer than one AES block
+    let data: Vec<u8> = (0..=255).cycle().take(4099).collect();
+
+    server
+        .upload_named(
+            &alice,
+            "bob@example.com",
+            "quarterly report.bin",
+            "application/octet-stream",
+            &data,
+        )
+        .await;
+
+    let sent = server.list(&alice, "send").await;
+    assert_eq!(sent["results"], 1);
+    assert_eq!(sent["files"][0]["file_name"], "quarterly report.bin");
+    assert_eq!(sent["files"][0]["recipient_email"], "bob@example.com");
+    let received = server.list(&bob, "receive").await;
+    assert_eq!(received["results"], 1);
+    assert_eq!(received["files"][0]["sender_email"], "alice@example.com");
+    let shared_id = &received["files"][0]["share_id"];
+
+    let response = server.retrieve(&bob, shared_id, "not-the-password").await;
+    assert_eq!(response.status(), StatusCode::BAD_REQUEST);
+    assert_eq!(problem_code(response), "wrong_share_password");
+
+    let response = server.retrieve(&alice, shared_id, SHARE_PASSWORD).await;
+    assert_eq!(response.status(), StatusCode::BAD_REQUEST);
+    assert_eq!(problem_code(response), "share_not_found");
+
+    let response = server.retrieve(&bob, shared_id, SHARE_PASSWORD).await;
+    assert_eq!(response.status(), StatusCode::OK);
+    assert_eq!(
+        response.headers()[header::CONTENT_TYPE],
+        "application/octet-stream"
+    );
+    assert_eq!(
+        response.headers()[header::CONTENT_DISPOSITION],
+        "attachment; filename=\"quarterly report.bin\"; filename*=UTF-8''quarterly%20report.bin"
+    );
+    assert!(response.headers().contains_key("x-request-id"));
+    assert_eq!(response.body(), &data);
+
+    let uri = format!("/api/file/shares/{}", shared_id.as_str().unwrap());
+    let (status, details) = server.json(get(&uri, &alice)).await;
+    assert_eq!(status, StatusCode::OK);
+    assert_eq!(details["share"]["file_size"], data.len());
+    assert_eq!(details["share"]["download_count"], 1);
+
+    server.stop().await;
+}
+
+#[tokio::test]
+async fn expired_shares_are_refused_and_cleaned_up() {
+    let Some(server) = TestServer::start().await else {
+        return;
+    };
+    let alice = server.user_with_token("alice@example.com").await;
+    let bob = server.user_with_token("bob@example.com").await;
+    for (file_name, data) in [("stale.txt", b"old news"), ("fresh.txt", b"new news")] {
+        let (status, body) = server
+            .upload_named(&alice, "bob@example.com", file_name, "text/plain", data)
+            .await;
+        assert_eq!(status, StatusCode::OK, "{}", body);
+    }
+
+    // Uploads only accept future dates, so expire one share in place. Names
+    // are encrypted at rest, so find it through the list
+    let received = server.list(&bob, "receive").await;
+    let stale = received["files"]
+        .as_array()
+        .unwrap()
+        .iter()
+        .find(|file| file["file_name"] == "stale.txt")
+        .unwrap()
+        .clone();
+    let DbPool::Postgres(pool) = &server.pool else {
+        unreachable!("TEST_DATABASE_URL is a Postgres database");
+    };
+    let file_name: String = sqlx::query_scalar(
+        r#"
+        UPDATE shared_links
+        SET expiration_date = NOW() - INTERVAL '1 minute'
+        FROM files
+        WHERE shared_links.id = $1 AND files.id = shared_links.file_id
+        RETURNING files.file_name
+        "#,
+    )
+    .bind(Uuid::parse_str(stale["share_id"].as_str().unwrap()).unwrap())
+    .fetch_one(pool)
+    .await
+    .unwrap();
+    assert_eq!(file_name, "");
+
+    let response = server
+        .retrieve(&bob, &stale["share_id"], SHARE_PASSWORD)
+        .await;
+    assert_eq!(response.status(), StatusCode::BAD_REQUEST);
+    assert_eq!(problem_code(response), "share_not_found");
+
+    assert_eq!(server.shares.delete_expired_files().await.unwrap(), 1);
+
+    let sent = server.list(&alice, "send").await;
+    assert_eq!(sent["results"], 1);
+    assert_eq!(sent["files"][0]["file_name"], "fresh.txt");
+    let received = server.list(&bob, "receive").await;
+    assert_eq!(received["results"], 1);
+    let response = server
+        .retrieve(&bob, &received["files"][0]["share_id"], SHARE_PASSWORD)
+        .await;
+    assert_eq!(response.status(), StatusCode::OK);
+    assert_eq!(response.body(), b"new news");
+
+    server.stop().await;
+}
@@ -0,0 +1,141 @@
+//! Problem details bodies and the language their messages are in.
+
+mod common;
+
+use axum::http::{Request, StatusCode, header};
+use serde_json::{Value, json};
+
+use common::api::{TestApp, TestClient, get, post_json};
+
+async fn problem(app: &TestApp, request: Request<Vec<u8>>) -> (StatusCode, String, Value) {
+    let response = app.respond(request).await;
+    assert_eq!(
+        response.headers()[header::CONTENT_TYPE],
+        "application/problem+json"
+    );
+    let language = response.headers()[header::CONTENT_LANGUAGE]
+        .to_str()
+        .unwrap()
+        .to_string();
+    let body = serde_json::from_slice(response.body()).unwrap();
+    (response.status(), language, body)
+}
+
+fn with_language(mut request: Request<Vec<u8>>, accept_language: &str) -> Request<Vec<u8>> {
+    request
+        .headers_mut()
+        .insert(header::ACCEPT_LANGUAGE, accept_language.parse().unwrap());
+    request
+}
+
+#[tokio::test]
+async fn errors_are_problem_details() {
+    let app = TestApp::new();
+
+    let request = Request::get("/api/users/me").body(Vec::new()).unwrap();
+    let (status, language, body) = problem(&app, request).await;
+    assert_eq!(status, StatusCode::UNAUTHORIZED);
+    assert_eq!(language, "en");
+    assert_eq!(
+        body,
+        json!({
+            "type": "urn:secure-share:problem:token_not_provided",
+            "title": "Unauthorized",
+            "status": 401,
+            "detail": "Token not provided",
+            "code": "token_not_provided",
+        })
+    );
+
+    let register = json!({
+        "name": "Short",
+        "email": "not-an-email",
+        "password": "password123",
+        "passwordConfirm": "password321",
+    });
+    let (status, _, body) = problem(&app, post_json("/api/auth/register", None, &register)).await;
+    assert_eq!(status, StatusCode::UNPROCESSABLE_ENTITY);
+    assert_eq!(body["code"], "validation_failed");
+    assert_eq!(body["errors"]["name"][0]["code"], "name_too_short");
+    assert_eq!(body["errors"]["email"][0]["code"], "email_invalid");
+    assert_eq!(
+        body["errors"]["passwordConfirm"][0]["code"],
+        "passwords_do_not_match"
+    );
+}
+
+#[tokio::test]
+async fn language_follows_accept_language_weights() {
+    let app = TestApp::new();
+    let me = || Request::get("/api/users/me").body(Vec::new()).unwrap();
+
+    let (_, language, body) = problem(&app, with_language(me(), "fr, zh-CN;q=0.8, en;q=0.5")).await;
+    assert_eq!(language, "zh");
+    assert_eq!(body["detail"], "未提供令牌");
+
+    let (_, language, body) = problem(&app, with_language(me(), "zh;q=0, en-GB")).await;
+    assert_eq!(language, "en");
+    assert_eq!(body["detail"], "Token not provided");
+
+    // Nothing supported falls back to English
+    let (_, language, _) = problem(&app, with_language(me(), "fr, de")).await;
+    assert_eq!(language, "en");
+}
+
+#[tokio::test]
+async fn saved_locale_wins_over_the_header() {
+    let app = TestApp::new();
+    let token = app.user_with_token("locale@example.com").await;
+
+    let mut request = post_json(
+        "/api/users/locale",
+        Some(&token),
+        &json!({ "locale": "zh" }),
+    );
+    *request.method_mut() = "PUT".parse().unwrap();
+    let (status, _) = app.send(request).await;
+    assert_eq!(status, StatusCode::OK);
+
+    let request = with_language(
+        post_json(
+            "/api/file/register",
+            Some(&token),
+            &json!({ "shared_id": "x" }),
+        ),
+        "en",
+    );
+    let (_, language, body) = problem(&app, request).await;
+    assert_eq!(language, "zh");
+    assert_eq!(body["code"], "invalid_input");
+    assert_eq!(body["detail"], "JSON 请求体缺少字段或字段类型错误");
+}
+
+#[tokio::test]
+async fn unreadable_input_is_described_without_parser_output() {
+    let app = TestApp::new();
+    let token = app.user_with_token("input@example.com").await;
+
+    let mut request = post_json("/api/file/register", Some(&token), &json!({}));
+    *request.body_mut() = b"{\"shared_id\":".to_vec();
+    let (status, _, body) = problem(&app, request).await;
+    assert_eq!(status, StatusCode::BAD_REQUEST);
+    assert_eq!(body["code"], "invalid_input");
+    assert_eq!(body["detail"], "The request body is not valid JSON");
+
+    let body = json!({ "shared_id": 7, "password": "share-password" });
+    let (status, _, body) =
+        problem(&app, post_json("/api/file/register", Some(&token), &body)).await;
+    assert_eq!(status, StatusCode::UNPROCESSABLE_ENTITY);
+    assert_eq!(
+        body["detail"],
+        "The JSON body has missing fields or values of the wrong type"
+    );
+
+    let (status, _, body) = problem(&app, get("/api/list/send?page=first", &token)).await;
+    assert_eq!(status, StatusCode::BAD_REQUEST);
+    assert_eq!(body["code"], "invalid_input");
+    assert_eq!(
+        body["detail"],
+        "The query string is malformed or has values of the wrong type"
+    );
+}
@@ -0,0 +1,1083 @@
+//! Drives the router against in-memory repositories, so no database is needed.
+
+mod common;
+
+use std::{fs, sync::Arc};
+
+use axum::http::{Method, Request, StatusCode, header};
+use base64::{Engine, prelude::BASE64_STANDARD};
+use chrono::{Duration, Utc};
+use secure_share::{
+    db::{FileRepository, MemoryDb, ShareRepository, UserRepository},
+    kek::{self, Keyring},
+    models::{FileMetadata, NewFile},
+    utils::keys,
+};
+use secure_share_crypto::{Recipient, Sender, SigningKey, seal_file, verifying_key_to_pem};
+use serde_json::{Value, json};
+use uuid::Uuid;
+
+use common::api::{BOUNDARY, PASSWORD, SHARE_PASSWORD, TestApp, TestClient, get, post_json};
+
+#[tokio::test]
+async fn register_stores_user_with_key_pair() {
+    let app = TestApp::new();
+
+    let (status, _) = app.register("alice@example.com").await;
+    assert_eq!(status, StatusCode::CREATED);
+
+    let user = app
+        .db
+        .get_user(None, None, Some("alice@example.com"))
+        .await
+        .unwrap()
+        .unwrap();
+    assert!(user.public_key.is_some());
+    assert!(user.signing_key.is_some());
+    assert!(app.key_dir.join(format!("{}.pem", user.id)).exists());
+    assert!(
+        app.key_dir
+            .join(format!("{}.signing.pem", user.id))
+            .exists()
+    );
+    #[cfg(unix)]
+    {
+        use std::os::unix::fs::PermissionsExt;
+        let metadata = fs::metadata(app.key_dir.join(format!("{}.pem", user.id))).unwrap();
+        assert_eq!(metadata.permissions().mode() & 0o777, 0o600);
+    }
+}
+
+#[tokio::test]
+async fn register_rejects_taken_email() {
+    let app = TestApp::new();
+    app.register("alice@example.com").await;
+
+    let (status, body) = app.register("alice@example.com").await;
+
+    assert_eq!(status, StatusCode::CONFLICT);
+    assert_eq!(body["code"], "email_already_exists");
+}
+
+#[tokio::test]
+async fn login_rejects_wrong_password() {
+    let app = TestApp::new();
+    app.register("alice@example.com").await;
+
+    let body = json!({ "email": "alice@example.com", "password": "not-the-password" });
+    let (status, body) = app.json(post_json("/api/auth/login", None, &body)).await;
+
+    assert_eq!(status, StatusCode::BAD_REQUEST);
+    assert_eq!(body["code"], "wrong_credentials");
+}
+
+#[tokio::test]
+async fn me_requires_token() {
+    let app = TestApp::new();
+    let token = app.user_with_token("alice@example.com").await;
+
+    let (status, body) = app.json(get("/api/users/me", &token)).await;
+    assert_eq!(status, StatusCode::OK);
+    assert_eq!(body["data"]["user"]["email"], "alice@example.com");
+
+    let request = Request::get("/api/users/me").body(Vec::new()).unwrap();
+    let (status, body) = app.json(request).await;
+    assert_eq!(status, StatusCode::UNAUTHORIZED);
+    assert_eq!(body["code"], "token_not_provided");
+}
+
+#[tokio::test]
+async fn search_finds_other_users_only() {
+    let app = TestApp::new();
+    let token = app.user_with_token("alice@example.com").await;
+    app.register("bob@example.com").await;
+
+    let (status, body) = app
+        .json(get("/api/users/search-emails?query=bob@", &token))
+        .await;
+    assert_eq!(status, StatusCode::OK);
+    assert_eq!(body["emails"], json!([{ "email": "bob@example.com" }]));
+
+    let (_, body) = app
+        .json(get("/api/users/search-emails?query=alice", &token))
+        .await;
+    assert_eq!(body["emails"], json!([]));
+}
+
+#[tokio::test]
+async fn shared_file_round_trips_to_recipient() {
+    let app = TestApp::new();
+    let alice = app.user_with_token("alice@example.com").await;
+    let bob = app.user_with_token("bob@example.com").await;
+
+    let (status, body) = app
+        .upload(&alice, "bob@example.com", b"meeting notes")
+        .await;
+    assert_eq!(status, StatusCode::OK, "{}", body);
+
+    let (_, sent) = app.json(get("/api/list/send", &alice)).await;
+    assert_eq!(sent["results"], 1);
+    assert_eq!(sent["files"][0]["recipient_email"], "bob@example.com");
+
+    let (_, received) = app.json(get("/api/list/receive", &bob)).await;
+    assert_eq!(received["results"], 1);
+    assert_eq!(received["files"][0]["sender_email"], "alice@example.com");
+    let shared_id = received["files"][0]["share_id"].as_str().unwrap();
+
+    let body = json!({ "shared_id": shared_id, "password": SHARE_PASSWORD });
+    let (status, data) = app
+        .send(post_json("/api/file/register", Some(&bob), &body))
+        .await;
+    assert_eq!(status, StatusCode::OK);
+    assert_eq!(data, b"meeting notes");
+
+    // Only ciphertext is stored
+    let shared_id = Uuid::parse_str(shared_id).unwrap();
+    let bob_id = app
+        .db
+        .get_user(None, None, Some("bob@example.com"))
+        .await
+        .unwrap()
+        .unwrap()
+        .id;
+    let link = app.db.get_shared(shared_id, bob_id).await.unwrap().unwrap();
+    let file = app
+        .db
+        .get_file(link.file_id.unwrap())
+        .await
+        .unwrap()
+        .unwrap();
+    assert_ne!(file.encrypted_file, b"meeting notes");
+}
+
+#[tokio::test]
+async fn retrieve_rejects_wrong_share_password_and_other_users() {
+    let app = TestApp::new();
+    let alice = app.user_with_token("alice@example.com").await;
+    let bob = app.user_with_token("bob@example.com").await;
+    app.upload(&alice, "bob@example.com", b"meeting notes")
+        .await;
+    let (_, received) = app.json(get("/api/list/receive", &bob)).await;
+    let shared_id = received["files"][0]["share_id"].clone();
+
+    let body = json!({ "shared_id": shared_id, "password": "not-the-password" });
+    let (status, body) = app
+        .json(post_json("/api/file/register", Some(&bob), &body))
+        .await;
+    assert_eq!(status, StatusCode::BAD_REQUEST);
+    assert_eq!(body["code"], "wrong_share_password");
+
+    let body = json!({ "shared_id": shared_id, "password": SHARE_PASSWORD });
+    let (status, body) = app
+        .json(post_json("/api/file/register", Some(&alice), &body))
+        .await;
+    assert_eq!(status, StatusCode::BAD_REQUEST);
+    assert_eq!(body["code"], "share_not_found");
+}
+
+#[tokio::test]
+async fn upload_rejects_unknown_recipient() {
+    let app = TestApp::new();
+    let alice = app.user_with_token("alice@example.com").await;
+
+    let (status, body) = app.upload(&alice, "nobody@example.com", b"notes").await;
+
+    assert_eq!(status, StatusCode::BAD_REQUEST);
+    assert_eq!(body["code"], "recipient_not_found");
+}
+
+#[tokio::test]
+async fn upload_rejects_senders_without_a_key_pair() {
+    let app = TestApp::new();
+    app.register("bob@example.com").await;
+    // An account whose key pair was never generated
+    let password = secure_share::utils::password::hash(PASSWORD).unwrap();
+    app.db
+        .save_user(
+            "Test User Account".to_string(),
+            "alice@example.com".to_string(),
+            password,
+        )
+        .await
+        .unwrap();
+    let alice = app.login("alice@example.com").await;
+
+    let (status, body) = app.upload(&alice, "bob@example.com", b"notes").await;
+
+    assert_eq!(status, StatusCode::BAD_REQUEST);
+    assert_eq!(body["code"], "sender_has_no_key");
+}
+
+#[tokio::test]
+async fn share_details_are_shown_to_sender_and_recipient_only() {
+    let app = TestApp::new();
+    let alice = app.user_with_token("alice@example.com").await;
+    let bob = app.user_with_token("bob@example.com").await;
+    let carol = app.user_with_token("carol@example.com").await;
+    app.upload(&alice, "bob@example.com", b"meeting notes")
+        .await;
+
+    let (_, sent) = app.json(get("/api/list/send", &alice)).await;
+    let file = &sent["files"][0];
+    assert_eq!(file["content_type"], "text/plain");
+    assert_eq!(file["recipient_name"], "Test User Account");
+    assert_eq!(file["download_count"], 0);
+    assert_eq!(file["password_protected"], true);
+    assert_eq!(file["expired"], false);
+    let share_id = file["share_id"].as_str().unwrap();
+    let (_, received) = app.json(get("/api/list/receive", &bob)).await;
+    assert_eq!(received["files"][0]["share_id"], share_id);
+    assert_eq!(received["files"][0]["file_id"], file["file_id"]);
+
+    let body = json!({ "shared_id": share_id, "password": SHARE_PASSWORD });
+    let (status, _) = app
+        .send(post_json("/api/file/register", Some(&bob), &body))
+        .await;
+    assert_eq!(status, StatusCode::OK);
+
+    let uri = format!("/api/file/shares/{}", share_id);
+    for token in [&alice, &bob] {
+        let (status, body) = app.json(get(&uri, token)).await;
+        assert_eq!(status, StatusCode::OK, "{}", body);
+        let share = &body["share"];
+        assert_eq!(share["share_id"], share_id);
+        assert_eq!(share["file_id"], file["file_id"]);
+        assert_eq!(share["file_name"], "notes.txt");
+        assert_eq!(share["file_size"], 13);
+        assert_eq!(share["sender_email"], "alice@example.com");
+        assert_eq!(share["recipient_email"], "bob@example.com");
+        assert_eq!(share["download_count"], 1);
+        assert!(share["last_downloaded_at"].is_string());
+    }
+
+    let (status, body) = app.json(get(&uri, &carol)).await;
+    assert_eq!(status, StatusCode::NOT_FOUND);
+    assert_eq!(body["code"], "share_not_found");
+    let (status, body) = app.json(get("/api/file/shares/not-a-uuid", &alice)).await;
+    assert_eq!(status, StatusCode::BAD_REQUEST);
+    assert_eq!(body["code"], "invalid_shared_id");
+}
+
+#[tokio::test]
+async fn downloads_carry_detected_type_and_encoded_file_name() {
+    let app = TestApp::new();
+    let alice = app.user_with_token("alice@example.com").await;
+    let bob = app.user_with_token("bob@example.com").await;
+    // Declared as opaque bytes, but the signature says PDF
+    app.upload_named(
+        &alice,
+        "bob@example.com",
+        "../../résumé 2026.pdf",
+        "application/octet-stream",
+        b"%PDF-1.7 not really",
+    )
+    .await;
+    app.upload_named(
+        &alice,
+        "bob@example.com",
+        "page.html",
+        "text/html",
+        b"<script>alert(1)</script>",
+    )
+    .await;
+
+    let (_, received) = app
+        .json(get("/api/list/receive?sort=file_name", &bob))
+        .await;
+    let pdf = &received["files"][1];
+    assert_eq!(pdf["file_name"], "résumé 2026.pdf");
+    assert_eq!(pdf["content_type"], "application/pdf");
+    let html = &received["files"][0];
+    assert_eq!(html["content_type"], "text/html");
+
+    let retrieve = |file: &Value| {
+        let body = json!({ "shared_id": file["share_id"], "password": SHARE_PASSWORD });
+        app.respond(post_json("/api/file/register", Some(&bob), &body))
+    };
+    let response = retrieve(pdf).await;
+    assert_eq!(response.status(), StatusCode::OK);
+    assert_eq!(response.headers()[header::CONTENT_TYPE], "application/pdf");
+    assert_eq!(
+        response.headers()[header::CONTENT_DISPOSITION],
+        "inline; filename=\"r_sum_ 2026.pdf\"; filename*=UTF-8''r%C3%A9sum%C3%A9%202026.pdf"
+    );
+    assert_eq!(
+        response.headers()[header::X_CONTENT_TYPE_OPTIONS],
+        "nosniff"
+    );
+
+    // Never rendered by the browser, whatever the sender declared
+    let response = retrieve(html).await;
+    assert_eq!(response.headers()[header::CONTENT_TYPE], "text/html");
+    assert_eq!(
+        response.headers()[header::CONTENT_DISPOSITION],
+        "attachment; filename=\"page.html\"; filename*=UTF-8''page.html"
+    );
+}
+
+#[tokio::test]
+async fn lists_page_by_cursor_and_reject_cursors_of_other_sorts() {
+    let app = TestApp::new();
+    let alice = app.user_with_token("alice@example.com").await;
+    app.register("bob@example.com").await;
+    for data in [b"one", b"two", b"six"] {
+        app.upload(&alice, "bob@example.com", data).await;
+    }
+
+    let (status, first) = app.json(get("/api/list/send?limit=2", &alice)).await;
+    assert_eq!(status, StatusCode::OK);
+    assert_eq!(first["files"].as_array().unwrap().len(), 2);
+    assert_eq!(first["results"], 3);
+    let cursor = first["next_cursor"].as_str().unwrap();
+
+    let uri = format!("/api/list/send?limit=2&cursor={}", cursor);
+    let (_, second) = app.json(get(&uri, &alice)).await;
+    assert_eq!(second["files"].as_array().unwrap().len(), 1);
+    assert_eq!(second["next_cursor"], Value::Null);
+    assert_ne!(second["files"][0]["file_id"], first["files"][0]["file_id"]);
+    assert_ne!(second["files"][0]["file_id"], first["files"][1]["file_id"]);
+
+    let uri = format!("/api/list/send?limit=2&sort=file_name&cursor={}", cursor);
+    let (status, body) = app.json(get(&uri, &alice)).await;
+    assert_eq!(status, StatusCode::BAD_REQUEST);
+    assert_eq!(body["code"], "invalid_cursor");
+
+    let uri = format!("/api/list/send?limit=50&page={}", usize::MAX);
+    let (status, body) = app.json(get(&uri, &alice)).await;
+    assert_eq!(status, StatusCode::UNPROCESSABLE_ENTITY);
+    assert_eq!(body["errors"]["page"][0]["code"], "range");
+}
+
+#[tokio::test]
+async fn file_names_are_stored_encrypted_but_listed_in_clear() {
+    let app = TestApp::new();
+    let alice = app.user_with_token("alice@example.com").await;
+    let bob = app.user_with_token("bob@example.com").await;
+    for file_name in ["b-report.txt", "a-report.txt", "notes.txt"] {
+        app.upload_named(&alice, "bob@example.com", file_name, "text/plain", b"data")
+            .await;
+    }
+
+    let (_, sent) = app.json(get("/api/list/send", &alice)).await;
+    for listed in sent["files"].as_array().unwrap() {
+        let file_id = Uuid::parse_str(listed["file_id"].as_str().unwrap()).unwrap();
+        let file = app.db.get_file(file_id).await.unwrap().unwrap();
+        assert_eq!(file.file_name, "");
+        assert_eq!(file.content_type, "application/octet-stream");
+        assert!(file.encrypted_metadata.is_some());
+        assert_eq!(listed["content_type"], "text/plain");
+    }
+
+    // Filtered and sorted once decrypted, and paged by cursor across both
+    let uri = "/api/list/send?limit=1&sort=file_name&file_name=REPORT";
+    let (_, first) = app.json(get(uri, &alice)).await;
+    assert_eq!(first["results"], 2);
+    assert_eq!(first["files"][0]["file_name"], "a-report.txt");
+    let uri = format!("{}&cursor={}", uri, first["next_cursor"].as_str().unwrap());
+    let (_, second) = app.json(get(&uri, &alice)).await;
+    assert_eq!(second["files"][0]["file_name"], "b-report.txt");
+    assert_eq!(second["next_cursor"], Value::Null);
+
+    let (_, received) = app
+        .json(get("/api/list/receive?sort=file_name&order=desc", &bob))
+        .await;
+    assert_eq!(received["files"][0]["file_name"], "notes.txt");
+    let share_id = received["files"][0]["share_id"].as_str().unwrap();
+    for token in [&alice, &bob] {
+        let (status, details) = app
+            .json(get(&format!("/api/file/shares/{}", share_id), token))
+            .await;
+        assert_eq!(status, StatusCode::OK);
+        assert_eq!(details["share"]["file_name"], "notes.txt");
+    }
+}
+
+#[tokio::test]
+async fn name_searches_only_cover_the_newest_shares() {
+    let app = TestApp::with_config(|env| env.limits.name_search_window = 2, None);
+    let alice = app.user_with_token("alice@example.com").await;
+    app.register("bob@example.com").await;
+    for file_name in ["old-report.txt", "new-report.txt", "notes.txt"] {
+        app.upload_named(&alice, "bob@example.com", file_name, "text/plain", b"data")
+            .await;
+        // Distinct creation times, so which shares are newest is certain
+        tokio::time::sleep(std::time::Duration::from_millis(5)).await;
+    }
+
+    let (_, body) = app
+        .json(get("/api/list/send?file_name=report", &alice))
+        .await;
+    assert_eq!(body["results"], 1);
+    assert_eq!(body["files"][0]["file_name"], "new-report.txt");
+
+    // Lists not involving names still see everything
+    let (_, body) = app.json(get("/api/list/send", &alice)).await;
+    assert_eq!(body["results"], 3);
+}
+
+#[tokio::test]
+async fn key_material_is_wrapped_with_the_kek() {
+    let keyring = Keyring::parse(&kek::generate_key_line("k1")).unwrap();
+    let app = TestApp::with_kek(Some(Arc::new(keyring)));
+    let alice = app.user_with_token("alice@example.com").await;
+    let bob = app.user_with_token("bob@example.com").await;
+    app.upload(&alice, "bob@example.com", b"wrapped twice")
+        .await;
+
+    for entry in fs::read_dir(&app.key_dir).unwrap() {
+        let contents = fs::read_to_string(entry.unwrap().path()).unwrap();
+        assert!(contents.starts_with("-----BEGIN SECURE-SHARE WRAPPED KEY-----"));
+    }
+    let (_, sent) = app.json(get("/api/list/send", &alice)).await;
+    let file_id = Uuid::parse_str(sent["files"][0]["file_id"].as_str().unwrap()).unwrap();
+    let file = app.db.get_file(file_id).await.unwrap().unwrap();
+    assert_eq!(
+        kek::stored_key_id(&file.encrypted_aes_key)
+            .unwrap()
+            .as_deref(),
+        Some("k1")
+    );
+    let sender_key = file.sender_encrypted_aes_key.unwrap();
+    assert_eq!(
+        kek::stored_key_id(&sender_key).unwrap().as_deref(),
+        Some("k1")
+    );
+    assert_eq!(sent["files"][0]["file_name"], "notes.txt");
+
+    let (_, received) = app.json(get("/api/list/receive", &bob)).await;
+    let body = json!({
+        "shared_id": received["files"][0]["share_id"],
+        "password": SHARE_PASSWORD,
+    });
+    let (status, data) = app
+        .send(post_json("/api/file/register", Some(&bob), &body))
+        .await;
+    assert_eq!(status, StatusCode::OK);
+    assert_eq!(data, b"wrapped twice");
+}
+
+#[tokio::test(flavor = "multi_thread")]
+async fn concurrent_key_file_rewrites_do_not_share_a_staging_file() {
+    let keyring = Keyring::parse(&kek::generate_key_line("k1")).unwrap();
+    let key_dir = common::api::key_dir();
+    let env = common::api::config("", &key_dir);
+    let db = Arc::new(MemoryDb::default());
+    let repositories: common::api::Repositories = (db.clone(), db.clone(), db);
+    let app_state = common::api::app_state(env, repositories, None, Some(Arc::new(keyring)));
+    let app_state = Arc::new(app_state);
+    let path = keys::private_key_path(&key_dir.to_string_lossy(), Uuid::new_v4(), 1);
+
+    // As `kek rewrap` does while the server writes the same key
+    let pems: Vec<String> = (0..8).map(|n| format!("key {}", n)).collect();
+    let writes = pems.iter().cloned().map(|pem| {
+        let app_state = app_state.clone();
+        let path = path.clone();
+        tokio::spawn(async move { keys::write_key_file(&app_state, &path, &pem).await })
+    });
+    for write in futures_util::future::join_all(writes).await {
+        write.unwrap().unwrap();
+    }
+
+    let pem = keys::read_key_file_pem(&app_state, &path).await.unwrap();
+    assert!(pems.contains(&pem));
+    assert_eq!(fs::read_dir(&key_dir).unwrap().count(), 1);
+    fs::remove_dir_all(&key_dir).unwrap();
+}
+
+#[tokio::test]
+async fn rotated_key_pairs_keep_old_shares_readable() {
+    let app = TestApp::new();
+    let alice = app.user_with_token("alice@example.com").await;
+    let bob = app.user_with_token("bob@example.com").await;
+    app.upload_named(
+        &alice,
+        "bob@example.com",
+        "before.txt",
+        "text/plain",
+        b"before",
+    )
+    .await;
+
+    for token in [&alice, &bob] {
+        let (status, body) = app
+            .json(post_json("/api/users/keys/rotate", Some(token), &json!({})))
+            .await;
+        assert_eq!(status, StatusCode::OK, "{}", body);
+        assert_eq!(body["key"]["version"], 2);
+        assert_eq!(body["key"]["status"], "active");
+    }
+    let (status, body) = app
+        .upload_named(
+            &alice,
+            "bob@example.com",
+            "after.txt",
+            "text/plain",
+            b"after",
+        )
+        .await;
+    assert_eq!(status, StatusCode::OK, "{}", body);
+
+    let (_, keys) = app.json(get("/api/users/keys", &bob)).await;
+    let keys: Vec<_> = keys["keys"]
+        .as_array()
+        .unwrap()
+        .iter()
+        .map(|key| (key["version"].as_i64().unwrap(), key["status"].clone()))
+        .collect();
+    assert_eq!(keys, [(1, json!("retired")), (2, json!("active"))]);
+    let bob_user = app.db.get_user(None, None, Some("bob@example.com")).await;
+    let bob_user = bob_user.unwrap().unwrap();
+    assert!(app.key_dir.join(format!("{}.pem", bob_user.id)).exists());
+    assert!(app.key_dir.join(format!("{}.v2.pem", bob_user.id)).exists());
+
+    // Each file is read with the key pair it was wrapped to
+    let (_, received) = app
+        .json(get("/api/list/receive?sort=file_name", &bob))
+        .await;
+    let files = received["files"].as_array().unwrap();
+    let names: Vec<_> = files.iter().map(|file| &file["file_name"]).collect();
+    assert_eq!(names, ["after.txt", "before.txt"]);
+    for (file, contents) in files.iter().zip([&b"after"[..], b"before"]) {
+        let body = json!({ "shared_id": file["share_id"], "password": SHARE_PASSWORD });
+        let (status, data) = app
+            .send(post_json("/api/file/register", Some(&bob), &body))
+            .await;
+        assert_eq!(status, StatusCode::OK);
+        assert_eq!(data, contents);
+    }
+    let (_, sent) = app.json(get("/api/list/send?sort=file_name", &alice)).await;
+    let share_id = sent["files"][1]["share_id"].as_str().unwrap();
+    let (_, details) = app
+        .json(get(&format!("/api/file/shares/{}", share_id), &alice))
+        .await;
+    assert_eq!(details["share"]["file_name"], "before.txt");
+
+    let file_id = Uuid::parse_str(sent["files"][0]["file_id"].as_str().unwrap()).unwrap();
+    let file = app.db.get_file(file_id).await.unwrap().unwrap();
+    assert_eq!(
+        (file.recipient_key_version, file.sender_key_version),
+        (2, 2)
+    );
+}
+
+#[tokio::test]
+async fn rotation_claims_a_key_file_left_by_a_crash() {
+    let app = TestApp::new();
+    let token = app.user_with_token("alice@example.com").await;
+    let user = app.db.get_user(None, None, Some("alice@example.com")).await;
+    let user = user.unwrap().unwrap();
+    // The key file of a rotation that never reached the database
+    std::fs::copy(
+        app.key_dir.join(format!("{}.pem", user.id)),
+        app.key_dir.join(format!("{}.v2.pem", user.id)),
+    )
+    .unwrap();
+
+    let (status, body) = app
+        .json(post_json(
+            "/api/users/keys/rotate",
+            Some(&token),
+            &json!({}),
+        ))
+        .await;
+    assert_eq!(status, StatusCode::OK, "{}", body);
+    assert_eq!(body["key"]["version"], 2);
+    let (_, keys) = app.json(get("/api/users/keys", &token)).await;
+    assert_eq!(keys["keys"][0]["public_key"], keys["keys"][1]["public_key"]);
+
+    // A leftover of another type is only replaced once it is abandoned
+    let body = json!({ "key_type": "rsa" });
+    std::fs::copy(
+        app.key_dir.join(format!("{}.pem", user.id)),
+        app.key_dir.join(format!("{}.v3.pem", user.id)),
+    )
+    .unwrap();
+    let (status, _) = app
+        .json(post_json("/api/users/keys/rotate", Some(&token), &body))
+        .await;
+    assert_eq!(status, StatusCode::CONFLICT);
+}
+
+#[tokio::test(flavor = "multi_thread")]
+async fn concurrent_rotations_store_the_key_written_to_disk() {
+    let app = TestApp::new();
+    let token = app.user_with_token("alice@example.com").await;
+    let user = app.db.get_user(None, None, Some("alice@example.com")).await;
+    let user = user.unwrap().unwrap();
+
+    let rotate = || {
+        app.json(post_json(
+            "/api/users/keys/rotate",
+            Some(&token),
+            &json!({}),
+        ))
+    };
+    let ((first, _), (second, _)) = tokio::join!(rotate(), rotate());
+    for status in [first, second] {
+        assert!(
+            [StatusCode::OK, StatusCode::CONFLICT].contains(&status),
+            "{}",
+            status
+        );
+    }
+
+    for key in app.db.get_user_keys(user.id).await.unwrap() {
+        let path = keys::private_key_path(&app.key_dir.to_string_lossy(), user.id, key.version);
+        let pem = fs::read_to_string(path).unwrap();
+        let public_key = secure_share_crypto::private_key_from_pem(&pem)
+            .unwrap()
+            .public_key()
+            .to_pem()
+            .unwrap();
+        assert_eq!(key.public_key, BASE64_STANDARD.encode(public_key));
+    }
+    let leftovers = fs::read_dir(&app.key_dir)
+        .unwrap()
+        .filter(|entry| entry.as_ref().unwrap().path().extension() == Some("tmp".as_ref()))
+        .count();
+    assert_eq!(leftovers, 0);
+}
+
+#[tokio::test]
+async fn rsa_and_x25519_key_pairs_share_both_ways() {
+    let app = TestApp::new();
+    let alice = app.user_with_token("alice@example.com").await;
+    let body = json!({
+        "name": "Legacy Test Account",
+        "email": "carol@example.com",
+        "password": PASSWORD,
+        "passwordConfirm": PASSWORD,
+        "key_type": "rsa",
+    });
+    let (status, body) = app.json(post_json("/api/auth/register", None, &body)).await;
+    assert_eq!(status, StatusCode::CREATED, "{}", body);
+    let carol = app.login("carol@example.com").await;
+
+    for (token, key_type) in [(&alice, "x25519"), (&carol, "rsa")] {
+        let (_, keys) = app.json(get("/api/users/keys", token)).await;
+        assert_eq!(keys["keys"][0]["key_type"], key_type);
+    }
+
+    let (status, body) = app
+        .upload_named(
+            &alice,
+            "carol@example.com",
+            "to-rsa.txt",
+            "text/plain",
+            b"to rsa",
+        )
+        .await;
+    assert_eq!(status, StatusCode::OK, "{}", body);
+    let (status, body) = app
+        .upload_named(
+            &carol,
+            "alice@example.com",
+            "to-x25519.txt",
+            "text/plain",
+            b"to x25519",
+        )
+        .await;
+    assert_eq!(status, StatusCode::OK, "{}", body);
+
+    for (token, name, contents) in [
+        (&carol, "to-rsa.txt", &b"to rsa"[..]),
+        (&alice, "to-x25519.txt", b"to x25519"),
+    ] {
+        let (_, received) = app.json(get("/api/list/receive", token)).await;
+        let file = &received["files"][0];
+        assert_eq!(file["file_name"], name);
+        let body = json!({ "shared_id": file["share_id"], "password": SHARE_PASSWORD });
+        let (status, data) = app
+            .send(post_json("/api/file/register", Some(token), &body))
+            .await;
+        assert_eq!(status, StatusCode::OK);
+        assert_eq!(data, contents);
+    }
+
+    // Rotating without a type uses the server's default
+    let (status, body) = app
+        .json(post_json(
+            "/api/users/keys/rotate",
+            Some(&carol),
+            &json!({}),
+        ))
+        .await;
+    assert_eq!(status, StatusCode::OK, "{}", body);
+    assert_eq!(body["key"]["key_type"], "x25519");
+    let (_, sent) = app.json(get("/api/list/send", &carol)).await;
+    assert_eq!(sent["files"][0]["file_name"], "to-x25519.txt");
+
+    let body = json!({ "key_type": "dsa" });
+    let (status, _) = app
+        .json(post_json("/api/users/keys/rotate", Some(&carol), &body))
+        .await;
+    assert_eq!(status, StatusCode::UNPROCESSABLE_ENTITY);
+}
+
+#[tokio::test]
+async fn downloads_report_whether_the_sender_signature_holds() {
+    let app = TestApp::new();
+    let alice = app.user_with_token("alice@example.com").await;
+    let bob = app.user_with_token("bob@example.com").await;
+    app.upload(&alice, "bob@example.com", b"signed file").await;
+
+    // Copies of the upload: one claiming another size, one without signature
+    let (_, received) = app.json(get("/api/list/receive", &bob)).await;
+    let share_id = Uuid::parse_str(received["files"][0]["share_id"].as_str().unwrap()).unwrap();
+    let bob_id = app
+        .db
+        .get_user(None, None, Some("bob@example.com"))
+        .await
+        .unwrap()
+        .unwrap()
+        .id;
+    let link = app.db.get_shared(share_id, bob_id).await.unwrap().unwrap();
+    let file = app
+        .db
+        .get_file(link.file_id.unwrap())
+        .await
+        .unwrap()
+        .unwrap();
+    assert_eq!(file.signature.as_ref().map(Vec::len), Some(64));
+    let copies = [
+        (100, file.signature.clone(), file.signing_key_version),
+        (200, None, None),
+    ];
+    for (file_size, signature, signing_key_version) in copies {
+        app.db
+            .save_encrypted_file(NewFile {
+                user_id: file.user_id.unwrap(),
+                file_name: String::new(),
+                file_size,
+                content_type: file.content_type.clone(),
+                recipient_user_id: bob_id,
+                password: link.password.clone(),
+                expiration_date: Utc::now() + Duration::days(1),
+                encrypted_aes_key: file.encrypted_aes_key.clone(),
+                encrypted_file: file.encrypted_file.clone(),
+                iv: file.iv.clone(),
+                encrypted_metadata: file.encrypted_metadata.clone(),
+                sender_encrypted_aes_key: file.sender_encrypted_aes_key.clone(),
+                recipient_key_version: file.recipient_key_version,
+                sender_key_version: file.sender_key_version,
+                content_digest: file.content_digest.clone(),
+                signature,
+                signing_key_version,
+            })
+            .await
+            .unwrap();
+    }
+
+    let (_, received) = app.json(get("/api/list/receive", &bob)).await;
+    for listed in received["files"].as_array().unwrap() {
+        let expected = match listed["file_size"].as_i64().unwrap() {
+            11 => "verified",
+            100 => "invalid",
+            _ => "unsigned",
+        };
+        let response = app
+            .retrieve(&bob, &listed["share_id"], SHARE_PASSWORD)
+            .await;
+        assert_eq!(response.status(), StatusCode::OK);
+        assert_eq!(response.headers()["x-signature-status"], expected);
+
+        let uri = format!("/api/file/shares/{}", listed["share_id"].as_str().unwrap());
+        for token in [&alice, &bob] {
+            let (_, details) = app.json(get(&uri, token)).await;
+            assert_eq!(details["share"]["signature_status"], expected);
+        }
+    }
+}
+
+fn put_signing_key(token: &str, public_key: &str) -> Request<Vec<u8>> {
+    let body = json!({ "public_key": public_key });
+    let mut request = post_json("/api/users/signing-key", Some(token), &body);
+    *request.method_mut() = Method::PUT;
+    request
+}
+
+#[tokio::test]
+async fn client_held_signing_keys_stop_server_signing() {
+    let app = TestApp::new();
+    let alice = app.user_with_token("alice@example.com").await;
+    let bob = app.user_with_token("bob@example.com").await;
+    app.upload(&alice, "bob@example.com", b"signed file").await;
+    let alice_id = app
+        .db
+        .get_user(None, None, Some("alice@example.com"))
+        .await
+        .unwrap()
+        .unwrap()
+        .id;
+
+    let signing_key = SigningKey::generate(&mut rand::rngs::OsRng);
+    let pem = verifying_key_to_pem(&signing_key.verifying_key()).unwrap();
+    let (status, body) = app
+        .json(put_signing_key(&alice, &BASE64_STANDARD.encode(&pem)))
+        .await;
+    assert_eq!(status, StatusCode::OK, "{}", body);
+    assert_eq!(body["key"]["version"], 2);
+    assert_eq!(body["key"]["held_by"], "client");
+    let key_file = app.key_dir.join(format!("{}.signing.pem", alice_id));
+    assert!(!key_file.exists());
+
+    app.upload(&alice, "bob@example.com", b"unsigned file")
+        .await;
+    let (_, received) = app.json(get("/api/list/receive", &bob)).await;
+    for listed in received["files"].as_array().unwrap() {
+        let (expected, version) = match listed["file_size"].as_i64().unwrap() {
+            11 => ("verified", json!(1)),
+            _ => ("unsigned", Value::Null),
+        };
+        let response = app
+            .retrieve(&bob, &listed["share_id"], SHARE_PASSWORD)
+            .await;
+        assert_eq!(response.headers()["x-signature-status"], expected);
+        let uri = format!("/api/file/shares/{}", listed["share_id"].as_str().unwrap());
+        let (_, details) = app.json(get(&uri, &bob)).await;
+        assert_eq!(details["share"]["signing_key_version"], version);
+    }
+
+    let (status, _) = app
+        .json(put_signing_key(
+            &alice,
+            &BASE64_STANDARD.encode("not a key"),
+        ))
+        .await;
+    assert_eq!(status, StatusCode::BAD_REQUEST);
+}
+
+#[tokio::test(flavor = "multi_thread")]
+async fn concurrent_first_uploads_publish_the_signing_key_on_disk() {
+    let key_dir = common::api::key_dir();
+    let env = common::api::config("", &key_dir);
+    let db = Arc::new(MemoryDb::default());
+    let repositories: common::api::Repositories = (db.clone(), db.clone(), db.clone());
+    let app_state = Arc::new(common::api::app_state(env, repositories, None, None));
+    // An account from before uploads were signed
+    let user = db
+        .save_user(
+            "Test User Account".to_string(),
+            "alice@example.com".to_string(),
+            "hash".to_string(),
+        )
+        .await
+        .unwrap();
+
+    let uploads = (0..8).map(|_| {
+        let app_state = app_state.clone();
+        let user = user.clone();
+        tokio::spawn(async move { keys::signing_key(&app_state, &user).await })
+    });
+    let mut verifying_keys = Vec::new();
+    for upload in futures_util::future::join_all(uploads).await {
+        let (version, signing_key) = upload.unwrap().unwrap().unwrap();
+        assert_eq!(version, 1);
+        verifying_keys.push(signing_key.verifying_key());
+    }
+    verifying_keys.dedup();
+    assert_eq!(verifying_keys.len(), 1);
+
+    let path = keys::signing_key_path(&key_dir.to_string_lossy(), user.id);
+    let pem = fs::read_to_string(path).unwrap();
+    let on_disk = secure_share_crypto::signing_key_from_pem(&pem).unwrap();
+    let stored = db.get_signing_keys(user.id).await.unwrap();
+    let public_key = verifying_key_to_pem(&on_disk.verifying_key()).unwrap();
+    assert_eq!(stored.len(), 1);
+    assert_eq!(stored[0].public_key, BASE64_STANDARD.encode(public_key));
+    assert_eq!(on_disk.verifying_key(), verifying_keys[0]);
+    fs::remove_dir_all(&key_dir).unwrap();
+}
+
+fn envelope_form(token: &str, fields: &[(&str, Vec<u8>)]) -> Request<Vec<u8>> {
+    let mut body = Vec::new();
+    for (name, value) in fields {
+        body.extend_from_slice(
+            format!("--{BOUNDARY}\r\nContent-Disposition: form-data; name=\"{name}\"\r\n\r\n")
+                .as_bytes(),
+        );
+        body.extend_from_slice(value);
+        body.extend_from_slice(b"\r\n");
+    }
+    body.extend_from_slice(format!("--{BOUNDARY}--\r\n").as_bytes());
+    Request::post("/api/file/envelope")
+        .header(header::AUTHORIZATION, format!("Bearer {}", token))
+        .header(
+            header::CONTENT_TYPE,
+            format!("multipart/form-data; boundary={}", BOUNDARY),
+        )
+        .body(body)
+        .unwrap()
+}
+
+#[tokio::test]
+async fn envelope_sizes_that_overflow_the_padding_are_rejected() {
+    let app = TestApp::with_config(|env| env.limits.max_upload_bytes = usize::MAX, None);
+    let alice = app.user_with_token("alice@example.com").await;
+    let expiration_date = (Utc::now() + Duration::days(1)).to_rfc3339();
+
+    for file_size in [i64::MAX, i64::MAX - 15] {
+        let fields = [
+            ("ciphertext", vec![0; 16]),
+            ("file_size", file_size.to_string().into_bytes()),
+            ("recipient_email", b"bob@example.com".to_vec()),
+            ("password", SHARE_PASSWORD.as_bytes().to_vec()),
+            ("expiration_date", expiration_date.clone().into_bytes()),
+        ];
+        let (status, body) = app.json(envelope_form(&alice, &fields)).await;
+        assert_eq!(status, StatusCode::BAD_REQUEST, "{}", body);
+        assert_eq!(body["code"], "invalid_envelope");
+    }
+}
+
+#[tokio::test]
+async fn envelopes_sealed_by_the_sender_are_stored_and_served_as_sealed() {
+    let app = TestApp::new();
+    let alice = app.user_with_token("alice@example.com").await;
+    let bob = app.user_with_token("bob@example.com").await;
+    let mut ids = Vec::new();
+    for email in ["alice@example.com", "bob@example.com"] {
+        let user = app.db.get_user(None, None, Some(email)).await.unwrap();
+        ids.push(user.unwrap().id);
+    }
+    let (alice_id, bob_id) = (ids[0], ids[1]);
+    let signing_key = SigningKey::generate(&mut rand::rngs::OsRng);
+    let pem = verifying_key_to_pem(&signing_key.verifying_key()).unwrap();
+    let (status, _) = app
+        .json(put_signing_key(&alice, &BASE64_STANDARD.encode(&pem)))
+        .await;
+    assert_eq!(status, StatusCode::OK);
+
+    let alice_key = app.db.get_active_user_key(alice_id).await.unwrap().unwrap();
+    let bob_key = app.db.get_active_user_key(bob_id).await.unwrap().unwrap();
+    let metadata = FileMetadata {
+        file_name: "sealed.txt".to_string(),
+        content_type: "text/plain".to_string(),
+    };
+    let data = b"sealed by alice";
+    let sealed = seal_file(
+        &mut rand::rngs::OsRng,
+        data,
+        &metadata,
+        &Sender {
+            id: alice_id.into_bytes(),
+            public_key: &keys::decode_public_key(&alice_key.public_key).unwrap(),
+            signing_key: &signing_key,
+        },
+        &Recipient {
+            id: bob_id.into_bytes(),
+            public_key: &keys::decode_public_key(&bob_key.public_key).unwrap(),
+        },
+    )
+    .unwrap();
+    let expiration_date = (Utc::now() + Duration::days(1)).to_rfc3339();
+    let fields = |ciphertext: Vec<u8>, signing_key_version: i32| {
+        vec![
+            ("ciphertext", ciphertext),
+            ("iv", sealed.iv.clone()),
+            ("encrypted_key", sealed.encrypted_key.clone()),
+            ("sender_encrypted_key", sealed.sender_encrypted_key.clone()),
+            ("encrypted_metadata", sealed.encrypted_metadata.clone()),
+            ("signature", sealed.signature.clone()),
+            ("file_size", data.len().to_string().into_bytes()),
+            (
+                "recipient_key_version",
+                bob_key.version.to_string().into_bytes(),
+            ),
+            (
+                "sender_key_version",
+                alice_key.version.to_string().into_bytes(),
+            ),
+            (
+                "signing_key_version",
+                signing_key_version.to_string().into_bytes(),
+            ),
+            ("recipient_email", b"bob@example.com".to_vec()),
+            ("password", SHARE_PASSWORD.as_bytes().to_vec()),
+            ("expiration_date", expiration_date.clone().into_bytes()),
+        ]
+    };
+
+    // Claiming the retired server key, or not matching the signature
+    let (status, _) = app
+        .json(envelope_form(&alice, &fields(sealed.ciphertext.clone(), 1)))
+        .await;
+    assert_eq!(status, StatusCode::CONFLICT);
+    let mut tampered = sealed.ciphertext.clone();
+    tampered[0] ^= 1;
+    let (status, body) = app.json(envelope_form(&alice, &fields(tampered, 2))).await;
+    assert_eq!(status, StatusCode::BAD_REQUEST);
+    assert_eq!(body["code"], "invalid_envelope");
+
+    // Sizes past the upload limit, below zero, unreadable or not fitting the
+    // ciphertext, and a ciphertext not fitting the size
+    let too_long = [sealed.ciphertext.clone(), vec![0; 16]].concat();
+    for (file_size, ciphertext, expected) in [
+        (
+            i64::MAX.to_string(),
+            &sealed.ciphertext,
+            StatusCode::PAYLOAD_TOO_LARGE,
+        ),
+        (
+            "-1".to_string(),
+            &sealed.ciphertext,
+            StatusCode::UNPROCESSABLE_ENTITY,
+        ),
+        (
+            "size".to_string(),
+            &sealed.ciphertext,
+            StatusCode::BAD_REQUEST,
+        ),
+        (
+            (data.len() + 16).to_string(),
+            &sealed.ciphertext,
+            StatusCode::BAD_REQUEST,
+        ),
+        ("0".to_string(), &sealed.ciphertext, StatusCode::BAD_REQUEST),
+        (data.len().to_string(), &too_long, StatusCode::BAD_REQUEST),
+    ] {
+        let mut fields = fields(ciphertext.clone(), 2);
+        fields[6].1 = file_size.clone().into_bytes();
+        let (status, _) = app.json(envelope_form(&alice, &fields)).await;
+        assert_eq!(status, expected, "{}", file_size);
+    }
+
+    let (status, body) = app
+        .json(envelope_form(&alice, &fields(sealed.ciphertext.clone(), 2)))
+        .await;
+    assert_eq!(status, StatusCode::OK, "{}", body);
+    let (_, sent) = app.json(get("/api/list/send", &alice)).await;
+    assert_eq!(sent["files"][0]["file_name"], "sealed.txt");
+
+    let (_, received) = app.json(get("/api/list/receive", &bob)).await;
+    let share_id = &received["files"][0]["share_id"];
+    let body = json!({ "shared_id": share_id, "password": SHARE_PASSWORD });
+    let (status, body) = app
+        .json(post_json("/api/file/envelope/retrieve", Some(&bob), &body))
+        .await;
+    assert_eq!(status, StatusCode::OK, "{}", body);
+    let envelope = &body["envelope"];
+    assert_eq!(envelope["signature_status"], "verified");
+    assert_eq!(envelope["signing_key_version"], 2);
+    assert_eq!(envelope["sender_id"], alice_id.to_string());
+    let field = |name: &str| {
+        BASE64_STANDARD
+            .decode(envelope[name].as_str().unwrap())
+            .unwrap()
+    };
+    assert_eq!(field("ciphertext"), sealed.ciphertext);
+    let pem = fs::read_to_string(app.key_dir.join(format!("{}.pem", bob_id))).unwrap();
+    let private_key = secure_share_crypto::private_key_from_pem(&pem).unwrap();
+    let decrypted = secure_share_crypto::decrypt(
+        &field("encrypted_key"),
+        &field("ciphertext"),
+        &field("iv"),
+        &private_key,
+    )
+    .unwrap();
+    assert_eq!(decrypted, data);
+
+    let response = app.retrieve(&bob, share_id, SHARE_PASSWORD).await;
+    assert_eq!(response.headers()["x-signature-status"], "verified");
+    assert_eq!(response.body(), data);
+}
@@ -0,0 +1,232 @@
+//! The transit provider runs against a stand-in speaking the subset of Vault's
+//! transit API it uses, so no Vault is needed.
+
+use std::{
+    collections::HashMap,
+    sync::{Arc, Mutex},
+};
+
+use axum::{
+    Json, Router,
+    extract::{Path, State},
+    http::{HeaderMap, StatusCode},
+    routing::{get, post},
+};
+use base64::{Engine, prelude::BASE64_STANDARD};
+use secure_share::kek::{
+    KekError, KeyEncryptionKey, Keyring, TransitKek, WrappedKey, generate_key_line, stored_key_id,
+    unwrap_stored, wrap_stored,
+};
+use serde_json::{Value, json};
+
+const TOKEN: &str = "test-token";
+
+#[tokio::test]
+async fn keyring_unwraps_with_old_keys_after_rotation() {
+    let old_key = generate_key_line("old");
+    let old = Keyring::parse(&old_key).unwrap();
+    let wrapped = old.wrap(b"private key").await.unwrap();
+    assert_eq!(wrapped.key_id, "old");
+
+    let rotated = Keyring::parse(&format!(
+        "# rotated\n{}\n{}\n",
+        old_key,
+        generate_key_line("new")
+    ))
+    .unwrap();
+    assert_eq!(rotated.current_key_id().await.unwrap(), "new");
+    assert_eq!(rotated.unwrap(&wrapped).await.unwrap(), b"private key");
+    let rewrapped = rotated.wrap(b"private key").await.unwrap();
+    assert_eq!(rewrapped.key_id, "new");
+    assert!(matches!(
+        old.unwrap(&rewrapped).await,
+        Err(KekError::UnknownKey(key_id)) if key_id == "new"
+    ));
+}
+
+#[tokio::test]
+async fn tampered_or_relabelled_keys_are_rejected() {
+    let keyring = Keyring::parse(&format!(
+        "{},{}",
+        generate_key_line("a"),
+        generate_key_line("b")
+    ))
+    .unwrap();
+    let wrapped = keyring.wrap(&[7; 32]).await.unwrap();
+
+    let mut tampered = wrapped.clone();
+    tampered.ciphertext[20] ^= 1;
+    assert!(matches!(
+        keyring.unwrap(&tampered).await,
+        Err(KekError::Integrity)
+    ));
+
+    let relabelled = WrappedKey {
+        key_id: "a".to_string(),
+        ..wrapped
+    };
+    assert!(matches!(
+        keyring.unwrap(&relabelled).await,
+        Err(KekError::Integrity)
+    ));
+}
+
+#[tokio::test]
+async fn keys_stored_without_a_kek_pass_through() {
+    let keyring = Keyring::parse(&generate_key_line("k1")).unwrap();
+    let rsa_ciphertext = [0x5a; 256];
+
+    assert_eq!(stored_key_id(&rsa_ciphertext).unwrap(), None);
+    assert_eq!(
+        unwrap_stored(Some(&keyring), &rsa_ciphertext)
+            .await
+            .unwrap(),
+        rsa_ciphertext
+    );
+    assert_eq!(
+        wrap_stored(None, &rsa_ciphertext).await.unwrap(),
+        rsa_ciphertext
+    );
+
+    let stored = wrap_stored(Some(&keyring), &rsa_ciphertext).await.unwrap();
+    assert_eq!(stored_key_id(&stored).unwrap().as_deref(), Some("k1"));
+    assert_eq!(
+        unwrap_stored(Some(&keyring), &stored).await.unwrap(),
+        rsa_ciphertext
+    );
+    assert!(matches!(
+        unwrap_stored(None, &stored).await,
+        Err(KekError::NotConfigured)
+    ));
+}
+
+#[test]
+fn keyring_rejects_malformed_keys() {
+    for keys in [
+        "",
+        "# only a comment",
+        "no-separator",
+        "short:c2hvcnQ=",
+        ":AAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAA=",
+    ] {
+        assert!(
+            matches!(Keyring::parse(keys), Err(KekError::Malformed(_))),
+            "{}",
+            keys
+        );
+    }
+    let key = generate_key_line("twice");
+    assert!(Keyring::parse(&format!("{}\n{}", key, key)).is_err());
+}
+
+/// Versions of one transit key and every plaintext it encrypted.
+#[derive(Default)]
+struct Transit {
+    latest_version: u32,
+    plaintexts: HashMap<String, String>,
+}
+
+type TransitState = Arc<Mutex<Transit>>;
+
+fn authorized(headers: &HeaderMap) -> Result<(), StatusCode> {
+    match headers.get("X-Vault-Token") {
+        Some(token) if token == TOKEN => Ok(()),
+        _ => Err(StatusCode::FORBIDDEN),
+    }
+}
+
+async fn key_info(
+    State(transit): State<TransitState>,
+    headers: HeaderMap,
+    Path(_name): Path<String>,
+) -> Result<Json<Value>, StatusCode> {
+    authorized(&headers)?;
+    let latest_version = transit.lock().unwrap().latest_version;
+    Ok(Json(
+        json!({ "data": { "latest_version": latest_version } }),
+    ))
+}
+
+async fn encrypt(
+    State(transit): State<TransitState>,
+    headers: HeaderMap,
+    Path(_name): Path<String>,
+    Json(body): Json<Value>,
+) -> Result<Json<Value>, StatusCode> {
+    authorized(&headers)?;
+    let mut transit = transit.lock().unwrap();
+    let ciphertext = format!(
+        "vault:v{}:{}",
+        transit.latest_version,
+        transit.plaintexts.len()
+    );
+    let plaintext = body["plaintext"].as_str().unwrap().to_string();
+    transit.plaintexts.insert(ciphertext.clone(), plaintext);
+    Ok(Json(json!({ "data": { "ciphertext": ciphertext } })))
+}
+
+async fn decrypt(
+    State(transit): State<TransitState>,
+    headers: HeaderMap,
+    Path(_name): Path<String>,
+    Json(body): Json<Value>,
+) -> Result<Json<Value>, StatusCode> {
+    authorized(&headers)?;
+    let transit = transit.lock().unwrap();
+    let plaintext = transit
+        .plaintexts
+        .get(body["ciphertext"].as_str().unwrap())
+        .ok_or(StatusCode::BAD_REQUEST)?;
+    Ok(Json(json!({ "data": { "plaintext": plaintext } })))
+}
+
+async fn start_transit() -> (String, TransitState) {
+    let transit = Arc::new(Mutex::new(Transit {
+        latest_version: 1,
+        ..Transit::default()
+    }));
+    let app = Router::new()
+        .route("/v1/transit/keys/{name}", get(key_info))
+        .route("/v1/transit/encrypt/{name}", post(encrypt))
+        .route("/v1/transit/decrypt/{name}", post(decrypt))
+        .with_state(transit.clone());
+    let listener = tokio::net::TcpListener::bind("127.0.0.1:0").await.unwrap();
+    let url = format!("http://{}/", listener.local_addr().unwrap());
+    tokio::spawn(async move { axum::serve(listener, app).await.unwrap() });
+    (url, transit)
+}
+
+#[tokio::test]
+async fn transit_provider_wraps_through_the_api() {
+    let (url, transit) = start_transit().await;
+    let kek = TransitKek::new(&url, "secure-share", TOKEN.to_string());
+
+    assert_eq!(kek.current_key_id().await.unwrap(), "secure-share:v1");
+    let wrapped = kek.wrap(b"aes key").await.unwrap();
+    assert_eq!(wrapped.key_id, "secure-share:v1");
+    let sent = transit.lock().unwrap().plaintexts.values().next().cloned();
+    assert_eq!(sent, Some(BASE64_STANDARD.encode(b"aes key")));
+
+    // Rotated in Vault; old versions still decrypt
+    transit.lock().unwrap().latest_version = 2;
+    assert_eq!(kek.current_key_id().await.unwrap(), "secure-share:v2");
+    assert_eq!(
+        kek.wrap(b"aes key").await.unwrap().key_id,
+        "secure-share:v2"
+    );
+    assert_eq!(kek.unwrap(&wrapped).await.unwrap(), b"aes key");
+
+    let other_key = WrappedKey {
+        key_id: "other:v1".to_string(),
+        ..wrapped
+    };
+    assert!(matches!(
+        kek.unwrap(&other_key).await,
+        Err(KekError::UnknownKey(_))
+    ));
+    let unauthorized = TransitKek::new(&url, "secure-share", "wrong".to_string());
+    assert!(matches!(
+        unauthorized.wrap(b"aes key").await,
+        Err(KekError::Provider(_))
+    ));
+}
@@ -0,0 +1,47 @@
+use secure_share::utils::media_type::{content_disposition, detect, sanitize_file_name};
+
+#[test]
+fn detect_prefers_signatures_then_declared_type_then_extension() {
+    let png = b"\x89PNG\r\n\x1a\n\0\0\0\rIHDR";
+    assert_eq!(detect(png, "photo.txt", "text/plain"), "image/png");
+    assert_eq!(detect(b"RIFF\0\0\0\0WEBPVP8 ", "x", ""), "image/webp");
+    // ZIP containers keep the more specific type of their extension
+    let zip = b"PK\x03\x04rest";
+    assert_eq!(
+        detect(zip, "report.docx", ""),
+        "application/vnd.openxmlformats-officedocument.wordprocessingml.document"
+    );
+    assert_eq!(detect(zip, "archive.bin", ""), "application/zip");
+
+    assert_eq!(
+        detect(b"a,b", "data.csv", "Text/Plain; charset=utf-8"),
+        "text/plain"
+    );
+    assert_eq!(
+        detect(b"a,b", "data.csv", "application/octet-stream"),
+        "text/csv"
+    );
+    assert_eq!(detect(b"a,b", "data.csv", "not a type"), "text/csv");
+    assert_eq!(detect(b"\0\x01", "blob", ""), "application/octet-stream");
+}
+
+#[test]
+fn file_names_cannot_escape_their_header() {
+    assert_eq!(sanitize_file_name("C:\\Users\\me\\notes.txt"), "notes.txt");
+    assert_eq!(sanitize_file_name("/etc/.."), "download");
+    assert_eq!(sanitize_file_name("  \r\n "), "download");
+
+    let disposition = content_disposition(
+        "a\"b\r\nSet-Cookie: token=x; c.txt",
+        "application/octet-stream",
+    );
+    assert_eq!(
+        disposition,
+        "attachment; filename=\"a_bSet-Cookie: token=x; c.txt\"; \
+         filename*=UTF-8''a%22bSet-Cookie%3A%20token%3Dx%3B%20c.txt"
+    );
+    assert!(!disposition.contains(['\r', '\n']));
+
+    assert!(content_disposition("scan.png", "image/png").starts_with("inline;"));
+    assert!(content_disposition("logo.svg", "image/svg+xml").starts_with("attachment;"));
+}
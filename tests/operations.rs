@@ -0,0 +1,74 @@
+//! Probes and metrics for the platform running the server.
+
+mod common;
+
+use axum::http::{Request, StatusCode, header};
+use serde_json::json;
+
+use common::api::{TestApp, TestClient, get, key_dir};
+
+fn unauthenticated(uri: &str) -> Request<Vec<u8>> {
+    Request::get(uri).body(Vec::new()).unwrap()
+}
+
+#[tokio::test]
+async fn ready_while_every_dependency_is_available() {
+    let app = TestApp::new();
+
+    let (status, body) = app.json(unauthenticated("/readyz")).await;
+    assert_eq!(status, StatusCode::OK);
+    assert_eq!(
+        body,
+        json!({
+            "status": "successful",
+            "checks": { "database": "ok", "key_directory": "ok", "kek": "disabled" },
+        })
+    );
+}
+
+#[tokio::test]
+async fn unavailable_without_revealing_what_failed() {
+    // A regular file where the key directory's parent should be
+    let blocked = key_dir();
+    std::fs::write(&blocked, b"").unwrap();
+    let app = TestApp::with_config(
+        |env| env.storage.private_key_dir = blocked.join("keys").to_string_lossy().into_owned(),
+        None,
+    );
+
+    let (status, body) = app.json(unauthenticated("/readyz")).await;
+    assert_eq!(status, StatusCode::SERVICE_UNAVAILABLE);
+    assert_eq!(
+        body,
+        json!({
+            "status": "fail",
+            "checks": { "database": "ok", "key_directory": "fail", "kek": "disabled" },
+        })
+    );
+    std::fs::remove_file(&blocked).unwrap();
+}
+
+#[tokio::test]
+async fn metrics_need_the_configured_token() {
+    let app = TestApp::new();
+    let (status, _) = app.send(unauthenticated("/metrics")).await;
+    assert_eq!(status, StatusCode::NOT_FOUND);
+
+    let app = TestApp::with_config(
+        |env| env.metrics.token = Some("scrape-token".to_string()),
+        None,
+    );
+    let (status, _) = app.send(unauthenticated("/metrics")).await;
+    assert_eq!(status, StatusCode::UNAUTHORIZED);
+    let (status, _) = app.send(get("/metrics", "test-secret")).await;
+    assert_eq!(status, StatusCode::UNAUTHORIZED);
+
+    let response = app.respond(get("/metrics", "scrape-token")).await;
+    assert_eq!(response.status(), StatusCode::OK);
+    assert!(
+        response.headers()[header::CONTENT_TYPE]
+            .to_str()
+            .unwrap()
+            .starts_with("text/plain"),
+    );
+}
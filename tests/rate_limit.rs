@@ -0,0 +1,141 @@
+//! Token bucket math, both stores, and what a limited client gets back.
+
+mod common;
+
+use std::time::Duration;
+
+use axum::http::{StatusCode, header};
+use secure_share::{
+    db::DbPool,
+    migrations,
+    rate_limit::{
+        Decision, MemoryRateLimitStore, PgRateLimitStore, Quota, RateLimitStore, take_token,
+    },
+};
+use serde_json::{Value, json};
+
+use common::{
+    TestDatabase,
+    api::{TestApp, TestClient, post_json},
+};
+
+#[test]
+fn buckets_refill_at_the_quota_rate_up_to_the_burst() {
+    let quota = Quota::per_minute(6);
+
+    assert_eq!(take_token(6.0, 0.0, &quota), (5.0, Decision::Allowed));
+    // A token every ten seconds
+    assert_eq!(take_token(0.0, 10.0, &quota), (0.0, Decision::Allowed));
+    assert_eq!(take_token(0.0, 3600.0, &quota), (5.0, Decision::Allowed));
+    // Clocks going backwards refill nothing
+    assert_eq!(
+        take_token(0.5, -10.0, &quota),
+        (
+            0.5,
+            Decision::Limited {
+                retry_after: Duration::from_secs(5)
+            }
+        )
+    );
+    assert_eq!(
+        take_token(0.0, 0.0, &quota),
+        (
+            0.0,
+            Decision::Limited {
+                retry_after: Duration::from_secs(10)
+            }
+        )
+    );
+}
+
+async fn drains_after_the_burst(store: &dyn RateLimitStore) {
+    let quota = Quota::per_minute(3);
+    for _ in 0..3 {
+        let decision = store.take("test:ip:127.0.0.1", &quota).await.unwrap();
+        assert_eq!(decision, Decision::Allowed);
+    }
+    let Decision::Limited { retry_after } = store.take("test:ip:127.0.0.1", &quota).await.unwrap()
+    else {
+        panic!("a fourth request within the minute was allowed");
+    };
+    assert!(retry_after > Duration::from_secs(19), "{:?}", retry_after);
+    assert!(retry_after <= Duration::from_secs(20), "{:?}", retry_after);
+    // Buckets are per key
+    let decision = store.take("test:ip:127.0.0.2", &quota).await.unwrap();
+    assert_eq!(decision, Decision::Allowed);
+}
+
+#[tokio::test]
+async fn memory_store_limits_each_key() {
+    let store = MemoryRateLimitStore::default();
+    drains_after_the_burst(&store).await;
+    // Nothing has been idle for long yet
+    assert_eq!(store.prune().await.unwrap(), 0);
+}
+
+#[tokio::test]
+async fn postgres_store_limits_each_key_and_prunes_idle_buckets() {
+    let Some(database) = TestDatabase::create().await else {
+        eprintln!("TEST_DATABASE_URL is not set, skipping");
+        return;
+    };
+    let pool = DbPool::connect(&database.url, &Default::default())
+        .await
+        .unwrap();
+    migrations::prepare(&pool, true).await.unwrap();
+    let DbPool::Postgres(pg_pool) = &pool else {
+        unreachable!("TEST_DATABASE_URL is a Postgres database");
+    };
+    let store = PgRateLimitStore::new(pg_pool.clone());
+
+    drains_after_the_burst(&store).await;
+    assert_eq!(store.prune().await.unwrap(), 0);
+    sqlx::query(
+        "UPDATE rate_limit_buckets SET updated_at = NOW() - INTERVAL '2 hours' WHERE key = $1",
+    )
+    .bind("test:ip:127.0.0.1")
+    .execute(pg_pool)
+    .await
+    .unwrap();
+    assert_eq!(store.prune().await.unwrap(), 1);
+
+    pool.close().await;
+    database.drop().await;
+}
+
+#[tokio::test]
+async fn limited_requests_get_429_with_retry_after() {
+    let app = TestApp::with_config(|env| env.rate_limit.auth_per_minute = 2, None);
+    let body = json!({ "email": "nobody@example.com", "password": "password123" });
+
+    for _ in 0..2 {
+        let (status, _) = app.send(post_json("/api/auth/login", None, &body)).await;
+        assert_eq!(status, StatusCode::BAD_REQUEST);
+    }
+    let response = app.respond(post_json("/api/auth/login", None, &body)).await;
+    assert_eq!(response.status(), StatusCode::TOO_MANY_REQUESTS);
+    assert_eq!(response.headers()[header::RETRY_AFTER], "30");
+    assert_eq!(
+        response.headers()[header::CONTENT_TYPE],
+        "application/problem+json"
+    );
+    let problem: Value = serde_json::from_slice(response.body()).unwrap();
+    assert_eq!(problem["code"], "too_many_requests");
+}
+
+#[tokio::test]
+async fn key_rotations_are_limited_per_user() {
+    let app = TestApp::new();
+    let alice = app.user_with_token("alice@example.com").await;
+    let bob = app.user_with_token("bob@example.com").await;
+    let rotate = |token| post_json("/api/users/keys/rotate", Some(token), &json!({}));
+
+    for _ in 0..2 {
+        let (status, _) = app.send(rotate(&alice)).await;
+        assert_eq!(status, StatusCode::OK);
+    }
+    let (status, _) = app.send(rotate(&alice)).await;
+    assert_eq!(status, StatusCode::TOO_MANY_REQUESTS);
+    let (status, _) = app.send(rotate(&bob)).await;
+    assert_eq!(status, StatusCode::OK);
+}
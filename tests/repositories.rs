@@ -0,0 +1,891 @@
+//! Runs the same scenarios against every repository implementation: in memory,
+//! SQLite in a temporary file, and Postgres when `TEST_DATABASE_URL` is set.
+
+mod common;
+
+use std::{fs, path::PathBuf, sync::Arc};
+
+use chrono::{Duration, Utc};
+use secure_share::{
+    config::DatabaseConfig,
+    db::{
+        AdminExt, DbClient, DbPool, FileRepository, ListQuery, ListedFile, MemoryDb,
+        ShareRepository, SqliteClient, UserRepository,
+    },
+    dtos::{ListSort, ShareStatus, SortOrder},
+    migrations,
+    models::NewFile,
+};
+use uuid::Uuid;
+
+use common::TestDatabase;
+
+struct Backend {
+    name: &'static str,
+    users: Arc<dyn UserRepository>,
+    files: Arc<dyn FileRepository>,
+    shares: Arc<dyn ShareRepository>,
+    pool: Option<DbPool>,
+    dir: Option<PathBuf>,
+    database: Option<TestDatabase>,
+}
+
+impl Backend {
+    fn memory() -> Self {
+        let db = Arc::new(MemoryDb::default());
+        Self {
+            name: "memory",
+            users: db.clone(),
+            files: db.clone(),
+            shares: db,
+            pool: None,
+            dir: None,
+            database: None,
+        }
+    }
+
+    async fn connect(
+        name: &'static str,
+        url: &str,
+        dir: Option<PathBuf>,
+        database: Option<TestDatabase>,
+    ) -> Self {
+        let pool = DbPool::connect(url, &DatabaseConfig::default())
+            .await
+            .unwrap();
+        migrations::prepare(&pool, true).await.unwrap();
+        let (users, files, shares) = pool.repositories();
+        Self {
+            name,
+            users,
+            files,
+            shares,
+            pool: Some(pool),
+            dir,
+            database,
+        }
+    }
+
+    async fn all() -> Vec<Self> {
+        let mut backends = vec![Self::memory()];
+
+        let dir = std::env::temp_dir().join(format!("secure-share-test-{}", Uuid::new_v4()));
+        fs::create_dir_all(&dir).unwrap();
+        let url = format!("sqlite://{}", dir.join("secure-share.db").display());
+        backends.push(Self::connect("sqlite", &url, Some(dir), None).await);
+
+        if let Some(database) = TestDatabase::create().await {
+            let url = database.url.clone();
+            backends.push(Self::connect("postgres", &url, None, Some(database)).await);
+        }
+
+        backends
+    }
+
+    /// Emails are unique per call, so substring searches only match the users
+    /// a scenario created.
+    fn email(local: &str) -> String {
+        format!("{}-{}@example.com", local, Uuid::new_v4().simple())
+    }
+
+    async fn user_with_key(&self, local: &str) -> (Uuid, String) {
+        let email = Self::email(local);
+        let user = self
+            .users
+            .save_user(local.to_string(), email.clone(), "hash".to_string())
+            .await
+            .unwrap();
+        self.users
+            .save_user_key(user.id, 1, "rsa".to_string(), "public key".to_string())
+            .await
+            .unwrap();
+        (user.id, email)
+    }
+
+    async fn share(&self, sender: Uuid, recipient: Uuid, file_name: &str, expires_in: Duration) {
+        self.share_sized(sender, recipient, file_name, 4, expires_in)
+            .await;
+    }
+
+    async fn share_sized(
+        &self,
+        sender: Uuid,
+        recipient: Uuid,
+        file_name: &str,
+        file_size: i64,
+        expires_in: Duration,
+    ) {
+        self.files
+            .save_encrypted_file(NewFile {
+                user_id: sender,
+                file_name: file_name.to_string(),
+                file_size,
+                content_type: "text/plain".to_string(),
+                recipient_user_id: recipient,
+                password: "share password".to_string(),
+                expiration_date: Utc::now() + expires_in,
+                encrypted_aes_key: vec![1; 256],
+                encrypted_file: vec![2; 16],
+                iv: vec![3; 16],
+                encrypted_metadata: None,
+                sender_encrypted_aes_key: None,
+                recipient_key_version: 1,
+                sender_key_version: 1,
+                content_digest: None,
+                signature: None,
+                signing_key_version: None,
+            })
+            .await
+            .unwrap();
+    }
+
+    async fn close(self) {
+        if let Some(pool) = &self.pool {
+            pool.close().await;
+        }
+        if let Some(dir) = &self.dir {
+            let _ = fs::remove_dir_all(dir);
+        }
+        if let Some(database) = self.database {
+            database.drop().await;
+        }
+    }
+}
+
+fn page(limit: usize, offset: usize) -> ListQuery {
+    ListQuery {
+        limit,
+        offset,
+        ..ListQuery::default()
+    }
+}
+
+#[tokio::test]
+async fn users_round_trip_and_emails_are_unique() {
+    for backend in Backend::all().await {
+        let name = backend.name;
+        let email = Backend::email("alice");
+        let user = backend
+            .users
+            .save_user("alice".to_string(), email.clone(), "hash".to_string())
+            .await
+            .unwrap();
+        assert_eq!(user.email, email, "{}", name);
+        assert!(user.public_key.is_none(), "{}", name);
+        assert!(user.created_at.is_some(), "{}", name);
+
+        let err = backend
+            .users
+            .save_user("other".to_string(), email.clone(), "hash".to_string())
+            .await
+            .unwrap_err();
+        let is_unique_violation = err
+            .as_database_error()
+            .is_some_and(|err| err.is_unique_violation());
+        assert!(is_unique_violation, "{}: {}", name, err);
+
+        let by_id = backend.users.get_user(Some(user.id), None, None).await;
+        let by_email = backend.users.get_user(None, None, Some(&email)).await;
+        assert_eq!(by_id.unwrap().unwrap().id, user.id, "{}", name);
+        assert_eq!(by_email.unwrap().unwrap().id, user.id, "{}", name);
+        let missing = backend
+            .users
+            .get_user(Some(Uuid::new_v4()), None, None)
+            .await;
+        assert!(missing.unwrap().is_none(), "{}", name);
+
+        let user = backend
+            .users
+            .update_user_name(user.id, "alice2".to_string())
+            .await
+            .unwrap();
+        assert_eq!(user.name, "alice2", "{}", name);
+        let user = backend
+            .users
+            .update_user_password(user.id, "hash2".to_string())
+            .await
+            .unwrap();
+        assert_eq!(user.password, "hash2", "{}", name);
+        let user = backend
+            .users
+            .update_user_locale(user.id, Some("zh".to_string()))
+            .await
+            .unwrap();
+        assert_eq!(user.locale.as_deref(), Some("zh"), "{}", name);
+        backend
+            .users
+            .save_signing_key(user.id, 1, "server".to_string(), "signing key".to_string())
+            .await
+            .unwrap();
+        let user = backend.users.get_user(Some(user.id), None, None).await;
+        let user = user.unwrap().unwrap();
+        assert_eq!(user.signing_key.as_deref(), Some("signing key"), "{}", name);
+        backend
+            .users
+            .save_user_key(user.id, 1, "rsa".to_string(), "public key".to_string())
+            .await
+            .unwrap();
+        let user = backend.users.get_user(Some(user.id), None, None).await;
+        assert_eq!(
+            user.unwrap().unwrap().public_key.as_deref(),
+            Some("public key"),
+            "{}",
+            name
+        );
+
+        backend.close().await;
+    }
+}
+
+#[tokio::test]
+async fn rotating_keys_retires_the_active_one_and_files_keep_their_version() {
+    for backend in Backend::all().await {
+        let name = backend.name;
+        let (alice, _) = backend.user_with_key("alice").await;
+        let (bob, _) = backend.user_with_key("bob").await;
+
+        let key = backend
+            .users
+            .save_user_key(bob, 2, "x25519".to_string(), "second key".to_string())
+            .await
+            .unwrap();
+        assert_eq!(
+            (key.version, key.status.as_str()),
+            (2, "active"),
+            "{}",
+            name
+        );
+        let err = backend
+            .users
+            .save_user_key(bob, 2, "x25519".to_string(), "racing key".to_string())
+            .await
+            .unwrap_err();
+        let is_unique_violation = err
+            .as_database_error()
+            .is_some_and(|err| err.is_unique_violation());
+        assert!(is_unique_violation, "{}: {}", name, err);
+
+        let keys = backend.users.get_user_keys(bob).await.unwrap();
+        let keys: Vec<_> = keys
+            .iter()
+            .map(|key| {
+                let status = (key.status.as_str(), key.retired_at.is_some());
+                (key.version, key.key_type.as_str(), status)
+            })
+            .collect();
+        assert_eq!(
+            keys,
+            [
+                (1, "rsa", ("retired", true)),
+                (2, "x25519", ("active", false))
+            ],
+            "{}",
+            name
+        );
+        let active = backend.users.get_active_user_key(bob).await.unwrap();
+        assert_eq!(active.unwrap().public_key, "second key", "{}", name);
+        let user = backend.users.get_user(Some(bob), None, None).await;
+        let public_key = user.unwrap().unwrap().public_key;
+        assert_eq!(public_key.as_deref(), Some("second key"), "{}", name);
+        let keyless = backend.users.get_active_user_key(Uuid::new_v4()).await;
+        assert!(keyless.unwrap().is_none(), "{}", name);
+
+        backend
+            .files
+            .save_encrypted_file(NewFile {
+                user_id: alice,
+                file_name: "rotated.txt".to_string(),
+                file_size: 4,
+                content_type: "text/plain".to_string(),
+                recipient_user_id: bob,
+                password: String::new(),
+                expiration_date: Utc::now() + Duration::hours(1),
+                encrypted_aes_key: vec![1; 256],
+                encrypted_file: vec![2; 16],
+                iv: vec![3; 16],
+                encrypted_metadata: None,
+                sender_encrypted_aes_key: Some(vec![4; 256]),
+                recipient_key_version: 2,
+                sender_key_version: 1,
+                content_digest: Some(vec![5; 32]),
+                signature: Some(vec![6; 64]),
+                signing_key_version: Some(1),
+            })
+            .await
+            .unwrap();
+        let (sent, _) = backend
+            .shares
+            .get_sent_files(alice, &ListQuery::default())
+            .await
+            .unwrap();
+        assert_eq!(sent[0].key_version, 1, "{}", name);
+        let (received, _) = backend
+            .shares
+            .get_receive_files(bob, &ListQuery::default())
+            .await
+            .unwrap();
+        assert_eq!(received[0].key_version, 2, "{}", name);
+        let file = backend.files.get_file(sent[0].file_id).await.unwrap();
+        let file = file.unwrap();
+        assert_eq!(file.signature, Some(vec![6; 64]), "{}", name);
+        assert_eq!(
+            (file.recipient_key_version, file.sender_key_version),
+            (2, 1),
+            "{}",
+            name
+        );
+        let details = backend
+            .shares
+            .get_share_details(sent[0].share_id, bob)
+            .await
+            .unwrap()
+            .unwrap();
+        assert_eq!(
+            (details.recipient_key_version, details.sender_key_version),
+            (2, 1),
+            "{}",
+            name
+        );
+        assert_eq!(
+            (details.sender_id, details.recipient_id),
+            (alice, bob),
+            "{}",
+            name
+        );
+        assert_eq!(details.content_digest, Some(vec![5; 32]), "{}", name);
+        assert_eq!(details.signature, file.signature, "{}", name);
+        assert_eq!(details.signing_key_version, Some(1), "{}", name);
+        assert_eq!(details.sender_signing_key, None, "{}", name);
+
+        backend.close().await;
+    }
+}
+
+#[tokio::test]
+async fn files_keep_the_signing_key_version_they_were_signed_with() {
+    for backend in Backend::all().await {
+        let name = backend.name;
+        let (alice, _) = backend.user_with_key("alice").await;
+        let (bob, _) = backend.user_with_key("bob").await;
+        backend
+            .users
+            .save_signing_key(alice, 1, "server".to_string(), "first".to_string())
+            .await
+            .unwrap();
+        backend
+            .files
+            .save_encrypted_file(NewFile {
+                user_id: alice,
+                file_name: String::new(),
+                file_size: 4,
+                content_type: "text/plain".to_string(),
+                recipient_user_id: bob,
+                password: String::new(),
+                expiration_date: Utc::now() + Duration::hours(1),
+                encrypted_aes_key: vec![1; 256],
+                encrypted_file: vec![2; 16],
+                iv: vec![3; 16],
+                encrypted_metadata: None,
+                sender_encrypted_aes_key: None,
+                recipient_key_version: 1,
+                sender_key_version: 1,
+                content_digest: Some(vec![5; 32]),
+                signature: Some(vec![6; 64]),
+                signing_key_version: Some(1),
+            })
+            .await
+            .unwrap();
+        backend
+            .users
+            .save_signing_key(alice, 2, "client".to_string(), "second".to_string())
+            .await
+            .unwrap();
+        let taken = backend
+            .users
+            .save_signing_key(alice, 2, "client".to_string(), "third".to_string())
+            .await;
+        let taken = taken.unwrap_err();
+        assert!(
+            taken.as_database_error().unwrap().is_unique_violation(),
+            "{}",
+            name
+        );
+
+        let keys: Vec<_> = backend
+            .users
+            .get_signing_keys(alice)
+            .await
+            .unwrap()
+            .into_iter()
+            .map(|key| (key.version, key.held_by, key.status))
+            .collect();
+        let expected = [
+            (1, "server".to_string(), "retired".to_string()),
+            (2, "client".to_string(), "active".to_string()),
+        ];
+        assert_eq!(keys, expected, "{}", name);
+        let user = backend.users.get_user(Some(alice), None, None).await;
+        let signing_key = user.unwrap().unwrap().signing_key;
+        assert_eq!(signing_key.as_deref(), Some("second"), "{}", name);
+
+        let (sent, _) = backend
+            .shares
+            .get_sent_files(alice, &ListQuery::default())
+            .await
+            .unwrap();
+        let details = backend
+            .shares
+            .get_share_details(sent[0].share_id, alice)
+            .await
+            .unwrap()
+            .unwrap();
+        assert_eq!(details.signing_key_version, Some(1), "{}", name);
+        assert_eq!(
+            details.sender_signing_key.as_deref(),
+            Some("first"),
+            "{}",
+            name
+        );
+
+        backend.close().await;
+    }
+}
+
+#[tokio::test]
+async fn search_matches_substrings_of_other_users_with_keys() {
+    for backend in Backend::all().await {
+        let name = backend.name;
+        let (alice, alice_email) = backend.user_with_key("alice").await;
+        let (_, bob_email) = backend.user_with_key("bob").await;
+        backend
+            .users
+            .save_user(
+                "carol".to_string(),
+                Backend::email("carol"),
+                "hash".to_string(),
+            )
+            .await
+            .unwrap();
+
+        let bob_local = bob_email.split('@').next().unwrap();
+        let found = backend
+            .users
+            .search_by_email(alice, bob_local[2..].to_string())
+            .await
+            .unwrap();
+        let emails: Vec<_> = found.iter().map(|user| user.email.as_str()).collect();
+        assert_eq!(emails, [bob_email.as_str()], "{}", name);
+
+        let own = backend
+            .users
+            .search_by_email(alice, alice_email)
+            .await
+            .unwrap();
+        assert!(own.is_empty(), "{}", name);
+
+        // Carol has no key pair yet, so nothing can be shared with her
+        let keyless = backend
+            .users
+            .search_by_email(alice, "carol-".to_string())
+            .await
+            .unwrap();
+        assert!(keyless.is_empty(), "{}", name);
+
+        backend.close().await;
+    }
+}
+
+#[tokio::test]
+async fn shares_list_newest_first_with_totals() {
+    for backend in Backend::all().await {
+        let name = backend.name;
+        let (alice, alice_email) = backend.user_with_key("alice").await;
+        let (bob, bob_email) = backend.user_with_key("bob").await;
+        for file_name in ["first.txt", "second.txt", "third.txt"] {
+            backend
+                .share(alice, bob, file_name, Duration::days(1))
+                .await;
+            tokio::time::sleep(std::time::Duration::from_millis(5)).await;
+        }
+
+        let (sent, total) = backend
+            .shares
+            .get_sent_files(alice, &page(2, 0))
+            .await
+            .unwrap();
+        assert_eq!(total, 3, "{}", name);
+        let names: Vec<_> = sent.iter().map(|file| file.file_name.as_str()).collect();
+        assert_eq!(names, ["third.txt", "second.txt"], "{}", name);
+        assert_eq!(sent[0].recipient_email, bob_email, "{}", name);
+
+        let (sent, _) = backend
+            .shares
+            .get_sent_files(alice, &page(2, 2))
+            .await
+            .unwrap();
+        let names: Vec<_> = sent.iter().map(|file| file.file_name.as_str()).collect();
+        assert_eq!(names, ["first.txt"], "{}", name);
+
+        let (received, total) = backend
+            .shares
+            .get_receive_files(bob, &ListQuery::default())
+            .await
+            .unwrap();
+        assert_eq!(total, 3, "{}", name);
+        assert_eq!(received[0].sender_email, alice_email, "{}", name);
+        let (nothing, total) = backend
+            .shares
+            .get_receive_files(alice, &ListQuery::default())
+            .await
+            .unwrap();
+        assert!(nothing.is_empty() && total == 0, "{}", name);
+
+        // Received entries are keyed by share, which only the recipient can open
+        let share_id = received[0].share_id;
+        let link = backend.shares.get_shared(share_id, bob).await.unwrap();
+        let link = link.unwrap();
+        assert_eq!(link.password, "share password", "{}", name);
+        let other = backend.shares.get_shared(share_id, alice).await.unwrap();
+        assert!(other.is_none(), "{}", name);
+
+        let file = backend.files.get_file(link.file_id.unwrap()).await.unwrap();
+        let file = file.unwrap();
+        assert_eq!(file.file_name, "third.txt", "{}", name);
+        assert_eq!(file.user_id, Some(alice), "{}", name);
+        assert_eq!(file.encrypted_aes_key, vec![1; 256], "{}", name);
+        assert_eq!(file.encrypted_file, vec![2; 16], "{}", name);
+        assert_eq!(file.iv, vec![3; 16], "{}", name);
+
+        backend.close().await;
+    }
+}
+
+#[tokio::test]
+async fn delete_expired_files_keeps_active_shares() {
+    for backend in Backend::all().await {
+        let name = backend.name;
+        let (alice, _) = backend.user_with_key("alice").await;
+        let (bob, _) = backend.user_with_key("bob").await;
+        backend
+            .share(alice, bob, "expired.txt", -Duration::minutes(1))
+            .await;
+        backend
+            .share(alice, bob, "active.txt", Duration::days(1))
+            .await;
+
+        let (received, _) = backend
+            .shares
+            .get_receive_files(bob, &ListQuery::default())
+            .await
+            .unwrap();
+        let expired = received
+            .iter()
+            .find(|file| file.file_name == "expired.txt")
+            .unwrap();
+        let expired = backend.shares.get_shared(expired.share_id, bob).await;
+        assert!(expired.unwrap().is_none(), "{}", name);
+
+        let deleted = backend.shares.delete_expired_files().await.unwrap();
+        assert_eq!(deleted, 1, "{}", name);
+
+        let (sent, total) = backend
+            .shares
+            .get_sent_files(alice, &ListQuery::default())
+            .await
+            .unwrap();
+        assert_eq!(total, 1, "{}", name);
+        assert_eq!(sent[0].file_name, "active.txt", "{}", name);
+        let file = backend.files.get_file(sent[0].file_id).await.unwrap();
+        assert!(file.is_some(), "{}", name);
+
+        backend.close().await;
+    }
+}
+
+#[tokio::test]
+async fn share_details_are_visible_to_both_parties_and_count_downloads() {
+    for backend in Backend::all().await {
+        let name = backend.name;
+        let (alice, alice_email) = backend.user_with_key("alice").await;
+        let (bob, bob_email) = backend.user_with_key("bob").await;
+        let (carol, _) = backend.user_with_key("carol").await;
+        backend
+            .share_sized(alice, bob, "notes.txt", 42, -Duration::minutes(1))
+            .await;
+
+        let (sent, _) = backend
+            .shares
+            .get_sent_files(alice, &ListQuery::default())
+            .await
+            .unwrap();
+        let share_id = sent[0].share_id;
+        assert_ne!(share_id, sent[0].file_id, "{}", name);
+        assert_eq!(sent[0].content_type, "text/plain", "{}", name);
+        assert_eq!(sent[0].recipient_name, "bob", "{}", name);
+        assert!(sent[0].password_protected, "{}", name);
+        let (received, _) = backend
+            .shares
+            .get_receive_files(bob, &ListQuery::default())
+            .await
+            .unwrap();
+        assert_eq!(received[0].share_id, share_id, "{}", name);
+        assert_eq!(received[0].file_id, sent[0].file_id, "{}", name);
+        assert_eq!(received[0].sender_name, "alice", "{}", name);
+
+        // Expired shares can no longer be downloaded but can still be inspected
+        for user in [alice, bob] {
+            let details = backend.shares.get_share_details(share_id, user).await;
+            let details = details.unwrap().unwrap();
+            assert_eq!(details.file_id, sent[0].file_id, "{}", name);
+            assert_eq!(details.file_name, "notes.txt", "{}", name);
+            assert_eq!(details.file_size, 42, "{}", name);
+            assert_eq!(details.content_type, "text/plain", "{}", name);
+            assert_eq!(details.sender_email, alice_email, "{}", name);
+            assert_eq!(details.recipient_email, bob_email, "{}", name);
+            assert_eq!(details.download_count, 0, "{}", name);
+            assert!(details.last_downloaded_at.is_none(), "{}", name);
+            assert!(details.password_protected, "{}", name);
+        }
+        let stranger = backend.shares.get_share_details(share_id, carol).await;
+        assert!(stranger.unwrap().is_none(), "{}", name);
+        let missing = backend.shares.get_share_details(Uuid::new_v4(), alice);
+        assert!(missing.await.unwrap().is_none(), "{}", name);
+
+        backend.shares.record_download(share_id).await.unwrap();
+        backend.shares.record_download(share_id).await.unwrap();
+        let details = backend.shares.get_share_details(share_id, alice).await;
+        let details = details.unwrap().unwrap();
+        assert_eq!(details.download_count, 2, "{}", name);
+        assert!(details.last_downloaded_at.is_some(), "{}", name);
+        let (sent, _) = backend
+            .shares
+            .get_sent_files(alice, &ListQuery::default())
+            .await
+            .unwrap();
+        assert_eq!(sent[0].download_count, 2, "{}", name);
+
+        backend.close().await;
+    }
+}
+
+#[tokio::test]
+async fn shares_sort_by_name_and_size_and_page_by_cursor() {
+    for backend in Backend::all().await {
+        let name = backend.name;
+        let (alice, _) = backend.user_with_key("alice").await;
+        let (bob, _) = backend.user_with_key("bob").await;
+        for (file_name, file_size) in [("b.txt", 30), ("a.txt", 10), ("c.txt", 20), ("d.txt", 20)] {
+            backend
+                .share_sized(alice, bob, file_name, file_size, Duration::days(1))
+                .await;
+        }
+
+        let by_name = ListQuery {
+            limit: 2,
+            sort: ListSort::FileName,
+            order: SortOrder::Asc,
+            ..ListQuery::default()
+        };
+        let (first, total) = backend
+            .shares
+            .get_sent_files(alice, &by_name)
+            .await
+            .unwrap();
+        let names: Vec<_> = first.iter().map(|file| file.file_name.as_str()).collect();
+        assert_eq!(names, ["a.txt", "b.txt"], "{}", name);
+        assert_eq!(total, 4, "{}", name);
+        let last = first.last().unwrap();
+        let after = ListQuery {
+            after: Some((last.sort_value(ListSort::FileName).unwrap(), last.id())),
+            ..by_name
+        };
+        let (second, total) = backend.shares.get_sent_files(alice, &after).await.unwrap();
+        let names: Vec<_> = second.iter().map(|file| file.file_name.as_str()).collect();
+        assert_eq!(names, ["c.txt", "d.txt"], "{}", name);
+        assert_eq!(total, 4, "{}", name);
+
+        // One row per page, so the cursor has to step between the equal sizes
+        let mut by_size = ListQuery {
+            limit: 1,
+            sort: ListSort::FileSize,
+            order: SortOrder::Desc,
+            ..ListQuery::default()
+        };
+        let mut seen = Vec::new();
+        loop {
+            let (files, _) = backend
+                .shares
+                .get_receive_files(bob, &by_size)
+                .await
+                .unwrap();
+            let Some(file) = files.first() else {
+                break;
+            };
+            seen.push((file.file_size, file.file_name.clone()));
+            by_size.after = Some((file.sort_value(ListSort::FileSize).unwrap(), file.id()));
+        }
+        let sizes: Vec<_> = seen.iter().map(|(size, _)| *size).collect();
+        assert_eq!(sizes, [30, 20, 20, 10], "{}", name);
+        seen.sort();
+        seen.dedup();
+        assert_eq!(seen.len(), 4, "{}", name);
+
+        backend.close().await;
+    }
+}
+
+#[tokio::test]
+async fn shares_filter_by_status_email_name_and_date() {
+    for backend in Backend::all().await {
+        let name = backend.name;
+        let started = Utc::now() - Duration::seconds(1);
+        let (alice, _) = backend.user_with_key("alice").await;
+        let (bob, bob_email) = backend.user_with_key("bob").await;
+        let (carol, _) = backend.user_with_key("carol").await;
+        backend
+            .share(alice, bob, "Report-2026.pdf", Duration::days(1))
+            .await;
+        backend
+            .share(alice, bob, "notes.txt", -Duration::minutes(1))
+            .await;
+        backend
+            .share(alice, carol, "report_draft.txt", Duration::days(1))
+            .await;
+
+        let sent = |query: ListQuery| {
+            let shares = backend.shares.clone();
+            async move {
+                let (files, total) = shares.get_sent_files(alice, &query).await.unwrap();
+                let mut names: Vec<_> = files.into_iter().map(|file| file.file_name).collect();
+                names.sort();
+                assert_eq!(names.len() as i64, total);
+                names
+            }
+        };
+
+        let active = sent(ListQuery {
+            status: Some(ShareStatus::Active),
+            ..ListQuery::default()
+        });
+        assert_eq!(
+            active.await,
+            ["Report-2026.pdf", "report_draft.txt"],
+            "{}",
+            name
+        );
+        let expired = sent(ListQuery {
+            status: Some(ShareStatus::Expired),
+            ..ListQuery::default()
+        });
+        assert_eq!(expired.await, ["notes.txt"], "{}", name);
+
+        let to_bob = sent(ListQuery {
+            email: Some(bob_email.clone()),
+            ..ListQuery::default()
+        });
+        assert_eq!(to_bob.await, ["Report-2026.pdf", "notes.txt"], "{}", name);
+
+        let report = sent(ListQuery {
+            file_name: Some("REPORT".to_string()),
+            ..ListQuery::default()
+        });
+        assert_eq!(
+            report.await,
+            ["Report-2026.pdf", "report_draft.txt"],
+            "{}",
+            name
+        );
+        // Wildcards in the filter match literally
+        let underscore = sent(ListQuery {
+            file_name: Some("_".to_string()),
+            ..ListQuery::default()
+        });
+        assert_eq!(underscore.await, ["report_draft.txt"], "{}", name);
+        let percent = sent(ListQuery {
+            file_name: Some("%".to_string()),
+            ..ListQuery::default()
+        });
+        assert!(percent.await.is_empty(), "{}", name);
+
+        let since_start = sent(ListQuery {
+            created_after: Some(started),
+            created_before: Some(Utc::now() + Duration::minutes(1)),
+            ..ListQuery::default()
+        });
+        assert_eq!(since_start.await.len(), 3, "{}", name);
+        let future = sent(ListQuery {
+            created_after: Some(Utc::now() + Duration::minutes(1)),
+            ..ListQuery::default()
+        });
+        assert!(future.await.is_empty(), "{}", name);
+
+        let query = ListQuery {
+            status: Some(ShareStatus::Active),
+            ..ListQuery::default()
+        };
+        let (received, total) = backend.shares.get_receive_files(bob, &query).await.unwrap();
+        assert_eq!(total, 1, "{}", name);
+        assert_eq!(received[0].file_name, "Report-2026.pdf", "{}", name);
+
+        backend.close().await;
+    }
+}
+
+async fn seal_legacy_name(admin: &impl AdminExt, backend: &Backend) {
+    let name = backend.name;
+    let (alice, _) = backend.user_with_key("alice").await;
+    let (bob, _) = backend.user_with_key("bob").await;
+    backend
+        .share(alice, bob, "legacy.txt", Duration::days(1))
+        .await;
+
+    let unsealed = admin.list_unsealed_files(None, 10).await.unwrap();
+    assert_eq!(unsealed.len(), 1, "{}", name);
+    let file = &unsealed[0];
+    assert_eq!(file.file_name, "legacy.txt", "{}", name);
+    assert_eq!(file.sender_user_id, Some(alice), "{}", name);
+    assert_eq!(file.recipient_user_id, Some(bob), "{}", name);
+    let after = admin.list_unsealed_files(Some(file.file_id), 10).await;
+    assert!(after.unwrap().is_empty(), "{}", name);
+
+    let sealed = admin
+        .seal_file_metadata(file.file_id, vec![4; 32], Some((vec![5; 256], 2)))
+        .await
+        .unwrap();
+    assert!(sealed, "{}", name);
+    // Only once, so a concurrent run cannot overwrite it
+    let sealed = admin
+        .seal_file_metadata(file.file_id, vec![6; 32], None)
+        .await
+        .unwrap();
+    assert!(!sealed, "{}", name);
+
+    let stored = backend.files.get_file(file.file_id).await.unwrap().unwrap();
+    assert_eq!(stored.file_name, "", "{}", name);
+    assert_eq!(stored.content_type, "application/octet-stream", "{}", name);
+    assert_eq!(stored.encrypted_metadata, Some(vec![4; 32]), "{}", name);
+    assert_eq!(
+        stored.sender_encrypted_aes_key,
+        Some(vec![5; 256]),
+        "{}",
+        name
+    );
+    assert_eq!(stored.sender_key_version, 2, "{}", name);
+    let unsealed = admin.list_unsealed_files(None, 10).await.unwrap();
+    assert!(unsealed.is_empty(), "{}", name);
+}
+
+#[tokio::test]
+async fn legacy_names_are_sealed_once() {
+    for backend in Backend::all().await {
+        match backend.pool.clone() {
+            Some(DbPool::Postgres(pool)) => seal_legacy_name(&DbClient::new(pool), &backend).await,
+            Some(DbPool::Sqlite(pool)) => {
+                seal_legacy_name(&SqliteClient::new(pool), &backend).await
+            }
+            // The admin tool always runs against a database
+            None => {}
+        }
+        backend.close().await;
+    }
+}